@@ -1,3 +1,5 @@
+use std::{fmt, str::FromStr};
+
 use search_engine_compressors::*;
 
 #[derive(Debug, Clone, PartialEq)]
@@ -9,14 +11,54 @@ pub enum CompressionAlgorithm {
     VarByte,
 }
 
+impl fmt::Display for CompressionAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            CompressionAlgorithm::Simple9 => "Simple9",
+            CompressionAlgorithm::Simple16 => "Simple16",
+            CompressionAlgorithm::PforDelta => "PforDelta",
+            CompressionAlgorithm::RiceCoding => "RiceCoding",
+            CompressionAlgorithm::VarByte => "VarByte",
+        };
+        write!(f, "{}", name)
+    }
+}
+
 impl CompressionAlgorithm {
-    pub fn to_string(&self) -> String {
+    // the id which is saved in the footer of the inverted index
+    pub fn get_id(&self) -> u8 {
         match self {
-            CompressionAlgorithm::Simple9 => String::from("Simple9"),
-            CompressionAlgorithm::Simple16 => String::from("Simple16"),
-            CompressionAlgorithm::PforDelta => String::from("PforDelta"),
-            CompressionAlgorithm::RiceCoding => String::from("RiceCoding"),
-            CompressionAlgorithm::VarByte => String::from("VarByte"),
+            CompressionAlgorithm::Simple9 => 0,
+            CompressionAlgorithm::Simple16 => 1,
+            CompressionAlgorithm::PforDelta => 2,
+            CompressionAlgorithm::RiceCoding => 3,
+            CompressionAlgorithm::VarByte => 4,
+        }
+    }
+
+    pub fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(CompressionAlgorithm::Simple9),
+            1 => Some(CompressionAlgorithm::Simple16),
+            2 => Some(CompressionAlgorithm::PforDelta),
+            3 => Some(CompressionAlgorithm::RiceCoding),
+            4 => Some(CompressionAlgorithm::VarByte),
+            _ => None,
+        }
+    }
+}
+
+// the names which are used for the algorithms in the config file
+impl FromStr for CompressionAlgorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "varbyte" => Ok(CompressionAlgorithm::VarByte),
+            "simple9" => Ok(CompressionAlgorithm::Simple9),
+            "simple16" => Ok(CompressionAlgorithm::Simple16),
+            "pfordelta" => Ok(CompressionAlgorithm::PforDelta),
+            _ => Err(format!("unknown compression algorithm {}", s)),
         }
     }
 }
@@ -68,41 +110,34 @@ impl Compressor {
     pub fn compress_list_with_d_gaps(&self, list: &Vec<u32>) -> Vec<u8> {
         match self.compression_algorithm {
             CompressionAlgorithm::Simple9 => {
-                return simple9::compress(&Self::transform_list_for_d_gap_encoding(list));
+                simple9::compress(&Self::transform_list_for_d_gap_encoding(list))
             }
             CompressionAlgorithm::Simple16 => {
-                return simple16::compress(&Self::transform_list_for_d_gap_encoding(list));
+                simple16::compress(&Self::transform_list_for_d_gap_encoding(list))
             }
             CompressionAlgorithm::PforDelta => {
                 let mut list_with_differences = Self::transform_list_for_d_gap_encoding(list);
                 if list_with_differences.len() < 128 {
-                    list_with_differences.reserve(128 - list_with_differences.len());
-                    for _ in 0..128 - list_with_differences.len() {
-                        list_with_differences.push(0);
-                    }
+                    list_with_differences.resize(128, 0);
                 }
-                return p_for_delta::compress(&list_with_differences);
+                p_for_delta::compress(&list_with_differences)
             }
             CompressionAlgorithm::RiceCoding => {
-                return rice::compress(&Self::transform_list_for_d_gap_encoding(list), None);
+                rice::compress(&Self::transform_list_for_d_gap_encoding(list), None)
             }
             CompressionAlgorithm::VarByte => {
-                return var_byte::compress(&Self::transform_list_for_d_gap_encoding(list));
+                var_byte::compress(&Self::transform_list_for_d_gap_encoding(list))
             }
         }
     }
 
-    pub fn decompress_list_with_dgaps(&self, list: &Vec<u8>) -> Vec<u32> {
+    pub fn decompress_list_with_dgaps(&self, list: &[u8]) -> Vec<u32> {
         match self.compression_algorithm {
             CompressionAlgorithm::Simple9 => {
-                return Self::reconstruct_list_from_d_gap_encoding(simple9::decompress_from_bytes(
-                    list,
-                ));
+                Self::reconstruct_list_from_d_gap_encoding(simple9::decompress_from_bytes(list))
             }
             CompressionAlgorithm::Simple16 => {
-                return Self::reconstruct_list_from_d_gap_encoding(
-                    simple16::decompress_from_bytes(list),
-                );
+                Self::reconstruct_list_from_d_gap_encoding(simple16::decompress_from_bytes(list))
             }
             CompressionAlgorithm::PforDelta => {
                 let list = p_for_delta::decompress(list);
@@ -113,49 +148,35 @@ impl Compressor {
                     }
                     index += 1;
                 }
-                return Self::reconstruct_list_from_d_gap_encoding(list[0..index].to_vec());
+                Self::reconstruct_list_from_d_gap_encoding(list[0..index].to_vec())
             }
             CompressionAlgorithm::RiceCoding => {
-                return Self::reconstruct_list_from_d_gap_encoding(rice::decompress(list).unwrap());
+                Self::reconstruct_list_from_d_gap_encoding(rice::decompress(list).unwrap())
             }
             CompressionAlgorithm::VarByte => {
-                return Self::reconstruct_list_from_d_gap_encoding(var_byte::decompress(list));
+                Self::reconstruct_list_from_d_gap_encoding(var_byte::decompress(list))
             }
         }
     }
 
     pub fn compress_list(&self, list: &Vec<u32>) -> Vec<u8> {
         match self.compression_algorithm {
-            CompressionAlgorithm::Simple9 => {
-                return simple9::compress(&list);
-            }
-            CompressionAlgorithm::Simple16 => {
-                return simple16::compress(&list);
-            }
+            CompressionAlgorithm::Simple9 => simple9::compress(list),
+            CompressionAlgorithm::Simple16 => simple16::compress(list),
             CompressionAlgorithm::PforDelta => {
                 let mut p_for_delta_vec = vec![0; 128];
-                for i in 0..list.len() {
-                    p_for_delta_vec[i] = list[i];
-                }
-                return p_for_delta::compress(&p_for_delta_vec);
-            }
-            CompressionAlgorithm::RiceCoding => {
-                return rice::compress(&(list), None);
-            }
-            CompressionAlgorithm::VarByte => {
-                return var_byte::compress(&(list));
+                p_for_delta_vec[..list.len()].copy_from_slice(list);
+                p_for_delta::compress(&p_for_delta_vec)
             }
+            CompressionAlgorithm::RiceCoding => rice::compress(list, None),
+            CompressionAlgorithm::VarByte => var_byte::compress(list),
         }
     }
 
-    pub fn decompress_list(&self, list: &Vec<u8>) -> Vec<u32> {
+    pub fn decompress_list(&self, list: &[u8]) -> Vec<u32> {
         match self.compression_algorithm {
-            CompressionAlgorithm::Simple9 => {
-                return simple9::decompress_from_bytes(list);
-            }
-            CompressionAlgorithm::Simple16 => {
-                return simple16::decompress_from_bytes(list);
-            }
+            CompressionAlgorithm::Simple9 => simple9::decompress_from_bytes(list),
+            CompressionAlgorithm::Simple16 => simple16::decompress_from_bytes(list),
             CompressionAlgorithm::PforDelta => {
                 let list = p_for_delta::decompress(list);
                 let mut index = 0;
@@ -167,12 +188,8 @@ impl Compressor {
                 }
                 list[0..index].to_vec()
             }
-            CompressionAlgorithm::RiceCoding => {
-                return rice::decompress(list).unwrap();
-            }
-            CompressionAlgorithm::VarByte => {
-                return var_byte::decompress(list);
-            }
+            CompressionAlgorithm::RiceCoding => rice::decompress(list).unwrap(),
+            CompressionAlgorithm::VarByte => var_byte::decompress(list),
         }
     }
 }
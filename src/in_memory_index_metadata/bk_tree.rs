@@ -1,52 +1,298 @@
-use bk_tree::{BKTree, metrics};
+use std::io::{self, Read, Write};
 
+use bk_tree::{Metric, metrics::Levenshtein};
+
+use crate::{
+    in_memory_index_metadata::lexicon::Lexicon,
+    utils::{
+        checksum::ChecksumWriter,
+        index_format::{BK_TREE_MAGIC, check_format_version, write_file_header},
+    },
+};
+
+// A BK-tree over the terms of a segment, it finds the terms which are within an edit distance of
+// a word. The nodes are kept in flat vectors so that the tree can be saved and loaded as it is
+// instead of being rebuilt from millions of terms. Node 0 is the root and every node keeps its
+// children along with their distance to it.
 pub struct BkTree {
-    bk_tree: BKTree<String>,
+    nodes: BkTreeNodes,
+    children: Vec<Vec<(u32, u32)>>,
+}
+
+// A tree is built from the terms as they are indexed, but a saved tree only keeps their term ids
+// since the terms themselves are already in the lexicon
+enum BkTreeNodes {
+    Terms(Vec<String>),
+    TermIds(Vec<u32>),
 }
 
 impl BkTree {
     pub fn new() -> Self {
         Self {
-            bk_tree: BKTree::new(metrics::Levenshtein),
+            nodes: BkTreeNodes::Terms(Vec::new()),
+            children: Vec::new(),
         }
     }
 
-    pub fn find(&mut self, key: &str, edit_distance: u32) -> Vec<String> {
-        let mut result: Vec<String> = Vec::new();
-        let result_words = self.bk_tree.find(key, edit_distance).collect::<Vec<_>>();
-        for word in result_words {
-            result.push(word.1.clone());
+    pub fn len(&self) -> usize {
+        self.children.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.children.is_empty()
+    }
+
+    pub fn find(&self, key: &str, edit_distance: u32, lexicon: &Lexicon) -> Vec<String> {
+        self.find_with_distance(key, edit_distance, lexicon)
+            .into_iter()
+            .map(|(term, _)| term)
+            .collect()
+    }
+
+    // Returns the terms within edit_distance of the key along with their distance. A child can
+    // only hold terms within edit_distance of the key if the distance of the child to its parent
+    // is within edit_distance of the distance of the key to the parent. The lexicon is only used
+    // by a loaded tree to look up the terms of its term ids.
+    pub fn find_with_distance(
+        &self,
+        key: &str,
+        edit_distance: u32,
+        lexicon: &Lexicon,
+    ) -> Vec<(String, u32)> {
+        let mut result = Vec::new();
+        if self.children.is_empty() {
+            return result;
+        }
+        let mut stack = vec![0u32];
+        while let Some(node) = stack.pop() {
+            let term = match &self.nodes {
+                BkTreeNodes::Terms(terms) => terms[node as usize].clone(),
+                BkTreeNodes::TermIds(term_ids) => match lexicon.get_term(term_ids[node as usize]) {
+                    Some(term) => term,
+                    None => continue,
+                },
+            };
+            let distance = Levenshtein.distance(key, term.as_str());
+            for &(child_distance, child) in &self.children[node as usize] {
+                if child_distance.abs_diff(distance) <= edit_distance {
+                    stack.push(child);
+                }
+            }
+            if distance <= edit_distance {
+                result.push((term, distance));
+            }
         }
         result
     }
 
+    // Only a tree which is being built can be added to, a loaded tree has lost its terms
     pub fn add(&mut self, key: &str) {
-        self.bk_tree.add(String::from(key));
+        let BkTreeNodes::Terms(terms) = &mut self.nodes else {
+            panic!("terms can not be added to a loaded bk tree");
+        };
+        let new_node = terms.len() as u32;
+        if terms.is_empty() {
+            terms.push(key.to_string());
+            self.children.push(Vec::new());
+            return;
+        }
+        let mut node = 0;
+        loop {
+            let distance = Levenshtein.distance(key, terms[node].as_str());
+            if distance == 0 {
+                return;
+            }
+            match self.children[node]
+                .iter()
+                .find(|(child_distance, _)| *child_distance == distance)
+            {
+                Some(&(_, child)) => node = child as usize,
+                None => {
+                    self.children[node].push((distance, new_node));
+                    break;
+                }
+            }
+        }
+        terms.push(key.to_string());
+        self.children.push(Vec::new());
+    }
+
+    // Every node is saved as the term id of its term, which get_term_id looks up, followed by its
+    // children. The nodes are followed by the checksum of everything after the header.
+    pub fn save<W: Write>(
+        &self,
+        mut writer: W,
+        get_term_id: impl Fn(&str) -> u32,
+    ) -> io::Result<()> {
+        write_file_header(&mut writer, &BK_TREE_MAGIC)?;
+        let mut writer = ChecksumWriter::new(writer);
+        writer.write_all(&(self.len() as u32).to_le_bytes())?;
+        for node in 0..self.len() {
+            let term_id = match &self.nodes {
+                BkTreeNodes::Terms(terms) => match get_term_id(&terms[node]) {
+                    0 => {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidInput,
+                            format!(
+                                "the term {} of the bk tree is not in the lexicon",
+                                terms[node]
+                            ),
+                        ));
+                    }
+                    term_id => term_id,
+                },
+                BkTreeNodes::TermIds(term_ids) => term_ids[node],
+            };
+            writer.write_all(&term_id.to_le_bytes())?;
+            writer.write_all(&(self.children[node].len() as u32).to_le_bytes())?;
+            for (distance, child) in &self.children[node] {
+                writer.write_all(&distance.to_le_bytes())?;
+                writer.write_all(&child.to_le_bytes())?;
+            }
+        }
+        writer.finish()?;
+        Ok(())
+    }
+
+    // A tree which was saved before it had a header starts with the no of nodes right away, keeps
+    // the terms themselves and has no checksum. The whole file is read at once so that the counts
+    // in it can be checked against its length before anything is allocated for them.
+    pub fn load(&mut self, bytes: &[u8]) -> io::Result<()> {
+        if bytes.len() < 4 || bytes[..4] != BK_TREE_MAGIC {
+            return self.read_terms(bytes);
+        }
+        if bytes.len() < 12 {
+            return Err(get_truncated_error());
+        }
+        check_format_version(read_u32(&mut &bytes[4..8])?, "the bk tree")?;
+        let (body, checksum) = bytes[8..].split_at(bytes.len() - 12);
+        if crc32fast::hash(body) != read_u32(&mut &checksum[..])? {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "the checksum of the bk tree does not match its contents",
+            ));
+        }
+        self.read_term_ids(body)
+    }
+
+    fn read_term_ids(&mut self, mut bytes: &[u8]) -> io::Result<()> {
+        let no_of_nodes = read_count(&mut bytes, 8)?;
+        let mut term_ids = Vec::with_capacity(no_of_nodes);
+        let mut children = Vec::with_capacity(no_of_nodes);
+        for _ in 0..no_of_nodes {
+            term_ids.push(read_u32(&mut bytes)?);
+            children.push(read_children(&mut bytes, no_of_nodes)?);
+        }
+        self.nodes = BkTreeNodes::TermIds(term_ids);
+        self.children = children;
+        Ok(())
+    }
+
+    fn read_terms(&mut self, mut bytes: &[u8]) -> io::Result<()> {
+        let no_of_nodes = read_count(&mut bytes, 8)?;
+        let mut terms = Vec::with_capacity(no_of_nodes);
+        let mut children = Vec::with_capacity(no_of_nodes);
+        for _ in 0..no_of_nodes {
+            let term_length = read_count(&mut bytes, 1)?;
+            let (term_bytes, rest) = bytes.split_at(term_length);
+            bytes = rest;
+            terms.push(
+                String::from_utf8(term_bytes.to_vec())
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+            );
+            children.push(read_children(&mut bytes, no_of_nodes)?);
+        }
+        self.nodes = BkTreeNodes::Terms(terms);
+        self.children = children;
+        Ok(())
     }
 }
 
+fn get_truncated_error() -> io::Error {
+    io::Error::new(io::ErrorKind::UnexpectedEof, "the bk tree is truncated")
+}
+
+fn read_u32(bytes: &mut &[u8]) -> io::Result<u32> {
+    let mut buf = [0u8; 4];
+    bytes
+        .read_exact(&mut buf)
+        .map_err(|_| get_truncated_error())?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+// Reads a count of things which take up at least min_size bytes each, a count which is more than
+// the rest of the bytes can hold is from a damaged file
+fn read_count(bytes: &mut &[u8], min_size: usize) -> io::Result<usize> {
+    let count = read_u32(bytes)? as usize;
+    if count > bytes.len() / min_size {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("the bk tree has {} entries which do not fit in it", count),
+        ));
+    }
+    Ok(count)
+}
+
+fn read_children(bytes: &mut &[u8], no_of_nodes: usize) -> io::Result<Vec<(u32, u32)>> {
+    let no_of_children = read_count(bytes, 8)?;
+    let mut children = Vec::with_capacity(no_of_children);
+    for _ in 0..no_of_children {
+        let distance = read_u32(bytes)?;
+        let child = read_u32(bytes)?;
+        if child as usize >= no_of_nodes {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("bk tree node {} does not exist", child),
+            ));
+        }
+        children.push((distance, child));
+    }
+    Ok(children)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn create_bk_tree(words: &[&str]) -> (BkTree, Lexicon) {
+        let mut bk_tree = BkTree::new();
+        for word in words {
+            bk_tree.add(word);
+        }
+        let mut sorted_words = words.to_vec();
+        sorted_words.sort();
+        sorted_words.dedup();
+        let lexicon = Lexicon::from_terms(
+            sorted_words
+                .into_iter()
+                .enumerate()
+                .map(|(rank, word)| (word, rank as u32 + 1)),
+        );
+        (bk_tree, lexicon)
+    }
+
+    fn save(bk_tree: &BkTree, lexicon: &Lexicon) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bk_tree
+            .save(&mut bytes, |term| lexicon.get_term_id(term).unwrap_or(0))
+            .unwrap();
+        bytes
+    }
+
     #[test]
     fn test_add_and_find_exact_match() {
-        let mut bk_tree = BkTree::new();
-        bk_tree.add("hello");
+        let (bk_tree, lexicon) = create_bk_tree(&["hello"]);
 
-        let results = bk_tree.find("hello", 0);
+        let results = bk_tree.find("hello", 0, &lexicon);
         assert_eq!(results.len(), 1);
         assert_eq!(results[0], "hello");
     }
 
     #[test]
     fn test_find_with_edit_distance() {
-        let mut bk_tree = BkTree::new();
-        bk_tree.add("hello");
-        bk_tree.add("help");
-        bk_tree.add("world");
+        let (bk_tree, lexicon) = create_bk_tree(&["hello", "help", "world"]);
 
-        let results = bk_tree.find("helo", 1);
+        let results = bk_tree.find("helo", 1, &lexicon);
         assert!(results.contains(&"hello".to_string()));
         assert!(results.contains(&"help".to_string()));
         assert!(!results.contains(&"world".to_string()));
@@ -54,19 +300,114 @@ mod tests {
 
     #[test]
     fn test_find_empty_tree() {
-        let mut bk_tree = BkTree::new();
-        let results = bk_tree.find("test", 2);
+        let bk_tree = BkTree::new();
+        let results = bk_tree.find("test", 2, &Lexicon::new());
         assert_eq!(results.len(), 0);
     }
 
+    #[test]
+    fn test_find_with_distance() {
+        let (bk_tree, lexicon) =
+            create_bk_tree(&["stephen", "steven", "stephan", "king", "stephen"]);
+        assert_eq!(bk_tree.len(), 4);
+
+        let mut results = bk_tree.find_with_distance("stephn", 2, &lexicon);
+        results.sort();
+        assert_eq!(
+            results,
+            vec![
+                ("stephan".to_string(), 1),
+                ("stephen".to_string(), 1),
+                ("steven".to_string(), 2)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let (bk_tree, lexicon) = create_bk_tree(&["book", "books", "cake", "boo", "cape", "cart"]);
+        let mut bytes = save(&bk_tree, &lexicon);
+        assert_eq!(bytes[..4], BK_TREE_MAGIC);
+        // the terms are only kept in the lexicon
+        assert!(!bytes.windows(4).any(|term| term == b"cake"));
+
+        let mut loaded_bk_tree = BkTree::new();
+        loaded_bk_tree.load(&bytes).unwrap();
+        assert_eq!(loaded_bk_tree.len(), bk_tree.len());
+        let mut results = loaded_bk_tree.find("bok", 1, &lexicon);
+        results.sort();
+        assert_eq!(results, vec!["boo", "book"]);
+        // a loaded tree saves the same bytes
+        assert_eq!(save(&loaded_bk_tree, &Lexicon::new()), bytes);
+
+        assert!(BkTree::new().load(&bytes[..bytes.len() - 3]).is_err());
+
+        // a damaged term id is caught by the checksum
+        bytes[12] ^= 1;
+        let e = BkTree::new().load(&bytes).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+
+        // a term which is not in the lexicon can not be saved
+        let (bk_tree, _) = create_bk_tree(&["book"]);
+        assert!(bk_tree.save(Vec::new(), |_| 0).is_err());
+    }
+
+    #[test]
+    fn test_load_too_many_nodes() {
+        let (bk_tree, lexicon) = create_bk_tree(&["book", "books"]);
+        let bytes = save(&bk_tree, &lexicon);
+
+        // a damaged count of nodes or children is caught before anything is allocated for it
+        let mut body = bytes[8..bytes.len() - 4].to_vec();
+        body[..4].copy_from_slice(&u32::MAX.to_le_bytes());
+        let e = BkTree::new().read_term_ids(&body).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+
+        let mut body = bytes[8..bytes.len() - 4].to_vec();
+        body[8..12].copy_from_slice(&u32::MAX.to_le_bytes());
+        let e = BkTree::new().read_term_ids(&body).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+
+        let e = BkTree::new().load(&u32::MAX.to_le_bytes()).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_load_tree_without_header() {
+        // the tree as it was saved before it had a header and a checksum, with "book" as the
+        // root and "books" and "cake" as its children
+        let mut old_bytes = 3u32.to_le_bytes().to_vec();
+        for (term, children) in [
+            ("book", vec![(1u32, 1u32), (4, 2)]),
+            ("books", vec![]),
+            ("cake", vec![]),
+        ] {
+            old_bytes.extend((term.len() as u32).to_le_bytes());
+            old_bytes.extend(term.as_bytes());
+            old_bytes.extend((children.len() as u32).to_le_bytes());
+            for (distance, child) in children {
+                old_bytes.extend(distance.to_le_bytes());
+                old_bytes.extend(child.to_le_bytes());
+            }
+        }
+
+        let mut loaded_bk_tree = BkTree::new();
+        loaded_bk_tree.load(&old_bytes).unwrap();
+        assert_eq!(
+            loaded_bk_tree.find("boks", 1, &Lexicon::new()),
+            vec!["books"]
+        );
+
+        // a term longer than the rest of the file
+        old_bytes[4..8].copy_from_slice(&1000u32.to_le_bytes());
+        assert!(BkTree::new().load(&old_bytes).is_err());
+    }
+
     #[test]
     fn test_add_multiple_words() {
-        let mut bk_tree = BkTree::new();
-        bk_tree.add("cat");
-        bk_tree.add("dog");
-        bk_tree.add("bat");
+        let (bk_tree, lexicon) = create_bk_tree(&["cat", "dog", "bat"]);
 
-        let results = bk_tree.find("cat", 1);
+        let results = bk_tree.find("cat", 1, &lexicon);
         assert!(results.contains(&"cat".to_string()));
         assert!(results.contains(&"bat".to_string()));
         assert!(!results.contains(&"dog".to_string()));
@@ -0,0 +1,229 @@
+use std::{
+    io::{self, Write},
+    mem,
+    ops::Deref,
+    slice,
+    sync::Arc,
+};
+
+use memmap2::Mmap;
+
+use crate::utils::chunk_block_max_metadata::ChunkBlockMaxMetadata;
+
+/// A value which is saved as its little endian bytes one after the other.
+///
+/// # Safety
+///
+/// An implementing type must not have any padding and every bit pattern of its size has to be a
+/// valid value, so that a mapped column on a little endian machine can be read in place.
+pub unsafe trait Pod: Copy + Send + Sync + 'static {
+    fn read_le(bytes: &[u8]) -> Self;
+    fn write_le<W: Write + ?Sized>(&self, writer: &mut W) -> io::Result<()>;
+}
+
+macro_rules! impl_pod {
+    ($($t:ty),*) => {
+        $(
+            unsafe impl Pod for $t {
+                fn read_le(bytes: &[u8]) -> Self {
+                    <$t>::from_le_bytes(bytes.try_into().unwrap())
+                }
+
+                fn write_le<W: Write + ?Sized>(&self, writer: &mut W) -> io::Result<()> {
+                    writer.write_all(&self.to_le_bytes())
+                }
+            }
+        )*
+    };
+}
+
+impl_pod!(u8, u32, u64, f32);
+
+// repr(C) with two 4 byte fields, there is no padding
+unsafe impl Pod for ChunkBlockMaxMetadata {
+    fn read_le(bytes: &[u8]) -> Self {
+        ChunkBlockMaxMetadata::new(u32::read_le(&bytes[..4]), f32::read_le(&bytes[4..]))
+    }
+
+    fn write_le<W: Write + ?Sized>(&self, writer: &mut W) -> io::Result<()> {
+        self.chunk_last_doc_id.write_le(writer)?;
+        self.chunk_max_term_score.write_le(writer)
+    }
+}
+
+// A column of the term metadata, it is built in memory while a segment is written and mapped
+// from the saved file when the segment is loaded, so that loading does not read the whole file
+// and the pages which are never queried are never read at all. A mapped column is copied into
+// memory the first time it is modified.
+pub enum Column<T: Pod> {
+    Owned(Vec<T>),
+    Mapped {
+        mmap: Arc<Mmap>,
+        offset: usize,
+        len: usize,
+    },
+}
+
+impl<T: Pod> Column<T> {
+    pub fn new() -> Self {
+        Column::Owned(Vec::new())
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Column::Owned(Vec::with_capacity(capacity))
+    }
+
+    // The values can only be read in place on a little endian machine when they are aligned,
+    // otherwise they are copied
+    pub fn map(mmap: &Arc<Mmap>, offset: usize, len: usize) -> io::Result<Self> {
+        let size = mem::size_of::<T>();
+        let end = len
+            .checked_mul(size)
+            .and_then(|byte_len| byte_len.checked_add(offset))
+            .filter(|&end| end <= mmap.len())
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "a column runs past the end of the file",
+                )
+            })?;
+        let bytes = &mmap[offset..end];
+        if cfg!(target_endian = "little")
+            && (bytes.as_ptr() as usize).is_multiple_of(mem::align_of::<T>())
+        {
+            Ok(Column::Mapped {
+                mmap: Arc::clone(mmap),
+                offset,
+                len,
+            })
+        } else {
+            Ok(Column::Owned(
+                bytes.chunks_exact(size).map(T::read_le).collect(),
+            ))
+        }
+    }
+
+    pub fn to_mut(&mut self) -> &mut Vec<T> {
+        if let Column::Mapped { .. } = self {
+            *self = Column::Owned(self.to_vec());
+        }
+        match self {
+            Column::Owned(values) => values,
+            Column::Mapped { .. } => unreachable!(),
+        }
+    }
+
+    pub fn push(&mut self, value: T) {
+        self.to_mut().push(value);
+    }
+
+    pub fn shrink_to_fit(&mut self) {
+        if let Column::Owned(values) = self {
+            values.shrink_to_fit();
+        }
+    }
+
+    pub fn get_byte_len(&self) -> usize {
+        self.len() * mem::size_of::<T>()
+    }
+
+    pub fn write<W: Write + ?Sized>(&self, writer: &mut W) -> io::Result<()> {
+        if cfg!(target_endian = "little") {
+            // SAFETY: T is Pod, so its in memory bytes are its little endian bytes
+            let bytes =
+                unsafe { slice::from_raw_parts(self.as_ptr() as *const u8, self.get_byte_len()) };
+            writer.write_all(bytes)
+        } else {
+            self.iter().try_for_each(|value| value.write_le(writer))
+        }
+    }
+}
+
+// lets the columns of different types be saved one after the other
+pub trait SavedColumn {
+    fn get_len(&self) -> usize;
+    fn get_byte_len(&self) -> usize;
+    fn write_to(&self, writer: &mut dyn Write) -> io::Result<()>;
+}
+
+impl<T: Pod> SavedColumn for Column<T> {
+    fn get_len(&self) -> usize {
+        self.len()
+    }
+
+    fn get_byte_len(&self) -> usize {
+        Column::get_byte_len(self)
+    }
+
+    fn write_to(&self, writer: &mut dyn Write) -> io::Result<()> {
+        self.write(writer)
+    }
+}
+
+impl<T: Pod> Default for Column<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Pod> From<Vec<T>> for Column<T> {
+    fn from(values: Vec<T>) -> Self {
+        Column::Owned(values)
+    }
+}
+
+impl<T: Pod> Deref for Column<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        match self {
+            Column::Owned(values) => values,
+            // SAFETY: map checked that the values are in bounds and aligned on a little endian
+            // machine, and T is Pod. The mapped file is never modified while it is mapped, a saved
+            // file is replaced by renaming a new one over it.
+            Column::Mapped { mmap, offset, len } => unsafe {
+                slice::from_raw_parts(mmap.as_ptr().add(*offset) as *const T, *len)
+            },
+        }
+    }
+}
+
+impl<T: Pod + std::fmt::Debug> std::fmt::Debug for Column<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+
+    use tempfile::NamedTempFile;
+
+    use super::*;
+
+    #[test]
+    fn test_map_column() {
+        let values: Column<u32> = vec![7, 11, 13].into();
+        let scores: Column<ChunkBlockMaxMetadata> = vec![ChunkBlockMaxMetadata::new(9, 1.5)].into();
+        let mut temp_file = NamedTempFile::new().unwrap();
+        values.write(&mut temp_file).unwrap();
+        scores.write(&mut temp_file).unwrap();
+
+        let file = File::open(temp_file.path()).unwrap();
+        let mmap = Arc::new(unsafe { Mmap::map(&file).unwrap() });
+        let mut mapped_values = Column::<u32>::map(&mmap, 0, 3).unwrap();
+        assert!(matches!(mapped_values, Column::Mapped { .. }));
+        assert_eq!(&mapped_values[..], &[7, 11, 13]);
+        let mapped_scores = Column::<ChunkBlockMaxMetadata>::map(&mmap, 12, 1).unwrap();
+        assert_eq!(mapped_scores[0], ChunkBlockMaxMetadata::new(9, 1.5));
+
+        // a misaligned column is copied
+        let misaligned = Column::<u32>::map(&mmap, 1, 2).unwrap();
+        assert!(matches!(misaligned, Column::Owned(_)));
+        assert!(Column::<u32>::map(&mmap, 8, 10).is_err());
+
+        mapped_values.push(17);
+        assert_eq!(&mapped_values[..], &[7, 11, 13, 17]);
+    }
+}
@@ -1,34 +1,76 @@
 use std::{
-    collections::HashMap,
-    io::{self, Read, Write},
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+    fs::{self, File},
+    io::{self, BufWriter, Read, Write},
+    path::Path,
+    sync::Arc,
 };
 
+use memmap2::Mmap;
+
 use crate::{
-    in_memory_index_metadata::bk_tree::BkTree,
+    in_memory_index_metadata::{
+        bk_tree::BkTree,
+        column::{Column, Pod, SavedColumn},
+        lexicon::Lexicon,
+    },
+    scoring::{ScoringModel, bm_25::BM25Params},
     utils::{
+        checksum::ChecksumWriter,
         chunk_block_max_metadata::ChunkBlockMaxMetadata,
         in_memory_term_metadata::InMemoryTermMetadata,
+        index_format::{TERMS_MAGIC, read_file_header, write_file_header},
     },
 };
 
+// the sections of terms.sidx start at multiples of this so that every column can be mapped in place
+const COLUMN_ALIGNMENT: usize = 8;
+// the columns of the term metadata and the lexicon which are saved in terms.sidx
+const NO_OF_COLUMNS: usize = 11;
+// the magic and the version, the counts and the scoring parameters, the offset, length and
+// checksum of every column and the checksum of the header
+const TERMS_HEADER_LEN: usize = 8 + 6 * 4 + NO_OF_COLUMNS * 24 + 4;
+
 // While serving queries we will need to know which blocks are occupied by which terms and
 // which terms map to which ids so we keep an instance of InMemoryIndexMetadata in memory
 // term_max_scores,term_frequencies and term_block_max_metadata are needed for query processing
+//
+// Every per term value is kept in a column indexed by term id - 1. When a segment is loaded the
+// columns and the lexicon are mapped from terms.sidx, so loading takes about as long as opening
+// the file and only the pages which queries touch are ever read.
 pub struct InMemoryIndexMetadata {
     pub no_of_blocks: u32,
     pub no_of_terms: u32, // no of terms in the collection
     pub bk_tree: BkTree,
-    term_frequencies: Vec<u32>,
+    // the model and the BM25 parameters the max scores were computed with
+    pub scoring_model: ScoringModel,
+    pub bm25_params: BM25Params,
+    // the terms in lexicographic order, the terms are moved here from term_to_id_map once all of
+    // them have been added
+    pub lexicon: Lexicon,
+    term_to_id_map: HashMap<String, u32>,
+
+    term_frequencies: Column<u32>,
+    // the no of occurrences of each term, it is empty for an index which was saved before it was
+    // kept track of
+    term_collection_frequencies: Column<u64>,
 
     // Vec<Vec<u32>> has been made into a 1D vector
     // we keep track of the indexes in this vector which help
     // us extract the block ids when we need to
-    term_block_ids: Vec<u32>,
-    term_block_id_offsets: Vec<usize>,
-
-    term_max_scores: Vec<f32>,
-    term_to_id_map: HashMap<String, u32>,
-    term_block_max_metadata: Vec<Box<[ChunkBlockMaxMetadata]>>,
+    term_block_ids: Column<u32>,
+    term_block_id_offsets: Column<u32>,
+    // the no of chunks of the term in each of its blocks, at the same offsets as the block ids.
+    // It is empty for an index which was saved before it was kept track of.
+    term_block_chunk_counts: Column<u32>,
+
+    term_max_scores: Column<f32>,
+    // the block max metadata of all the terms is flattened the same way as the block ids
+    term_chunk_offsets: Column<u32>,
+    chunk_block_max_metadata: Column<ChunkBlockMaxMetadata>,
+    // the checksums of the columns which were opened from terms.sidx
+    column_checksums: Option<[u32; NO_OF_COLUMNS]>,
 }
 
 impl InMemoryIndexMetadata {
@@ -37,26 +79,56 @@ impl InMemoryIndexMetadata {
             no_of_blocks: 0,
             no_of_terms: 0,
             bk_tree: BkTree::new(),
+            scoring_model: ScoringModel::BM25,
+            bm25_params: BM25Params::default(),
+            lexicon: Lexicon::new(),
             term_to_id_map: HashMap::with_capacity(6_000_000),
-            term_frequencies: Vec::with_capacity(6_000_000),
-            term_max_scores: Vec::with_capacity(6_000_000),
-            term_block_ids: Vec::with_capacity(6_000_000),
-            term_block_id_offsets: Vec::with_capacity(6_000_000),
-            term_block_max_metadata: Vec::with_capacity(6_000_000),
+            term_frequencies: Column::with_capacity(6_000_000),
+            term_collection_frequencies: Column::with_capacity(6_000_000),
+            term_max_scores: Column::with_capacity(6_000_000),
+            term_block_ids: Column::with_capacity(6_000_000),
+            term_block_id_offsets: Column::with_capacity(6_000_000),
+            term_block_chunk_counts: Column::with_capacity(6_000_000),
+            term_chunk_offsets: Column::with_capacity(6_000_000),
+            chunk_block_max_metadata: Column::with_capacity(6_000_000),
+            column_checksums: None,
         }
     }
 
+    // the terms of the lexicon followed by the ones which have not been moved to it yet
+    fn iter_terms(&self) -> impl Iterator<Item = (String, u32)> + '_ {
+        self.lexicon.iter().chain(
+            self.term_to_id_map
+                .iter()
+                .map(|(term, &term_id)| (term.clone(), term_id)),
+        )
+    }
+
+    // The terms in lexicographic order, the lexicon is decoded as the terms are iterated over. The
+    // terms which have not been moved to the lexicon yet are sorted up front.
+    pub fn iter_sorted_terms(&self) -> impl Iterator<Item = String> + '_ {
+        let mut added_terms: Vec<String> = self.term_to_id_map.keys().cloned().collect();
+        added_terms.sort_unstable();
+        let lexicon_terms: Box<dyn Iterator<Item = String> + '_> =
+            Box::new(self.lexicon.iter().map(|(term, _)| term));
+        merge_sorted_terms(vec![lexicon_terms, Box::new(added_terms.into_iter())])
+    }
+
+    pub fn get_no_of_saved_terms(&self) -> usize {
+        self.lexicon.len() + self.term_to_id_map.len()
+    }
+
+    // Saves the term metadata in the format of term_metadata.sidx, which indexes saved before
+    // terms.sidx have. It is only written by the tests of loading such indexes.
+    #[cfg(test)]
     pub fn save_term_metadata<W: Write>(&self, mut writer: W) -> io::Result<()> {
         assert_eq!(
             self.term_block_id_offsets.len(),
             self.term_frequencies.len()
         );
         assert_eq!(self.term_max_scores.len(), self.term_frequencies.len());
-        assert_eq!(self.term_to_id_map.len(), self.term_frequencies.len());
-        assert_eq!(
-            self.term_block_max_metadata.len(),
-            self.term_frequencies.len()
-        );
+        assert_eq!(self.get_no_of_saved_terms(), self.term_frequencies.len());
+        assert_eq!(self.term_chunk_offsets.len(), self.term_frequencies.len());
 
         writer.write_all(&self.no_of_blocks.to_le_bytes())?;
         writer.write_all(&self.no_of_terms.to_le_bytes())?;
@@ -65,9 +137,9 @@ impl InMemoryIndexMetadata {
         for i in 0..self.term_frequencies.len() {
             writer.write_all(&self.term_frequencies[i].to_le_bytes())?;
             writer.write_all(&self.term_max_scores[i].to_le_bytes())?;
-            writer.write_all(&(self.term_block_id_offsets[i] as u32).to_le_bytes())?;
+            writer.write_all(&self.term_block_id_offsets[i].to_le_bytes())?;
             // Write term block max metadata
-            let metadata = &self.term_block_max_metadata[i];
+            let metadata = self.get_chunk_block_max_metadata(i as u32 + 1).unwrap();
             writer.write_all(&(metadata.len() as u32).to_le_bytes())?;
             for chunk in metadata.iter() {
                 writer.write_all(&chunk.chunk_last_doc_id.to_le_bytes())?;
@@ -80,16 +152,31 @@ impl InMemoryIndexMetadata {
             writer.write_all(&self.term_block_ids[i].to_le_bytes())?;
         }
 
-        writer.write_all(&(self.term_to_id_map.len() as u32).to_le_bytes())?;
-        for (term, id) in &self.term_to_id_map {
+        writer.write_all(&(self.get_no_of_saved_terms() as u32).to_le_bytes())?;
+        for (term, id) in self.iter_terms() {
             writer.write_all(&(term.len() as u32).to_le_bytes())?;
             writer.write_all(term.as_bytes())?;
             writer.write_all(&id.to_le_bytes())?;
         }
+
+        // the chunk counts, collection frequencies, scoring model and BM25 parameters come last so
+        // that an index without them can still be loaded
+        writer.write_all(&(self.term_block_chunk_counts.len() as u32).to_le_bytes())?;
+        for chunk_count in self.term_block_chunk_counts.iter() {
+            writer.write_all(&chunk_count.to_le_bytes())?;
+        }
+        writer.write_all(&(self.term_collection_frequencies.len() as u32).to_le_bytes())?;
+        for collection_frequency in self.term_collection_frequencies.iter() {
+            writer.write_all(&collection_frequency.to_le_bytes())?;
+        }
+        writer.write_all(&[self.scoring_model.get_id()])?;
+        writer.write_all(&self.bm25_params.k1.to_le_bytes())?;
+        writer.write_all(&self.bm25_params.b.to_le_bytes())?;
         writer.flush()?;
         Ok(())
     }
 
+    // Loads term_metadata.sidx of an index which was saved before terms.sidx, it is read in full
     pub fn load_term_metadata<R: Read>(&mut self, mut reader: R) -> io::Result<()> {
         let mut buf = [0u8; 4];
         reader.read_exact(&mut buf)?;
@@ -104,7 +191,8 @@ impl InMemoryIndexMetadata {
         let mut term_frequencies = Vec::with_capacity(num_terms);
         let mut term_max_scores = Vec::with_capacity(num_terms);
         let mut term_block_id_offsets = Vec::with_capacity(num_terms);
-        let mut term_block_max_metadata = Vec::with_capacity(num_terms);
+        let mut term_chunk_offsets = Vec::with_capacity(num_terms);
+        let mut chunk_block_max_metadata = Vec::new();
 
         for _ in 0..num_terms {
             reader.read_exact(&mut buf)?;
@@ -112,12 +200,11 @@ impl InMemoryIndexMetadata {
             reader.read_exact(&mut buf)?;
             term_max_scores.push(f32::from_le_bytes(buf));
             reader.read_exact(&mut buf)?;
-            let stored_offset = u32::from_le_bytes(buf) as usize;
-            term_block_id_offsets.push(stored_offset);
+            term_block_id_offsets.push(u32::from_le_bytes(buf));
 
             reader.read_exact(&mut buf)?;
             let num_chunks = u32::from_le_bytes(buf) as usize;
-            let mut chunks = Vec::with_capacity(num_chunks);
+            term_chunk_offsets.push(chunk_block_max_metadata.len() as u32);
             for _ in 0..num_chunks {
                 reader.read_exact(&mut buf)?;
                 let chunk_last_doc_id = u32::from_le_bytes(buf);
@@ -125,18 +212,18 @@ impl InMemoryIndexMetadata {
                 reader.read_exact(&mut buf)?;
                 let chunk_max_term_score = f32::from_le_bytes(buf);
 
-                chunks.push(ChunkBlockMaxMetadata {
+                chunk_block_max_metadata.push(ChunkBlockMaxMetadata {
                     chunk_last_doc_id,
                     chunk_max_term_score,
                 });
             }
-            term_block_max_metadata.push(chunks.into_boxed_slice());
         }
 
-        self.term_frequencies = term_frequencies;
-        self.term_block_id_offsets = term_block_id_offsets;
-        self.term_max_scores = term_max_scores;
-        self.term_block_max_metadata = term_block_max_metadata;
+        self.term_frequencies = term_frequencies.into();
+        self.term_block_id_offsets = term_block_id_offsets.into();
+        self.term_max_scores = term_max_scores.into();
+        self.term_chunk_offsets = term_chunk_offsets.into();
+        self.chunk_block_max_metadata = chunk_block_max_metadata.into();
 
         reader.read_exact(&mut buf)?;
         let block_id_length = u32::from_le_bytes(buf) as usize;
@@ -146,7 +233,7 @@ impl InMemoryIndexMetadata {
             term_block_ids.push(u32::from_le_bytes(buf));
         }
 
-        self.term_block_ids = term_block_ids;
+        self.term_block_ids = term_block_ids.into();
 
         reader.read_exact(&mut buf)?;
         let map_size = u32::from_le_bytes(buf) as usize;
@@ -168,52 +255,327 @@ impl InMemoryIndexMetadata {
         }
 
         self.term_to_id_map = term_to_id_map;
-        assert_eq!(
-            self.term_block_id_offsets.len(),
-            self.term_frequencies.len()
-        );
-        assert_eq!(self.term_max_scores.len(), self.term_frequencies.len());
-        assert_eq!(self.term_to_id_map.len(), self.term_frequencies.len());
-        assert_eq!(
-            self.term_block_max_metadata.len(),
-            self.term_frequencies.len()
-        );
+        self.lexicon = Lexicon::new();
+        self.build_lexicon();
+        // the BK-tree of the terms is loaded separately
+        self.bk_tree = BkTree::new();
+
+        self.term_block_chunk_counts = Column::new();
+        self.term_collection_frequencies = Column::new();
+        // an index saved before BM25 could be swapped out or tuned was always scored with the
+        // default parameters
+        self.scoring_model = ScoringModel::BM25;
+        self.bm25_params = BM25Params::default();
+        if let Some(chunk_counts_length) = read_optional_u32(&mut reader)? {
+            let term_block_chunk_counts = self.term_block_chunk_counts.to_mut();
+            term_block_chunk_counts.reserve(chunk_counts_length as usize);
+            for _ in 0..chunk_counts_length {
+                reader.read_exact(&mut buf)?;
+                term_block_chunk_counts.push(u32::from_le_bytes(buf));
+            }
+        }
+        if let Some(collection_frequencies_length) = read_optional_u32(&mut reader)? {
+            let term_collection_frequencies = self.term_collection_frequencies.to_mut();
+            term_collection_frequencies.reserve(collection_frequencies_length as usize);
+            let mut collection_frequency_buf = [0u8; 8];
+            for _ in 0..collection_frequencies_length {
+                reader.read_exact(&mut collection_frequency_buf)?;
+                term_collection_frequencies.push(u64::from_le_bytes(collection_frequency_buf));
+            }
+            let mut scoring_model_buf = [0u8; 1];
+            reader.read_exact(&mut scoring_model_buf)?;
+            self.scoring_model = ScoringModel::from_id(scoring_model_buf[0]).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("unknown scoring model {}", scoring_model_buf[0]),
+                )
+            })?;
+            if let Some(k1) = read_optional_u32(&mut reader)? {
+                reader.read_exact(&mut buf)?;
+                self.bm25_params = BM25Params {
+                    k1: f32::from_bits(k1),
+                    b: f32::from_le_bytes(buf),
+                };
+            }
+        }
+        self.check_columns()
+    }
+
+    // The header of terms.sidx starts with the magic and the format version, then holds the
+    // counts, the scoring model and the BM25 parameters followed by the offset, the length and
+    // the checksum of every column, and ends with a checksum of itself. The columns follow the
+    // header one after the other, each of them starting at a multiple of COLUMN_ALIGNMENT.
+    pub fn save_terms<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        // the terms which were set after the lexicon was built are saved along with it
+        let merged_lexicon;
+        let lexicon = if self.term_to_id_map.is_empty() {
+            &self.lexicon
+        } else {
+            let terms: Vec<(String, u32)> = self.iter_terms().collect();
+            merged_lexicon = Lexicon::from_terms(
+                terms
+                    .iter()
+                    .map(|(term, term_id)| (term.as_str(), *term_id)),
+            );
+            &merged_lexicon
+        };
+        let (lexicon_blocks, lexicon_block_offsets, lexicon_term_ids) = lexicon.get_columns();
+        let columns: [&dyn SavedColumn; NO_OF_COLUMNS] = [
+            &self.term_frequencies,
+            &self.term_collection_frequencies,
+            &self.term_max_scores,
+            &self.term_block_id_offsets,
+            &self.term_block_ids,
+            &self.term_block_chunk_counts,
+            &self.term_chunk_offsets,
+            &self.chunk_block_max_metadata,
+            lexicon_blocks,
+            lexicon_block_offsets,
+            lexicon_term_ids,
+        ];
+
+        let mut header = Vec::with_capacity(TERMS_HEADER_LEN);
+        write_file_header(&mut header, &TERMS_MAGIC)?;
+        header.extend_from_slice(&self.no_of_blocks.to_le_bytes());
+        header.extend_from_slice(&self.no_of_terms.to_le_bytes());
+        header.extend_from_slice(&(lexicon.len() as u32).to_le_bytes());
+        header.extend_from_slice(&[self.scoring_model.get_id(), 0, 0, 0]);
+        header.extend_from_slice(&self.bm25_params.k1.to_le_bytes());
+        header.extend_from_slice(&self.bm25_params.b.to_le_bytes());
+        let mut offset = align(TERMS_HEADER_LEN);
+        for column in columns {
+            header.extend_from_slice(&(offset as u64).to_le_bytes());
+            header.extend_from_slice(&(column.get_len() as u64).to_le_bytes());
+            header.extend_from_slice(&get_column_checksum(column)?.to_le_bytes());
+            header.extend_from_slice(&[0; 4]);
+            offset = align(offset + column.get_byte_len());
+        }
+        let header_checksum = crc32fast::hash(&header);
+        header.extend_from_slice(&header_checksum.to_le_bytes());
+        writer.write_all(&header)?;
+
+        let mut written = TERMS_HEADER_LEN;
+        for column in columns {
+            writer.write_all(&vec![0; align(written) - written])?;
+            written = align(written);
+            column.write_to(&mut writer)?;
+            written += column.get_byte_len();
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    // A new file is written next to the old one and renamed over it, the old file may still be
+    // mapped by the segment which is being saved
+    pub fn save_terms_file(&self, terms_path: &Path) -> io::Result<()> {
+        let temp_path = terms_path.with_extension("sidx.tmp");
+        self.save_terms(BufWriter::new(File::create(&temp_path)?))?;
+        fs::rename(&temp_path, terms_path)
+    }
+
+    pub fn open_terms_file(terms_path: &Path) -> io::Result<Self> {
+        let file = File::open(terms_path)?;
+        // SAFETY: terms.sidx is only ever replaced by renaming a new file over it, the mapped
+        // file itself is never modified
+        let mmap = Arc::new(unsafe { Mmap::map(&file)? });
+
+        let file_name = terms_path.display().to_string();
+        let mut reader = &mmap[..];
+        read_file_header(&mut reader, &TERMS_MAGIC, &file_name)?;
+        // only the header is checked when the file is opened, the columns are checked when all
+        // of them are read anyway
+        if mmap.len() < TERMS_HEADER_LEN
+            || crc32fast::hash(&mmap[..TERMS_HEADER_LEN - 4])
+                != u32::from_le_bytes(
+                    mmap[TERMS_HEADER_LEN - 4..TERMS_HEADER_LEN]
+                        .try_into()
+                        .unwrap(),
+                )
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("the header of {} does not match its checksum", file_name),
+            ));
+        }
+        let mut buf = [0u8; 4];
+        let mut read_u32 = |reader: &mut &[u8]| -> io::Result<u32> {
+            reader.read_exact(&mut buf)?;
+            Ok(u32::from_le_bytes(buf))
+        };
+        let no_of_blocks = read_u32(&mut reader)?;
+        let no_of_terms = read_u32(&mut reader)?;
+        let lexicon_len = read_u32(&mut reader)? as usize;
+        let scoring_model_id = read_u32(&mut reader)?.to_le_bytes()[0];
+        let scoring_model = ScoringModel::from_id(scoring_model_id).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unknown scoring model {}", scoring_model_id),
+            )
+        })?;
+        let bm25_params = BM25Params {
+            k1: f32::from_bits(read_u32(&mut reader)?),
+            b: f32::from_bits(read_u32(&mut reader)?),
+        };
+        let mut sections = [(0usize, 0usize); NO_OF_COLUMNS];
+        let mut column_checksums = [0u32; NO_OF_COLUMNS];
+        let mut section_buf = [0u8; 8];
+        for (section, column_checksum) in sections.iter_mut().zip(column_checksums.iter_mut()) {
+            reader.read_exact(&mut section_buf)?;
+            let offset = u64::from_le_bytes(section_buf) as usize;
+            reader.read_exact(&mut section_buf)?;
+            *section = (offset, u64::from_le_bytes(section_buf) as usize);
+            reader.read_exact(&mut section_buf)?;
+            *column_checksum = u32::from_le_bytes(section_buf[..4].try_into().unwrap());
+        }
+        let [
+            term_frequencies,
+            term_collection_frequencies,
+            term_max_scores,
+            term_block_id_offsets,
+            term_block_ids,
+            term_block_chunk_counts,
+            term_chunk_offsets,
+            chunk_block_max_metadata,
+            lexicon_blocks,
+            lexicon_block_offsets,
+            lexicon_term_ids,
+        ] = sections;
+
+        let in_memory_index_metadata = Self {
+            no_of_blocks,
+            no_of_terms,
+            bk_tree: BkTree::new(),
+            scoring_model,
+            bm25_params,
+            lexicon: Lexicon::from_columns(
+                lexicon_len,
+                map_column(&mmap, lexicon_blocks)?,
+                map_column(&mmap, lexicon_block_offsets)?,
+                map_column(&mmap, lexicon_term_ids)?,
+            ),
+            term_to_id_map: HashMap::new(),
+            term_frequencies: map_column(&mmap, term_frequencies)?,
+            term_collection_frequencies: map_column(&mmap, term_collection_frequencies)?,
+            term_max_scores: map_column(&mmap, term_max_scores)?,
+            term_block_id_offsets: map_column(&mmap, term_block_id_offsets)?,
+            term_block_ids: map_column(&mmap, term_block_ids)?,
+            term_block_chunk_counts: map_column(&mmap, term_block_chunk_counts)?,
+            term_chunk_offsets: map_column(&mmap, term_chunk_offsets)?,
+            chunk_block_max_metadata: map_column(&mmap, chunk_block_max_metadata)?,
+            column_checksums: Some(column_checksums),
+        };
+        in_memory_index_metadata.check_columns()?;
+        Ok(in_memory_index_metadata)
+    }
+
+    // Reads every column and compares it with the checksum it was saved with. The metadata which
+    // was not opened from terms.sidx has no checksums to compare with.
+    pub fn check_checksums(&self) -> io::Result<()> {
+        let Some(column_checksums) = self.column_checksums else {
+            return Ok(());
+        };
+        let (lexicon_blocks, lexicon_block_offsets, lexicon_term_ids) = self.lexicon.get_columns();
+        let columns: [&dyn SavedColumn; NO_OF_COLUMNS] = [
+            &self.term_frequencies,
+            &self.term_collection_frequencies,
+            &self.term_max_scores,
+            &self.term_block_id_offsets,
+            &self.term_block_ids,
+            &self.term_block_chunk_counts,
+            &self.term_chunk_offsets,
+            &self.chunk_block_max_metadata,
+            lexicon_blocks,
+            lexicon_block_offsets,
+            lexicon_term_ids,
+        ];
+        for (column_no, (column, checksum)) in columns.into_iter().zip(column_checksums).enumerate()
+        {
+            if get_column_checksum(column)? != checksum {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "column {} of the term metadata does not match its checksum",
+                        column_no
+                    ),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    // every term has a value in each of the per term columns
+    fn check_columns(&self) -> io::Result<()> {
+        let no_of_terms = self.term_frequencies.len();
+        if self.term_max_scores.len() != no_of_terms
+            || self.term_block_id_offsets.len() != no_of_terms
+            || self.term_chunk_offsets.len() != no_of_terms
+            || self.get_no_of_saved_terms() != no_of_terms
+            || !(self.term_collection_frequencies.is_empty()
+                || self.term_collection_frequencies.len() == no_of_terms)
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "the columns of the term metadata have different lengths",
+            ));
+        }
         Ok(())
     }
 
     // should save memory hopefully
     pub fn close(&mut self) {
         self.term_block_id_offsets.shrink_to_fit();
-        self.term_block_max_metadata.shrink_to_fit();
+        self.term_chunk_offsets.shrink_to_fit();
+        self.chunk_block_max_metadata.shrink_to_fit();
         self.term_frequencies.shrink_to_fit();
+        self.term_collection_frequencies.shrink_to_fit();
         self.term_block_ids.shrink_to_fit();
+        self.term_block_chunk_counts.shrink_to_fit();
         self.term_max_scores.shrink_to_fit();
-        self.term_to_id_map.shrink_to_fit();
+        self.build_lexicon();
     }
+
+    // Moves the terms which have been added to the lexicon
+    fn build_lexicon(&mut self) {
+        if self.term_to_id_map.is_empty() {
+            return;
+        }
+        let terms: Vec<(String, u32)> = self.iter_terms().collect();
+        self.lexicon = Lexicon::from_terms(
+            terms
+                .iter()
+                .map(|(term, term_id)| (term.as_str(), *term_id)),
+        );
+        self.term_to_id_map = HashMap::new();
+    }
+
     pub fn get_term_metadata<'a>(&'a self, term: &str) -> Option<InMemoryTermMetadata<'a>> {
         let term_id = self.get_term_id(term);
         if term_id == 0 {
             return None;
         }
         let term_frequency = self.get_term_frequency(term_id);
+        let collection_frequency = self.get_collection_frequency(term_id);
         let max_score = self.get_max_term_score(term_id);
         let block_ids = self.get_block_ids(term_id);
         let chunk_block_max_metadata = self.get_chunk_block_max_metadata(term_id);
+        let block_chunk_counts = self.get_block_chunk_counts(term_id);
         Some(InMemoryTermMetadata::new(
             term_id,
             term_frequency,
+            collection_frequency,
             max_score,
             block_ids,
             chunk_block_max_metadata,
+            block_chunk_counts,
         ))
     }
 
-    pub fn get_all_terms(&self) -> Vec<&str> {
-        let mut keys = Vec::with_capacity(self.term_to_id_map.len());
-        for (key, _) in &self.term_to_id_map {
-            keys.push(key.as_str());
-        }
-        keys
+    // Every term along with its id, the lexicon is decoded each time so the terms are not kept in
+    // memory once the caller is done with them
+    pub fn get_all_terms(&self) -> Vec<(String, u32)> {
+        let mut terms = Vec::with_capacity(self.get_no_of_saved_terms());
+        terms.extend(self.iter_terms());
+        terms
     }
 
     pub fn set_term_id(&mut self, term: String, term_id: u32) {
@@ -221,17 +583,25 @@ impl InMemoryIndexMetadata {
     }
 
     pub fn get_term_id(&self, term: &str) -> u32 {
-        if let Some(pointer) = self.term_to_id_map.get(term) {
-            *pointer
-        } else {
-            0
+        if let Some(term_id) = self.term_to_id_map.get(term) {
+            return *term_id;
         }
+        self.lexicon.get_term_id(term).unwrap_or(0)
     }
 
     pub fn get_term_frequency(&self, term_id: u32) -> u32 {
         self.term_frequencies[(term_id - 1) as usize]
     }
 
+    // Every document containing the term has at least one occurrence of it, so the document
+    // frequency stands in for the collection frequency of an index which does not have it
+    pub fn get_collection_frequency(&self, term_id: u32) -> u64 {
+        match self.term_collection_frequencies.get((term_id - 1) as usize) {
+            Some(collection_frequency) => *collection_frequency,
+            None => self.get_term_frequency(term_id) as u64,
+        }
+    }
+
     pub fn get_max_term_score(&self, term_id: u32) -> f32 {
         self.term_max_scores[(term_id - 1) as usize]
     }
@@ -240,43 +610,78 @@ impl InMemoryIndexMetadata {
         self.bk_tree.add(&term);
     }
 
+    // The BK-tree is saved to a file of its own next to the term metadata, an index which was
+    // saved without it can not suggest spelling corrections
+    pub fn save_bk_tree<W: Write>(&self, writer: W) -> io::Result<()> {
+        self.bk_tree.save(writer, |term| self.get_term_id(term))
+    }
+
+    pub fn load_bk_tree(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.bk_tree.load(bytes)
+    }
+
     pub fn set_chunk_block_max_metadata(
         &mut self,
         chunk_block_max_metadata: Vec<ChunkBlockMaxMetadata>,
     ) {
-        let boxed: Box<[ChunkBlockMaxMetadata]> = chunk_block_max_metadata.into();
-        self.term_block_max_metadata.push(boxed);
+        self.term_chunk_offsets
+            .push(self.chunk_block_max_metadata.len() as u32);
+        self.chunk_block_max_metadata
+            .to_mut()
+            .extend(chunk_block_max_metadata);
     }
 
-    pub fn get_chunk_block_max_metadata(
-        &self,
-        term_id: u32,
-    ) -> Option<&Box<[ChunkBlockMaxMetadata]>> {
-        if self.term_block_max_metadata.len() > (term_id - 1) as usize {
-            return Some(&self.term_block_max_metadata[(term_id - 1) as usize]);
-        }
-        None
+    pub fn get_chunk_block_max_metadata(&self, term_id: u32) -> Option<&[ChunkBlockMaxMetadata]> {
+        let term_index = (term_id - 1) as usize;
+        let start = *self.term_chunk_offsets.get(term_index)? as usize;
+        let end = match self.term_chunk_offsets.get(term_index + 1) {
+            Some(&end) => end as usize,
+            None => self.chunk_block_max_metadata.len(),
+        };
+        Some(&self.chunk_block_max_metadata[start..end])
     }
 
     pub fn set_term_frequency(&mut self, term_frequency: u32) {
         self.term_frequencies.push(term_frequency)
     }
 
+    pub fn set_collection_frequency(&mut self, collection_frequency: u64) {
+        self.term_collection_frequencies.push(collection_frequency)
+    }
+
     pub fn set_max_term_score(&mut self, max_term_score: f32) {
         self.term_max_scores.push(max_term_score)
     }
 
     pub fn set_block_ids(&mut self, block_ids: Vec<u32>) {
-        self.term_block_id_offsets.push(self.term_block_ids.len());
-        self.term_block_ids.extend(block_ids);
+        self.term_block_id_offsets
+            .push(self.term_block_ids.len() as u32);
+        self.term_block_ids.to_mut().extend(block_ids);
+    }
+
+    // Has to be called right after set_block_ids with a count for every block id
+    pub fn set_block_chunk_counts(&mut self, block_chunk_counts: &[u32]) {
+        self.term_block_chunk_counts
+            .to_mut()
+            .extend_from_slice(block_chunk_counts);
+    }
+
+    // None if the chunk counts were not kept track of when the index was written
+    pub fn get_block_chunk_counts(&self, term_id: u32) -> Option<&[u32]> {
+        if self.term_block_chunk_counts.len() != self.term_block_ids.len() {
+            return None;
+        }
+        let term_offset_start = self.term_block_id_offsets[term_id as usize - 1] as usize;
+        let no_of_blocks = self.get_block_ids(term_id).len();
+        Some(&self.term_block_chunk_counts[term_offset_start..term_offset_start + no_of_blocks])
     }
 
     pub fn get_block_ids(&self, term_id: u32) -> &[u32] {
         let term_id = term_id as usize;
 
-        let term_offset_start = self.term_block_id_offsets[term_id - 1];
+        let term_offset_start = self.term_block_id_offsets[term_id - 1] as usize;
         let term_offset_end = match term_id > self.term_block_id_offsets.len() - 1 {
-            false => self.term_block_id_offsets[term_id],
+            false => self.term_block_id_offsets[term_id] as usize,
             true => self.term_block_ids.len(),
         };
 
@@ -284,6 +689,56 @@ impl InMemoryIndexMetadata {
     }
 }
 
+// Merges iterators of sorted terms into one iterator of the distinct terms in lexicographic order.
+// Only the current term of every iterator is kept in memory.
+pub fn merge_sorted_terms<I: Iterator<Item = String>>(
+    mut term_iterators: Vec<I>,
+) -> impl Iterator<Item = String> {
+    let mut heap = BinaryHeap::with_capacity(term_iterators.len());
+    for (i, term_iterator) in term_iterators.iter_mut().enumerate() {
+        if let Some(term) = term_iterator.next() {
+            heap.push(Reverse((term, i)));
+        }
+    }
+    let mut last_term: Option<String> = None;
+    std::iter::from_fn(move || {
+        while let Some(Reverse((term, i))) = heap.pop() {
+            if let Some(next_term) = term_iterators[i].next() {
+                heap.push(Reverse((next_term, i)));
+            }
+            if last_term.as_ref() != Some(&term) {
+                last_term = Some(term.clone());
+                return Some(term);
+            }
+        }
+        None
+    })
+}
+
+fn align(offset: usize) -> usize {
+    offset.next_multiple_of(COLUMN_ALIGNMENT)
+}
+
+fn get_column_checksum(column: &dyn SavedColumn) -> io::Result<u32> {
+    let mut writer = ChecksumWriter::new(io::sink());
+    column.write_to(&mut writer)?;
+    Ok(writer.get_checksum())
+}
+
+fn map_column<T: Pod>(mmap: &Arc<Mmap>, (offset, len): (usize, usize)) -> io::Result<Column<T>> {
+    Column::map(mmap, offset, len)
+}
+
+// None if the reader is already at the end, for the parts of the file which older indexes do not have
+fn read_optional_u32<R: Read>(reader: &mut R) -> io::Result<Option<u32>> {
+    let mut buf = [0u8; 4];
+    match reader.read_exact(&mut buf) {
+        Ok(()) => Ok(Some(u32::from_le_bytes(buf))),
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -320,9 +775,9 @@ mod tests {
 
         let terms = in_memory_index_metadata.get_all_terms();
         assert_eq!(terms.len(), 3);
-        assert!(terms.contains(&"apple"));
-        assert!(terms.contains(&"banana"));
-        assert!(terms.contains(&"cherry"));
+        assert!(terms.contains(&("apple".to_string(), 1)));
+        assert!(terms.contains(&("banana".to_string(), 2)));
+        assert!(terms.contains(&("cherry".to_string(), 3)));
     }
 
     #[test]
@@ -354,7 +809,11 @@ mod tests {
         assert_eq!(term_meta.block_ids, vec![5, 10, 15]);
 
         // Verify it's in the terms list
-        assert!(in_memory_index_metadata.get_all_terms().contains(&"rust"));
+        assert!(
+            in_memory_index_metadata
+                .get_all_terms()
+                .contains(&("rust".to_string(), 1))
+        );
     }
 
     #[test]
@@ -389,6 +848,28 @@ mod tests {
         assert_eq!(terms.len(), 3);
     }
 
+    #[test]
+    fn test_merge_sorted_terms() {
+        let terms: Vec<String> = merge_sorted_terms(vec![
+            vec!["apple", "cherry", "fig"].into_iter().map(String::from),
+            vec![].into_iter().map(String::from),
+            vec!["banana", "cherry", "grape"]
+                .into_iter()
+                .map(String::from),
+            vec!["apple", "fig"].into_iter().map(String::from),
+        ])
+        .collect();
+        assert_eq!(terms, vec!["apple", "banana", "cherry", "fig", "grape"]);
+
+        // the terms which have not been moved to the lexicon are merged with the ones which have
+        let mut in_memory_index_metadata = InMemoryIndexMetadata::new();
+        in_memory_index_metadata.set_term_id("cherry".to_string(), 1);
+        in_memory_index_metadata.set_term_id("apple".to_string(), 2);
+        in_memory_index_metadata.set_term_id("banana".to_string(), 3);
+        let terms: Vec<String> = in_memory_index_metadata.iter_sorted_terms().collect();
+        assert_eq!(terms, vec!["apple", "banana", "cherry"]);
+    }
+
     #[test]
     fn test_index_with_complex_metadata() {
         let mut in_memory_index_metadata = InMemoryIndexMetadata::new();
@@ -416,4 +897,201 @@ mod tests {
         assert_eq!(term_meta.block_ids, vec![1, 5, 10, 15, 20]);
         assert_eq!(term_meta.chunk_block_max_metadata.unwrap().to_vec(), chunks);
     }
+
+    #[test]
+    fn test_save_and_load_block_chunk_counts() {
+        let mut in_memory_index_metadata = InMemoryIndexMetadata::new();
+        for (term, term_id, block_ids, chunk_counts) in [
+            ("alpha", 1, vec![0], vec![1]),
+            ("beta", 2, vec![0, 1, 2], vec![2, 5, 1]),
+        ] {
+            in_memory_index_metadata.set_term_id(term.to_string(), term_id);
+            in_memory_index_metadata.set_term_frequency(10);
+            in_memory_index_metadata.set_max_term_score(1.0);
+            in_memory_index_metadata.set_chunk_block_max_metadata(Vec::new());
+            in_memory_index_metadata.set_block_ids(block_ids);
+            in_memory_index_metadata.set_block_chunk_counts(&chunk_counts);
+        }
+
+        let mut bytes = Vec::new();
+        in_memory_index_metadata
+            .save_term_metadata(&mut bytes)
+            .unwrap();
+        let mut loaded_metadata = InMemoryIndexMetadata::new();
+        loaded_metadata
+            .load_term_metadata(bytes.as_slice())
+            .unwrap();
+        assert_eq!(
+            loaded_metadata
+                .get_term_metadata("beta")
+                .unwrap()
+                .block_chunk_counts,
+            Some([2, 5, 1].as_slice())
+        );
+
+        // an index saved without the chunk counts is still loaded, without them. The chunk counts
+        // are followed by no collection frequencies, the scoring model and the BM25 parameters.
+        bytes.truncate(bytes.len() - 4 * 5 - 4 - 1 - 8);
+        let mut loaded_metadata = InMemoryIndexMetadata::new();
+        loaded_metadata
+            .load_term_metadata(bytes.as_slice())
+            .unwrap();
+        let term_metadata = loaded_metadata.get_term_metadata("beta").unwrap();
+        assert_eq!(term_metadata.block_ids, vec![0, 1, 2]);
+        assert_eq!(term_metadata.block_chunk_counts, None);
+    }
+
+    #[test]
+    fn test_save_and_load_collection_frequencies() {
+        let mut in_memory_index_metadata = InMemoryIndexMetadata::new();
+        in_memory_index_metadata.scoring_model = ScoringModel::DirichletLM;
+        for (term, term_id, collection_frequency) in [("alpha", 1, 12), ("beta", 2, 40)] {
+            in_memory_index_metadata.set_term_id(term.to_string(), term_id);
+            in_memory_index_metadata.set_term_frequency(10);
+            in_memory_index_metadata.set_collection_frequency(collection_frequency);
+            in_memory_index_metadata.set_max_term_score(1.0);
+            in_memory_index_metadata.set_chunk_block_max_metadata(Vec::new());
+            in_memory_index_metadata.set_block_ids(vec![0]);
+            in_memory_index_metadata.set_block_chunk_counts(&[1]);
+        }
+
+        let mut bytes = Vec::new();
+        in_memory_index_metadata
+            .save_term_metadata(&mut bytes)
+            .unwrap();
+        let mut loaded_metadata = InMemoryIndexMetadata::new();
+        loaded_metadata
+            .load_term_metadata(bytes.as_slice())
+            .unwrap();
+        assert_eq!(loaded_metadata.scoring_model, ScoringModel::DirichletLM);
+        assert_eq!(
+            loaded_metadata
+                .get_term_metadata("beta")
+                .unwrap()
+                .collection_frequency,
+            40
+        );
+
+        // without the collection frequencies the document frequency is used and the index is
+        // taken to be scored with BM25
+        bytes.truncate(bytes.len() - 4 - 8 * 2 - 1 - 8);
+        let mut loaded_metadata = InMemoryIndexMetadata::new();
+        loaded_metadata
+            .load_term_metadata(bytes.as_slice())
+            .unwrap();
+        assert_eq!(loaded_metadata.scoring_model, ScoringModel::BM25);
+        assert_eq!(
+            loaded_metadata
+                .get_term_metadata("beta")
+                .unwrap()
+                .collection_frequency,
+            10
+        );
+    }
+
+    #[test]
+    fn test_save_and_open_terms_file() {
+        let mut in_memory_index_metadata = InMemoryIndexMetadata::new();
+        in_memory_index_metadata.scoring_model = ScoringModel::BM25Plus;
+        in_memory_index_metadata.bm25_params = BM25Params { k1: 0.9, b: 0.4 };
+        for (term, term_id, chunks) in [
+            ("alpha", 1, vec![ChunkBlockMaxMetadata::new(4, 1.5)]),
+            (
+                "beta",
+                2,
+                vec![
+                    ChunkBlockMaxMetadata::new(8, 2.5),
+                    ChunkBlockMaxMetadata::new(9, 0.5),
+                ],
+            ),
+        ] {
+            in_memory_index_metadata.set_term_id(term.to_string(), term_id);
+            in_memory_index_metadata.set_term_frequency(term_id * 10);
+            in_memory_index_metadata.set_collection_frequency(term_id as u64 * 30);
+            in_memory_index_metadata.set_max_term_score(term_id as f32);
+            in_memory_index_metadata.set_chunk_block_max_metadata(chunks);
+            in_memory_index_metadata.set_block_ids(vec![0, term_id]);
+            in_memory_index_metadata.set_block_chunk_counts(&[1, term_id]);
+        }
+        in_memory_index_metadata.no_of_terms = 2;
+        in_memory_index_metadata.no_of_blocks = 3;
+        in_memory_index_metadata.close();
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let terms_path = temp_dir.path().join("terms.sidx");
+        in_memory_index_metadata
+            .save_terms_file(&terms_path)
+            .unwrap();
+        let loaded_metadata = InMemoryIndexMetadata::open_terms_file(&terms_path).unwrap();
+        assert_eq!(loaded_metadata.no_of_blocks, 3);
+        assert_eq!(loaded_metadata.scoring_model, ScoringModel::BM25Plus);
+        assert_eq!(loaded_metadata.bm25_params, BM25Params { k1: 0.9, b: 0.4 });
+        assert_eq!(loaded_metadata.get_term_id("gamma"), 0);
+        let term_metadata = loaded_metadata.get_term_metadata("beta").unwrap();
+        assert_eq!(
+            term_metadata,
+            in_memory_index_metadata.get_term_metadata("beta").unwrap()
+        );
+        assert_eq!(term_metadata.term_frequency, 20);
+        assert_eq!(term_metadata.collection_frequency, 60);
+        assert_eq!(term_metadata.block_ids, &[0, 2]);
+        assert_eq!(term_metadata.block_chunk_counts, Some([1, 2].as_slice()));
+        assert_eq!(term_metadata.chunk_block_max_metadata.unwrap().len(), 2);
+        let terms: Vec<String> = loaded_metadata.iter_sorted_terms().collect();
+        assert_eq!(terms, vec!["alpha", "beta"]);
+
+        // the file can be replaced while it is mapped
+        loaded_metadata.save_terms_file(&terms_path).unwrap();
+        assert_eq!(loaded_metadata.get_term_id("alpha"), 1);
+
+        let loaded_metadata = InMemoryIndexMetadata::open_terms_file(&terms_path).unwrap();
+        assert!(loaded_metadata.check_checksums().is_ok());
+        drop(loaded_metadata);
+
+        // a damaged column is only noticed once the columns are checked
+        let mut bytes = std::fs::read(&terms_path).unwrap();
+        // the term frequencies are the first column
+        let first_column = align(TERMS_HEADER_LEN);
+        bytes[first_column] ^= 1;
+        std::fs::write(&terms_path, &bytes).unwrap();
+        let loaded_metadata = InMemoryIndexMetadata::open_terms_file(&terms_path).unwrap();
+        let e = loaded_metadata.check_checksums().unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+        drop(loaded_metadata);
+
+        // a damaged header is noticed when the file is opened
+        bytes[first_column] ^= 1;
+        bytes[10] ^= 1;
+        std::fs::write(&terms_path, &bytes).unwrap();
+        let e = InMemoryIndexMetadata::open_terms_file(&terms_path)
+            .err()
+            .unwrap();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+
+        std::fs::write(&terms_path, [0u8; 12]).unwrap();
+        assert!(InMemoryIndexMetadata::open_terms_file(&terms_path).is_err());
+    }
+
+    #[test]
+    fn test_save_and_load_bm25_params() {
+        let mut in_memory_index_metadata = InMemoryIndexMetadata::new();
+        in_memory_index_metadata.bm25_params = BM25Params { k1: 0.9, b: 0.4 };
+
+        let mut bytes = Vec::new();
+        in_memory_index_metadata
+            .save_term_metadata(&mut bytes)
+            .unwrap();
+        let mut loaded_metadata = InMemoryIndexMetadata::new();
+        loaded_metadata
+            .load_term_metadata(bytes.as_slice())
+            .unwrap();
+        assert_eq!(loaded_metadata.bm25_params, BM25Params { k1: 0.9, b: 0.4 });
+
+        // an index saved without the parameters was built with the default ones
+        bytes.truncate(bytes.len() - 8);
+        loaded_metadata
+            .load_term_metadata(bytes.as_slice())
+            .unwrap();
+        assert_eq!(loaded_metadata.bm25_params, BM25Params::default());
+    }
 }
@@ -0,0 +1,361 @@
+use std::{cmp::Ordering, sync::OnceLock};
+
+use crate::{in_memory_index_metadata::column::Column, utils::wildcard::WildcardPattern};
+
+// the no of terms in a block of the lexicon, only the first term of a block is stored in full
+pub const TERMS_PER_BLOCK: usize = 16;
+
+// The terms of a segment in lexicographic order, front coded in blocks of TERMS_PER_BLOCK. The
+// first term of every block is stored in full and the rest as the length of the prefix they share
+// with the term before them followed by the rest of the term. A term is looked up by binary
+// searching the first terms of the blocks and scanning the block it falls in, prefix and wildcard
+// queries scan the range of blocks which start with the literal prefix of the pattern.
+#[derive(Debug, Default)]
+pub struct Lexicon {
+    len: usize,
+    blocks: Column<u8>,
+    // the offset of every block in blocks
+    block_offsets: Column<u32>,
+    // The id of every term in lexicographic order. The merge gives the terms ids in lexicographic
+    // order so the id of a term is its rank + 1 and this is left empty.
+    term_ids: Column<u32>,
+    // the rank of every term id - 1, only built when terms are looked up by the ids which are not
+    // their ranks
+    ranks_by_id: OnceLock<Vec<u32>>,
+}
+
+impl Lexicon {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_terms<'a, I: IntoIterator<Item = (&'a str, u32)>>(terms: I) -> Self {
+        let mut terms: Vec<(&str, u32)> = terms.into_iter().collect();
+        terms.sort_unstable_by(|a, b| a.0.cmp(b.0));
+
+        let mut blocks = Vec::new();
+        let mut block_offsets = Vec::with_capacity(terms.len().div_ceil(TERMS_PER_BLOCK));
+        let mut previous_term: &[u8] = &[];
+        for (rank, (term, _)) in terms.iter().enumerate() {
+            let term = term.as_bytes();
+            if rank.is_multiple_of(TERMS_PER_BLOCK) {
+                block_offsets.push(blocks.len() as u32);
+                write_varint(&mut blocks, term.len() as u32);
+                blocks.extend_from_slice(term);
+            } else {
+                let shared = term
+                    .iter()
+                    .zip(previous_term)
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                write_varint(&mut blocks, shared as u32);
+                write_varint(&mut blocks, (term.len() - shared) as u32);
+                blocks.extend_from_slice(&term[shared..]);
+            }
+            previous_term = term;
+        }
+
+        let ids_are_ranks = terms
+            .iter()
+            .enumerate()
+            .all(|(rank, &(_, term_id))| term_id as usize == rank + 1);
+        let term_ids = if ids_are_ranks {
+            Vec::new()
+        } else {
+            terms.iter().map(|&(_, term_id)| term_id).collect()
+        };
+        Self {
+            len: terms.len(),
+            blocks: blocks.into(),
+            block_offsets: block_offsets.into(),
+            term_ids: term_ids.into(),
+            ranks_by_id: OnceLock::new(),
+        }
+    }
+
+    pub fn from_columns(
+        len: usize,
+        blocks: Column<u8>,
+        block_offsets: Column<u32>,
+        term_ids: Column<u32>,
+    ) -> Self {
+        Self {
+            len,
+            blocks,
+            block_offsets,
+            term_ids,
+            ranks_by_id: OnceLock::new(),
+        }
+    }
+
+    pub fn get_columns(&self) -> (&Column<u8>, &Column<u32>, &Column<u32>) {
+        (&self.blocks, &self.block_offsets, &self.term_ids)
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn get_term_id_at(&self, rank: usize) -> u32 {
+        if self.term_ids.is_empty() {
+            rank as u32 + 1
+        } else {
+            self.term_ids[rank]
+        }
+    }
+
+    fn get_first_term(&self, block_no: usize) -> &[u8] {
+        let mut position = self.block_offsets[block_no] as usize;
+        let term_len = read_varint(&self.blocks, &mut position) as usize;
+        &self.blocks[position..position + term_len]
+    }
+
+    // the no of the block which the term would be in
+    fn find_block(&self, term: &[u8]) -> usize {
+        let (mut low, mut high) = (0, self.block_offsets.len());
+        while low < high {
+            let middle = (low + high) / 2;
+            if self.get_first_term(middle) <= term {
+                low = middle + 1;
+            } else {
+                high = middle;
+            }
+        }
+        low.saturating_sub(1)
+    }
+
+    pub fn get_term_id(&self, term: &str) -> Option<u32> {
+        if self.is_empty() {
+            return None;
+        }
+        let term = term.as_bytes();
+        let mut terms = self.iter_from_block(self.find_block(term));
+        while let Some((rank, current_term)) = terms.next_term() {
+            match current_term.cmp(term) {
+                Ordering::Less => {}
+                Ordering::Equal => return Some(self.get_term_id_at(rank)),
+                Ordering::Greater => return None,
+            }
+        }
+        None
+    }
+
+    // The term with the id, the term is decoded from the start of its block
+    pub fn get_term(&self, term_id: u32) -> Option<String> {
+        let rank = if self.term_ids.is_empty() {
+            (term_id as usize).checked_sub(1)?
+        } else {
+            let ranks_by_id = self.ranks_by_id.get_or_init(|| {
+                let max_term_id = self.term_ids.iter().copied().max().unwrap_or(0);
+                let mut ranks_by_id = vec![u32::MAX; max_term_id as usize];
+                for (rank, &term_id) in self.term_ids.iter().enumerate() {
+                    if let Some(rank_by_id) =
+                        ranks_by_id.get_mut((term_id as usize).wrapping_sub(1))
+                    {
+                        *rank_by_id = rank as u32;
+                    }
+                }
+                ranks_by_id
+            });
+            *ranks_by_id.get((term_id as usize).checked_sub(1)?)? as usize
+        };
+        if rank >= self.len {
+            return None;
+        }
+        self.iter_from_block(rank / TERMS_PER_BLOCK)
+            .nth(rank % TERMS_PER_BLOCK)
+            .map(|(term, _)| term)
+    }
+
+    pub fn iter(&self) -> LexiconIterator<'_> {
+        self.iter_from_block(0)
+    }
+
+    fn iter_from_block(&self, block_no: usize) -> LexiconIterator<'_> {
+        LexiconIterator {
+            lexicon: self,
+            rank: block_no * TERMS_PER_BLOCK,
+            position: 0,
+            term: Vec::new(),
+        }
+    }
+
+    pub fn find_prefix<'a>(&'a self, prefix: &'a str) -> impl Iterator<Item = (String, u32)> + 'a {
+        let block_no = if self.is_empty() {
+            0
+        } else {
+            self.find_block(prefix.as_bytes())
+        };
+        self.iter_from_block(block_no)
+            .skip_while(move |(term, _)| term.as_str() < prefix)
+            .take_while(move |(term, _)| term.starts_with(prefix))
+    }
+
+    pub fn find_wildcard<'a>(
+        &'a self,
+        pattern: &'a WildcardPattern,
+    ) -> impl Iterator<Item = (String, u32)> + 'a {
+        self.find_prefix(pattern.get_prefix())
+            .filter(|(term, _)| pattern.matches(term))
+    }
+}
+
+// Decodes the terms of the lexicon in order starting from the first term of a block
+pub struct LexiconIterator<'a> {
+    lexicon: &'a Lexicon,
+    rank: usize,
+    position: usize,
+    term: Vec<u8>,
+}
+
+impl LexiconIterator<'_> {
+    fn next_term(&mut self) -> Option<(usize, &[u8])> {
+        if self.rank >= self.lexicon.len {
+            return None;
+        }
+        let blocks = &self.lexicon.blocks;
+        if self.rank.is_multiple_of(TERMS_PER_BLOCK) {
+            self.position = self.lexicon.block_offsets[self.rank / TERMS_PER_BLOCK] as usize;
+            self.term.clear();
+        } else {
+            let shared = read_varint(blocks, &mut self.position) as usize;
+            self.term.truncate(shared);
+        }
+        let suffix_len = read_varint(blocks, &mut self.position) as usize;
+        self.term
+            .extend_from_slice(&blocks[self.position..self.position + suffix_len]);
+        self.position += suffix_len;
+        self.rank += 1;
+        Some((self.rank - 1, &self.term))
+    }
+}
+
+impl Iterator for LexiconIterator<'_> {
+    type Item = (String, u32);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (rank, term) = self.next_term()?;
+        let term = String::from_utf8_lossy(term).into_owned();
+        Some((term, self.lexicon.get_term_id_at(rank)))
+    }
+}
+
+fn write_varint(bytes: &mut Vec<u8>, mut value: u32) {
+    while value >= 0x80 {
+        bytes.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+fn read_varint(bytes: &[u8], position: &mut usize) -> u32 {
+    let mut value = 0;
+    let mut shift = 0;
+    loop {
+        let byte = bytes[*position];
+        *position += 1;
+        value |= ((byte & 0x7f) as u32) << shift;
+        if byte & 0x80 == 0 {
+            return value;
+        }
+        shift += 7;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_test_lexicon() -> Lexicon {
+        let terms: Vec<String> = (0..100).map(|i| format!("term{:03}", i)).collect();
+        Lexicon::from_terms(
+            terms
+                .iter()
+                .enumerate()
+                .map(|(i, term)| (term.as_str(), i as u32 + 1)),
+        )
+    }
+
+    #[test]
+    fn test_get_term_id() {
+        let lexicon = create_test_lexicon();
+        assert_eq!(lexicon.len(), 100);
+        // the ids are the ranks of the terms so they are not stored
+        assert!(lexicon.get_columns().2.is_empty());
+        for (i, term) in ["term000", "term015", "term016", "term099"]
+            .iter()
+            .enumerate()
+        {
+            let expected = [1, 16, 17, 100][i];
+            assert_eq!(lexicon.get_term_id(term), Some(expected));
+        }
+        assert_eq!(lexicon.get_term_id("term0155"), None);
+        assert_eq!(lexicon.get_term_id("aardvark"), None);
+        assert_eq!(lexicon.get_term_id("zebra"), None);
+        assert_eq!(Lexicon::new().get_term_id("term000"), None);
+
+        assert_eq!(lexicon.get_term(1).as_deref(), Some("term000"));
+        assert_eq!(lexicon.get_term(17).as_deref(), Some("term016"));
+        assert_eq!(lexicon.get_term(100).as_deref(), Some("term099"));
+        assert_eq!(lexicon.get_term(0), None);
+        assert_eq!(lexicon.get_term(101), None);
+    }
+
+    #[test]
+    fn test_term_ids_which_are_not_ranks() {
+        let lexicon = Lexicon::from_terms([("hello", 42), ("apple", 7), ("zebra", 1)]);
+        assert_eq!(lexicon.get_term_id("hello"), Some(42));
+        assert_eq!(lexicon.get_term_id("apple"), Some(7));
+        assert_eq!(lexicon.get_term(42).as_deref(), Some("hello"));
+        assert_eq!(lexicon.get_term(1).as_deref(), Some("zebra"));
+        assert_eq!(lexicon.get_term(2), None);
+        let terms: Vec<(String, u32)> = lexicon.iter().collect();
+        assert_eq!(
+            terms,
+            vec![
+                ("apple".to_string(), 7),
+                ("hello".to_string(), 42),
+                ("zebra".to_string(), 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_find_prefix_and_wildcard() {
+        let lexicon = Lexicon::from_terms([
+            ("astronomy", 1),
+            ("color", 2),
+            ("astro", 3),
+            ("colour", 4),
+            ("astronaut", 5),
+            ("comet", 6),
+            ("asteroid", 7),
+        ]);
+        let terms: Vec<(String, u32)> = lexicon.find_prefix("astro").collect();
+        assert_eq!(
+            terms,
+            vec![
+                ("astro".to_string(), 3),
+                ("astronaut".to_string(), 5),
+                ("astronomy".to_string(), 1)
+            ]
+        );
+        assert_eq!(lexicon.find_prefix("zebra").count(), 0);
+
+        let pattern = WildcardPattern::new("co*o*r").unwrap();
+        let terms: Vec<String> = lexicon
+            .find_wildcard(&pattern)
+            .map(|(term, _)| term)
+            .collect();
+        assert_eq!(terms, vec!["color", "colour"]);
+
+        // the range of a prefix spans several blocks
+        let lexicon = create_test_lexicon();
+        assert_eq!(lexicon.find_prefix("term0").count(), 100);
+        assert_eq!(lexicon.find_prefix("term01").count(), 10);
+    }
+}
@@ -1,2 +1,4 @@
 mod bk_tree;
+pub mod column;
 pub mod in_memory_index_metadata;
+pub mod lexicon;
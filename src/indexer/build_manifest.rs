@@ -0,0 +1,474 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    indexer::indexer::read_string,
+    utils::{
+        checksum::{ChecksumReader, ChecksumWriter},
+        index_format::{
+            BUILD_MANIFEST_MAGIC, RUN_DOCUMENTS_MAGIC, read_file_header, write_file_header,
+        },
+        paths::{get_build_manifest_path, get_run_documents_path},
+    },
+};
+
+// Where a build of the index started from, a build which was stopped is resumed after the
+// documents of the runs it had written
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct BuildReport {
+    pub no_of_resumed_docs: u32,
+    pub no_of_resumed_runs: usize,
+}
+
+impl BuildReport {
+    pub fn is_resumed(&self) -> bool {
+        self.no_of_resumed_runs > 0
+    }
+}
+
+// The lengths, title lengths, names and urls of documents in the order of their doc ids
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Documents {
+    pub lengths: Vec<u32>,
+    pub title_lengths: Vec<u32>,
+    pub names: Vec<String>,
+    pub urls: Vec<String>,
+}
+
+impl Documents {
+    pub fn len(&self) -> usize {
+        self.lengths.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lengths.is_empty()
+    }
+
+    pub fn append(&mut self, mut documents: Documents) {
+        self.lengths.append(&mut documents.lengths);
+        self.title_lengths.append(&mut documents.title_lengths);
+        self.names.append(&mut documents.names);
+        self.urls.append(&mut documents.urls);
+    }
+
+    fn write<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(&(self.len() as u32).to_le_bytes())?;
+        for i in 0..self.len() {
+            write_string(writer, &self.names[i])?;
+            write_string(writer, &self.urls[i])?;
+            writer.write_all(&self.lengths[i].to_le_bytes())?;
+            writer.write_all(&self.title_lengths[i].to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    fn read<R: Read>(reader: &mut R) -> io::Result<Self> {
+        let mut buffer: [u8; 4] = [0; 4];
+        reader.read_exact(&mut buffer)?;
+        let no_of_docs = u32::from_le_bytes(buffer);
+        let mut documents = Documents::default();
+        for _ in 0..no_of_docs {
+            documents.names.push(read_string(reader)?);
+            documents.urls.push(read_string(reader)?);
+            reader.read_exact(&mut buffer)?;
+            documents.lengths.push(u32::from_le_bytes(buffer));
+            reader.read_exact(&mut buffer)?;
+            documents.title_lengths.push(u32::from_le_bytes(buffer));
+        }
+        Ok(documents)
+    }
+}
+
+// How far a file of the dataset was read, the documents which were read include the ones which
+// were skipped for having no text
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FileProgress {
+    pub documents_read: u32,
+    pub is_finished: bool,
+}
+
+// A run is what was indexed between two flushes of the SPIMI dictionary, along with how far every
+// file which the run has documents of had been read when it was flushed
+#[derive(Debug, Clone, PartialEq)]
+struct BuildRun {
+    files: Vec<(String, FileProgress)>,
+    no_of_docs: u32,
+}
+
+// The build manifest is saved next to the temporary index files every time a run is written, along
+// with the metadata of the documents of the run in <run id>.tmpdocs. A build which is stopped
+// skips the files which its manifest records as finished when it is started again and continues
+// the others after the documents which were already indexed, and the temporary
+// index files can be merged after a restart since their document lengths are no longer only in
+// memory. A run which was not recorded in the manifest is incomplete and is removed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BuildManifest {
+    directory_path: PathBuf,
+    dataset_path: String,
+    // the no of documents which were indexed before the build, the doc ids of the build continue
+    // from them
+    first_doc_id: u32,
+    runs: Vec<BuildRun>,
+}
+
+impl BuildManifest {
+    pub fn new(directory_path: &Path, dataset_path: &Path, first_doc_id: u32) -> Self {
+        Self {
+            directory_path: directory_path.to_path_buf(),
+            dataset_path: dataset_path.to_string_lossy().into_owned(),
+            first_doc_id,
+            runs: Vec::new(),
+        }
+    }
+
+    // Returns None if there is no build in the directory
+    pub fn load(directory_path: &Path) -> io::Result<Option<Self>> {
+        let manifest_path = get_build_manifest_path(directory_path);
+        let file = match File::open(&manifest_path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let mut reader = BufReader::new(file);
+        let file_name = manifest_path.display().to_string();
+        read_file_header(&mut reader, &BUILD_MANIFEST_MAGIC, &file_name)?;
+        let mut reader = ChecksumReader::new(reader);
+
+        let mut buffer: [u8; 4] = [0; 4];
+        let dataset_path = read_string(&mut reader)?;
+        reader.read_exact(&mut buffer)?;
+        let first_doc_id = u32::from_le_bytes(buffer);
+        reader.read_exact(&mut buffer)?;
+        let no_of_runs = u32::from_le_bytes(buffer);
+        let mut runs = Vec::new();
+        for _ in 0..no_of_runs {
+            reader.read_exact(&mut buffer)?;
+            let no_of_docs = u32::from_le_bytes(buffer);
+            reader.read_exact(&mut buffer)?;
+            let no_of_files = u32::from_le_bytes(buffer);
+            let mut files = Vec::with_capacity(no_of_files as usize);
+            for _ in 0..no_of_files {
+                let file = read_string(&mut reader)?;
+                reader.read_exact(&mut buffer)?;
+                let documents_read = u32::from_le_bytes(buffer);
+                let mut is_finished = [0u8; 1];
+                reader.read_exact(&mut is_finished)?;
+                files.push((
+                    file,
+                    FileProgress {
+                        documents_read,
+                        is_finished: is_finished[0] == 1,
+                    },
+                ));
+            }
+            runs.push(BuildRun { files, no_of_docs });
+        }
+        reader.finish(&file_name)?;
+
+        Ok(Some(Self {
+            directory_path: directory_path.to_path_buf(),
+            dataset_path,
+            first_doc_id,
+            runs,
+        }))
+    }
+
+    // The manifest is written to a temporary file which is renamed over the old one, so a stopped
+    // build leaves either the old or the new manifest behind
+    pub fn save(&self) -> io::Result<()> {
+        let manifest_path = get_build_manifest_path(&self.directory_path);
+        let temp_path = manifest_path.with_extension("sidx.tmp");
+        let mut writer = BufWriter::new(File::create(&temp_path)?);
+        write_file_header(&mut writer, &BUILD_MANIFEST_MAGIC)?;
+        let mut writer = ChecksumWriter::new(writer);
+        write_string(&mut writer, &self.dataset_path)?;
+        writer.write_all(&self.first_doc_id.to_le_bytes())?;
+        writer.write_all(&(self.runs.len() as u32).to_le_bytes())?;
+        for run in &self.runs {
+            writer.write_all(&run.no_of_docs.to_le_bytes())?;
+            writer.write_all(&(run.files.len() as u32).to_le_bytes())?;
+            for (file, progress) in &run.files {
+                write_string(&mut writer, file)?;
+                writer.write_all(&progress.documents_read.to_le_bytes())?;
+                writer.write_all(&[progress.is_finished as u8])?;
+            }
+        }
+        let writer = writer.finish()?;
+        writer.get_ref().sync_all()?;
+        drop(writer);
+        fs::rename(temp_path, manifest_path)
+    }
+
+    pub fn get_directory_path(&self) -> &Path {
+        &self.directory_path
+    }
+
+    pub fn is_for_dataset(&self, dataset_path: &Path) -> bool {
+        self.dataset_path == dataset_path.to_string_lossy()
+    }
+
+    pub fn get_first_doc_id(&self) -> u32 {
+        self.first_doc_id
+    }
+
+    // the id of the run which is written next
+    pub fn get_next_run_id(&self) -> u32 {
+        self.runs.len() as u32
+    }
+
+    pub fn get_no_of_runs(&self) -> usize {
+        self.runs.len()
+    }
+
+    // the no of documents in the runs which have been written so far
+    pub fn get_no_of_docs(&self) -> u32 {
+        self.runs.iter().map(|run| run.no_of_docs).sum()
+    }
+
+    // how far every file had been read when the last run with documents of it was written
+    pub fn get_file_progress(&self) -> HashMap<String, FileProgress> {
+        self.runs
+            .iter()
+            .flat_map(|run| run.files.iter().cloned())
+            .collect()
+    }
+
+    // Records a run whose temporary index file has been written. The documents are written first,
+    // the run only counts once the manifest is saved.
+    pub fn add_run(
+        &mut self,
+        files: BTreeMap<PathBuf, FileProgress>,
+        documents: &Documents,
+    ) -> io::Result<()> {
+        let run_id = self.get_next_run_id();
+        let documents_path = get_run_documents_path(&self.directory_path, run_id);
+        let mut writer = BufWriter::new(File::create(&documents_path)?);
+        write_file_header(&mut writer, &RUN_DOCUMENTS_MAGIC)?;
+        let mut writer = ChecksumWriter::new(writer);
+        documents.write(&mut writer)?;
+        writer.finish()?.get_ref().sync_all()?;
+
+        self.runs.push(BuildRun {
+            files: files
+                .into_iter()
+                .map(|(file, progress)| (file.to_string_lossy().into_owned(), progress))
+                .collect(),
+            no_of_docs: documents.len() as u32,
+        });
+        self.save()
+    }
+
+    // The documents of all the runs in the order of their doc ids
+    pub fn load_documents(&self) -> io::Result<Documents> {
+        let mut documents = Documents::default();
+        for (run_id, run) in self.runs.iter().enumerate() {
+            let documents_path = get_run_documents_path(&self.directory_path, run_id as u32);
+            let file_name = documents_path.display().to_string();
+            let mut reader = BufReader::new(File::open(&documents_path)?);
+            read_file_header(&mut reader, &RUN_DOCUMENTS_MAGIC, &file_name)?;
+            let mut reader = ChecksumReader::new(reader);
+            let run_documents = Documents::read(&mut reader)?;
+            reader.finish(&file_name)?;
+            if run_documents.len() != run.no_of_docs as usize {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "{} has {} documents but the build manifest recorded {}",
+                        file_name,
+                        run_documents.len(),
+                        run.no_of_docs
+                    ),
+                ));
+            }
+            documents.append(run_documents);
+        }
+        Ok(documents)
+    }
+
+    // Removes the files of the runs which were being written when the build stopped, and the
+    // temporary index files of an older build which did not have a manifest
+    pub fn remove_unfinished_runs(&self) -> io::Result<()> {
+        for (path, run_id) in scan_run_files(&self.directory_path)? {
+            let is_finished = path.extension().is_some_and(|ext| ext != "part")
+                && run_id.is_some_and(|run_id| run_id < self.get_next_run_id());
+            if !is_finished {
+                fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Removes the manifest and the files of every run once the build is no longer needed
+    pub fn remove(directory_path: &Path) -> io::Result<()> {
+        for (path, _) in scan_run_files(directory_path)? {
+            fs::remove_file(path)?;
+        }
+        match fs::remove_file(get_build_manifest_path(directory_path)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+}
+
+// the files of the runs in the directory along with their run ids, if their names have one
+fn scan_run_files(directory_path: &Path) -> io::Result<Vec<(PathBuf, Option<u32>)>> {
+    let mut run_files = Vec::new();
+    for entry in fs::read_dir(directory_path)? {
+        let path = entry?.path();
+        let Some(file_name) = path.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        let Some(stem) = [".tmpidx", ".tmpidx.part", ".tmpdocs"]
+            .iter()
+            .find_map(|extension| file_name.strip_suffix(extension))
+        else {
+            continue;
+        };
+        let run_id = stem.parse().ok();
+        run_files.push((path, run_id));
+    }
+    Ok(run_files)
+}
+
+pub(crate) fn write_string<W: Write>(writer: &mut W, string: &str) -> io::Result<()> {
+    writer.write_all(&(string.len() as u32).to_le_bytes())?;
+    writer.write_all(string.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use crate::utils::paths::{get_run_path, get_unfinished_run_path};
+
+    use super::*;
+
+    fn create_documents(names: &[&str]) -> Documents {
+        Documents {
+            lengths: names.iter().map(|name| name.len() as u32 * 10).collect(),
+            title_lengths: names.iter().map(|_| 1).collect(),
+            names: names.iter().map(|name| name.to_string()).collect(),
+            urls: names
+                .iter()
+                .map(|name| format!("https://en.wikipedia.org/wiki/{}", name))
+                .collect(),
+        }
+    }
+
+    fn create_files(files: &[(&str, u32, bool)]) -> BTreeMap<PathBuf, FileProgress> {
+        files
+            .iter()
+            .map(|&(file, documents_read, is_finished)| {
+                (
+                    PathBuf::from(file),
+                    FileProgress {
+                        documents_read,
+                        is_finished,
+                    },
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_save_and_load_build_manifest() {
+        let temp_dir = TempDir::new().unwrap();
+        let dataset_path = Path::new("wikipedia");
+        assert_eq!(BuildManifest::load(temp_dir.path()).unwrap(), None);
+
+        let mut manifest = BuildManifest::new(temp_dir.path(), dataset_path, 0);
+        manifest
+            .add_run(
+                create_files(&[
+                    ("wikipedia/batch_0/batch_0.zst", 2, true),
+                    ("wikipedia/batch_1/batch_1.zst", 1, false),
+                ]),
+                &create_documents(&["Misery", "Movie", "Carrie"]),
+            )
+            .unwrap();
+        manifest
+            .add_run(
+                create_files(&[
+                    ("wikipedia/batch_1/batch_1.zst", 3, true),
+                    ("wikipedia/batch_2/batch_2.zst", 1, true),
+                ]),
+                &create_documents(&["Novel", "River", "Garden"]),
+            )
+            .unwrap();
+
+        let loaded_manifest = BuildManifest::load(temp_dir.path()).unwrap().unwrap();
+        assert_eq!(loaded_manifest, manifest);
+        assert!(loaded_manifest.is_for_dataset(dataset_path));
+        assert!(!loaded_manifest.is_for_dataset(Path::new("other")));
+        assert_eq!(loaded_manifest.get_no_of_docs(), 6);
+        assert_eq!(loaded_manifest.get_next_run_id(), 2);
+        // the progress of a file which is in several runs is the one of the last run
+        let file_progress = loaded_manifest.get_file_progress();
+        assert_eq!(file_progress.len(), 3);
+        assert_eq!(
+            file_progress["wikipedia/batch_1/batch_1.zst"],
+            FileProgress {
+                documents_read: 3,
+                is_finished: true
+            }
+        );
+        assert_eq!(
+            loaded_manifest.load_documents().unwrap(),
+            create_documents(&["Misery", "Movie", "Carrie", "Novel", "River", "Garden"])
+        );
+
+        // a damaged manifest is not mistaken for a build which can be resumed
+        let manifest_path = get_build_manifest_path(temp_dir.path());
+        let mut bytes = fs::read(&manifest_path).unwrap();
+        bytes[10] ^= 1;
+        fs::write(&manifest_path, bytes).unwrap();
+        assert!(BuildManifest::load(temp_dir.path()).is_err());
+    }
+
+    #[test]
+    fn test_remove_unfinished_runs() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut manifest = BuildManifest::new(temp_dir.path(), Path::new("wikipedia"), 0);
+        fs::write(get_run_path(temp_dir.path(), 0), b"run").unwrap();
+        manifest
+            .add_run(
+                create_files(&[("batch_0.zst", 1, true)]),
+                &create_documents(&["Misery"]),
+            )
+            .unwrap();
+
+        // the build stopped while the second run was being written
+        fs::write(get_unfinished_run_path(temp_dir.path(), 1), b"half a run").unwrap();
+        fs::write(get_run_documents_path(temp_dir.path(), 1), b"documents").unwrap();
+        fs::write(temp_dir.path().join("old.tmpidx"), b"older build").unwrap();
+        fs::write(temp_dir.path().join("inverted_index.idx"), b"index").unwrap();
+
+        manifest.remove_unfinished_runs().unwrap();
+        let mut file_names: Vec<String> = fs::read_dir(temp_dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        file_names.sort();
+        assert_eq!(
+            file_names,
+            vec![
+                "0.tmpdocs",
+                "0.tmpidx",
+                "build_manifest.sidx",
+                "inverted_index.idx"
+            ]
+        );
+
+        BuildManifest::remove(temp_dir.path()).unwrap();
+        let file_names: Vec<String> = fs::read_dir(temp_dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        assert_eq!(file_names, vec!["inverted_index.idx"]);
+        assert_eq!(BuildManifest::load(temp_dir.path()).unwrap(), None);
+    }
+}
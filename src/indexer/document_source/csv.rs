@@ -0,0 +1,132 @@
+use std::{io, path::Path};
+
+use csv::{ByteRecord, ReaderBuilder};
+
+use crate::indexer::document_source::document_source::{
+    DocumentSource, FieldMapping, SourceDocument, get_document_file_extension, open_document_file,
+};
+
+// A table with a document on every row and a header which names the columns, the columns are
+// picked with the field mapping. The values of a .csv file are separated by commas and those of a
+// .tsv file by tabs, a value in double quotes can contain separators, line breaks and doubled
+// double quotes.
+#[derive(Debug, Clone)]
+pub struct CsvSource {
+    field_mapping: FieldMapping,
+}
+
+impl CsvSource {
+    pub fn new(field_mapping: FieldMapping) -> Self {
+        Self { field_mapping }
+    }
+}
+
+impl DocumentSource for CsvSource {
+    fn accepts(&self, path: &Path) -> bool {
+        get_document_file_extension(path)
+            .0
+            .is_some_and(|extension| matches!(extension.as_str(), "csv" | "tsv"))
+    }
+
+    fn read_documents(
+        &self,
+        path: &Path,
+        add_document: &mut dyn FnMut(SourceDocument) -> io::Result<()>,
+    ) -> io::Result<()> {
+        let delimiter = match get_document_file_extension(path).0.as_deref() {
+            Some("tsv") => b'\t',
+            _ => b',',
+        };
+        // a row with fewer values than the header leaves the rest of its columns empty
+        let mut reader = ReaderBuilder::new()
+            .delimiter(delimiter)
+            .flexible(true)
+            .from_reader(open_document_file(path)?);
+        let header = reader.byte_headers()?.clone();
+        if header.is_empty() {
+            return Ok(());
+        }
+        let find_column = |name: &str| {
+            header
+                .iter()
+                .position(|column| String::from_utf8_lossy(column).trim() == name)
+        };
+        let Some(text_column) = find_column(&self.field_mapping.text) else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "{} has no column named {}",
+                    path.display(),
+                    self.field_mapping.text
+                ),
+            ));
+        };
+        let title_column = find_column(&self.field_mapping.title);
+        let url_column = find_column(&self.field_mapping.url);
+
+        let mut record = ByteRecord::new();
+        while reader.read_byte_record(&mut record)? {
+            // a value which is not valid UTF-8 is read with the invalid bytes replaced
+            let get = |column: Option<usize>| {
+                column
+                    .and_then(|column| record.get(column))
+                    .map(|value| String::from_utf8_lossy(value).into_owned())
+                    .unwrap_or_default()
+            };
+            add_document(SourceDocument {
+                title: get(title_column),
+                url: get(url_column),
+                text: get(Some(text_column)),
+            })?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_read_csv() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("articles.csv");
+        std::fs::write(
+            &path,
+            "id,headline,body\r\n\
+             1,Misery,\"a novel, by \"\"Stephen\"\" King\"\r\n\
+             \r\n\
+             2,Floods,\"the river rose\nand the town flooded\"\n\
+             3,Short\n",
+        )
+        .unwrap();
+
+        let source = CsvSource::new(FieldMapping {
+            title: "headline".to_string(),
+            url: "id".to_string(),
+            text: "body".to_string(),
+        });
+        assert!(source.accepts(&path));
+        assert!(source.accepts(Path::new("articles.tsv.gz")));
+        let mut documents = Vec::new();
+        source
+            .read_documents(&path, &mut |document| {
+                documents.push(document);
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(documents.len(), 3);
+        assert_eq!(documents[0].text, "a novel, by \"Stephen\" King");
+        assert_eq!(documents[0].url, "1");
+        assert_eq!(documents[1].title, "Floods");
+        assert_eq!(documents[1].text, "the river rose\nand the town flooded");
+        assert_eq!(documents[2].text, "");
+
+        // the text has to come from somewhere
+        let source = CsvSource::new(FieldMapping::default());
+        let e = source.read_documents(&path, &mut |_| Ok(())).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,457 @@
+use std::{
+    fmt,
+    fs::File,
+    io::{self, BufRead, BufReader},
+    ops::Range,
+    path::Path,
+    str::FromStr,
+    sync::Arc,
+};
+
+use crate::indexer::document_source::{
+    csv::CsvSource, jsonl::JsonlSource, mediawiki::MediaWikiSource, text::TextSource,
+    trec::TrecSource, warc::WarcSource,
+};
+
+// A document as it is read from a file, before its text and title are tokenized. The url is only
+// shown with the results, a format without urls can use anything which identifies the document.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SourceDocument {
+    pub title: String,
+    pub url: String,
+    pub text: String,
+}
+
+// Reads the documents out of the files of a dataset. The files of a dataset are read on several
+// threads at once, every file is read by one of them from start to end.
+pub trait DocumentSource: fmt::Debug + Send + Sync {
+    // whether the file is one which this source reads, the other files of the dataset are skipped
+    fn accepts(&self, path: &Path) -> bool;
+
+    // Passes every document of the file to add_document in the order they are in the file, an
+    // error returned by add_document stops the reading
+    fn read_documents(
+        &self,
+        path: &Path,
+        add_document: &mut dyn FnMut(SourceDocument) -> io::Result<()>,
+    ) -> io::Result<()>;
+}
+
+// The names of the fields which the title, the url and the text of a document are read from, the
+// keys of a json object or the columns in the header of a csv file. A key of a nested json object
+// is written as a path such as meta.title.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldMapping {
+    pub title: String,
+    pub url: String,
+    pub text: String,
+}
+
+impl Default for FieldMapping {
+    fn default() -> Self {
+        Self {
+            title: "title".to_string(),
+            url: "url".to_string(),
+            text: "text".to_string(),
+        }
+    }
+}
+
+// The formats which a dataset can be read in, the json lines of the preprocessing script are read
+// by default
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum DocumentFormat {
+    #[default]
+    Jsonl,
+    Text,
+    Csv,
+    Trec,
+    Warc,
+    MediaWiki,
+}
+
+impl DocumentFormat {
+    pub fn get_source(&self, field_mapping: FieldMapping) -> Arc<dyn DocumentSource> {
+        match self {
+            DocumentFormat::Jsonl => Arc::new(JsonlSource::new(field_mapping)),
+            DocumentFormat::Text => Arc::new(TextSource),
+            DocumentFormat::Csv => Arc::new(CsvSource::new(field_mapping)),
+            DocumentFormat::Trec => Arc::new(TrecSource),
+            DocumentFormat::Warc => Arc::new(WarcSource),
+            DocumentFormat::MediaWiki => Arc::new(MediaWikiSource),
+        }
+    }
+}
+
+impl fmt::Display for DocumentFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            DocumentFormat::Jsonl => "jsonl",
+            DocumentFormat::Text => "text",
+            DocumentFormat::Csv => "csv",
+            DocumentFormat::Trec => "trec",
+            DocumentFormat::Warc => "warc",
+            DocumentFormat::MediaWiki => "mediawiki",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for DocumentFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "jsonl" => Ok(DocumentFormat::Jsonl),
+            "text" => Ok(DocumentFormat::Text),
+            "csv" => Ok(DocumentFormat::Csv),
+            "trec" => Ok(DocumentFormat::Trec),
+            "warc" => Ok(DocumentFormat::Warc),
+            "mediawiki" => Ok(DocumentFormat::MediaWiki),
+            _ => Err(format!("unknown document format {}", s)),
+        }
+    }
+}
+
+const COMPRESSION_EXTENSIONS: [&str; 4] = ["gz", "bz2", "zst", "zstd"];
+
+// Splits the extension of a compressed file off its name, batch.jsonl.gz has the extension jsonl
+// and is compressed. The extension is None for a file without one, like the zstd batches of the
+// preprocessing script.
+pub fn get_document_file_extension(path: &Path) -> (Option<String>, bool) {
+    let extension = |path: &Path| {
+        path.extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_ascii_lowercase())
+    };
+    match extension(path) {
+        Some(compression) if COMPRESSION_EXTENSIONS.contains(&compression.as_str()) => {
+            (extension(&path.with_extension("")), true)
+        }
+        extension => (extension, false),
+    }
+}
+
+// whether the file has one of the extensions, compressed or not
+pub fn has_document_file_extension(path: &Path, extensions: &[&str]) -> bool {
+    get_document_file_extension(path)
+        .0
+        .is_some_and(|extension| extensions.contains(&extension.as_str()))
+}
+
+// Opens a file of a dataset, a file compressed with gzip, bzip2 or zstd is decompressed as it is
+// read
+pub fn open_document_file(path: &Path) -> io::Result<Box<dyn BufRead + Send>> {
+    let file = File::open(path)?;
+    let compression = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extension.to_ascii_lowercase());
+    Ok(match compression.as_deref() {
+        // a file written as several gzip members one after the other, the way WARC files are
+        // compressed, is read as one stream
+        Some("gz") => Box::new(BufReader::new(flate2::bufread::MultiGzDecoder::new(
+            BufReader::new(file),
+        ))),
+        Some("bz2") => Box::new(BufReader::new(bzip2::read::MultiBzDecoder::new(
+            BufReader::new(file),
+        ))),
+        Some("zst" | "zstd") => Box::new(BufReader::new(zstd::Decoder::new(file)?)),
+        _ => Box::new(BufReader::new(file)),
+    })
+}
+
+// Reads a line without its line break, a line which is not valid UTF-8 is read with the invalid
+// bytes replaced. Returns false at the end of the file.
+pub fn read_line<R: BufRead + ?Sized>(
+    reader: &mut R,
+    bytes: &mut Vec<u8>,
+    line: &mut String,
+) -> io::Result<bool> {
+    bytes.clear();
+    line.clear();
+    if reader.read_until(b'\n', bytes)? == 0 {
+        return Ok(false);
+    }
+    while matches!(bytes.last(), Some(b'\n' | b'\r')) {
+        bytes.pop();
+    }
+    line.push_str(&String::from_utf8_lossy(bytes));
+    Ok(true)
+}
+
+// Replaces the character references of xml and html, the named ones which are not common are left
+// as they are
+pub fn decode_entities(text: &str) -> String {
+    if !text.contains('&') {
+        return text.to_string();
+    }
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let Some(end) = rest.as_bytes()[..rest.len().min(12)]
+            .iter()
+            .position(|&byte| byte == b';')
+        else {
+            decoded.push('&');
+            rest = &rest[1..];
+            continue;
+        };
+        let entity = &rest[1..end];
+        let character = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some(' '),
+            _ => entity
+                .strip_prefix("#x")
+                .or_else(|| entity.strip_prefix("#X"))
+                .map(|hex| u32::from_str_radix(hex, 16))
+                .or_else(|| entity.strip_prefix('#').map(|decimal| decimal.parse()))
+                .and_then(|code| code.ok())
+                .and_then(char::from_u32),
+        };
+        match character {
+            Some(character) => {
+                decoded.push(character);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+// The title and the visible text of an html page. The scripts, the styles and the comments are
+// dropped along with the tags, a tag is replaced by a space so the words on either side of it are
+// not joined.
+pub fn html_to_text(html: &str) -> (Option<String>, String) {
+    let title = find_element(html, "title").map(|title| decode_entities(title.trim()));
+    let mut text = String::with_capacity(html.len() / 2);
+    let lowercase_html = html.to_ascii_lowercase();
+    let mut position = 0;
+    while let Some(offset) = html[position..].find('<') {
+        let start = position + offset;
+        text.push_str(&html[position..start]);
+        text.push(' ');
+        let tag = &lowercase_html[start..];
+        let end = if tag.starts_with("<!--") {
+            tag.find("-->").map(|end| end + 3)
+        } else if tag.starts_with("<script") {
+            tag.find("</script>").map(|end| end + 9)
+        } else if tag.starts_with("<style") {
+            tag.find("</style>").map(|end| end + 8)
+        } else if tag.starts_with("<title") {
+            tag.find("</title>").map(|end| end + 8)
+        } else {
+            tag.find('>').map(|end| end + 1)
+        };
+        position = match end {
+            Some(end) => start + end,
+            None => html.len(),
+        };
+    }
+    text.push_str(&html[position..]);
+    (title, decode_entities(&text))
+}
+
+// the contents of the first element with the name, which is expected to have no nested elements
+pub fn find_element<'a>(text: &'a str, name: &str) -> Option<&'a str> {
+    find_element_range(text, name).map(|range| &text[range])
+}
+
+pub fn find_element_range(text: &str, name: &str) -> Option<Range<usize>> {
+    let lowercase_text = text.to_ascii_lowercase();
+    let start_tag = format!("<{}", name);
+    let mut position = 0;
+    let start = loop {
+        let start = position + lowercase_text[position..].find(&start_tag)?;
+        let after_name = start + start_tag.len();
+        // <title> and <title lang="en"> but not <titles>
+        match lowercase_text.as_bytes().get(after_name) {
+            Some(b'>' | b' ' | b'\t' | b'\n' | b'\r') => {
+                break after_name + lowercase_text[after_name..].find('>')? + 1;
+            }
+            Some(b'/') => return Some(after_name..after_name),
+            _ => position = after_name,
+        }
+    };
+    let end = start + lowercase_text[start..].find(&format!("</{}>", name))?;
+    Some(start..end)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Read, path::PathBuf};
+
+    use tempfile::TempDir;
+
+    use super::*;
+
+    // three gzip members written one after the other by zlib, compressed with dynamic huffman
+    // codes, with the fixed codes and stored without compression
+    const MEMBERS: &[u8] = &[
+        31, 139, 8, 0, 0, 0, 0, 0, 2, 3, 237, 142, 193, 17, 2, 33, 16, 4, 83, 153, 0, 44, 115, 90,
+        185, 81, 182, 128, 133, 98, 57, 169, 203, 222, 67, 203, 191, 1, 248, 236, 121, 244, 116,
+        81, 103, 63, 160, 14, 129, 213, 39, 51, 110, 7, 124, 176, 69, 26, 146, 218, 227, 2, 49, 83,
+        98, 106, 78, 116, 36, 178, 57, 154, 236, 25, 30, 153, 183, 106, 80, 67, 100, 71, 172, 187,
+        115, 65, 168, 185, 118, 217, 42, 228, 62, 206, 93, 16, 164, 35, 116, 241, 120, 170, 54, 20,
+        89, 158, 168, 5, 179, 235, 224, 250, 229, 68, 249, 116, 188, 19, 174, 95, 250, 87, 253, 80,
+        245, 2, 53, 52, 45, 15, 194, 1, 0, 0, 31, 139, 8, 0, 0, 0, 0, 0, 2, 3, 75, 84, 40, 44, 205,
+        76, 45, 81, 40, 202, 44, 75, 45, 82, 72, 203, 201, 47, 47, 86, 40, 72, 44, 46, 81, 40, 201,
+        72, 85, 200, 207, 73, 81, 200, 205, 204, 201, 1, 0, 100, 146, 30, 125, 37, 0, 0, 0, 31,
+        139, 8, 0, 0, 0, 0, 0, 4, 3, 1, 38, 0, 217, 255, 116, 104, 101, 32, 103, 97, 114, 100, 101,
+        110, 32, 119, 97, 115, 32, 102, 117, 108, 108, 32, 111, 102, 32, 114, 111, 115, 101, 115,
+        32, 105, 110, 32, 115, 112, 114, 105, 110, 103, 39, 235, 83, 60, 38, 0, 0, 0,
+    ];
+
+    // a pattern of 300 bytes repeated 400 times, so the matches reach back across the output
+    // which has already been handed out
+    const REPEATED_PATTERN: &[u8] = &[
+        31, 139, 8, 0, 0, 0, 0, 0, 2, 3, 237, 208, 185, 43, 47, 0, 0, 0, 224, 31, 229, 72, 148,
+        129, 200, 160, 136, 73, 202, 61, 96, 64, 142, 200, 49, 145, 72, 114, 132, 92, 147, 193, 34,
+        41, 41, 215, 162, 44, 6, 12, 24, 73, 174, 82, 132, 148, 66, 148, 44, 12, 138, 100, 208, 19,
+        146, 98, 123, 127, 195, 155, 223, 247, 39, 124, 95, 32, 38, 175, 107, 229, 163, 110, 47,
+        107, 183, 230, 109, 185, 45, 35, 234, 235, 207, 87, 84, 70, 219, 242, 91, 205, 110, 214,
+        94, 221, 199, 74, 87, 94, 76, 224, 59, 56, 190, 112, 112, 61, 168, 229, 172, 244, 188, 35,
+        98, 127, 164, 54, 61, 33, 54, 49, 183, 105, 246, 42, 121, 244, 181, 231, 125, 50, 243, 105,
+        109, 184, 177, 188, 164, 186, 115, 234, 48, 164, 126, 43, 101, 49, 237, 184, 55, 233, 113,
+        123, 126, 124, 108, 102, 245, 50, 180, 114, 225, 183, 239, 101, 40, 122, 103, 32, 59, 236,
+        249, 246, 230, 225, 39, 181, 121, 233, 179, 225, 180, 236, 186, 63, 238, 98, 174, 187, 170,
+        32, 191, 162, 125, 250, 36, 178, 245, 40, 103, 179, 248, 126, 162, 40, 252, 238, 96, 99,
+        227, 224, 46, 188, 104, 226, 190, 120, 51, 231, 168, 53, 242, 100, 186, 189, 34, 191, 160,
+        170, 123, 238, 34, 174, 255, 186, 236, 180, 225, 115, 169, 57, 245, 231, 225, 230, 246, 57,
+        44, 123, 96, 39, 122, 232, 165, 239, 119, 161, 50, 244, 114, 117, 102, 108, 124, 126, 251,
+        49, 169, 247, 56, 109, 49, 101, 171, 62, 228, 112, 170, 179, 186, 164, 188, 113, 120, 237,
+        41, 115, 242, 189, 231, 117, 52, 249, 106, 182, 41, 55, 49, 54, 33, 189, 118, 100, 63, 162,
+        227, 188, 244, 172, 37, 104, 125, 176, 48, 62, 248, 59, 240, 175, 244, 128, 43, 87, 174,
+        92, 185, 114, 229, 202, 149, 43, 87, 174, 92, 185, 114, 229, 202, 149, 43, 87, 174, 92,
+        185, 114, 229, 202, 149, 43, 87, 174, 92, 185, 114, 229, 202, 149, 43, 87, 174, 92, 185,
+        114, 229, 202, 149, 43, 87, 174, 92, 185, 114, 229, 202, 149, 43, 87, 174, 92, 185, 114,
+        229, 202, 149, 43, 87, 174, 92, 185, 114, 229, 202, 149, 43, 87, 174, 92, 185, 114, 229,
+        202, 149, 43, 87, 174, 92, 185, 114, 229, 202, 149, 43, 87, 174, 92, 185, 114, 229, 202,
+        149, 43, 87, 174, 92, 185, 114, 229, 202, 149, 43, 87, 174, 92, 185, 114, 229, 202, 149,
+        43, 87, 174, 92, 185, 114, 229, 202, 149, 43, 87, 174, 92, 185, 114, 229, 202, 149, 43, 87,
+        174, 92, 185, 114, 229, 202, 149, 43, 87, 174, 92, 185, 114, 229, 202, 149, 43, 87, 174,
+        92, 185, 114, 229, 202, 149, 43, 87, 174, 92, 185, 114, 229, 202, 149, 43, 87, 174, 92,
+        185, 114, 229, 202, 149, 43, 87, 174, 92, 185, 114, 229, 202, 149, 43, 87, 174, 92, 185,
+        114, 229, 202, 149, 43, 87, 174, 92, 185, 114, 229, 202, 149, 43, 87, 174, 92, 185, 114,
+        229, 202, 149, 43, 87, 174, 92, 185, 114, 229, 202, 149, 43, 87, 174, 92, 185, 114, 229,
+        202, 149, 43, 87, 174, 92, 185, 114, 229, 202, 149, 43, 87, 174, 92, 185, 114, 229, 202,
+        149, 43, 87, 174, 92, 185, 114, 229, 202, 149, 43, 87, 174, 92, 185, 114, 229, 202, 149,
+        43, 87, 174, 92, 185, 114, 229, 202, 149, 43, 87, 174, 92, 185, 114, 229, 202, 149, 43, 87,
+        174, 92, 185, 114, 229, 202, 149, 43, 87, 174, 92, 185, 114, 229, 202, 149, 43, 87, 174,
+        92, 185, 114, 229, 202, 149, 43, 87, 174, 92, 185, 114, 229, 202, 149, 43, 87, 174, 92,
+        185, 114, 229, 202, 149, 43, 87, 174, 92, 185, 114, 229, 202, 149, 43, 87, 174, 92, 185,
+        114, 229, 202, 149, 43, 87, 174, 92, 185, 114, 229, 202, 149, 43, 87, 174, 92, 185, 114,
+        229, 202, 149, 43, 87, 174, 92, 185, 114, 229, 202, 149, 43, 87, 174, 92, 185, 114, 229,
+        202, 149, 43, 87, 174, 92, 185, 114, 229, 202, 149, 43, 87, 174, 92, 185, 114, 229, 202,
+        149, 43, 87, 174, 92, 185, 114, 229, 202, 149, 43, 87, 174, 92, 185, 114, 229, 202, 149,
+        43, 87, 174, 92, 185, 114, 229, 202, 149, 43, 87, 174, 92, 185, 114, 229, 202, 149, 43, 87,
+        174, 92, 185, 114, 229, 202, 149, 43, 87, 174, 92, 185, 114, 229, 202, 149, 43, 87, 174,
+        92, 185, 114, 229, 202, 149, 43, 87, 174, 92, 185, 114, 229, 202, 149, 43, 87, 174, 92,
+        185, 114, 229, 202, 149, 43, 87, 174, 92, 185, 114, 229, 202, 149, 43, 87, 174, 92, 185,
+        114, 229, 202, 149, 43, 87, 174, 92, 185, 114, 229, 202, 149, 43, 87, 174, 92, 185, 114,
+        229, 234, 191, 188, 250, 11, 118, 191, 13, 3, 192, 212, 1, 0,
+    ];
+
+    // decompresses the bytes through a .gz file of the dataset
+    fn decompress(bytes: &[u8]) -> io::Result<Vec<u8>> {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("batch.jsonl.gz");
+        std::fs::write(&path, bytes).unwrap();
+        let mut output = Vec::new();
+        open_document_file(&path)?.read_to_end(&mut output)?;
+        Ok(output)
+    }
+
+    #[test]
+    fn test_document_file_extension() {
+        let extension = |path: &str| get_document_file_extension(&PathBuf::from(path));
+        assert_eq!(extension("batch.jsonl"), (Some("jsonl".to_string()), false));
+        assert_eq!(
+            extension("batch.JSONL.gz"),
+            (Some("jsonl".to_string()), true)
+        );
+        assert_eq!(extension("batches/batch_0.zst"), (None, true));
+        assert_eq!(
+            extension("enwiki-latest-pages-articles.xml.bz2"),
+            (Some("xml".to_string()), true)
+        );
+        assert!(has_document_file_extension(
+            Path::new("docs/readme.txt.bz2"),
+            &["txt"]
+        ));
+        assert!(!has_document_file_extension(Path::new("readme"), &["txt"]));
+    }
+
+    #[test]
+    fn test_html_to_text() {
+        let html = "<html><head><title>Misery &amp; Company</title><style>p { color: red }</style>\
+                    <script>var misery = 1;</script></head><body><p>A novel<br>by Stephen&nbsp;King\
+                    </p><!-- a <b>comment</b> --><p>&#8220;Annie&#x201D;</p></body></html>";
+        let (title, text) = html_to_text(html);
+        assert_eq!(title.as_deref(), Some("Misery & Company"));
+        let words: Vec<&str> = text.split_whitespace().collect();
+        assert_eq!(
+            words,
+            vec![
+                "A",
+                "novel",
+                "by",
+                "Stephen",
+                "King",
+                "\u{201C}Annie\u{201D}"
+            ]
+        );
+        assert_eq!(decode_entities("AT&T &unknown; &lt;3"), "AT&T &unknown; <3");
+        assert_eq!(find_element("<titles>no</titles>", "title"), None);
+    }
+
+    #[test]
+    fn test_gzip_members() {
+        let expected = [
+            "misery is a novel by stephen king, annie wilkes keeps paul sheldon in her house in \
+             colorado after a car crash and makes him write a new misery novel. "
+                .repeat(3),
+            "a quiet river flows past the old mill".to_string(),
+            "the garden was full of roses in spring".to_string(),
+        ]
+        .concat();
+        assert_eq!(decompress(MEMBERS).unwrap(), expected.as_bytes());
+
+        let pattern: Vec<u8> = (0..300u32)
+            .map(|i| ((i * i * 7 + i * 13) % 251) as u8)
+            .collect();
+        assert_eq!(decompress(REPEATED_PATTERN).unwrap(), pattern.repeat(400));
+
+        // a flipped bit in the compressed data or in the checksum is caught, flate2 reports a
+        // damaged deflate stream as invalid input
+        for position in [40, MEMBERS.len() - 6] {
+            let mut damaged = MEMBERS.to_vec();
+            damaged[position] ^= 0x10;
+            let e = decompress(&damaged).unwrap_err();
+            assert!(
+                matches!(
+                    e.kind(),
+                    io::ErrorKind::InvalidData
+                        | io::ErrorKind::InvalidInput
+                        | io::ErrorKind::UnexpectedEof
+                ),
+                "{:?}",
+                e
+            );
+        }
+        let e = decompress(&MEMBERS[..100]).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof);
+        assert!(decompress(b"not gzip at all").is_err());
+    }
+}
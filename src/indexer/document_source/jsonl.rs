@@ -0,0 +1,177 @@
+use std::{
+    io::{self, BufRead},
+    path::Path,
+};
+
+use serde_json::Value;
+
+use crate::indexer::{
+    document_source::document_source::{
+        DocumentSource, FieldMapping, SourceDocument, get_document_file_extension,
+        open_document_file,
+    },
+    types::WikiArticle1,
+};
+
+// One json object per line, plain or compressed. A compressed file without an extension of its own
+// is read as json lines as well, like the zstd batches which the preprocessing script writes.
+#[derive(Debug, Clone)]
+pub struct JsonlSource {
+    field_mapping: FieldMapping,
+}
+
+impl JsonlSource {
+    pub fn new(field_mapping: FieldMapping) -> Self {
+        Self { field_mapping }
+    }
+
+    fn parse_document(&self, line: &[u8]) -> serde_json::Result<SourceDocument> {
+        // the documents of the preprocessing script are read straight into their fields
+        if self.field_mapping == FieldMapping::default() {
+            let article: WikiArticle1 = serde_json::from_slice(line)?;
+            return Ok(SourceDocument {
+                title: article.title,
+                url: article.url,
+                text: article.text,
+            });
+        }
+        let mut object: Value = serde_json::from_slice(line)?;
+        Ok(SourceDocument {
+            title: take_field(&mut object, &self.field_mapping.title),
+            url: take_field(&mut object, &self.field_mapping.url),
+            text: take_field(&mut object, &self.field_mapping.text),
+        })
+    }
+}
+
+// The field at the dotted path as text, the strings of an array are joined into paragraphs and a
+// missing field is empty
+fn take_field(object: &mut Value, path: &str) -> String {
+    let mut value = object;
+    for key in path.split('.') {
+        match value.get_mut(key) {
+            Some(field) => value = field,
+            None => return String::new(),
+        }
+    }
+    value_to_text(value.take())
+}
+
+fn value_to_text(value: Value) -> String {
+    match value {
+        Value::String(text) => text,
+        Value::Null => String::new(),
+        Value::Array(values) => values
+            .into_iter()
+            .map(value_to_text)
+            .filter(|text| !text.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n\n"),
+        value => value.to_string(),
+    }
+}
+
+impl DocumentSource for JsonlSource {
+    fn accepts(&self, path: &Path) -> bool {
+        match get_document_file_extension(path) {
+            (Some(extension), _) => matches!(extension.as_str(), "jsonl" | "ndjson" | "json"),
+            (None, is_compressed) => is_compressed,
+        }
+    }
+
+    fn read_documents(
+        &self,
+        path: &Path,
+        add_document: &mut dyn FnMut(SourceDocument) -> io::Result<()>,
+    ) -> io::Result<()> {
+        let mut reader = open_document_file(path)?;
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            if reader.read_until(b'\n', &mut line)? == 0 {
+                return Ok(());
+            }
+            if line.iter().all(|byte| byte.is_ascii_whitespace()) {
+                continue;
+            }
+            match self.parse_document(&line) {
+                Ok(document) => add_document(document)?,
+                Err(e) => eprintln!("Failed to parse line: {}", e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::File, io::Write};
+
+    use tempfile::TempDir;
+
+    use super::*;
+
+    fn read_all(source: &dyn DocumentSource, path: &Path) -> Vec<SourceDocument> {
+        let mut documents = Vec::new();
+        source
+            .read_documents(path, &mut |document| {
+                documents.push(document);
+                Ok(())
+            })
+            .unwrap();
+        documents
+    }
+
+    #[test]
+    fn test_read_json_lines() {
+        let temp_dir = TempDir::new().unwrap();
+        let batch_path = temp_dir.path().join("batch_0.zst");
+        let mut encoder = zstd::Encoder::new(File::create(&batch_path).unwrap(), 3).unwrap();
+        writeln!(
+            encoder,
+            r#"{{"title": "Misery", "url": "https://en.wikipedia.org/wiki/Misery", "text": "a novel"}}"#
+        )
+        .unwrap();
+        writeln!(encoder, "not json").unwrap();
+        writeln!(encoder).unwrap();
+        write!(encoder, r#"{{"title": "Carrie", "text": "another novel"}}"#).unwrap();
+        encoder.finish().unwrap();
+
+        let source = JsonlSource::new(FieldMapping::default());
+        assert!(source.accepts(&batch_path));
+        assert!(source.accepts(Path::new("news.jsonl.bz2")));
+        assert!(!source.accepts(Path::new("news.csv")));
+        let documents = read_all(&source, &batch_path);
+        assert_eq!(documents.len(), 2);
+        assert_eq!(documents[0].url, "https://en.wikipedia.org/wiki/Misery");
+        assert_eq!(documents[1].title, "Carrie");
+        assert_eq!(documents[1].url, "");
+
+        // the fields of other corpora are mapped
+        let news_path = temp_dir.path().join("news.jsonl.bz2");
+        let mut encoder = bzip2::write::BzEncoder::new(
+            File::create(&news_path).unwrap(),
+            bzip2::Compression::fast(),
+        );
+        writeln!(
+            encoder,
+            r#"{{"id": 7, "meta": {{"headline": "Floods"}}, "paragraphs": ["the river rose", "", "the town flooded"]}}"#
+        )
+        .unwrap();
+        encoder.finish().unwrap();
+
+        let source = JsonlSource::new(FieldMapping {
+            title: "meta.headline".to_string(),
+            url: "id".to_string(),
+            text: "paragraphs".to_string(),
+        });
+        let documents = read_all(&source, &news_path);
+        assert_eq!(
+            documents,
+            vec![SourceDocument {
+                title: "Floods".to_string(),
+                url: "7".to_string(),
+                text: "the river rose\n\nthe town flooded".to_string(),
+            }]
+        );
+    }
+}
@@ -0,0 +1,156 @@
+use std::{io, path::Path};
+
+use crate::indexer::document_source::{
+    document_source::{
+        DocumentSource, SourceDocument, decode_entities, find_element, has_document_file_extension,
+        open_document_file, read_line,
+    },
+    wikitext::strip_wikitext,
+};
+
+// The urls of the articles of a dump which does not say where its wiki is
+const DEFAULT_BASE_URL: &str = "https://en.wikipedia.org/wiki/";
+
+// The XML dumps of MediaWiki such as enwiki-latest-pages-articles.xml.bz2, which are read as they
+// are decompressed since they do not fit in memory. Every article is a document, the pages of the
+// other namespaces (talk pages, templates, categories ...) and the redirects are skipped. The
+// markup is stripped off the wikitext of an article, so a dump can be indexed without the python
+// preprocessing script.
+#[derive(Debug, Clone)]
+pub struct MediaWikiSource;
+
+impl MediaWikiSource {
+    fn parse_page(page: &str, base_url: &str) -> Option<SourceDocument> {
+        if find_element(page, "ns").is_some_and(|namespace| namespace.trim() != "0")
+            || page.contains("<redirect")
+        {
+            return None;
+        }
+        let title = decode_entities(find_element(page, "title")?.trim());
+        let text = strip_wikitext(&decode_entities(
+            find_element(page, "text").unwrap_or_default(),
+        ));
+        Some(SourceDocument {
+            url: format!("{}{}", base_url, title.replace(' ', "_")),
+            title,
+            text,
+        })
+    }
+}
+
+impl DocumentSource for MediaWikiSource {
+    fn accepts(&self, path: &Path) -> bool {
+        has_document_file_extension(path, &["xml"])
+    }
+
+    fn read_documents(
+        &self,
+        path: &Path,
+        add_document: &mut dyn FnMut(SourceDocument) -> io::Result<()>,
+    ) -> io::Result<()> {
+        let mut reader = open_document_file(path)?;
+        let mut bytes = Vec::new();
+        let mut line = String::new();
+        let mut page = String::new();
+        let mut is_in_page = false;
+        let mut base_url = DEFAULT_BASE_URL.to_string();
+        while read_line(&mut reader, &mut bytes, &mut line)? {
+            let tag = line.trim_start();
+            if !is_in_page {
+                // the siteinfo has the url of the main page, the articles are next to it
+                if tag.starts_with("<base>")
+                    && let Some(base) = find_element(tag, "base")
+                    && let Some(end) = base.rfind('/')
+                {
+                    base_url = base[..=end].to_string();
+                }
+                if tag.starts_with("<page>") {
+                    is_in_page = true;
+                    page.clear();
+                }
+            } else if tag.starts_with("</page>") {
+                is_in_page = false;
+                if let Some(document) = Self::parse_page(&page, &base_url) {
+                    add_document(document)?;
+                }
+            } else {
+                page.push_str(&line);
+                page.push('\n');
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::File, io::Write};
+
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_read_mediawiki_dump() {
+        let dump = r#"<mediawiki xmlns="http://www.mediawiki.org/xml/export-0.11/" xml:lang="en">
+  <siteinfo>
+    <sitename>Wikipedia</sitename>
+    <base>https://simple.wikipedia.org/wiki/Main_Page</base>
+  </siteinfo>
+  <page>
+    <title>Misery (novel)</title>
+    <ns>0</ns>
+    <id>1</id>
+    <revision>
+      <text bytes="61" xml:space="preserve">{{Infobox book | name = Misery}}
+'''Misery''' is a novel by [[Stephen King]].&lt;ref&gt;{{cite book}}&lt;/ref&gt;
+It was published in 1987 &amp;amp; filmed in 1990.
+[[Category:1987 novels]]</text>
+    </revision>
+  </page>
+  <page>
+    <title>Misery novel</title>
+    <ns>0</ns>
+    <redirect title="Misery (novel)" />
+    <revision>
+      <text xml:space="preserve">#REDIRECT [[Misery (novel)]]</text>
+    </revision>
+  </page>
+  <page>
+    <title>Talk:Misery (novel)</title>
+    <ns>1</ns>
+    <revision>
+      <text xml:space="preserve">Is this a good article?</text>
+    </revision>
+  </page>
+</mediawiki>
+"#;
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir
+            .path()
+            .join("simplewiki-latest-pages-articles.xml.bz2");
+        let mut encoder =
+            bzip2::write::BzEncoder::new(File::create(&path).unwrap(), bzip2::Compression::fast());
+        encoder.write_all(dump.as_bytes()).unwrap();
+        encoder.finish().unwrap();
+
+        assert!(MediaWikiSource.accepts(&path));
+        let mut documents = Vec::new();
+        MediaWikiSource
+            .read_documents(&path, &mut |document| {
+                documents.push(document);
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(
+            documents,
+            vec![SourceDocument {
+                title: "Misery (novel)".to_string(),
+                url: "https://simple.wikipedia.org/wiki/Misery_(novel)".to_string(),
+                text: "Misery is a novel by Stephen King.\n\
+                       It was published in 1987 & filmed in 1990."
+                    .to_string(),
+            }]
+        );
+    }
+}
@@ -0,0 +1,8 @@
+pub mod csv;
+pub mod document_source;
+pub mod jsonl;
+pub mod mediawiki;
+pub mod text;
+pub mod trec;
+pub mod warc;
+pub mod wikitext;
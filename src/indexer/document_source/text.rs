@@ -0,0 +1,67 @@
+use std::{
+    io::{self, Read},
+    path::Path,
+};
+
+use crate::indexer::document_source::document_source::{
+    DocumentSource, SourceDocument, has_document_file_extension, open_document_file,
+};
+
+// A directory of plain text files where every file is a document. The title of a document is the
+// name of its file without the extensions and its url is the path of the file.
+#[derive(Debug, Clone)]
+pub struct TextSource;
+
+impl DocumentSource for TextSource {
+    fn accepts(&self, path: &Path) -> bool {
+        has_document_file_extension(path, &["txt", "text", "md"])
+    }
+
+    fn read_documents(
+        &self,
+        path: &Path,
+        add_document: &mut dyn FnMut(SourceDocument) -> io::Result<()>,
+    ) -> io::Result<()> {
+        let mut bytes = Vec::new();
+        open_document_file(path)?.read_to_end(&mut bytes)?;
+        let file_name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let title = file_name.split('.').next().unwrap_or_default().to_string();
+        add_document(SourceDocument {
+            title,
+            url: path.to_string_lossy().into_owned(),
+            text: String::from_utf8_lossy(&bytes).into_owned(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_read_text_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("misery.txt");
+        std::fs::write(&path, "misery is a novel\nby stephen king\n").unwrap();
+
+        assert!(TextSource.accepts(&path));
+        assert!(TextSource.accepts(Path::new("notes.md.gz")));
+        assert!(!TextSource.accepts(Path::new("misery.pdf")));
+        let mut documents = Vec::new();
+        TextSource
+            .read_documents(&path, &mut |document| {
+                documents.push(document);
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(documents.len(), 1);
+        assert_eq!(documents[0].title, "misery");
+        assert_eq!(documents[0].url, path.to_string_lossy());
+        assert_eq!(documents[0].text, "misery is a novel\nby stephen king\n");
+    }
+}
@@ -0,0 +1,121 @@
+use std::{io, path::Path};
+
+use crate::indexer::document_source::document_source::{
+    DocumentSource, SourceDocument, find_element, find_element_range, html_to_text,
+    open_document_file, read_line,
+};
+
+// The SGML collections of TREC, where every document is a <DOC> element with its id in <DOCNO>,
+// its title in <TITLE> or <HEADLINE> and its text in one or more <TEXT> elements. The files of
+// these collections rarely have an extension, so every file in the dataset is read.
+#[derive(Debug, Clone)]
+pub struct TrecSource;
+
+impl TrecSource {
+    fn parse_document(document: &str) -> SourceDocument {
+        let docno = find_element(document, "docno")
+            .map(|docno| docno.trim().to_string())
+            .unwrap_or_default();
+        let title = find_element(document, "title")
+            .or_else(|| find_element(document, "headline"))
+            .map(|title| collapse_whitespace(&html_to_text(title).1))
+            .unwrap_or_else(|| docno.clone());
+
+        let mut text = String::new();
+        let mut rest = document;
+        while let Some(section) = find_element_range(rest, "text") {
+            text.push_str(&html_to_text(&rest[section.clone()]).1);
+            text.push('\n');
+            rest = &rest[section.end..];
+        }
+        if text.is_empty() {
+            text = html_to_text(document).1;
+        }
+        SourceDocument {
+            title,
+            url: docno,
+            text,
+        }
+    }
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+impl DocumentSource for TrecSource {
+    fn accepts(&self, path: &Path) -> bool {
+        path.file_name()
+            .is_some_and(|name| !name.to_string_lossy().starts_with('.'))
+    }
+
+    fn read_documents(
+        &self,
+        path: &Path,
+        add_document: &mut dyn FnMut(SourceDocument) -> io::Result<()>,
+    ) -> io::Result<()> {
+        let mut reader = open_document_file(path)?;
+        let mut bytes = Vec::new();
+        let mut line = String::new();
+        let mut document = String::new();
+        let mut is_in_document = false;
+        while read_line(&mut reader, &mut bytes, &mut line)? {
+            let tag = line.trim().to_ascii_uppercase();
+            if tag.starts_with("<DOC>") {
+                is_in_document = true;
+                document.clear();
+            } else if tag.starts_with("</DOC>") {
+                if is_in_document {
+                    add_document(Self::parse_document(&document))?;
+                }
+                is_in_document = false;
+            } else if is_in_document {
+                document.push_str(&line);
+                document.push('\n');
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_read_trec_collection() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("FT911_1");
+        std::fs::write(
+            &path,
+            "<DOC>\n<DOCNO> FT911-1 </DOCNO>\n<HEADLINE>\nFT  14 MAY 91 / Misery\n</HEADLINE>\n\
+             <TEXT>\nA novel by <B>Stephen</B> King.\n</TEXT>\n<TEXT>\nAnnie &amp; Paul.\n</TEXT>\n\
+             </DOC>\n<doc>\n<docno>FT911-2</docno>\nno text elements here\n</doc>\n",
+        )
+        .unwrap();
+
+        assert!(TrecSource.accepts(&path));
+        assert!(!TrecSource.accepts(Path::new(".DS_Store")));
+        let mut documents = Vec::new();
+        TrecSource
+            .read_documents(&path, &mut |document| {
+                documents.push(document);
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(documents.len(), 2);
+        assert_eq!(documents[0].url, "FT911-1");
+        assert_eq!(documents[0].title, "FT 14 MAY 91 / Misery");
+        let words: Vec<&str> = documents[0].text.split_whitespace().collect();
+        assert_eq!(
+            words,
+            vec![
+                "A", "novel", "by", "Stephen", "King.", "Annie", "&", "Paul."
+            ]
+        );
+        assert_eq!(documents[1].title, "FT911-2");
+        assert!(documents[1].text.contains("no text elements here"));
+    }
+}
@@ -0,0 +1,206 @@
+use std::{
+    io::{self, Read},
+    path::Path,
+};
+
+use crate::indexer::document_source::document_source::{
+    DocumentSource, SourceDocument, has_document_file_extension, html_to_text, open_document_file,
+    read_line,
+};
+
+// Web archives, the records of a WARC file are a block of headers followed by as many bytes of
+// content as the Content-Length header says. The pages of the response and resource records and
+// the extracted text of the conversion records of a WET file are indexed, the other records and
+// the responses which are not text are skipped. The url of a document is the WARC-Target-URI of
+// its record and its title is the title of its page.
+#[derive(Debug, Clone)]
+pub struct WarcSource;
+
+struct WarcRecord {
+    record_type: String,
+    target_uri: String,
+    content_type: String,
+    content: Vec<u8>,
+}
+
+impl WarcSource {
+    fn parse_document(record: WarcRecord) -> Option<SourceDocument> {
+        let (content_type, body) = match record.record_type.as_str() {
+            "response" if record.content_type.starts_with("application/http") => {
+                split_http_response(&record.content)?
+            }
+            "response" | "resource" => (record.content_type.clone(), &record.content[..]),
+            "conversion" => ("text/plain".to_string(), &record.content[..]),
+            _ => return None,
+        };
+        let body = String::from_utf8_lossy(body);
+        let (title, text) = if content_type.contains("html") {
+            html_to_text(&body)
+        } else if content_type.starts_with("text/") {
+            (None, body.into_owned())
+        } else {
+            return None;
+        };
+        Some(SourceDocument {
+            title: title
+                .filter(|title| !title.is_empty())
+                .unwrap_or_else(|| record.target_uri.clone()),
+            url: record.target_uri,
+            text,
+        })
+    }
+}
+
+// the content type and the body of an http response
+fn split_http_response(response: &[u8]) -> Option<(String, &[u8])> {
+    let (header_length, separator_length) = response
+        .windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .map(|position| (position, 4))
+        .or_else(|| {
+            response
+                .windows(2)
+                .position(|window| window == b"\n\n")
+                .map(|position| (position, 2))
+        })?;
+    let headers = String::from_utf8_lossy(&response[..header_length]);
+    let content_type = find_header(headers.lines(), "content-type")
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    Some((content_type, &response[header_length + separator_length..]))
+}
+
+fn find_header<'a>(mut lines: impl Iterator<Item = &'a str>, name: &str) -> Option<String> {
+    lines.find_map(|line| {
+        let (header_name, value) = line.split_once(':')?;
+        header_name
+            .trim()
+            .eq_ignore_ascii_case(name)
+            .then(|| value.trim().to_string())
+    })
+}
+
+impl DocumentSource for WarcSource {
+    fn accepts(&self, path: &Path) -> bool {
+        has_document_file_extension(path, &["warc", "wet"])
+    }
+
+    fn read_documents(
+        &self,
+        path: &Path,
+        add_document: &mut dyn FnMut(SourceDocument) -> io::Result<()>,
+    ) -> io::Result<()> {
+        let mut reader = open_document_file(path)?;
+        let mut bytes = Vec::new();
+        let mut line = String::new();
+        loop {
+            // the records are separated by blank lines
+            let mut has_version = false;
+            while read_line(&mut reader, &mut bytes, &mut line)? {
+                if line.starts_with("WARC/") {
+                    has_version = true;
+                    break;
+                }
+            }
+            if !has_version {
+                return Ok(());
+            }
+
+            let mut headers = Vec::new();
+            while read_line(&mut reader, &mut bytes, &mut line)? && !line.is_empty() {
+                headers.push(line.clone());
+            }
+            let header = |name: &str| {
+                find_header(headers.iter().map(|header| header.as_str()), name).unwrap_or_default()
+            };
+            let content_length: u64 = header("content-length").parse().map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{} has a record without a content length", path.display()),
+                )
+            })?;
+            let mut content = Vec::new();
+            reader
+                .by_ref()
+                .take(content_length)
+                .read_to_end(&mut content)?;
+            if (content.len() as u64) < content_length {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+
+            let record = WarcRecord {
+                record_type: header("warc-type").to_ascii_lowercase(),
+                target_uri: header("warc-target-uri"),
+                content_type: header("content-type").to_ascii_lowercase(),
+                content,
+            };
+            if let Some(document) = Self::parse_document(record) {
+                add_document(document)?;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    fn warc_record(headers: &str, content: &str) -> String {
+        format!(
+            "WARC/1.0\r\n{}Content-Length: {}\r\n\r\n{}\r\n\r\n",
+            headers,
+            content.len(),
+            content
+        )
+    }
+
+    #[test]
+    fn test_read_warc_file() {
+        let page = "HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\n\r\n\
+                    <html><head><title>Misery</title></head><body>A novel by Stephen King</body></html>";
+        let image = "HTTP/1.1 200 OK\r\nContent-Type: image/png\r\n\r\n\u{1}PNG";
+        let contents = [
+            warc_record("WARC-Type: warcinfo\r\n", "software: crawler"),
+            warc_record(
+                "WARC-Type: response\r\nWARC-Target-URI: https://example.com/Misery\r\n\
+                 Content-Type: application/http; msgtype=response\r\n",
+                page,
+            ),
+            warc_record(
+                "WARC-Type: response\r\nWARC-Target-URI: https://example.com/logo.png\r\n\
+                 Content-Type: application/http; msgtype=response\r\n",
+                image,
+            ),
+            warc_record(
+                "WARC-Type: conversion\r\nWARC-Target-URI: https://example.com/River\r\n\
+                 Content-Type: text/plain\r\n",
+                "a quiet river\nflows past the mill",
+            ),
+        ]
+        .concat();
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("crawl.warc");
+        std::fs::write(&path, contents).unwrap();
+
+        assert!(WarcSource.accepts(&path));
+        assert!(WarcSource.accepts(Path::new("crawl.warc.wet.gz")));
+        let mut documents = Vec::new();
+        WarcSource
+            .read_documents(&path, &mut |document| {
+                documents.push(document);
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(documents.len(), 2);
+        assert_eq!(documents[0].title, "Misery");
+        assert_eq!(documents[0].url, "https://example.com/Misery");
+        assert_eq!(
+            documents[0].text.split_whitespace().collect::<Vec<_>>(),
+            vec!["A", "novel", "by", "Stephen", "King"]
+        );
+        assert_eq!(documents[1].title, "https://example.com/River");
+        assert_eq!(documents[1].text, "a quiet river\nflows past the mill");
+    }
+}
@@ -0,0 +1,324 @@
+use crate::indexer::document_source::document_source::decode_entities;
+
+// The sections at the end of an article which are mostly lists of sources and links, they are
+// dropped along with their subsections like the preprocessing script used to
+const DROPPED_SECTIONS: [&str; 6] = [
+    "See also",
+    "Further reading",
+    "Notes",
+    "References",
+    "External links",
+    "Bibliography",
+];
+
+// the links to these namespaces are images and categories rather than text
+const DROPPED_LINK_NAMESPACES: [&str; 4] = ["file:", "image:", "media:", "category:"];
+
+// the tags whose contents are not part of the text of the article
+const DROPPED_TAGS: [&str; 10] = [
+    "ref",
+    "references",
+    "math",
+    "chem",
+    "gallery",
+    "timeline",
+    "score",
+    "syntaxhighlight",
+    "source",
+    "imagemap",
+];
+
+// Turns the wikitext of an article into its plain text. The templates (infoboxes, citations,
+// navboxes ...), the tables, the references, the images and the categories are dropped, a link is
+// replaced by the text it shows and the headings, the list markers and the bold and italic quotes
+// are removed. Every paragraph is put on a line of its own.
+pub fn strip_wikitext(wikitext: &str) -> String {
+    let without_comments = remove_comments(wikitext);
+    let lines = drop_sections(&without_comments);
+    let mut stripped = String::with_capacity(lines.len());
+    strip_markup(&lines, &mut stripped);
+    let text = decode_entities(&stripped);
+
+    let mut plain_text = String::with_capacity(text.len());
+    for line in text.lines() {
+        let line = line.trim();
+        if !line.is_empty() {
+            if !plain_text.is_empty() {
+                plain_text.push('\n');
+            }
+            plain_text.push_str(line);
+        }
+    }
+    plain_text
+}
+
+fn remove_comments(wikitext: &str) -> String {
+    let mut text = String::with_capacity(wikitext.len());
+    let mut rest = wikitext;
+    while let Some(start) = rest.find("<!--") {
+        text.push_str(&rest[..start]);
+        rest = match rest[start..].find("-->") {
+            Some(end) => &rest[start + end + 3..],
+            None => "",
+        };
+    }
+    text.push_str(rest);
+    text
+}
+
+// The level and the title of a heading line such as == Early life ==
+fn parse_heading(line: &str) -> Option<(usize, &str)> {
+    let line = line.trim_end();
+    let level = line.bytes().take_while(|&byte| byte == b'=').count().min(6);
+    if level == 0 || !line.ends_with('=') {
+        return None;
+    }
+    let closing = line.bytes().rev().take_while(|&byte| byte == b'=').count();
+    let level = level.min(closing);
+    if line.len() <= level * 2 {
+        return None;
+    }
+    Some((level, line[level..line.len() - level].trim()))
+}
+
+// Drops the sections in DROPPED_SECTIONS up to the next heading of the same or a higher level, and
+// takes the markup off the other headings and off the lines of lists
+fn drop_sections(wikitext: &str) -> String {
+    let mut text = String::with_capacity(wikitext.len());
+    let mut dropped_level = None;
+    for line in wikitext.lines() {
+        if let Some((level, title)) = parse_heading(line) {
+            if dropped_level.is_some_and(|dropped_level| level > dropped_level) {
+                continue;
+            }
+            dropped_level = DROPPED_SECTIONS
+                .iter()
+                .any(|section| section.eq_ignore_ascii_case(title))
+                .then_some(level);
+            if dropped_level.is_none() {
+                text.push_str(title);
+                text.push('\n');
+            }
+            continue;
+        }
+        if dropped_level.is_some() || line.trim_start().starts_with("----") {
+            continue;
+        }
+        text.push_str(line.trim_start_matches(['*', '#', ':', ';']));
+        text.push('\n');
+    }
+    text
+}
+
+// The end of the element which starts at the opening delimiter, nested elements are skipped
+fn find_closing(text: &str, open: &str, close: &str) -> Option<usize> {
+    let bytes = text.as_bytes();
+    let mut depth = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i..].starts_with(open.as_bytes()) {
+            depth += 1;
+            i += open.len();
+        } else if bytes[i..].starts_with(close.as_bytes()) {
+            depth -= 1;
+            i += close.len();
+            if depth == 0 {
+                return Some(i);
+            }
+        } else {
+            i += 1;
+        }
+    }
+    None
+}
+
+// A table starts with a line beginning with {| and ends with a line beginning with |}, which is
+// only looked for at the start of a line since |} also ends the parameters of templates
+fn find_table_end(text: &str) -> usize {
+    let mut depth = 0;
+    let mut position = 0;
+    for line in text.split_inclusive('\n') {
+        position += line.len();
+        let line = line.trim_start();
+        if line.starts_with("{|") {
+            depth += 1;
+        } else if line.starts_with("|}") {
+            depth -= 1;
+            if depth == 0 {
+                return position;
+            }
+        }
+    }
+    text.len()
+}
+
+fn is_line_start(text: &str, position: usize) -> bool {
+    text[..position]
+        .bytes()
+        .rev()
+        .find(|&byte| byte != b' ' && byte != b'\t')
+        .is_none_or(|byte| byte == b'\n')
+}
+
+fn strip_markup(text: &str, stripped: &mut String) {
+    // the start of the text which has not been copied yet
+    let mut position = 0;
+    let mut i = 0;
+    while i < text.len() {
+        let rest = &text[i..];
+        let markup_length = if rest.starts_with("{|") && is_line_start(text, i) {
+            Some(find_table_end(rest))
+        } else if rest.starts_with("{{") {
+            find_closing(rest, "{{", "}}")
+        } else if rest.starts_with("[[") {
+            find_closing(rest, "[[", "]]")
+        } else if rest.starts_with('[') && is_external_link(&rest[1..]) {
+            rest.find(']').map(|end| end + 1)
+        } else if rest.starts_with('<') {
+            find_tag_end(rest)
+        } else if rest.starts_with("''") {
+            Some(rest.bytes().take_while(|&byte| byte == b'\'').count())
+        } else if let Some(magic_word) = rest.strip_prefix("__") {
+            // magic words such as __NOTOC__
+            let name_length = magic_word
+                .bytes()
+                .take_while(|byte| byte.is_ascii_uppercase())
+                .count();
+            (name_length > 0 && magic_word[name_length..].starts_with("__"))
+                .then_some(name_length + 4)
+        } else {
+            None
+        };
+        let Some(markup_length) = markup_length else {
+            i += rest.chars().next().map_or(1, char::len_utf8);
+            continue;
+        };
+
+        stripped.push_str(&text[position..i]);
+        let markup = &rest[..markup_length];
+        if markup.starts_with("[[") {
+            strip_link(&markup[2..markup_length - 2], stripped);
+        } else if markup.starts_with('[') {
+            // [https://example.com the site] shows the text after the url
+            if let Some((_, link_text)) = markup[1..markup_length - 1].split_once(' ') {
+                strip_markup(link_text, stripped);
+            }
+        }
+        i += markup_length;
+        position = i;
+    }
+    stripped.push_str(&text[position..]);
+}
+
+// [[Stephen King]] shows its target and [[Misery (novel)|Misery]] its text, the links to images
+// and categories are dropped
+fn strip_link(link: &str, stripped: &mut String) {
+    let (target, link_text) = link.split_once('|').unwrap_or((link, ""));
+    let target = target.trim();
+    let is_dropped = DROPPED_LINK_NAMESPACES.iter().any(|namespace| {
+        target
+            .get(..namespace.len())
+            .is_some_and(|prefix| prefix.eq_ignore_ascii_case(namespace))
+    });
+    if is_dropped {
+        return;
+    }
+    if link_text.trim().is_empty() {
+        strip_markup(target.trim_start_matches(':'), stripped);
+    } else {
+        strip_markup(link_text, stripped);
+    }
+}
+
+fn is_external_link(text: &str) -> bool {
+    ["http://", "https://", "ftp://", "//"]
+        .iter()
+        .any(|scheme| text.starts_with(scheme))
+}
+
+// The length of the tag at the start of the text, along with the contents up to the closing tag
+// for the tags whose contents are dropped. None if the text does not start with a tag.
+fn find_tag_end(text: &str) -> Option<usize> {
+    let name_start = if text[1..].starts_with('/') { 2 } else { 1 };
+    let name_length = text[name_start..]
+        .bytes()
+        .take_while(|byte| byte.is_ascii_alphanumeric())
+        .count();
+    if name_length == 0 {
+        return None;
+    }
+    // a < which is not closed on the same line is not a tag
+    let tag_end = text
+        .find(['>', '\n'])
+        .filter(|&end| text.as_bytes()[end] == b'>')?
+        + 1;
+    let name = text[name_start..name_start + name_length].to_ascii_lowercase();
+    let is_self_closing = text[..tag_end].ends_with("/>");
+    if name_start == 2 || is_self_closing || !DROPPED_TAGS.contains(&name.as_str()) {
+        return Some(tag_end);
+    }
+    let closing_tag = format!("</{}", name);
+    match text[tag_end..].to_ascii_lowercase().find(&closing_tag) {
+        Some(closing_start) => {
+            let closing_start = tag_end + closing_start;
+            Some(closing_start + text[closing_start..].find('>').map_or(0, |end| end + 1))
+        }
+        None => Some(tag_end),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_wikitext() {
+        let wikitext = "{{Short description|1987 novel by Stephen King}}
+{{Infobox book
+| name = Misery
+| author = [[Stephen King]]
+}}
+'''''Misery''''' is a [[psychological horror|horror]] novel by [[Stephen King]].<ref name=\"sk\">{{cite book|title=Misery}}</ref>
+[[File:Misery.jpg|thumb|The cover of [[Misery (novel)|the novel]]]]
+<!-- a comment which is not shown -->
+== Plot ==
+Paul Sheldon is rescued by Annie&nbsp;Wilkes, see [https://example.com the site].__NOTOC__
+[[Category:Novels by Stephen King]]
+* a <small>list</small> item<br/> where 1 <2
+{| class=\"wikitable\"
+|-
+| Year || 1987 {{x|}}
+|}
+=== Reception ===
+The book was a success.<ref>Review</ref>
+== References ==
+{{Reflist}}
+=== Sources ===
+* Book
+== External links ==
+* [https://example.com Official site]
+[[Category:1987 novels]]
+";
+        assert_eq!(
+            strip_wikitext(wikitext),
+            "Misery is a horror novel by Stephen King.\n\
+             Plot\n\
+             Paul Sheldon is rescued by Annie Wilkes, see the site.\n\
+             a list item where 1 <2\n\
+             Reception\n\
+             The book was a success."
+        );
+    }
+
+    #[test]
+    fn test_parse_heading() {
+        assert_eq!(parse_heading("== Early life =="), Some((2, "Early life")));
+        assert_eq!(parse_heading("===Career=== "), Some((3, "Career")));
+        assert_eq!(
+            parse_heading("== Unbalanced ==="),
+            Some((2, "Unbalanced ="))
+        );
+        assert_eq!(parse_heading("a == b =="), None);
+        assert_eq!(parse_heading("===="), None);
+    }
+}
@@ -1,237 +1,135 @@
 use std::{
-    fs::File,
-    io::{self, BufReader, Read},
-    path::Path,
-    sync::{
-        Arc, Mutex,
-        atomic::{AtomicU32, Ordering},
-        mpsc,
-    },
-    thread::sleep,
-    time::{Duration, SystemTime},
+    io, mem,
+    path::{Path, PathBuf},
+    sync::mpsc,
 };
 
-use once_cell::sync::Lazy;
-use regex::Regex;
 use rustc_hash::FxHashMap;
-use zstd::{Decoder, bulk::Decompressor};
 
 use crate::{
-    indexer::types::{WikiArticle, WikiArticle1},
+    indexer::{
+        build_manifest::{Documents, FileProgress},
+        document_source::document_source::DocumentSource,
+    },
     parser::parser::{Parser, Token},
-    utils::{posting::Posting, term::Term},
+    utils::{field::Field, posting::Posting, term::Term},
 };
 
-static TAG_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"<[^>]*>").unwrap());
-
-pub(crate) fn extract_plaintext(text: &[Vec<String>]) -> String {
-    let total_len: usize = text
-        .iter()
-        .map(|para| para.iter().map(|s| s.len()).sum::<usize>())
-        .sum();
-    let mut result = String::with_capacity(total_len + text.len() * 2);
-    for (i, paragraph) in text.iter().enumerate() {
-        if i > 0 {
-            result.push_str("\n\n");
-        }
-        for sentence in paragraph {
-            result.push_str(sentence);
+// Tokenizes the text of a field of a document and adds a term for every distinct token, along with
+// the positions of the token in the field. Returns the no of tokens in the field.
+fn add_field_terms(
+    text: &str,
+    field: Field,
+    local_doc_index: u32,
+    search_tokenizer: &Parser,
+    token_vec: &mut Vec<Token>,
+    terms: &mut Vec<Term>,
+) -> u32 {
+    token_vec.clear();
+    search_tokenizer.tokenize(text, token_vec);
+    let mut doc_postings: FxHashMap<&str, Vec<u32>> =
+        FxHashMap::with_capacity_and_hasher(token_vec.len(), Default::default());
+    for token in token_vec.iter() {
+        doc_postings
+            .entry(&token.word)
+            .or_default()
+            .push(token.position);
+    }
+    for (key, value) in doc_postings.drain() {
+        terms.push(Term {
+            posting: Posting::new(local_doc_index, value),
+            term: field.get_field_term(key),
+        });
+    }
+    token_vec.len() as u32
+}
+
+// The documents of a part of a file of the dataset along with their terms, which are sent to the
+// SPIMI thread. The doc ids of the postings start from 0 for every batch, the SPIMI thread assigns
+// the actual doc ids in the order the batches arrive in.
+pub struct DocumentBatch {
+    pub file: PathBuf,
+    pub terms: Vec<Term>,
+    pub documents: Documents,
+    // how far the file has been read with the documents of this batch
+    pub progress: FileProgress,
+}
+
+impl DocumentBatch {
+    fn new(file: &Path) -> Self {
+        Self {
+            file: file.to_path_buf(),
+            terms: Vec::with_capacity(50000),
+            documents: Documents::default(),
+            progress: FileProgress::default(),
         }
     }
-    TAG_REGEX.replace_all(&result, "").into_owned()
 }
 
-pub(crate) fn read_zstd_file(
+// the no of documents in a batch, a large file is sent in several batches so that it does not
+// have to fit in memory
+const DOCUMENTS_PER_BATCH: usize = 5000;
+
+// Reads the documents of a file with the document source and sends their terms in batches, the
+// text and the title of every document are indexed as separate fields. The documents which were
+// already indexed by a build which stopped are skipped.
+pub(crate) fn read_document_file(
     path: &Path,
-    tx: &mpsc::SyncSender<Vec<Term>>,
-    doc_id: &Arc<AtomicU32>,
-    doc_lengths: &Arc<Mutex<Vec<u32>>>,
-    doc_urls: &Arc<Mutex<Vec<String>>>,
-    doc_names: &Arc<Mutex<Vec<String>>>,
+    documents_to_skip: u32,
+    document_source: &dyn DocumentSource,
+    tx: &mpsc::SyncSender<DocumentBatch>,
     search_tokenizer: &Parser,
 ) -> io::Result<()> {
-    // let file = File::open(path)?;
-    let file = File::open(path)?;
-
-    // Wrap the file in a Zstd decoder
-    let mut decoder = zstd::Decoder::new(file)?;
-    let mut output: Vec<u8> = Vec::with_capacity(10 * 1024 * 1024); // e.g., 10MB
-    decoder.read_to_end(&mut output).unwrap();
-
-    // zstd::stream::copy_decode(file, output);
-
-    // let reader = BufReader::new(decoder);
-
-    // let stream = serde_json::Deserializer::from_reader(reader).into_iter::<WikiArticle1>();
-    let mut terms = Vec::with_capacity(50000);
-    let mut local_lengths = Vec::with_capacity(500);
-    let mut local_names = Vec::with_capacity(500);
-    let mut local_urls = Vec::with_capacity(500);
-    let mut local_doc_index = 0u32;
-
-    let mut start = 0;
+    let send = |batch: DocumentBatch| {
+        tx.send(batch).map_err(|_| {
+            io::Error::new(io::ErrorKind::BrokenPipe, "the indexing thread has stopped")
+        })
+    };
+    let mut batch = DocumentBatch::new(path);
+    let mut documents_read = 0u32;
     let mut token_vec: Vec<Token> = Vec::with_capacity(100);
-    // let current_time = SystemTime::now();
-
-    for (i, &byte) in output.iter().enumerate() {
-        if byte == b'\n' {
-            let line = &output[start..i];
-
-            if !line.is_empty() {
-                match serde_json::from_slice::<WikiArticle1>(line) {
-                    Ok(json) => {
-                        // println!("{:?}", json.text);
-                        // sleep(Duration::from_secs(2));
-                        //
-                        // let current_time = SystemTime::now();
-                        let mut doc_postings: FxHashMap<&str, Vec<u32>> =
-                            FxHashMap::with_capacity_and_hasher(400, Default::default());
-                        // // println!("{:?}", article);
-                        // // let plain_text = extract_plaintext(&article.text);
-                        token_vec.clear();
-                        search_tokenizer.tokenize(&json.text, &mut token_vec);
-                        // // println!("{:?}", tokens);
-                        // // sleep(Duration::from_secs(3));
-
-                        if token_vec.len() == 0 {
-                            continue;
-                        }
-                        local_lengths.push(token_vec.len() as u32);
-                        local_names.push(json.title);
-                        local_urls.push(json.url);
-                        for token in &token_vec {
-                            doc_postings
-                                .entry(&token.word)
-                                .or_insert_with(Vec::new)
-                                .push(token.position);
-                        }
-                        // println!("{}", doc_postings.len());
-                        for (key, value) in doc_postings.drain() {
-                            let term = Term {
-                                posting: Posting::new(local_doc_index, value),
-                                term: key.to_string(),
-                            };
-                            terms.push(term);
-                        }
-                        // let now_time = SystemTime::now();
-                        // println!("{:?}", now_time.duration_since(current_time).unwrap());
-                        local_doc_index += 1;
-                    }
-                    Err(e) => {
-                        eprintln!("Failed to parse line: {}", e);
-                        // Optionally print the raw line for debugging
-                        // if let Ok(s) = std::str::from_utf8(line) {
-                        //     eprintln!("Raw line: {}", s);
-                        // }
-                    }
-                }
-            }
-
-            start = i + 1;
-        }
-    }
 
-    // Handle last line
-    if start < output.len() {
-        let line = &output[start..];
-        if !line.is_empty() {
-            if let Ok(json) = serde_json::from_slice::<WikiArticle1>(line) {
-                let mut doc_postings: FxHashMap<&str, Vec<u32>> =
-                    FxHashMap::with_capacity_and_hasher(400, Default::default());
-                // println!("{:?}", article);
-                // let plain_text = extract_plaintext(&article.text);
-                token_vec.clear();
-                search_tokenizer.tokenize(&json.text, &mut token_vec);
-                // println!("{:?}", tokens);
-                // sleep(Duration::from_secs(3));
-                if !token_vec.len() == 0 {
-                    local_lengths.push(token_vec.len() as u32);
-                    local_names.push(json.title);
-                    local_urls.push(json.url);
-                    for token in &token_vec {
-                        doc_postings
-                            .entry(&token.word)
-                            .or_insert_with(Vec::new)
-                            .push(token.position);
-                    }
-                    // println!("{}", doc_postings.len());
-                    for (key, value) in doc_postings.drain() {
-                        let term = Term {
-                            posting: Posting::new(local_doc_index, value),
-                            term: key.to_string(),
-                        };
-                        terms.push(term);
-                    }
-                    // local_doc_index += 1;
-                }
-                // sleep(Duration::from_secs(2));
-            }
+    document_source.read_documents(path, &mut |document| {
+        documents_read += 1;
+        if documents_read <= documents_to_skip {
+            return Ok(());
         }
-    }
+        let local_doc_index = batch.documents.len() as u32;
+        let doc_length = add_field_terms(
+            &document.text,
+            Field::Body,
+            local_doc_index,
+            search_tokenizer,
+            &mut token_vec,
+            &mut batch.terms,
+        );
+        if doc_length != 0 {
+            let title_length = add_field_terms(
+                &document.title,
+                Field::Title,
+                local_doc_index,
+                search_tokenizer,
+                &mut token_vec,
+                &mut batch.terms,
+            );
+            batch.documents.lengths.push(doc_length);
+            batch.documents.title_lengths.push(title_length);
+            batch.documents.names.push(document.title);
+            batch.documents.urls.push(document.url);
+        }
+        if batch.documents.len() >= DOCUMENTS_PER_BATCH {
+            batch.progress.documents_read = documents_read;
+            send(mem::replace(&mut batch, DocumentBatch::new(path)))?;
+        }
+        Ok(())
+    })?;
 
-    // println!("{:?}", local_lengths.len());
-    // for result in stream {
-    //     match result {
-    //         Ok(article) => {
-    // let mut doc_postings: FxHashMap<&str, Vec<u32>> =
-    //     FxHashMap::with_capacity_and_hasher(500, Default::default());
-    // // println!("{:?}", article);
-    // // let plain_text = extract_plaintext(&article.text);
-    // let tokens = search_tokenizer.tokenize(&article.text);
-    // // println!("{:?}", tokens);
-    // // sleep(Duration::from_secs(3));
-
-    // if tokens.len() == 0 {
-    //     continue;
-    // }
-    // local_lengths.push(tokens.len() as u32);
-    // local_names.push(article.title);
-    // local_urls.push(article.url);
-    // for token in &tokens {
-    //     doc_postings
-    //         .entry(&token.word)
-    //         .or_insert_with(Vec::new)
-    //         .push(token.position);
-    // }
-    // for (key, value) in doc_postings.drain() {
-    //     let term = Term {
-    //         posting: Posting::new(local_doc_index, value),
-    //         term: key.to_string(),
-    //     };
-    //     terms.push(term);
-    // }
-    // local_doc_index += 1;
-    //         }
-    //         Err(e) => {
-    //             eprintln!("Error parsing: {}", e);
-    //         }
-    //     }
-    // }
-
-    let start_doc_id = {
-        let mut lengths = doc_lengths.lock().unwrap();
-        let mut names = doc_names.lock().unwrap();
-        let mut urls = doc_urls.lock().unwrap();
-
-        let start_id = doc_id.fetch_add(local_lengths.len() as u32, Ordering::SeqCst);
-
-        lengths.append(&mut local_lengths);
-        names.append(&mut local_names);
-        urls.append(&mut local_urls);
-
-        start_id
+    // the last batch is sent even without documents so the file is recorded as finished
+    batch.progress = FileProgress {
+        documents_read,
+        is_finished: true,
     };
-
-    for term in &mut terms {
-        term.posting.doc_id = start_doc_id + term.posting.doc_id + 1;
-    }
-
-    // let now_time = SystemTime::now();
-    // println!("{:?}", now_time.duration_since(current_time).unwrap());
-
-    tx.send(terms).unwrap();
-
-    Ok(())
+    send(batch)
 }
 
 pub(crate) fn vb_decode_posting_list(encoded_bytes: &[u8]) -> Vec<Posting> {
@@ -257,7 +155,7 @@ pub(crate) fn vb_decode_posting_list(encoded_bytes: &[u8]) -> Vec<Posting> {
     posting_list
 }
 
-pub(crate) fn vb_encode_posting_list(posting_list: &Vec<Posting>) -> Vec<u8> {
+pub(crate) fn vb_encode_posting_list(posting_list: &[Posting]) -> Vec<u8> {
     let mut posting_list_bytes: Vec<u8> = Vec::<u8>::with_capacity(200);
     // posting_list.sort_by(|a, b| a.doc_id.cmp(&b.doc_id));
     let mut indices: Vec<usize> = (0..posting_list.len()).collect();
@@ -1,17 +1,31 @@
 use crate::{
     compressor::compressor::CompressionAlgorithm,
     in_memory_index_metadata::in_memory_index_metadata::InMemoryIndexMetadata,
-    indexer::{helper::read_zstd_file, spimi::spimi::Spimi},
+    indexer::{
+        build_manifest::{BuildManifest, BuildReport, Documents},
+        document_source::document_source::{DocumentFormat, DocumentSource, FieldMapping},
+        helper::{DocumentBatch, read_document_file},
+        spimi::spimi::Spimi,
+    },
     parser::parser::Parser,
-    utils::{term::Term, types::DocumentMetadata},
+    scoring::{ScoringModel, bm_25::BM25Params},
+    segment::segment::Segment,
+    utils::{
+        bitmap::Bitmap,
+        checksum::{ChecksumReader, ChecksumWriter},
+        field::DocumentLengths,
+        index_format::{DOCUMENT_METADATA_MAGIC, FORMAT_VERSION, check_format_version},
+        paths::{get_delta_directory_path, get_inverted_index_path, get_segment_directory_path},
+        types::DocumentMetadata,
+    },
 };
 
 use std::{
+    fs::{self, File},
     io::{self, Read, Write},
     path::{Path, PathBuf},
     sync::{
-        Arc, Mutex,
-        atomic::{AtomicU32, Ordering},
+        Arc,
         mpsc::{self},
     },
     thread,
@@ -23,15 +37,21 @@ use std::{
 // needed to answer queries.
 pub struct Indexer {
     avg_doc_length: f32,
+    avg_title_length: f32,
     no_of_docs: u32,
     include_positions: bool,
     pub document_names: Box<[String]>,
     pub document_urls: Box<[String]>,
     pub document_lengths: Box<[u32]>,
+    pub title_lengths: Box<[u32]>,
+    pub deleted_documents: Bitmap,
     parser: Parser,
     compression_algorithm: CompressionAlgorithm,
+    scoring_model: ScoringModel,
+    bm25_params: BM25Params,
     index_directory_path: PathBuf,
     dataset_directory_path: PathBuf,
+    document_source: Arc<dyn DocumentSource>,
 }
 
 impl Indexer {
@@ -42,15 +62,21 @@ impl Indexer {
     ) -> Result<Self, std::io::Error> {
         Ok(Self {
             avg_doc_length: 0.0,
+            avg_title_length: 0.0,
             no_of_docs: 0,
             include_positions: false,
             document_lengths: Box::new([]),
+            title_lengths: Box::new([]),
             document_names: Box::new([]),
             document_urls: Box::new([]),
+            deleted_documents: Bitmap::new(),
             dataset_directory_path: PathBuf::new(),
             parser,
             compression_algorithm,
+            scoring_model: ScoringModel::BM25,
+            bm25_params: BM25Params::default(),
             index_directory_path,
+            document_source: DocumentFormat::default().get_source(FieldMapping::default()),
         })
     }
 
@@ -62,11 +88,31 @@ impl Indexer {
         self.avg_doc_length
     }
 
+    pub fn get_avg_title_length(&self) -> f32 {
+        self.avg_title_length
+    }
+
+    // The lengths of the fields of the documents which the documents are scored with
+    pub fn get_document_lengths(&self) -> DocumentLengths<'_> {
+        DocumentLengths {
+            body_lengths: &self.document_lengths,
+            average_body_length: self.avg_doc_length,
+            title_lengths: &self.title_lengths,
+            average_title_length: self.avg_title_length,
+        }
+    }
+
+    // The document metadata starts with the magic and the format version and ends with the
+    // checksum of everything after the magic
     pub fn save_document_metadata<W: Write>(&self, mut writer: W) -> io::Result<()> {
         assert_eq!(self.document_lengths.len(), self.document_names.len());
         assert_eq!(self.document_lengths.len(), self.document_urls.len());
         assert_eq!(self.document_lengths.len() as u32, self.no_of_docs);
 
+        writer.write_all(&DOCUMENT_METADATA_MAGIC)?;
+        let mut writer = ChecksumWriter::new(writer);
+        writer.write_all(&FORMAT_VERSION.to_le_bytes())?;
+
         writer.write_all(&self.no_of_docs.to_le_bytes())?;
         writer.write_all(&self.avg_doc_length.to_le_bytes())?;
 
@@ -80,11 +126,36 @@ impl Indexer {
             writer.write_all(&self.document_lengths[i].to_le_bytes())?;
         }
 
-        writer.flush()?;
+        // the lengths of the titles come after the documents so that the document metadata of an
+        // index which was built before the titles were indexed can still be loaded, such an index
+        // has no title lengths so they are saved as 0
+        writer.write_all(&self.avg_title_length.to_le_bytes())?;
+        for i in 0..self.document_lengths.len() {
+            let title_length = self.title_lengths.get(i).copied().unwrap_or(0);
+            writer.write_all(&title_length.to_le_bytes())?;
+        }
+
+        writer.finish()?;
         Ok(())
     }
 
+    // The document metadata of an index which was saved before it had a header starts with the
+    // no of documents right away
     pub fn load_document_metadata<R: Read>(&mut self, mut reader: R) -> io::Result<()> {
+        let mut buffer: [u8; 4] = [0; 4];
+        reader.read_exact(&mut buffer)?;
+        if buffer != DOCUMENT_METADATA_MAGIC {
+            return self.read_document_metadata(buffer.as_slice().chain(reader));
+        }
+        let mut reader = ChecksumReader::new(reader);
+        reader.read_exact(&mut buffer)?;
+        check_format_version(u32::from_le_bytes(buffer), "the document metadata")?;
+        self.read_document_metadata(&mut reader)?;
+        reader.finish("the document metadata")?;
+        Ok(())
+    }
+
+    fn read_document_metadata<R: Read>(&mut self, mut reader: R) -> io::Result<()> {
         let mut buffer: [u8; 4] = [0; 4];
         reader.read_exact(&mut buffer)?;
         self.no_of_docs = u32::from_le_bytes(buffer);
@@ -97,17 +168,8 @@ impl Indexer {
         let mut document_urls = Vec::with_capacity(self.no_of_docs as usize);
 
         for _ in 0..self.no_of_docs {
-            reader.read_exact(&mut buffer)?;
-            let name_length = u32::from_le_bytes(buffer) as usize;
-            let mut name_buffer: Vec<u8> = vec![0; name_length];
-            reader.read_exact(&mut name_buffer)?;
-            document_names.push(String::from_utf8(name_buffer).unwrap());
-
-            reader.read_exact(&mut buffer)?;
-            let url_length = u32::from_le_bytes(buffer) as usize;
-            let mut url_buffer: Vec<u8> = vec![0; url_length];
-            reader.read_exact(&mut url_buffer)?;
-            document_urls.push(String::from_utf8(url_buffer).unwrap());
+            document_names.push(read_string(&mut reader)?);
+            document_urls.push(read_string(&mut reader)?);
 
             reader.read_exact(&mut buffer)?;
             document_lengths.push(u32::from_le_bytes(buffer));
@@ -117,42 +179,20 @@ impl Indexer {
         self.document_names = document_names.into_boxed_slice();
         self.document_urls = document_urls.into_boxed_slice();
 
-        Ok(())
-    }
-
-    fn process_directory(
-        dir_path: &Path,
-        tx: &mpsc::SyncSender<Vec<Term>>,
-        doc_id: &Arc<AtomicU32>,
-        doc_lengths: &Arc<Mutex<Vec<u32>>>,
-        doc_urls: &Arc<Mutex<Vec<String>>>,
-        doc_names: &Arc<Mutex<Vec<String>>>,
-        search_tokenizer: &Parser,
-    ) -> io::Result<()> {
-        // let current_time = SystemTime::now();
-
-        for entry in std::fs::read_dir(dir_path).unwrap() {
-            let entry = entry.unwrap();
-            let path = entry.path();
-            if let Some(ext) = path.extension().and_then(|s| s.to_str()) {
-                if matches!(ext, "zst" | "zstd") {
-                    read_zstd_file(
-                        &path,
-                        tx,
-                        doc_id,
-                        doc_lengths,
-                        doc_urls,
-                        doc_names,
-                        search_tokenizer,
-                    )?;
+        let mut title_lengths = Vec::with_capacity(self.no_of_docs as usize);
+        match reader.read_exact(&mut buffer) {
+            Ok(()) => {
+                self.avg_title_length = f32::from_le_bytes(buffer);
+                for _ in 0..self.no_of_docs {
+                    reader.read_exact(&mut buffer)?;
+                    title_lengths.push(u32::from_le_bytes(buffer));
                 }
             }
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => self.avg_title_length = 0.0,
+            Err(e) => return Err(e),
         }
-        // let now_time = SystemTime::now();
-        // println!(
-        //     "time taken {:?}",
-        //     now_time.duration_since(current_time).unwrap()
-        // );
+        self.title_lengths = title_lengths.into_boxed_slice();
+
         Ok(())
     }
 
@@ -160,60 +200,149 @@ impl Indexer {
         self.dataset_directory_path = dataset_directory_path;
     }
 
+    // the source which the documents of the dataset and of added files are read with
+    pub fn set_document_source(&mut self, document_source: Arc<dyn DocumentSource>) {
+        self.document_source = document_source;
+    }
+
+    pub fn get_document_source(&self) -> &dyn DocumentSource {
+        self.document_source.as_ref()
+    }
+
     pub fn set_index_directory_path(&mut self, index_directory_path: PathBuf) {
         self.index_directory_path = index_directory_path;
     }
 
+    // positions are needed to answer phrase and proximity queries but they increase
+    // the size of the inverted index considerably
+    pub fn set_include_positions(&mut self, include_positions: bool) {
+        self.include_positions = include_positions;
+    }
+
+    pub fn get_include_positions(&self) -> bool {
+        self.include_positions
+    }
+
+    // the parser which the documents are tokenized with, it has to analyze the text the same way
+    // as the parser of the queries
+    pub fn set_parser(&mut self, parser: Parser) {
+        self.parser = parser;
+    }
+
+    pub fn set_scoring_model(&mut self, scoring_model: ScoringModel) {
+        self.scoring_model = scoring_model;
+    }
+
+    pub fn get_scoring_model(&self) -> ScoringModel {
+        self.scoring_model
+    }
+
+    pub fn set_bm25_params(&mut self, bm25_params: BM25Params) {
+        self.bm25_params = bm25_params;
+    }
+
+    pub fn get_bm25_params(&self) -> BM25Params {
+        self.bm25_params
+    }
+
     pub fn get_dataset_directory_path(&self) -> &str {
-        &self
-            .dataset_directory_path
+        self.dataset_directory_path
             .as_os_str()
             .to_str()
             .unwrap_or_default()
     }
 
     pub fn get_index_directory_path(&self) -> &str {
-        &self
-            .index_directory_path
+        self.index_directory_path
             .as_os_str()
             .to_str()
             .unwrap_or_default()
     }
 
     // we create the temporary index files and then merge them
-    pub fn index(&mut self) -> io::Result<InMemoryIndexMetadata> {
-        self.start_spimi().unwrap();
-        self.merge_spimi_files()
+    // the doc ids are assigned again so the deleted documents are no longer valid
+    pub fn index(&mut self) -> io::Result<(InMemoryIndexMetadata, BuildReport)> {
+        self.deleted_documents.clear();
+        let build_report = self.start_spimi()?;
+        Ok((self.merge_spimi_files()?, build_report))
     }
 
     // Starts the spmi function in another thread and then starts processing the directory
-    // which we need to index
-    fn start_spimi(&mut self) -> io::Result<()> {
-        let (tx, rx) = mpsc::sync_channel::<Vec<Term>>(10);
-        let files: Vec<_> = std::fs::read_dir(self.get_dataset_directory_path())
-            .unwrap()
-            .filter_map(|e| e.ok())
-            .map(|e| e.path())
-            .collect();
+    // which we need to index. A build of the same dataset which was stopped is resumed from the
+    // runs in its build manifest, the build of another dataset is thrown away.
+    fn start_spimi(&mut self) -> io::Result<BuildReport> {
+        let manifest = match BuildManifest::load(&self.index_directory_path)? {
+            Some(manifest) if manifest.is_for_dataset(&self.dataset_directory_path) => manifest,
+            _ => {
+                BuildManifest::remove(&self.index_directory_path)?;
+                BuildManifest::new(&self.index_directory_path, &self.dataset_directory_path, 0)
+            }
+        };
+        manifest.remove_unfinished_runs()?;
+        let build_report = BuildReport {
+            no_of_resumed_docs: manifest.get_no_of_docs(),
+            no_of_resumed_runs: manifest.get_no_of_runs(),
+        };
+
+        let manifest = self.run_spimi(vec![self.dataset_directory_path.clone()], manifest)?;
+        self.set_documents(manifest.load_documents()?);
+        Ok(build_report)
+    }
 
-        let estimated_docs = 6_000_000;
+    // The files of the dataset are the ones which are passed in and the ones anywhere inside the
+    // directories which are passed in, as long as the document source reads them. They are sorted
+    // so that the runs of a build which is resumed cover the files in the same order.
+    fn get_dataset_files(
+        paths: Vec<PathBuf>,
+        document_source: &dyn DocumentSource,
+    ) -> io::Result<Vec<PathBuf>> {
+        let mut files = Vec::new();
+        let mut paths = paths;
+        while let Some(path) = paths.pop() {
+            if path.is_dir() {
+                for entry in std::fs::read_dir(&path)? {
+                    paths.push(entry?.path());
+                }
+            } else if document_source.accepts(&path) {
+                files.push(path);
+            }
+        }
+        files.sort();
+        Ok(files)
+    }
 
-        // We use an instance of doc_id which is passed to the indexing threads
-        // This currently makes it faster
-        let doc_id = Arc::new(AtomicU32::new(0));
-        // The doc metadata in the form of arrays is also passed to the threads
-        let doc_lengths = Arc::new(Mutex::new(Vec::with_capacity(estimated_docs)));
-        let doc_names = Arc::new(Mutex::new(Vec::with_capacity(estimated_docs)));
-        let doc_urls = Arc::new(Mutex::new(Vec::with_capacity(estimated_docs)));
+    // Indexes the files (or directories of files) which the manifest has not recorded as finished
+    // into runs which are written to the directory of the manifest, a file which was partly
+    // indexed is continued after its last indexed document. The manifest is returned with the new
+    // runs, the documents of the runs are read back from it.
+    fn run_spimi(&self, files: Vec<PathBuf>, manifest: BuildManifest) -> io::Result<BuildManifest> {
+        let file_progress = manifest.get_file_progress();
+        let files: Vec<(PathBuf, u32)> =
+            Self::get_dataset_files(files, self.document_source.as_ref())?
+                .into_iter()
+                .filter_map(
+                    |file| match file_progress.get(file.to_string_lossy().as_ref()) {
+                        Some(progress) if progress.is_finished => None,
+                        Some(progress) => Some((file, progress.documents_read)),
+                        None => Some((file, 0)),
+                    },
+                )
+                .collect();
+        if files.is_empty() {
+            return Ok(manifest);
+        }
+        let (tx, rx) = mpsc::sync_channel::<DocumentBatch>(10);
 
-        let mut spmi = Spimi::new(self.get_index_directory_path().to_string());
-        // the spimi function is started
+        let mut spmi = Spimi::new(manifest.get_directory_path().to_str().unwrap().to_string());
+        // the spimi function is started, it assigns the doc ids and writes the runs
+        let mut manifest = manifest;
         let handle = thread::spawn(move || {
-            spmi.single_pass_in_memory_indexing(rx).unwrap();
+            spmi.single_pass_in_memory_indexing(rx, &mut manifest)
+                .map(|_| manifest)
         });
 
         let num_threads = 2;
-        let chunk_size = (files.len() + num_threads - 1) / num_threads;
+        let chunk_size = files.len().div_ceil(num_threads);
         let current_time = SystemTime::now();
 
         // the files are divided based on the number of threads
@@ -222,40 +351,45 @@ impl Indexer {
             .map(|chunk| {
                 let chunk = chunk.to_vec();
                 let tx = tx.clone();
-                let doc_id = Arc::clone(&doc_id);
-                let doc_lengths = Arc::clone(&doc_lengths);
-                let doc_names = Arc::clone(&doc_names);
-                let doc_urls = Arc::clone(&doc_urls);
                 let tokenizer = self.parser.clone();
-                thread::spawn(move || {
+                let document_source = Arc::clone(&self.document_source);
+                thread::spawn(move || -> io::Result<()> {
                     let mut files_processed = 0;
-                    for file in chunk {
+                    for (file, documents_to_skip) in chunk {
                         println!("{:?}", file.as_os_str());
-                        Self::process_directory(
+                        read_document_file(
                             &file,
+                            documents_to_skip,
+                            document_source.as_ref(),
                             &tx,
-                            &doc_id,
-                            &doc_lengths,
-                            &doc_urls,
-                            &doc_names,
                             &tokenizer,
                         )
-                        .unwrap();
+                        .map_err(|e| {
+                            io::Error::new(e.kind(), format!("{}: {}", file.display(), e))
+                        })?;
                         files_processed += 1;
                         println!("Out of {} files, done with {}", chunk_size, files_processed);
                     }
+                    Ok(())
                 })
             })
             .collect();
 
+        let mut result = Ok(());
         for (i, handle) in handles.into_iter().enumerate() {
-            handle.join().unwrap_or_else(|e| {
+            let thread_result = handle.join().unwrap_or_else(|e| {
                 panic!("Thread {} panicked: {:?}", i, e);
             });
+            if result.is_ok() {
+                result = thread_result;
+            }
         }
 
         drop(tx);
-        handle.join().unwrap();
+        // the runs which were written before a file could not be read are kept in the manifest,
+        // the build is resumed from them once the file is fixed
+        let manifest = handle.join().unwrap()?;
+        result?;
 
         let now_time = SystemTime::now();
         println!(
@@ -263,52 +397,146 @@ impl Indexer {
             now_time.duration_since(current_time).unwrap()
         );
 
-        let final_doc_count = doc_id.load(Ordering::SeqCst) as usize;
-
-        let mut lengths = Arc::try_unwrap(doc_lengths).unwrap().into_inner().unwrap();
-        let mut names = Arc::try_unwrap(doc_names).unwrap().into_inner().unwrap();
-        let mut urls = Arc::try_unwrap(doc_urls).unwrap().into_inner().unwrap();
-
-        lengths.truncate(final_doc_count);
-        names.truncate(final_doc_count);
-        urls.truncate(final_doc_count);
-
-        self.document_lengths = lengths.into_boxed_slice();
-        self.document_names = names.into_boxed_slice();
-        self.document_urls = urls.into_boxed_slice();
+        Ok(manifest)
+    }
 
-        self.no_of_docs = final_doc_count as u32;
+    fn set_documents(&mut self, documents: Documents) {
+        self.document_lengths = documents.lengths.into_boxed_slice();
+        self.title_lengths = documents.title_lengths.into_boxed_slice();
+        self.document_names = documents.names.into_boxed_slice();
+        self.document_urls = documents.urls.into_boxed_slice();
+        self.update_doc_statistics();
+    }
 
-        // the average length of the documents is calculated as
-        // it is needed during the processing of queries
+    // the average length of the documents and of their titles is calculated as
+    // it is needed during the processing of queries
+    fn update_doc_statistics(&mut self) {
+        self.no_of_docs = self.document_lengths.len() as u32;
         let mut doc_avg = 0;
         for doc_length in &self.document_lengths {
             doc_avg += doc_length
         }
         self.avg_doc_length = ((doc_avg as f64) / (self.no_of_docs as f64)) as f32;
-        Ok(())
+        let title_avg: u64 = self.title_lengths.iter().map(|&length| length as u64).sum();
+        self.avg_title_length = ((title_avg as f64) / (self.no_of_docs as f64)) as f32;
     }
 
+    // New documents are indexed into a delta inside the index directory with doc ids which continue
+    // from the documents which are already indexed. The delta is then written as a new segment,
+    // the existing segments are left untouched. Returns None if there were no documents to add.
+    pub fn add_documents(
+        &mut self,
+        files: Vec<PathBuf>,
+        segment_id: u32,
+    ) -> io::Result<Option<Segment>> {
+        let delta_directory_path = get_delta_directory_path(&self.index_directory_path);
+        if delta_directory_path.exists() {
+            fs::remove_dir_all(&delta_directory_path)?;
+        }
+        fs::create_dir_all(&delta_directory_path)?;
+
+        let first_doc_id = self.no_of_docs + 1;
+        let manifest = BuildManifest::new(&delta_directory_path, Path::new(""), self.no_of_docs);
+        let new_documents = self.run_spimi(files, manifest)?.load_documents()?;
+        if new_documents.is_empty() {
+            fs::remove_dir_all(&delta_directory_path)?;
+            return Ok(None);
+        }
+        let no_of_new_docs = new_documents.len() as u32;
+
+        let mut documents = Documents {
+            lengths: std::mem::take(&mut self.document_lengths).into_vec(),
+            title_lengths: std::mem::take(&mut self.title_lengths).into_vec(),
+            names: std::mem::take(&mut self.document_names).into_vec(),
+            urls: std::mem::take(&mut self.document_urls).into_vec(),
+        };
+        // the documents of an index built before the titles were indexed have no title lengths
+        documents.title_lengths.resize(documents.len(), 0);
+        documents.append(new_documents);
+        self.set_documents(documents);
+
+        let segment_directory_path =
+            get_segment_directory_path(&self.index_directory_path, segment_id);
+        fs::create_dir_all(&segment_directory_path)?;
+        let mut spmi = Spimi::new(delta_directory_path.to_str().unwrap().to_string());
+        let in_memory_index_metadata = spmi.merge_spimi_index_files_into(
+            File::create(get_inverted_index_path(&segment_directory_path))?,
+            self.include_positions,
+            &self.get_document_lengths(),
+            &self.deleted_documents,
+            self.compression_algorithm.clone(),
+            self.scoring_model,
+            self.bm25_params,
+            128,
+        )?;
+
+        let segment = Segment::new(
+            segment_id,
+            first_doc_id,
+            no_of_new_docs,
+            in_memory_index_metadata,
+        );
+        segment.save(&self.index_directory_path)?;
+        fs::remove_dir_all(&delta_directory_path)?;
+        Ok(Some(segment))
+    }
+
+    // The documents of the runs are read from the build manifest, so that the runs of a build
+    // which was stopped can be merged after a restart
     pub fn merge_spimi_files(&mut self) -> io::Result<InMemoryIndexMetadata> {
+        if let Some(manifest) = BuildManifest::load(&self.index_directory_path)? {
+            manifest.remove_unfinished_runs()?;
+            self.set_documents(manifest.load_documents()?);
+        }
         let mut spmi = Spimi::new(self.get_index_directory_path().to_string());
-        let result = spmi
-            .merge_spimi_index_files(
-                self.avg_doc_length,
-                self.include_positions,
-                &self.document_lengths,
-                self.compression_algorithm.clone(),
-                128,
-            )
-            .unwrap();
-
-        Ok(result)
+        spmi.merge_spimi_index_files(
+            self.include_positions,
+            &self.get_document_lengths(),
+            &self.deleted_documents,
+            self.compression_algorithm.clone(),
+            self.scoring_model,
+            self.bm25_params,
+            128,
+        )
+    }
+
+    // The runs and the build manifest are kept until the index is saved, so that a build whose
+    // index was not saved can still be merged again
+    pub fn remove_build_files(&self) -> io::Result<()> {
+        BuildManifest::remove(&self.index_directory_path)
+    }
+    // The document is only marked as deleted, its postings are removed on the next merge.
+    // Returns false if the document does not exist or has already been deleted.
+    pub fn delete_document(&mut self, doc_id: u32) -> bool {
+        if doc_id == 0 || doc_id > self.no_of_docs {
+            return false;
+        }
+        self.deleted_documents.insert(doc_id)
+    }
+
+    pub fn get_doc_ids_by_name(&self, doc_name: &str) -> Vec<u32> {
+        self.document_names
+            .iter()
+            .enumerate()
+            .filter(|(_, name)| name.as_str() == doc_name)
+            .map(|(i, _)| i as u32 + 1)
+            .collect()
+    }
+
+    pub fn save_deleted_documents<W: Write>(&self, writer: W) -> io::Result<()> {
+        self.deleted_documents.save(writer)
+    }
+
+    pub fn load_deleted_documents<R: Read>(&mut self, reader: R) -> io::Result<()> {
+        self.deleted_documents.load(reader)
     }
+
     pub fn get_doc_metadata(&self, doc_id: u32) -> Option<DocumentMetadata> {
         if doc_id <= self.document_lengths.len() as u32 {
             Some(DocumentMetadata {
                 doc_name: self.document_names[(doc_id - 1) as usize].clone(),
                 doc_url: self.document_urls[(doc_id - 1) as usize].clone(),
-                doc_length: self.document_lengths[(doc_id - 1) as usize].clone(),
+                doc_length: self.document_lengths[(doc_id - 1) as usize],
             })
         } else {
             None
@@ -316,6 +544,20 @@ impl Indexer {
     }
 }
 
+// A string prefixed with its length. The length of a damaged file can be anything, so the bytes
+// are only allocated as they are read.
+pub(crate) fn read_string<R: Read>(reader: &mut R) -> io::Result<String> {
+    let mut buffer: [u8; 4] = [0; 4];
+    reader.read_exact(&mut buffer)?;
+    let length = u32::from_le_bytes(buffer) as u64;
+    let mut bytes = Vec::new();
+    reader.by_ref().take(length).read_to_end(&mut bytes)?;
+    if bytes.len() as u64 != length {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    String::from_utf8(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
 #[cfg(test)]
 mod tests {
     use std::{
@@ -331,11 +573,7 @@ mod tests {
         // let temp_dir = TempDir::new().unwrap();
         let result_path = "index_run_2".to_string();
         let path = Path::new(&result_path);
-        if !path.exists() {
-            fs::create_dir_all(path).unwrap();
-        } else if path.is_file() {
-            fs::create_dir_all(path).unwrap();
-        }
+        fs::create_dir_all(path).unwrap();
         let mut indexer = Indexer::new(
             query_parser,
             CompressionAlgorithm::Simple16,
@@ -353,11 +591,7 @@ mod tests {
         // let temp_dir = TempDir::new().unwrap();
         let result_path = "index_run_2".to_string();
         let path = Path::new(&result_path);
-        if !path.exists() {
-            fs::create_dir_all(path).unwrap();
-        } else if path.is_file() {
-            fs::create_dir_all(path).unwrap();
-        }
+        fs::create_dir_all(path).unwrap();
         let mut indexer = Indexer::new(
             query_parser,
             CompressionAlgorithm::Simple16,
@@ -369,7 +603,7 @@ mod tests {
         indexer.start_spimi().unwrap();
 
         let doc_save_path = path.join("document_metadata.sidx");
-        let file = File::create(&doc_save_path.as_path()).unwrap();
+        let file = File::create(doc_save_path.as_path()).unwrap();
         let term_writer = BufWriter::new(file);
         indexer.save_document_metadata(term_writer).unwrap();
     }
@@ -380,11 +614,7 @@ mod tests {
         // let temp_dir = TempDir::new().unwrap();
         let result_path = "index_run_2".to_string();
         let path = Path::new(&result_path);
-        if !path.exists() {
-            fs::create_dir_all(path).unwrap();
-        } else if path.is_file() {
-            fs::create_dir_all(path).unwrap();
-        }
+        fs::create_dir_all(path).unwrap();
         let mut indexer = Indexer::new(
             query_parser,
             CompressionAlgorithm::Simple16,
@@ -395,7 +625,7 @@ mod tests {
         indexer.set_dataset_directory_path(dataset_directory_path.to_path_buf());
 
         let doc_save_path = path.join("document_metadata.sidx");
-        let file = File::open(&doc_save_path.as_path()).unwrap();
+        let file = File::open(doc_save_path.as_path()).unwrap();
         let term_writer = BufReader::new(file);
 
         indexer.load_document_metadata(term_writer).unwrap();
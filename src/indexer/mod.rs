@@ -1,3 +1,5 @@
+pub mod build_manifest;
+pub mod document_source;
 pub mod helper;
 pub mod indexer;
 pub mod spimi;
@@ -0,0 +1,154 @@
+use std::{
+    fs::File,
+    io::{self, BufReader},
+    path::Path,
+};
+
+use crate::{
+    compressor::compressor::CompressionAlgorithm,
+    in_memory_index_metadata::in_memory_index_metadata::InMemoryIndexMetadata,
+    utils::{block::Block, index_format::InvertedIndexFooter, posting::Posting},
+};
+
+// The struct which is used to iterate over the terms of an existing inverted index in the same
+// order as the temporary index files, so that the index can take part in a merge.
+// Since the term ids are assigned in sorted order of the terms, iterating over the term ids gives
+// us the terms in sorted order as well.
+// Every block is read in full, so the blocks are checked against their checksums on the way.
+pub struct IndexIterator<'a> {
+    index_file: File,
+    // None for an index which was saved before it had checksums
+    footer: Option<InvertedIndexFooter>,
+    in_memory_index_metadata: &'a InMemoryIndexMetadata,
+    compression_algorithm: CompressionAlgorithm,
+    // the blocks which are left out along with the postings in them, a repair skips the blocks
+    // which are damaged
+    skipped_block_ids: Vec<u32>,
+    terms: Vec<String>,
+    current_term_no: usize,
+    // the terms are stored one after the other so consecutive terms will mostly be in the same block
+    current_block: Option<Block>,
+    pub current_term: Option<String>,
+    pub current_postings: Option<Vec<Posting>>,
+}
+
+impl<'a> IndexIterator<'a> {
+    pub fn new(
+        inverted_index_path: &Path,
+        in_memory_index_metadata: &'a InMemoryIndexMetadata,
+        compression_algorithm: CompressionAlgorithm,
+    ) -> io::Result<IndexIterator<'a>> {
+        let footer = InvertedIndexFooter::read_from_file(inverted_index_path)?;
+        Self::create(
+            inverted_index_path,
+            in_memory_index_metadata,
+            compression_algorithm,
+            footer,
+            Vec::new(),
+        )
+    }
+
+    // Leaves out the blocks which a repair found to be damaged. The rest of the blocks were
+    // verified already, so a footer which can not be read does not stop the repair.
+    pub fn with_skipped_blocks(
+        inverted_index_path: &Path,
+        in_memory_index_metadata: &'a InMemoryIndexMetadata,
+        compression_algorithm: CompressionAlgorithm,
+        skipped_block_ids: Vec<u32>,
+    ) -> io::Result<IndexIterator<'a>> {
+        let footer = InvertedIndexFooter::read_from_file(inverted_index_path).unwrap_or(None);
+        Self::create(
+            inverted_index_path,
+            in_memory_index_metadata,
+            compression_algorithm,
+            footer,
+            skipped_block_ids,
+        )
+    }
+
+    fn create(
+        inverted_index_path: &Path,
+        in_memory_index_metadata: &'a InMemoryIndexMetadata,
+        compression_algorithm: CompressionAlgorithm,
+        footer: Option<InvertedIndexFooter>,
+        skipped_block_ids: Vec<u32>,
+    ) -> io::Result<IndexIterator<'a>> {
+        let mut terms = in_memory_index_metadata.get_all_terms();
+        terms.sort_unstable_by_key(|&(_, term_id)| term_id);
+        let terms = terms.into_iter().map(|(term, _)| term).collect();
+
+        let mut index_iterator = IndexIterator {
+            index_file: File::open(inverted_index_path)?,
+            footer,
+            in_memory_index_metadata,
+            compression_algorithm,
+            skipped_block_ids,
+            terms,
+            current_term_no: 0,
+            current_block: None,
+            current_term: None,
+            current_postings: None,
+        };
+        index_iterator.next()?;
+        Ok(index_iterator)
+    }
+
+    // Decodes the whole posting list of the next term.
+    // If the index was built without positions we only know the frequency of the term in each
+    // document, so the positions are filled with placeholders to keep the frequencies intact.
+    pub fn next(&mut self) -> io::Result<bool> {
+        if self.current_term_no >= self.terms.len() {
+            self.current_term = None;
+            self.current_postings = None;
+            return Ok(false);
+        }
+
+        let term = self.terms[self.current_term_no].as_str();
+        let term_metadata = self
+            .in_memory_index_metadata
+            .get_term_metadata(term)
+            .unwrap();
+        let mut postings = Vec::with_capacity(term_metadata.term_frequency as usize);
+
+        for &block_id in term_metadata.block_ids {
+            if self.skipped_block_ids.contains(&block_id) {
+                continue;
+            }
+            if self.current_block.as_ref().map(|block| block.block_id) != Some(block_id) {
+                let mut reader = BufReader::new(&mut self.index_file);
+                let mut block = Block::new(block_id, None);
+                match &self.footer {
+                    Some(footer) => block.decode_checked(&mut reader, footer)?,
+                    None => block.decode(&mut reader)?,
+                }
+                self.current_block = Some(block);
+            }
+            let block = self.current_block.as_ref().unwrap();
+
+            let term_index = block.check_if_term_exists(term_metadata.term_id);
+            if term_index == -1 {
+                continue;
+            }
+            for mut chunk in block.decode_chunks_for_term(
+                term_metadata.term_id,
+                term_index as usize,
+                self.compression_algorithm.clone(),
+            ) {
+                chunk.decode_doc_ids();
+                chunk.decode_doc_frequencies();
+                for i in 0..chunk.doc_ids.len() {
+                    let mut positions = chunk.get_posting_list(i);
+                    if positions.is_empty() {
+                        positions = vec![0; chunk.doc_frequencies[i] as usize];
+                    }
+                    postings.push(Posting::new(chunk.doc_ids[i], positions));
+                }
+            }
+        }
+
+        self.current_term = Some(term.to_string());
+        self.current_postings = Some(postings);
+        self.current_term_no += 1;
+        Ok(true)
+    }
+}
@@ -1,3 +1,4 @@
+pub mod index_iterator;
 pub mod spimi;
 mod spimi_iterator;
 pub mod spimi_merge_writer;
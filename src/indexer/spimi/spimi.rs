@@ -1,8 +1,10 @@
 use std::{
+    collections::BTreeMap,
     f32,
-    fs::File,
+    fs::{self, File},
     io::{self, BufWriter, Write},
-    path::Path,
+    mem,
+    path::{Path, PathBuf},
     sync::mpsc,
     time::SystemTime,
 };
@@ -11,16 +13,21 @@ use crate::{
     compressor::compressor::CompressionAlgorithm,
     in_memory_index_metadata::in_memory_index_metadata::InMemoryIndexMetadata,
     indexer::{
-        helper::vb_encode_posting_list,
-        spimi::{spimi_iterator::SpimiIterator, spimi_merge_writer::SpimiMergeWriter},
+        build_manifest::{BuildManifest, Documents, FileProgress},
+        helper::{DocumentBatch, vb_encode_posting_list},
+        spimi::{
+            index_iterator::IndexIterator, spimi_iterator::SpimiIterator,
+            spimi_merge_writer::SpimiMergeWriter,
+        },
     },
-    scoring::bm_25::{BM25Params, compute_term_score},
+    scoring::{ScoringModel, bm_25::BM25Params, scorer::TermStatistics},
     utils::{
+        bitmap::Bitmap,
         chunk_block_max_metadata::ChunkBlockMaxMetadata,
         dictionary::Dictionary,
-        paths::get_inverted_index_path,
+        field::{DocumentLengths, Field},
+        paths::{get_inverted_index_path, get_run_path, get_unfinished_run_path},
         posting::{Posting, merge_all_postings},
-        term::Term,
     },
 };
 
@@ -43,64 +50,199 @@ impl Spimi {
         }
     }
 
-    // We receive vectors which contain posting lists through a channel and write it to our
-    // in memory dictionary. Once the dictionary exceeds a maximum size it is written to disk.
-    // All the temporary indexes can be identified through the .tmpidx file name.
+    // We receive the terms of the documents of a batch through a channel and write them to our
+    // in memory dictionary. Once the dictionary exceeds a maximum size it is written to disk as a
+    // run, all the temporary indexes can be identified through the .tmpidx file name. A run always
+    // ends after a whole batch and is recorded in the build manifest along with its documents and
+    // how far every file was read, so that a build which is stopped can resume from there.
     pub fn single_pass_in_memory_indexing(
         &mut self,
-        rx: mpsc::Receiver<Vec<Term>>,
+        rx: mpsc::Receiver<DocumentBatch>,
+        manifest: &mut BuildManifest,
     ) -> Result<(), std::io::Error> {
-        let mut spmi_index = 0;
-        let path = Path::new(&self.index_directory_path);
-
-        while let Ok(terms) = rx.recv() {
-            for term in terms {
-                if self.dictionary.size() >= self.dictionary.max_size() {
-                    self.write_dictionary_to_disk(
-                        path.join(spmi_index.to_string() + ".tmpidx").as_path(),
-                        &self.dictionary,
-                    )?;
-                    spmi_index += 1;
-                    self.dictionary.clear();
-                }
-
+        // the progress of the files and the documents which are in the dictionary but not yet in a
+        // run, the batches of a file arrive in order so its last batch has its progress
+        let mut files = BTreeMap::new();
+        let mut documents = Documents::default();
+
+        while let Ok(batch) = rx.recv() {
+            // the doc ids continue from the documents which came before the batch
+            let first_doc_id =
+                manifest.get_first_doc_id() + manifest.get_no_of_docs() + documents.len() as u32;
+            for mut term in batch.terms {
+                term.posting.doc_id += first_doc_id + 1;
                 self.dictionary.add_term(&term.term);
                 self.dictionary.append_to_term(&term.term, term.posting);
             }
+            files.insert(batch.file, batch.progress);
+            documents.append(batch.documents);
+
+            if self.dictionary.size() >= self.dictionary.max_size() {
+                self.write_run(manifest, &mut files, &mut documents)?;
+            }
         }
 
         // Once the channel is closed there may still be unwritten posting lists in the dictionary
         // which have to be flushed to disk.
-        self.write_dictionary_to_disk(
-            path.join(spmi_index.to_string() + ".tmpidx").as_path(),
-            &self.dictionary,
-        )?;
+        if !files.is_empty() {
+            self.write_run(manifest, &mut files, &mut documents)?;
+        }
+        Ok(())
+    }
+
+    // The run is written under another name and renamed once it is complete, it only counts once
+    // the manifest which records it has been saved
+    fn write_run(
+        &mut self,
+        manifest: &mut BuildManifest,
+        files: &mut BTreeMap<PathBuf, FileProgress>,
+        documents: &mut Documents,
+    ) -> io::Result<()> {
+        let path = Path::new(&self.index_directory_path);
+        let run_id = manifest.get_next_run_id();
+        if self.dictionary.no_of_terms > 0 {
+            let unfinished_run_path = get_unfinished_run_path(path, run_id);
+            self.write_dictionary_to_disk(&unfinished_run_path, &self.dictionary)?;
+            fs::rename(unfinished_run_path, get_run_path(path, run_id))?;
+        }
+        manifest.add_run(mem::take(files), &mem::take(documents))?;
+        self.dictionary.clear();
         Ok(())
     }
 
     // Merges the temporary index files produced by the SPIMI run into a final file which is written to inverted_index.idx
     // It produces an InMemoryIndex which contains metadata related to the final inverted index file which is used during
     // query processing.
+    #[allow(
+        clippy::too_many_arguments,
+        reason = "the settings of the index are passed on to the merge as they are"
+    )]
     pub fn merge_spimi_index_files(
         &mut self,
-        l_avg: f32,
         include_positions: bool,
-        document_lengths: &Box<[u32]>,
+        document_lengths: &DocumentLengths,
+        deleted_documents: &Bitmap,
         compression_algorithm: CompressionAlgorithm,
+        scoring_model: ScoringModel,
+        bm25_params: BM25Params,
         chunk_size: u8,
     ) -> Result<InMemoryIndexMetadata, io::Error> {
-        let current_time = SystemTime::now();
-        let mut in_memory_index_metadata: InMemoryIndexMetadata = InMemoryIndexMetadata::new();
-
         // Iterators are created over our temporary index files
-        let mut merge_iterators =
-            SpimiIterator::scan_and_create_iterators(&self.index_directory_path)?;
+        let merge_iterators = SpimiIterator::scan_and_create_iterators(&self.index_directory_path)?;
         if merge_iterators.is_empty() {
+            let mut in_memory_index_metadata = InMemoryIndexMetadata::new();
+            in_memory_index_metadata.scoring_model = scoring_model;
+            in_memory_index_metadata.bm25_params = bm25_params;
             return Ok(in_memory_index_metadata);
         }
-        let mut no_of_terms: u32 = 0;
+        // the index is written to a temporary file which is renamed over the old index once it is
+        // complete, so a merge which is stopped leaves the old index as it was
         let path = Path::new(&self.index_directory_path);
-        let final_index_file = File::create(get_inverted_index_path(path).as_path())?;
+        let inverted_index_path = get_inverted_index_path(path);
+        let temp_index_path = inverted_index_path.with_extension("idx.tmp");
+        let final_index_file = File::create(&temp_index_path)?;
+        let in_memory_index_metadata = Self::merge_into_index_file(
+            merge_iterators,
+            Vec::new(),
+            final_index_file.try_clone()?,
+            include_positions,
+            document_lengths,
+            deleted_documents,
+            compression_algorithm,
+            scoring_model,
+            bm25_params,
+            chunk_size,
+        )?;
+        final_index_file.sync_all()?;
+        fs::rename(temp_index_path, inverted_index_path)?;
+        Ok(in_memory_index_metadata)
+    }
+
+    // Merges the temporary index files into final_index_file instead of inverted_index.idx,
+    // this is used to write the temporary index files of newly added documents as a new segment.
+    #[allow(
+        clippy::too_many_arguments,
+        reason = "the same settings as merge_spimi_index_files along with the file"
+    )]
+    pub fn merge_spimi_index_files_into(
+        &mut self,
+        final_index_file: File,
+        include_positions: bool,
+        document_lengths: &DocumentLengths,
+        deleted_documents: &Bitmap,
+        compression_algorithm: CompressionAlgorithm,
+        scoring_model: ScoringModel,
+        bm25_params: BM25Params,
+        chunk_size: u8,
+    ) -> Result<InMemoryIndexMetadata, io::Error> {
+        let merge_iterators = SpimiIterator::scan_and_create_iterators(&self.index_directory_path)?;
+        Self::merge_into_index_file(
+            merge_iterators,
+            Vec::new(),
+            final_index_file,
+            include_positions,
+            document_lengths,
+            deleted_documents,
+            compression_algorithm,
+            scoring_model,
+            bm25_params,
+            chunk_size,
+        )
+    }
+
+    // Merges existing inverted indexes (the segments of the index) into final_index_file.
+    // The scores of all the terms are recomputed since the number of documents and the average
+    // document length have changed since the indexes were written.
+    #[allow(
+        clippy::too_many_arguments,
+        reason = "the same settings as merge_spimi_index_files along with the file"
+    )]
+    pub fn merge_index_files(
+        index_iterators: Vec<IndexIterator>,
+        final_index_file: File,
+        include_positions: bool,
+        document_lengths: &DocumentLengths,
+        deleted_documents: &Bitmap,
+        compression_algorithm: CompressionAlgorithm,
+        scoring_model: ScoringModel,
+        bm25_params: BM25Params,
+        chunk_size: u8,
+    ) -> Result<InMemoryIndexMetadata, io::Error> {
+        Self::merge_into_index_file(
+            Vec::new(),
+            index_iterators,
+            final_index_file,
+            include_positions,
+            document_lengths,
+            deleted_documents,
+            compression_algorithm,
+            scoring_model,
+            bm25_params,
+            chunk_size,
+        )
+    }
+
+    #[allow(
+        clippy::too_many_arguments,
+        reason = "the runs and the segments are merged by the same code"
+    )]
+    fn merge_into_index_file(
+        mut merge_iterators: Vec<SpimiIterator>,
+        mut index_iterators: Vec<IndexIterator>,
+        final_index_file: File,
+        include_positions: bool,
+        document_lengths: &DocumentLengths,
+        deleted_documents: &Bitmap,
+        compression_algorithm: CompressionAlgorithm,
+        scoring_model: ScoringModel,
+        bm25_params: BM25Params,
+        chunk_size: u8,
+    ) -> Result<InMemoryIndexMetadata, io::Error> {
+        let current_time = SystemTime::now();
+        let mut in_memory_index_metadata: InMemoryIndexMetadata = InMemoryIndexMetadata::new();
+        in_memory_index_metadata.scoring_model = scoring_model;
+        in_memory_index_metadata.bm25_params = bm25_params;
+        let mut no_of_terms: u32 = 0;
 
         // The index writer is used to efficiently create our inverted index
         let mut spimi_merge_writer: SpimiMergeWriter = SpimiMergeWriter::new(
@@ -110,16 +252,20 @@ impl Spimi {
             include_positions,
             compression_algorithm,
         );
-        // The BM25 scoring params are created.
-        let bm25_params = BM25Params::default();
-        let no_of_docs = document_lengths.len() as u32;
-        let mut no_of_bad_terms = 0;
+        // The scorer which the max scores are computed with, the queries are scored with the same one
+        let scorer = scoring_model.get_scorer(bm25_params);
+        let no_of_docs = document_lengths.get_no_of_docs();
         loop {
             // We iterate over our iterators to find the smallest term
             // Since the size of the vector is quite small I have chosen to just loop over it.
             let smallest_term = merge_iterators
                 .iter()
                 .filter_map(|it| it.current_term.as_ref())
+                .chain(
+                    index_iterators
+                        .iter()
+                        .filter_map(|it| it.current_term.as_ref()),
+                )
                 .min()
                 .cloned();
 
@@ -131,22 +277,52 @@ impl Spimi {
             // The posting lists from the different iterators are accumulated and then merged
             // to create the final posting list for the current term.
             let mut posting_lists: Vec<Vec<Posting>> = Vec::with_capacity(50);
+            for it in index_iterators.iter_mut() {
+                if it.current_term.as_ref() == Some(&term) {
+                    if let Some(postings) = it.current_postings.take() {
+                        posting_lists.push(postings);
+                    }
+                    it.next()?;
+                }
+            }
             for it in merge_iterators.iter_mut() {
-                if let Some(curr_term) = &it.current_term {
-                    if curr_term == &term {
-                        if let Some(postings) = it.current_postings.take() {
-                            posting_lists.push(postings);
-                        }
-                        it.next()?;
+                if let Some(curr_term) = &it.current_term
+                    && curr_term == &term
+                {
+                    if let Some(postings) = it.current_postings.take() {
+                        posting_lists.push(postings);
                     }
+                    it.next()?;
                 }
             }
-            let final_merged = merge_all_postings(posting_lists);
+            let mut final_merged = merge_all_postings(posting_lists);
+
+            // The postings of deleted documents are purged, a term which is only
+            // present in deleted documents is dropped from the index.
+            if !deleted_documents.is_empty() {
+                final_merged.retain(|posting| !deleted_documents.contains(posting.doc_id));
+            }
+            // a repair which skips the damaged blocks can leave a term without postings as well
+            if final_merged.is_empty() {
+                continue;
+            }
 
             no_of_terms += 1;
 
             // The term_frequency is calculated for ranked retrieval
             let term_frequency = final_merged.len() as u32;
+            let collection_frequency: u64 = final_merged
+                .iter()
+                .map(|posting| posting.positions.len() as u64)
+                .sum();
+            // the terms of the title are scored with the lengths of the titles
+            let field = Field::from_term(&term);
+            let term_statistics = TermStatistics {
+                no_of_docs,
+                document_frequency: term_frequency,
+                collection_frequency,
+                average_document_length: document_lengths.get_average_length(field),
+            };
             // if term_frequency == 1 {
             //     no_of_bad_terms += 1;
             //     // println!("{} {}", term, term_frequency);
@@ -158,28 +334,25 @@ impl Spimi {
             // and stored as metadata
             let mut chunk_max_term_score: f32 = f32::MIN;
             let mut chunk_metadata: Vec<ChunkBlockMaxMetadata> = Vec::new();
-            let mut chunk_index: usize = 0;
 
-            for posting in &final_merged {
+            for (chunk_index, posting) in final_merged.iter().enumerate() {
                 let f_dt = posting.positions.len() as u32;
-                let l_d = document_lengths[(posting.doc_id - 1) as usize];
+                let l_d = document_lengths.get_length(field, posting.doc_id);
                 // We compute the contribution of this document to the term_score
-                let term_score: f32 =
-                    compute_term_score(f_dt, l_d, l_avg, no_of_docs, term_frequency, &bm25_params);
+                let term_score: f32 = scorer.score(f_dt, l_d, &term_statistics);
                 // The document may contribute to the max_term_score
                 max_term_score = max_term_score.max(term_score);
 
                 // The chunk_max_term_score is calculated but it is only added to the BlockMaxMetadata
                 // after the chunk is completed
                 chunk_max_term_score = chunk_max_term_score.max(term_score);
-                if (chunk_index + 1) % chunk_size as usize == 0 {
+                if (chunk_index + 1).is_multiple_of(chunk_size as usize) {
                     chunk_metadata.push(ChunkBlockMaxMetadata::new(
                         posting.doc_id,
                         chunk_max_term_score,
                     ));
                     chunk_max_term_score = f32::MIN;
                 }
-                chunk_index += 1;
             }
             if chunk_max_term_score != f32::MIN {
                 chunk_metadata.push(ChunkBlockMaxMetadata::new(
@@ -194,15 +367,21 @@ impl Spimi {
                 .add_term(no_of_terms, final_merged)
                 .unwrap();
 
+            // We add the term to the bk_tree as well which is used to suggest spelling corrections,
+            // the terms of the title are left out since they are the words of the body again
+            if field == Field::Body {
+                in_memory_index_metadata.add_term_to_bk_tree(term.clone());
+            }
             // We add the term to term_id mapping, the max_term_score the and the metadata for
             // block max ranking to the in memory index.
             in_memory_index_metadata.set_term_id(term, no_of_terms);
             in_memory_index_metadata.set_term_frequency(term_frequency);
+            in_memory_index_metadata.set_collection_frequency(collection_frequency);
             in_memory_index_metadata.set_max_term_score(max_term_score);
             in_memory_index_metadata.set_chunk_block_max_metadata(chunk_metadata);
             in_memory_index_metadata.set_block_ids(block_ids);
-            // We add the term to the bk_tree as well which helps speed up retrieval
-            // in_memory_index.add_term_to_bk_tree(term);
+            in_memory_index_metadata
+                .set_block_chunk_counts(spimi_merge_writer.get_block_chunk_counts());
         }
         // println!("no of bad terms {}", no_of_bad_terms);
         // We close the index_merge_writer so that the remaining terms can be written to the disk.
@@ -234,6 +413,7 @@ impl Spimi {
                 self.write_term_to_disk(&mut writer, key, value)?;
             }
             writer.flush()?;
+            writer.get_ref().sync_all()?;
         }
         Ok(())
     }
@@ -242,7 +422,7 @@ impl Spimi {
         &self,
         writer: &mut BufWriter<File>,
         term: &str,
-        posting_list: &Vec<Posting>,
+        posting_list: &[Posting],
     ) -> Result<(), std::io::Error> {
         writer.write_all(&(term.len() as u32).to_le_bytes())?;
         writer.write_all(term.as_bytes())?;
@@ -38,6 +38,7 @@ impl SpimiIterator {
         }
     }
 
+    #[cfg(test)]
     pub fn get_current_term(&mut self) -> u32 {
         self.current_term_no
     }
@@ -49,17 +50,16 @@ impl SpimiIterator {
             let entry = entry?;
             let path = entry.path();
 
-            if path.is_file() {
-                if let Some(ext) = path.extension() {
-                    if ext == "tmpidx" {
-                        let file = File::open(&path)?;
-                        let file_reader = BufReader::new(file);
-                        let mut merge_iter = SpimiIterator::new(file_reader);
-                        merge_iter.init()?; // Initialize the iterator
-                        iterators.push(merge_iter);
-                        println!("Created iterator for: {}", path.display());
-                    }
-                }
+            if path.is_file()
+                && let Some(ext) = path.extension()
+                && ext == "tmpidx"
+            {
+                let file = File::open(&path)?;
+                let file_reader = BufReader::new(file);
+                let mut merge_iter = SpimiIterator::new(file_reader);
+                merge_iter.init()?; // Initialize the iterator
+                iterators.push(merge_iter);
+                println!("Created iterator for: {}", path.display());
             }
         }
 
@@ -97,10 +97,10 @@ impl SpimiIterator {
                 if e.kind() == ErrorKind::UnexpectedEof {
                     return Ok(());
                 } else {
-                    return Err(io::Error::new(
-                        io::ErrorKind::Other,
-                        format!("Error reading file during advance: {}", e),
-                    ));
+                    return Err(io::Error::other(format!(
+                        "Error reading file during advance: {}",
+                        e
+                    )));
                 }
             }
 
@@ -8,6 +8,7 @@ use crate::{
     utils::{
         block::{Block, MINIMUM_BLOCK_SIZE},
         chunk::Chunk,
+        index_format::{FORMAT_VERSION, InvertedIndexFooter},
         posting::Posting,
     },
 };
@@ -32,6 +33,8 @@ pub struct SpimiMergeWriter {
     compression_algorithm: CompressionAlgorithm, // the compression algorithm which is going to be used for the chunks
     pub chunk_size: u8,                          // maximum number of postings in a single chunk
     block_buffer: Vec<u8>,
+    block_chunk_counts: Vec<u32>, // the no of chunks the last added term has in each of its blocks
+    block_checksums: Vec<u32>,    // the checksum of every block which has been written
 }
 
 impl SpimiMergeWriter {
@@ -52,13 +55,33 @@ impl SpimiMergeWriter {
             file_writer: BufWriter::new(file),
             compression_algorithm,
             chunk_size: chunk_size.unwrap_or(128),
+            block_chunk_counts: Vec::new(),
+            block_checksums: Vec::new(),
         }
     }
 
+    // The no of chunks in each of the blocks returned by the last add_term, which lets a query
+    // find the block holding a chunk without reading the blocks before it
+    pub fn get_block_chunk_counts(&self) -> &[u32] {
+        &self.block_chunk_counts
+    }
+
+    // Writes the last block followed by the footer with the checksums of all the blocks
     pub fn finish(&mut self) -> io::Result<()> {
         self.current_block.encode(&mut self.block_buffer);
+        self.block_checksums
+            .push(crc32fast::hash(&self.block_buffer));
         self.buffered_block_bytes.append(&mut self.block_buffer);
         self.file_writer.write_all(&self.buffered_block_bytes)?;
+        InvertedIndexFooter {
+            format_version: FORMAT_VERSION,
+            block_size: self.current_block.max_block_size as u32 * 1000,
+            chunk_size: self.chunk_size as u32,
+            compression_algorithm: self.compression_algorithm.clone(),
+            include_positions: self.include_positions,
+            block_checksums: std::mem::take(&mut self.block_checksums),
+        }
+        .write(&mut self.file_writer)?;
         self.flush()?;
         self.current_block_no += 1;
         Ok(())
@@ -90,6 +113,8 @@ impl SpimiMergeWriter {
         // we add the term to the block
         self.current_block.add_term(term);
         block_ids.push(self.current_block_no);
+        self.block_chunk_counts.clear();
+        self.block_chunk_counts.push(0);
         let mut i = 0;
         let postings_length = postings.len();
 
@@ -111,11 +136,13 @@ impl SpimiMergeWriter {
                     self.current_block.add_term(term);
 
                     block_ids.push(self.current_block_no);
+                    self.block_chunk_counts.push(0);
                     if chunk_bytes.len() as u32 > self.current_block.space_left() {
                         panic!("chunk cannot fit in block")
                     }
                     self.current_block.add_chunk_bytes(chunk_bytes);
                 }
+                *self.block_chunk_counts.last_mut().unwrap() += 1;
 
                 if i == postings_length {
                     block_ids.shrink_to_fit();
@@ -140,11 +167,13 @@ impl SpimiMergeWriter {
                         self.current_block.add_term(term);
 
                         block_ids.push(self.current_block_no);
+                        self.block_chunk_counts.push(0);
                         if chunk_bytes.len() as u32 > self.current_block.space_left() {
                             panic!("chunk cannot fit in block")
                         }
                         self.current_block.add_chunk_bytes(chunk_bytes);
                     }
+                    *self.block_chunk_counts.last_mut().unwrap() += 1;
                     block_ids.shrink_to_fit();
                     return Ok(block_ids);
                 }
@@ -153,7 +182,7 @@ impl SpimiMergeWriter {
             // we add this doc to the current chunk
             current_chunk.add_doc_id(current_posting.doc_id);
             current_chunk.add_doc_frequency(current_posting.positions.len() as u32);
-            if current_posting.positions.len() > 0 && self.include_positions {
+            if !current_posting.positions.is_empty() && self.include_positions {
                 current_chunk.add_doc_positions(current_posting.positions);
             }
             i += 1;
@@ -162,6 +191,8 @@ impl SpimiMergeWriter {
 
     fn write_block_to_index_file(&mut self) -> io::Result<()> {
         self.current_block.encode(&mut self.block_buffer);
+        self.block_checksums
+            .push(crc32fast::hash(&self.block_buffer));
         self.buffered_block_bytes.append(&mut self.block_buffer);
         if self.buffered_block_bytes.len() >= 3_000_000 {
             self.file_writer.write_all(&self.buffered_block_bytes)?;
@@ -372,7 +403,7 @@ mod tests {
         file.read_to_end(&mut buffer).unwrap();
 
         // File should contain data
-        assert!(buffer.len() > 0);
+        assert!(!buffer.is_empty());
 
         // First 4 bytes should be number of terms (at least 1)
         let no_of_terms = u32::from_le_bytes([buffer[0], buffer[1], buffer[2], buffer[3]]);
@@ -1,15 +1,10 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Deserialize, Serialize)]
-pub struct WikiArticle {
-    pub url: String,
-    pub text: Vec<Vec<String>>,
-    pub id: String,
-    pub title: String,
-}
-
+// a document of the batches which the preprocessing script writes, a document without a url is
+// still indexed
 #[derive(Debug, Deserialize, Serialize)]
 pub struct WikiArticle1 {
+    #[serde(default)]
     pub url: String,
     pub text: String,
     pub title: String,
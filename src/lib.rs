@@ -0,0 +1,53 @@
+//! A search engine over Wikipedia built on a compressed inverted index.
+//!
+//! The index is built with single pass in memory indexing and queried with ranked (WAND, BMW,
+//! MaxScore, BMMS), boolean, phrase and proximity retrieval. A [`SearchEngine`] is created with
+//! a [`SearchEngineBuilder`], which either builds a new index from a dataset or opens a saved one.
+//! A dataset is read with a [`DocumentSource`], the JSON lines of the preprocessing script are
+//! read by default and the other [`DocumentFormat`]s are CSV, plain text, TREC, WARC and
+//! MediaWiki XML.
+//! The text is split into terms by an [`AnalyzerConfig`], which finds the words with a
+//! [`Tokenizer`], can stem them with a [`Stemmer`] and is saved along with the index.
+//! The words of the queries can be expanded with the synonyms of a [`SynonymDictionary`].
+//! A [`SearchServer`] serves a search engine over HTTP with JSON responses.
+
+// the main file of a module is named after it, such as search_engine/search_engine.rs
+#![allow(clippy::module_inception)]
+
+mod compressor;
+mod in_memory_index_metadata;
+mod indexer;
+mod parser;
+mod query_processor;
+mod scoring;
+mod search_engine;
+mod segment;
+mod server;
+mod utils;
+
+pub use crate::{
+    compressor::compressor::CompressionAlgorithm,
+    indexer::{
+        build_manifest::BuildReport,
+        document_source::document_source::{
+            DocumentFormat, DocumentSource, FieldMapping, SourceDocument,
+        },
+    },
+    parser::{
+        analyzer::{AnalyzerConfig, Tokenizer},
+        stemmer::Stemmer,
+        synonyms::{SynonymDictionary, SynonymRule},
+    },
+    query_processor::retrieval_algorithms::QueryAlgorithm,
+    scoring::{ScoringModel, bm_25::BM25Params},
+    search_engine::{
+        builder::SearchEngineBuilder, error::SearchEngineError, search_engine::SearchEngine,
+        searcher::Searcher,
+    },
+    segment::segment_verifier::{IndexReport, SegmentReport},
+    server::server::SearchServer,
+    utils::{
+        field::DEFAULT_TITLE_WEIGHT,
+        types::{DocumentMetadata, SearchEngineMetadata, SearchResult},
+    },
+};
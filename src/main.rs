@@ -3,28 +3,127 @@ use rustyline::DefaultEditor;
 use rustyline::error::ReadlineError;
 use serde::{Deserialize, Serialize};
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::process;
 use std::time::SystemTime;
 
-use crate::compressor::compressor::CompressionAlgorithm;
-use crate::query_processor::retrieval_algorithms::QueryAlgorithm;
-use crate::search_engine::search_engine::SearchEngine;
+use search_engine::{
+    AnalyzerConfig, BM25Params, BuildReport, CompressionAlgorithm, DEFAULT_TITLE_WEIGHT,
+    DocumentFormat, FieldMapping, IndexReport, QueryAlgorithm, ScoringModel, SearchEngineBuilder,
+    SearchServer, Stemmer, SynonymDictionary, Tokenizer,
+};
 
-mod compressor;
-mod in_memory_index_metadata;
-mod indexer;
-mod parser;
-mod scoring;
-mod search_engine;
-mod utils;
-
-mod query_processor;
 #[derive(Debug, Serialize, Deserialize, Clone)]
 struct Config {
     index_dir: String,
     dataset_dir: String,
     query_algo: String,
     compression_algo: String,
+    #[serde(default = "default_scoring_model")]
+    scoring_model: String,
+    #[serde(default = "default_bm25_k1")]
+    bm25_k1: f32,
+    #[serde(default = "default_bm25_b")]
+    bm25_b: f32,
+    #[serde(default = "default_title_weight")]
+    title_weight: f32,
+    #[serde(default)]
+    positional_index: bool,
+    #[serde(default = "default_top_k")]
+    top_k: usize,
+    #[serde(default = "default_merge_factor")]
+    merge_factor: usize,
+    #[serde(default = "default_input_format")]
+    input_format: String,
+    // the json keys or csv columns which the title, the url and the text are read from
+    #[serde(default = "default_title_field")]
+    title_field: String,
+    #[serde(default = "default_url_field")]
+    url_field: String,
+    #[serde(default = "default_text_field")]
+    text_field: String,
+    // how the text is split into terms, it is recorded in the index when it is built
+    #[serde(default = "default_tokenizer")]
+    tokenizer: String,
+    #[serde(default)]
+    fold_diacritics: bool,
+    #[serde(default = "default_stemmer")]
+    stemmer: String,
+    // a file with one stop word per line, the built-in stop words are used without one
+    #[serde(default)]
+    stop_words_file: Option<String>,
+    #[serde(default = "default_min_token_length")]
+    min_token_length: usize,
+    #[serde(default = "default_max_token_length")]
+    max_token_length: usize,
+    #[serde(default)]
+    split_hyphens: bool,
+    // a file of synonym rules which the queries are expanded with, unlike the analyzer it can be
+    // changed without rebuilding the index
+    #[serde(default)]
+    synonyms_file: Option<String>,
+    #[serde(default = "default_synonym_weight")]
+    synonym_weight: f32,
+}
+
+fn default_top_k() -> usize {
+    20
+}
+
+fn default_merge_factor() -> usize {
+    10
+}
+
+fn default_input_format() -> String {
+    "jsonl".to_string()
+}
+
+fn default_title_field() -> String {
+    FieldMapping::default().title
+}
+
+fn default_url_field() -> String {
+    FieldMapping::default().url
+}
+
+fn default_text_field() -> String {
+    FieldMapping::default().text
+}
+
+fn default_tokenizer() -> String {
+    AnalyzerConfig::default().tokenizer.to_string()
+}
+
+fn default_stemmer() -> String {
+    AnalyzerConfig::default().stemmer.to_string()
+}
+
+fn default_min_token_length() -> usize {
+    AnalyzerConfig::default().min_token_length
+}
+
+fn default_max_token_length() -> usize {
+    AnalyzerConfig::default().max_token_length
+}
+
+fn default_synonym_weight() -> f32 {
+    SynonymDictionary::default().weight
+}
+
+fn default_scoring_model() -> String {
+    "bm25".to_string()
+}
+
+fn default_bm25_k1() -> f32 {
+    BM25Params::default().k1
+}
+
+fn default_bm25_b() -> f32 {
+    BM25Params::default().b
+}
+
+fn default_title_weight() -> f32 {
+    DEFAULT_TITLE_WEIGHT
 }
 
 impl Default for Config {
@@ -34,6 +133,26 @@ impl Default for Config {
             dataset_dir: "wikipedia".to_string(),
             query_algo: "wand".to_string(),
             compression_algo: "simple16".to_string(),
+            scoring_model: default_scoring_model(),
+            bm25_k1: default_bm25_k1(),
+            bm25_b: default_bm25_b(),
+            title_weight: default_title_weight(),
+            positional_index: false,
+            top_k: default_top_k(),
+            merge_factor: default_merge_factor(),
+            input_format: default_input_format(),
+            title_field: default_title_field(),
+            url_field: default_url_field(),
+            text_field: default_text_field(),
+            tokenizer: default_tokenizer(),
+            fold_diacritics: false,
+            stemmer: default_stemmer(),
+            stop_words_file: None,
+            min_token_length: default_min_token_length(),
+            max_token_length: default_max_token_length(),
+            split_hyphens: false,
+            synonyms_file: None,
+            synonym_weight: default_synonym_weight(),
         }
     }
 }
@@ -67,6 +186,40 @@ fn load_config(path: &str) -> Config {
 #[global_allocator]
 static GLOBAL: MiMalloc = MiMalloc;
 
+// Prints what verifying the index found, a segment with a lot of problems only has the first few
+// printed
+fn print_report(report: &IndexReport) {
+    for problem in &report.problems {
+        println!("{}", problem);
+    }
+    for segment_report in &report.segments {
+        println!(
+            "Segment {}: {} terms, {} blocks and {} postings",
+            segment_report.segment_id,
+            segment_report.no_of_terms,
+            segment_report.no_of_blocks,
+            segment_report.no_of_postings
+        );
+        for problem in segment_report.problems.iter().take(10) {
+            println!("  {}", problem);
+        }
+        if segment_report.problems.len() > 10 {
+            println!("  and {} more problems", segment_report.problems.len() - 10);
+        }
+    }
+}
+
+// a build which was stopped only indexed the documents which were not in its runs yet
+fn print_build_report(build_report: &BuildReport) {
+    if build_report.is_resumed() {
+        println!(
+            "The build was resumed after {} documents in {} runs",
+            build_report.no_of_resumed_docs, build_report.no_of_resumed_runs
+        );
+    }
+    println!("The index has been built");
+}
+
 fn main() {
     let mut rl = DefaultEditor::new().unwrap();
 
@@ -78,30 +231,126 @@ fn main() {
     println!("  Dataset Directory:       {}", config.dataset_dir);
     println!("  Query Algorithm:       {}", config.query_algo);
     println!("  Compression Algorithm: {}", config.compression_algo);
+    println!("  Scoring Model:         {}", config.scoring_model);
+    println!(
+        "  BM25 Parameters:       k1 = {}, b = {}",
+        config.bm25_k1, config.bm25_b
+    );
+    println!("  Title Weight:          {}", config.title_weight);
+    println!("  Positional Index:      {}", config.positional_index);
+    println!("  Results Per Page:      {}", config.top_k);
+    println!("  Merge Factor:          {}", config.merge_factor);
+    println!("  Input Format:          {}", config.input_format);
+    println!(
+        "  Fields:                title = {}, url = {}, text = {}",
+        config.title_field, config.url_field, config.text_field
+    );
+    println!("  Tokenizer:             {}", config.tokenizer);
+    println!("  Fold Diacritics:       {}", config.fold_diacritics);
+    println!("  Stemmer:               {}", config.stemmer);
+    println!(
+        "  Stop Words:            {}",
+        config.stop_words_file.as_deref().unwrap_or("built-in")
+    );
+    println!(
+        "  Token Length:          {} to {}",
+        config.min_token_length, config.max_token_length
+    );
+    println!("  Split Hyphens:         {}", config.split_hyphens);
+    println!(
+        "  Synonyms:              {} (weight {})",
+        config.synonyms_file.as_deref().unwrap_or("none"),
+        config.synonym_weight
+    );
     println!("\nWelcome to my CLI! Type 'help' for commands or 'exit' to quit.\n");
-    let compression_algo = match config.compression_algo.as_str() {
-        "varbyte" => CompressionAlgorithm::VarByte,
-        "simple9" => CompressionAlgorithm::Simple9,
-        "simple16" => CompressionAlgorithm::Simple16,
-        "pfordelta" => CompressionAlgorithm::PforDelta,
-        _ => CompressionAlgorithm::Simple16,
+    let compression_algo = config.compression_algo.parse().unwrap_or_else(|e| {
+        eprintln!("⚠ {}, compressing the index with simple16", e);
+        CompressionAlgorithm::Simple16
+    });
+    let query_algo = config.query_algo.parse().unwrap_or_else(|e| {
+        eprintln!("⚠ {}, answering the queries with wand", e);
+        QueryAlgorithm::Wand
+    });
+    let scoring_model = config.scoring_model.parse().unwrap_or_else(|e| {
+        eprintln!("⚠ {}, scoring the documents with bm25", e);
+        ScoringModel::BM25
+    });
+    let input_format = config.input_format.parse().unwrap_or_else(|e| {
+        eprintln!("⚠ {}, reading the dataset as jsonl", e);
+        DocumentFormat::Jsonl
+    });
+    let tokenizer = config.tokenizer.parse().unwrap_or_else(|e| {
+        eprintln!("⚠ {}, using the ascii tokenizer", e);
+        Tokenizer::Ascii
+    });
+    let stemmer = config.stemmer.parse().unwrap_or_else(|e| {
+        eprintln!("⚠ {}, the words are not stemmed", e);
+        Stemmer::None
+    });
+    let stop_words = match &config.stop_words_file {
+        Some(stop_words_file) => AnalyzerConfig::load_stop_words(Path::new(stop_words_file))
+            .unwrap_or_else(|e| {
+                eprintln!(
+                    "⚠ Error reading {}: {}, using the built-in stop words",
+                    stop_words_file, e
+                );
+                AnalyzerConfig::default().stop_words
+            }),
+        None => AnalyzerConfig::default().stop_words,
+    };
+    let analyzer_config = AnalyzerConfig {
+        tokenizer,
+        fold_diacritics: config.fold_diacritics,
+        stemmer,
+        stop_words,
+        min_token_length: config.min_token_length,
+        max_token_length: config.max_token_length,
+        split_hyphens: config.split_hyphens,
+    };
+    let synonyms = match &config.synonyms_file {
+        Some(synonyms_file) => {
+            SynonymDictionary::load(Path::new(synonyms_file)).unwrap_or_else(|e| {
+                eprintln!(
+                    "⚠ Error reading {}: {}, the queries are not expanded",
+                    synonyms_file, e
+                );
+                SynonymDictionary::default()
+            })
+        }
+        None => SynonymDictionary::default(),
+    };
+    let synonyms = SynonymDictionary {
+        weight: config.synonym_weight,
+        ..synonyms
+    };
+    let field_mapping = FieldMapping {
+        title: config.title_field,
+        url: config.url_field,
+        text: config.text_field,
     };
 
-    let query_algo = match config.query_algo.as_str() {
-        "boolean" => QueryAlgorithm::Boolean,
-        "bmw" => QueryAlgorithm::BlockMaxWand,
-        "bmms" => QueryAlgorithm::BlockMaxMaxScore,
-        "wand" => QueryAlgorithm::Wand,
-        "ms" => QueryAlgorithm::MaxScore,
-        _ => QueryAlgorithm::Wand,
+    let search_engine = SearchEngineBuilder::new(config.index_dir)
+        .dataset_directory_path(config.dataset_dir)
+        .compression_algorithm(compression_algo)
+        .query_algorithm(query_algo)
+        .scoring_model(scoring_model)
+        .bm25_params(config.bm25_k1, config.bm25_b)
+        .title_weight(config.title_weight)
+        .include_positions(config.positional_index)
+        .merge_factor(config.merge_factor)
+        .document_source(input_format.get_source(field_mapping))
+        .analyzer(analyzer_config)
+        .synonyms(synonyms)
+        .build();
+    let mut search_engine = match search_engine {
+        Ok(search_engine) => search_engine,
+        Err(e) => {
+            eprintln!("⚠ Error opening the search engine: {}", e);
+            process::exit(1);
+        }
     };
-    let mut search_engine = SearchEngine::new(
-        config.dataset_dir,
-        compression_algo,
-        query_algo,
-        config.index_dir,
-    )
-    .unwrap();
+    let mut last_query = String::new();
+    let mut offset = 0;
     loop {
         let readline = rl.readline("> ");
 
@@ -120,23 +369,72 @@ fn main() {
                     "help" => {
                         println!("The valid commands are->");
                         println!("index: Starts building your index ");
+                        println!(
+                            "ingest [dump file]: Builds your index straight from a Wikipedia pages-articles xml dump"
+                        );
+                        println!(
+                            "add [files or directories]: Adds the documents to your existing index as a new segment"
+                        );
+                        println!(
+                            "delete [document name]: Deletes the documents with the name from your index"
+                        );
                         println!("save: Saves your index if it has already been built");
                         println!("load: Loads your previously saved index");
                         println!(
                             "query [query string]: Queries your index for the particular query string entered"
                         );
+                        println!(
+                            "  use \"...\" for phrases and NEAR/k for proximity, these need a positional index"
+                        );
+                        println!(
+                            "  with the boolean algorithm use AND, OR, NOT, (...) and +term/-term"
+                        );
+                        println!("more: Shows the next page of results for the last query");
+                        println!("verify: Checks your saved index for damage");
+                        println!(
+                            "repair: Rebuilds the damaged segments of your saved index from what is left of them"
+                        );
+                        println!(
+                            "serve [address]: Serves your index over HTTP with JSON responses, 127.0.0.1:8080 by default, until POST /shutdown"
+                        );
                     }
-                    "index" => {
-                        search_engine.build_index().unwrap();
-                        println!("The index has been built")
+                    "index" => match search_engine.build_index() {
+                        Ok(build_report) => print_build_report(&build_report),
+                        Err(e) => println!("{}", e),
+                    },
+                    "ingest" => {
+                        let Some(dump_path) = parts.get(1) else {
+                            println!("Usage: ingest [dump file]");
+                            continue;
+                        };
+                        match search_engine.index_wikipedia_dump(PathBuf::from(dump_path)) {
+                            Ok(build_report) => print_build_report(&build_report),
+                            Err(e) => println!("{}", e),
+                        }
                     }
-                    "merge" => {
-                        search_engine.merge_spimi_files().unwrap();
-                        println!("The index has been built")
+                    "add" => {
+                        let files = parts[1..].iter().map(PathBuf::from).collect();
+                        match search_engine.add_documents(files) {
+                            Ok(()) => println!("The documents have been added to the index"),
+                            Err(e) => println!("{}", e),
+                        }
                     }
+                    "delete" => {
+                        let doc_name = parts[1..].join(" ");
+                        match search_engine.delete_documents_by_name(&doc_name) {
+                            Ok(no_of_deleted_docs) => {
+                                println!("{} documents have been deleted", no_of_deleted_docs)
+                            }
+                            Err(e) => println!("{}", e),
+                        }
+                    }
+                    "merge" => match search_engine.merge_spimi_files() {
+                        Ok(()) => println!("The index has been built"),
+                        Err(e) => println!("{}", e),
+                    },
                     "terms" => {
                         let terms = search_engine.get_terms();
-                        let mut max_length = 0;
+                        let max_length = 0;
                         for term in terms {
                             if term.len() <= 20 {
                                 println!("{}", term);
@@ -145,7 +443,13 @@ fn main() {
                         println!("{}", max_length);
                     }
                     "metadata" => {
-                        let metadata = search_engine.get_index_metadata();
+                        let metadata = match search_engine.get_index_metadata() {
+                            Ok(metadata) => metadata,
+                            Err(e) => {
+                                println!("{}", e);
+                                continue;
+                            }
+                        };
                         println!(
                             "The size of the inverted index is {:?}",
                             metadata.size_of_index
@@ -154,6 +458,10 @@ fn main() {
                             "The number of indexed documents is {:?}",
                             metadata.no_of_docs
                         );
+                        println!(
+                            "The number of deleted documents is {:?}",
+                            metadata.no_of_deleted_docs
+                        );
                         println!(
                             "The number of terms in the index is {:?}",
                             metadata.no_of_terms
@@ -162,6 +470,10 @@ fn main() {
                             "The number of blocks occupied by the index is {:?}",
                             metadata.no_of_blocks
                         );
+                        println!(
+                            "The number of segments in the index is {:?}",
+                            metadata.no_of_segments
+                        );
                         println!(
                             "The compression algorithm used by the index is {:?}",
                             metadata.compression_algorithm
@@ -170,6 +482,22 @@ fn main() {
                             "The query algorithm used by the index is {:?}",
                             metadata.query_algorithm
                         );
+                        println!(
+                            "The scoring model used by the index is {:?}",
+                            metadata.scoring_model
+                        );
+                        println!(
+                            "The BM25 parameters of the index are k1 = {} and b = {}",
+                            metadata.bm25_k1, metadata.bm25_b
+                        );
+                        println!(
+                            "The weight of a match in the title is {}",
+                            metadata.title_weight
+                        );
+                        println!(
+                            "The words of the index are found with the {} tokenizer and stemmed with {}",
+                            metadata.tokenizer, metadata.stemmer
+                        );
                         println!(
                             "The index directory path is {:?}",
                             metadata.dataset_directory_path
@@ -179,33 +507,108 @@ fn main() {
                             metadata.index_directory_path
                         );
                     }
-                    "save" => {
-                        search_engine.save_index().unwrap();
-                        println!("The index has been saved successfully")
-                    }
+                    "save" => match search_engine.save_index() {
+                        Ok(()) => println!("The index has been saved successfully"),
+                        Err(e) => println!("{}", e),
+                    },
                     "load" => {
                         let start_time = SystemTime::now();
-                        search_engine.load_index().unwrap();
+                        if let Err(e) = search_engine.load_index() {
+                            println!("{}", e);
+                            continue;
+                        }
                         let end_time = SystemTime::now();
                         // println!("{:?}", end_time.duration_since(start_time).unwrap());
                         println!(
                             "The index has been successfully loaded in {} seconds",
-                            end_time.duration_since(start_time).unwrap().as_secs()
+                            end_time
+                                .duration_since(start_time)
+                                .unwrap_or_default()
+                                .as_secs()
                         );
                     }
-                    "query" => {
-                        let query_string = parts[1..].join(" ");
-                        let query_results = search_engine.handle_query(query_string).unwrap();
-                        for i in (0..query_results.len()).rev() {
-                            println!(
-                                "{} {} score {}",
-                                query_results[i].0.doc_name,
-                                query_results[i].0.doc_url,
-                                query_results[i].1
-                            )
+                    "verify" => match search_engine.verify_index() {
+                        Ok(report) => {
+                            print_report(&report);
+                            if report.is_ok() {
+                                println!("The index is fine")
+                            } else {
+                                println!("The index is damaged, use repair to rebuild it")
+                            }
+                        }
+                        Err(e) => println!("{}", e),
+                    },
+                    "repair" => match search_engine.repair_index() {
+                        Ok(report) => {
+                            print_report(&report);
+                            if report.is_ok() {
+                                println!("The index is fine, there was nothing to repair")
+                            } else if report.is_repairable() {
+                                println!(
+                                    "The damaged segments have been rebuilt, the postings in damaged blocks are lost"
+                                )
+                            } else {
+                                println!(
+                                    "Some segments can not be repaired, the index has to be built again with index"
+                                )
+                            }
+                        }
+                        Err(e) => println!("{}", e),
+                    },
+                    "query" | "more" => {
+                        if command == "query" {
+                            last_query = parts[1..].join(" ");
+                            offset = 0;
+                        } else if last_query.is_empty() {
+                            println!("There is no previous query");
+                            continue;
+                        } else {
+                            offset += config.top_k;
+                        }
+                        match search_engine.query(&last_query, config.top_k, offset) {
+                            Ok(query_results) => {
+                                for (i, query_result) in query_results.iter().enumerate() {
+                                    println!(
+                                        "{}. {} {} score {}",
+                                        offset + i + 1,
+                                        query_result.document.doc_name,
+                                        query_result.document.doc_url,
+                                        query_result.score
+                                    )
+                                }
+                                if let Some(suggestion) = search_engine.suggest_query(&last_query) {
+                                    println!("Did you mean: {}", suggestion);
+                                }
+                            }
+                            Err(e) => println!("{}", e),
+                        }
+                    }
+                    "serve" => {
+                        let address = parts.get(1).copied().unwrap_or("127.0.0.1:8080");
+                        match SearchServer::bind(address) {
+                            Ok(mut server) => {
+                                server.set_default_k(config.top_k);
+                                println!("Serving the index on http://{}", address);
+                                println!(
+                                    "  GET /search?q=...&k=...&offset=..., /metadata, /terms/<term>"
+                                );
+                                println!("  POST /shutdown stops the server and the CLI");
+                                // a shutdown request ends the CLI along with the server
+                                match server.serve(&mut search_engine) {
+                                    Ok(()) => println!("The server has been shut down"),
+                                    Err(e) => println!("{}", e),
+                                }
+                                println!("Goodbye!");
+                                break;
+                            }
+                            Err(e) => println!("{}", e),
                         }
                     }
                     "quit" | "exit" => {
+                        // a segment merge which is still running would leave a partial segment behind
+                        if let Err(e) = search_engine.wait_for_segment_merges() {
+                            println!("{}", e);
+                        }
                         println!("Goodbye!");
                         break;
                     }
@@ -0,0 +1,244 @@
+use std::{
+    fmt,
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+    str::FromStr,
+};
+
+use crate::{
+    indexer::{build_manifest::write_string, indexer::read_string},
+    parser::{parser::STOP_WORDS, stemmer::Stemmer},
+    utils::{
+        checksum::{ChecksumReader, ChecksumWriter},
+        index_format::{ANALYZER_MAGIC, read_file_header, write_file_header},
+        paths::get_save_analyzer_path,
+    },
+};
+
+// How the words of a text are found. The ascii tokenizer splits the text at whitespace and only
+// keeps the words made of ASCII letters, digits and hyphens, which is how the indexes were built
+// before the tokenizer could be chosen. The unicode tokenizer splits it at the word boundaries of
+// Unicode (UAX #29) and keeps the words of every script. Chinese and Japanese are not written
+// with spaces, so their runs of characters are indexed as overlapping bigrams instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Tokenizer {
+    #[default]
+    Ascii,
+    Unicode,
+}
+
+impl fmt::Display for Tokenizer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Tokenizer::Ascii => "ascii",
+            Tokenizer::Unicode => "unicode",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for Tokenizer {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ascii" => Ok(Tokenizer::Ascii),
+            "unicode" => Ok(Tokenizer::Unicode),
+            _ => Err(format!("unknown tokenizer {}", s)),
+        }
+    }
+}
+
+// the bits of the flags byte of analyzer.sidx
+const SPLIT_HYPHENS_FLAG: u8 = 1;
+const UNICODE_TOKENIZER_FLAG: u8 = 2;
+const FOLD_DIACRITICS_FLAG: u8 = 4;
+
+// How the text of the documents and of the queries is turned into terms. The analyzer which an
+// index was built with is saved along with it, so the queries are always analyzed the same way
+// as the documents were. The default is the analyzer which the indexes were built with before it
+// could be configured.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnalyzerConfig {
+    pub tokenizer: Tokenizer,
+    // whether the words are folded to ASCII with unidecode, café is indexed as cafe and Zürich
+    // as zurich. The letters of other scripts are transliterated, except for the Chinese and
+    // Japanese ones which are kept for their bigrams.
+    pub fold_diacritics: bool,
+    pub stemmer: Stemmer,
+    // the words which are dropped, they are compared before the words are stemmed
+    pub stop_words: Vec<String>,
+    // the words which are shorter or longer, in characters, are dropped
+    pub min_token_length: usize,
+    pub max_token_length: usize,
+    // whether a hyphenated word such as state-of-the-art is indexed as its parts, every part takes
+    // up a position of its own. The unicode tokenizer always splits them.
+    pub split_hyphens: bool,
+}
+
+impl Default for AnalyzerConfig {
+    fn default() -> Self {
+        Self {
+            tokenizer: Tokenizer::Ascii,
+            fold_diacritics: false,
+            stemmer: Stemmer::None,
+            stop_words: STOP_WORDS.iter().map(|word| word.to_string()).collect(),
+            min_token_length: 1,
+            max_token_length: 20,
+            split_hyphens: false,
+        }
+    }
+}
+
+impl AnalyzerConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.min_token_length == 0 {
+            return Err("the min token length has to be at least 1".to_string());
+        }
+        if self.max_token_length < self.min_token_length {
+            return Err(format!(
+                "the max token length {} is less than the min token length {}",
+                self.max_token_length, self.min_token_length
+            ));
+        }
+        Ok(())
+    }
+
+    // Reads a stop word file, which has one word per line. The empty lines and the lines starting
+    // with # are skipped.
+    pub fn load_stop_words(path: &Path) -> io::Result<Vec<String>> {
+        let stop_words = fs::read_to_string(path)?
+            .lines()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| line.to_lowercase())
+            .collect();
+        Ok(stop_words)
+    }
+
+    pub fn save(&self, index_directory_path: &Path) -> io::Result<()> {
+        let mut writer =
+            BufWriter::new(File::create(get_save_analyzer_path(index_directory_path))?);
+        write_file_header(&mut writer, &ANALYZER_MAGIC)?;
+        let mut writer = ChecksumWriter::new(writer);
+        let mut flags = 0;
+        if self.split_hyphens {
+            flags |= SPLIT_HYPHENS_FLAG;
+        }
+        if self.tokenizer == Tokenizer::Unicode {
+            flags |= UNICODE_TOKENIZER_FLAG;
+        }
+        if self.fold_diacritics {
+            flags |= FOLD_DIACRITICS_FLAG;
+        }
+        writer.write_all(&[self.stemmer.get_id(), flags])?;
+        writer.write_all(&(self.min_token_length as u32).to_le_bytes())?;
+        writer.write_all(&(self.max_token_length as u32).to_le_bytes())?;
+        writer.write_all(&(self.stop_words.len() as u32).to_le_bytes())?;
+        for stop_word in &self.stop_words {
+            write_string(&mut writer, stop_word)?;
+        }
+        writer.finish()?.flush()
+    }
+
+    // Returns None for an index which was saved before the analyzer was recorded, such an index
+    // was built with the default analyzer
+    pub fn load(index_directory_path: &Path) -> io::Result<Option<Self>> {
+        let analyzer_path = get_save_analyzer_path(index_directory_path);
+        let file = match File::open(&analyzer_path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let mut reader = BufReader::new(file);
+        let file_name = analyzer_path.display().to_string();
+        read_file_header(&mut reader, &ANALYZER_MAGIC, &file_name)?;
+        let mut reader = ChecksumReader::new(reader);
+
+        let mut flags = [0u8; 2];
+        reader.read_exact(&mut flags)?;
+        let stemmer = Stemmer::from_id(flags[0]).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} has an unknown stemmer", file_name),
+            )
+        })?;
+        let mut buffer = [0u8; 4];
+        reader.read_exact(&mut buffer)?;
+        let min_token_length = u32::from_le_bytes(buffer) as usize;
+        reader.read_exact(&mut buffer)?;
+        let max_token_length = u32::from_le_bytes(buffer) as usize;
+        reader.read_exact(&mut buffer)?;
+        let no_of_stop_words = u32::from_le_bytes(buffer);
+        let mut stop_words = Vec::new();
+        for _ in 0..no_of_stop_words {
+            stop_words.push(read_string(&mut reader)?);
+        }
+        reader.finish(&file_name)?;
+
+        let tokenizer = if flags[1] & UNICODE_TOKENIZER_FLAG != 0 {
+            Tokenizer::Unicode
+        } else {
+            Tokenizer::Ascii
+        };
+        Ok(Some(Self {
+            tokenizer,
+            fold_diacritics: flags[1] & FOLD_DIACRITICS_FLAG != 0,
+            stemmer,
+            stop_words,
+            min_token_length,
+            max_token_length,
+            split_hyphens: flags[1] & SPLIT_HYPHENS_FLAG != 0,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_save_and_load_analyzer() {
+        let temp_dir = TempDir::new().unwrap();
+        assert_eq!(AnalyzerConfig::load(temp_dir.path()).unwrap(), None);
+
+        let stop_words_path = temp_dir.path().join("stop_words.txt");
+        fs::write(&stop_words_path, "# articles\nThe\n\n  an \nof\n").unwrap();
+        let analyzer_config = AnalyzerConfig {
+            tokenizer: Tokenizer::Unicode,
+            fold_diacritics: true,
+            stemmer: Stemmer::Snowball,
+            stop_words: AnalyzerConfig::load_stop_words(&stop_words_path).unwrap(),
+            min_token_length: 2,
+            max_token_length: 30,
+            split_hyphens: true,
+        };
+        assert_eq!(analyzer_config.stop_words, vec!["the", "an", "of"]);
+        analyzer_config.save(temp_dir.path()).unwrap();
+        assert_eq!(
+            AnalyzerConfig::load(temp_dir.path()).unwrap(),
+            Some(analyzer_config)
+        );
+
+        let analyzer_path = get_save_analyzer_path(temp_dir.path());
+        let mut bytes = fs::read(&analyzer_path).unwrap();
+        bytes[8] = 7;
+        fs::write(&analyzer_path, bytes).unwrap();
+        let e = AnalyzerConfig::load(temp_dir.path()).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_validate_analyzer() {
+        assert!(AnalyzerConfig::default().validate().is_ok());
+        let analyzer_config = AnalyzerConfig {
+            min_token_length: 5,
+            max_token_length: 4,
+            ..AnalyzerConfig::default()
+        };
+        assert!(analyzer_config.validate().is_err());
+    }
+}
@@ -1 +1,5 @@
+pub mod analyzer;
 pub mod parser;
+pub mod query_parser;
+pub mod stemmer;
+pub mod synonyms;
@@ -1,17 +1,34 @@
-use std::io;
+use std::{io, sync::Arc};
+
+use unicode_segmentation::UnicodeSegmentation;
+use unidecode::unidecode;
+
+use crate::{
+    parser::{
+        analyzer::{AnalyzerConfig, Tokenizer},
+        stemmer::Stemmer,
+        synonyms::{SynonymDictionary, SynonymMap},
+    },
+    utils::{
+        field::{Field, TITLE_PREFIX},
+        wildcard::{MIN_WILDCARD_PREFIX_LENGTH, WildcardPattern, is_wildcard_pattern},
+    },
+};
 // A custom error type to represent our possible errors
 #[derive(Debug)]
 pub enum TokenizationError {
     EmptyInput,
+    UnbalancedParentheses,
+    MissingOperand,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Token {
     pub position: u32,
     pub word: String,
 }
 
-const STOP_WORDS: &[&str] = &[
+pub const STOP_WORDS: &[&str] = &[
     "a", "an", "and", "are", "as", "at", "be", "by", "for", "from", "has", "he", "in", "is", "it",
     "its", "of", "on", "that", "the", "to", "was", "will", "with", "the", "this", "but", "they",
     "have", "had", "what", "when", "where", "who", "which", "why", "how", "all", "each", "every",
@@ -21,32 +38,355 @@ const STOP_WORDS: &[&str] = &[
 
 #[derive(Debug, Clone)]
 pub struct Parser {
+    analyzer_config: AnalyzerConfig,
     stop_word_set: HashSet<String>,
+    // shared by the clones of the parser which the reader threads of the indexer tokenize with
+    synonym_map: Arc<SynonymMap>,
 }
 
 use std::collections::HashSet;
 pub fn clean_word(word: &str) -> String {
-    // let trimmed = word.trim_matches(|c: char| !c.is_alphanumeric());
-    // unidecode(
     word.trim_matches(|c: char| !c.is_alphanumeric())
         .to_lowercase()
-    // )
 }
 
+// Cleans a word which may be a wildcard pattern, the wildcards are kept but a ? at the end of the
+// word is a question mark so it is trimmed like any other punctuation
+pub fn clean_wildcard_word(word: &str) -> String {
+    word.trim_start_matches(|c: char| !c.is_alphanumeric() && !matches!(c, '*' | '?'))
+        .trim_end_matches(|c: char| !c.is_alphanumeric() && c != '*')
+        .to_lowercase()
+}
+
+// A pattern has to start with a few literal characters, otherwise it would have to be matched
+// against every term of the index
+pub fn is_valid_wildcard_pattern(pattern: &str) -> bool {
+    WildcardPattern::new(pattern)
+        .is_some_and(|pattern| pattern.get_prefix().len() >= MIN_WILDCARD_PREFIX_LENGTH)
+        && pattern
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '-' | '*' | '?'))
+}
+
+// the length of a token is checked by the analyzer, as it can be configured
 pub fn is_valid_token(text: &str) -> bool {
-    !text.is_empty()
-        && text.len() <= 20
-        && text.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+    !text.is_empty() && text.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
+// A word of the unicode tokenizer can be in any script and keep the punctuation inside it, like
+// don't or 3.14, but a word which was folded into more than one word is dropped. A colon would
+// let a word clash with the prefix of the title terms, so a word which was folded into one is
+// dropped as well.
+fn is_valid_unicode_token(text: &str) -> bool {
+    text.chars().any(char::is_alphanumeric) && !text.chars().any(|c| c.is_whitespace() || c == ':')
+}
+
+// the Chinese characters and the Japanese kana, which are written without spaces between words
+fn is_cjk(c: char) -> bool {
+    matches!(c,
+        '\u{3040}'..='\u{30FF}'
+        | '\u{31F0}'..='\u{31FF}'
+        | '\u{3400}'..='\u{4DBF}'
+        | '\u{4E00}'..='\u{9FFF}'
+        | '\u{F900}'..='\u{FAFF}'
+        | '\u{FF66}'..='\u{FF9F}'
+        | '\u{20000}'..='\u{2FA1F}')
+}
+
+// A positional query is either an exact phrase ("misery movie") whose terms have to appear
+// at the same relative positions as in the query, or a proximity query (misery NEAR/5 movie)
+// whose terms have to appear within slop positions of each other in any order.
+#[derive(Debug, Clone)]
+pub struct PositionalQuery {
+    pub terms: Vec<Token>,
+    pub slop: u32,
+    pub ordered: bool,
 }
 
 pub struct TokenizeQueryResult {
     pub unigram: Vec<Token>,
+    pub positional: Vec<PositionalQuery>,
+}
+
+// A run of Chinese or Japanese characters is indexed as its overlapping bigrams, 東京都 as 東京
+// and 京都, since a query for either word then matches without a dictionary of their words. A
+// single character is indexed on its own. The bigrams are not checked against the stop words or
+// the token lengths.
+fn add_cjk_bigrams(cjk_run: &mut Vec<char>, add_term: &mut impl FnMut(Option<String>)) {
+    if cjk_run.len() == 1 {
+        add_term(Some(cjk_run[0].to_string()));
+    }
+    for bigram in cjk_run.windows(2) {
+        add_term(Some(bigram.iter().collect()));
+    }
+    cjk_run.clear();
+}
+
+// The synonyms of the no_of_tokens words of a query from first_token on. Every synonym is a list
+// of terms without a field along with their positions relative to its first term, the synonyms
+// are scored with weight.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SynonymExpansion {
+    pub first_token: usize,
+    pub no_of_tokens: usize,
+    pub synonyms: Vec<Vec<(String, u32)>>,
+    pub weight: f32,
+}
+
+// the term without its title: prefix, the synonyms are looked up in the same way in either field
+pub(crate) fn strip_field(term: &str) -> &str {
+    term.strip_prefix(TITLE_PREFIX).unwrap_or(term)
+}
+
+// Parses the NEAR/k proximity operator, returns the maximum allowed distance
+fn parse_near_operator(word: &str) -> Option<u32> {
+    word.strip_prefix("NEAR/")
+        .and_then(|distance| distance.parse::<u32>().ok())
 }
 
 impl Parser {
     pub fn new() -> Result<Parser, io::Error> {
-        let stop_word_set: HashSet<String> = STOP_WORDS.iter().map(|&s| s.to_string()).collect();
-        Ok(Parser { stop_word_set })
+        Ok(Parser::with_config(AnalyzerConfig::default()))
+    }
+
+    pub fn with_config(analyzer_config: AnalyzerConfig) -> Parser {
+        let stop_word_set: HashSet<String> = analyzer_config.stop_words.iter().cloned().collect();
+        Parser {
+            analyzer_config,
+            stop_word_set,
+            synonym_map: Arc::default(),
+        }
+    }
+
+    pub fn get_analyzer_config(&self) -> &AnalyzerConfig {
+        &self.analyzer_config
+    }
+
+    // The words of the rules are analyzed with the analyzer of the parser, so the synonyms have to
+    // be set again when the analyzer changes
+    pub fn set_synonyms(&mut self, synonym_dictionary: SynonymDictionary) {
+        self.synonym_map = Arc::new(SynonymMap::new(synonym_dictionary, |words| {
+            self.analyze_synonym(words)
+        }));
+    }
+
+    pub fn get_synonyms(&self) -> &SynonymDictionary {
+        self.synonym_map.get_dictionary()
+    }
+
+    pub(crate) fn get_synonym_map(&self) -> &SynonymMap {
+        &self.synonym_map
+    }
+
+    // The terms of the words of a synonym rule along with their positions, in the same way as the
+    // words of a phrase
+    fn analyze_synonym(&self, words: &str) -> Vec<Token> {
+        let mut tokens = Vec::new();
+        let mut position = 0;
+        for word in words.split_whitespace() {
+            self.analyze_word(clean_word(word), |term| {
+                if let Some(term) = term {
+                    tokens.push(Token {
+                        position,
+                        word: term,
+                    });
+                }
+                position += 1;
+            });
+        }
+        tokens
+    }
+
+    // The synonyms of the runs of words of a ranked query. The longest run of words which has
+    // synonyms is expanded first. A synonym made of a single term which is a word of the query is
+    // left out, since the word is scored on its own already, and so are the synonyms of the
+    // wildcard patterns.
+    pub fn expand_synonyms(&self, tokens: &[Token]) -> Vec<SynonymExpansion> {
+        let synonym_map = &self.synonym_map;
+        let mut expansions = Vec::new();
+        if synonym_map.is_empty() {
+            return expansions;
+        }
+        let mut i = 0;
+        while i < tokens.len() {
+            let field = Field::from_term(&tokens[i].word);
+            let terms: Vec<String> = tokens[i..]
+                .iter()
+                .take(synonym_map.get_max_no_of_terms())
+                .take_while(|token| {
+                    Field::from_term(&token.word) == field && !is_wildcard_pattern(&token.word)
+                })
+                .map(|token| strip_field(&token.word).to_string())
+                .collect();
+            let matched = (1..=terms.len()).rev().find_map(|no_of_terms| {
+                synonym_map
+                    .get_synonyms(&terms[..no_of_terms])
+                    .map(|synonyms| (no_of_terms, synonyms))
+            });
+            let Some((no_of_terms, synonyms)) = matched else {
+                i += 1;
+                continue;
+            };
+            let synonyms: Vec<Vec<(String, u32)>> = synonyms
+                .iter()
+                .filter(|synonym| match synonym.as_slice() {
+                    [token] => {
+                        let term = field.get_field_term(&token.word);
+                        !tokens.iter().any(|token| token.word == term)
+                    }
+                    _ => true,
+                })
+                .map(|synonym| {
+                    synonym
+                        .iter()
+                        .map(|token| (token.word.clone(), token.position - synonym[0].position))
+                        .collect()
+                })
+                .collect();
+            if !synonyms.is_empty() {
+                expansions.push(SynonymExpansion {
+                    first_token: i,
+                    no_of_tokens: no_of_terms,
+                    synonyms,
+                    weight: synonym_map.get_weight(),
+                });
+            }
+            i += no_of_terms;
+        }
+        expansions
+    }
+
+    pub fn is_stop_word(&self, word: &str) -> bool {
+        self.stop_word_set.contains(word)
+    }
+
+    // The term which a part of a cleaned word is indexed as, the stop words and the parts which
+    // are too short or too long are checked before they are stemmed
+    fn analyze_part(&self, part: String) -> Option<String> {
+        let length = part.chars().count();
+        let is_valid = match self.analyzer_config.tokenizer {
+            Tokenizer::Ascii => is_valid_token(&part),
+            Tokenizer::Unicode => is_valid_unicode_token(&part),
+        };
+        if length < self.analyzer_config.min_token_length
+            || length > self.analyzer_config.max_token_length
+            || !is_valid
+            || self.stop_word_set.contains(&part)
+        {
+            return None;
+        }
+        Some(match self.analyzer_config.stemmer {
+            Stemmer::None => part,
+            stemmer => stemmer.stem(&part),
+        })
+    }
+
+    // Passes the terms of a cleaned word to add_term, a hyphenated word has a term for every part
+    // if hyphens are split. None is passed for a part which is not indexed, as it still takes up a
+    // position.
+    fn analyze_word(&self, cleaned_word: String, mut add_term: impl FnMut(Option<String>)) {
+        if self.analyzer_config.tokenizer == Tokenizer::Unicode {
+            self.analyze_unicode_word(&cleaned_word, add_term);
+            return;
+        }
+        let cleaned_word = self.fold_word(cleaned_word);
+        if !self.analyzer_config.split_hyphens || !cleaned_word.contains('-') {
+            add_term(self.analyze_part(cleaned_word));
+            return;
+        }
+        let mut parts = cleaned_word
+            .split('-')
+            .filter(|part| !part.is_empty())
+            .peekable();
+        if parts.peek().is_none() {
+            add_term(None);
+        }
+        for part in parts {
+            add_term(self.analyze_part(part.to_string()));
+        }
+    }
+
+    // Splits a cleaned word at the word boundaries of Unicode, which also splits it at its hyphens
+    // and slashes. Unicode keeps a colon between letters inside the word, so the word is split at
+    // its colons as well, otherwise title:misery in a document would be indexed as the title term
+    // misery. The adjacent Chinese and Japanese characters are gathered into runs which are
+    // indexed as their bigrams.
+    fn analyze_unicode_word(&self, cleaned_word: &str, mut add_term: impl FnMut(Option<String>)) {
+        // a space has the same length as a colon, so the offsets of the segments still line up
+        let cleaned_word = cleaned_word.replace(':', " ");
+        let mut cjk_run: Vec<char> = Vec::new();
+        let mut cjk_run_end = 0;
+        let mut has_words = false;
+        for (start, segment) in cleaned_word.unicode_word_indices() {
+            has_words = true;
+            if segment.chars().all(is_cjk) {
+                // the characters on either side of punctuation are not joined into a bigram
+                if start != cjk_run_end {
+                    add_cjk_bigrams(&mut cjk_run, &mut add_term);
+                }
+                cjk_run.extend(segment.chars());
+                cjk_run_end = start + segment.len();
+                continue;
+            }
+            add_cjk_bigrams(&mut cjk_run, &mut add_term);
+            add_term(self.analyze_part(self.fold_word(segment.to_string())));
+        }
+        add_cjk_bigrams(&mut cjk_run, &mut add_term);
+        if !has_words {
+            add_term(None);
+        }
+    }
+
+    fn fold_word(&self, word: String) -> String {
+        if self.analyzer_config.fold_diacritics && !word.is_ascii() {
+            unidecode(&word).to_lowercase()
+        } else {
+            word
+        }
+    }
+
+    // The terms which a word of a query is looked up as, which is more than one for a hyphenated
+    // word if hyphens are split. The stop words are dropped.
+    pub fn analyze_query_terms(&self, word: &str) -> Vec<String> {
+        let mut query_terms = Vec::new();
+        self.analyze_query_word_into(word, &mut |query_term| {
+            query_terms.extend(query_term);
+        });
+        query_terms
+    }
+
+    // Passes the terms of a word of a query to add_term in the same way as analyze_word, so that
+    // the positions of the terms of a phrase match the positions they were indexed at
+    pub(crate) fn analyze_query_word_into(
+        &self,
+        word: &str,
+        add_term: &mut dyn FnMut(Option<String>),
+    ) {
+        let (field, word) = match word.strip_prefix(TITLE_PREFIX) {
+            Some(word) => (Field::Title, word),
+            None => (Field::Body, word),
+        };
+        let pattern = self.fold_word(clean_wildcard_word(word));
+        if is_wildcard_pattern(&pattern) {
+            add_term(is_valid_wildcard_pattern(&pattern).then(|| field.get_field_term(&pattern)));
+            return;
+        }
+        self.analyze_word(clean_word(word), |term| {
+            add_term(term.map(|term| field.get_field_term(&term)))
+        });
+    }
+
+    // Cleans a word of a query in the same way as during indexing and returns the term which it
+    // is looked up as. A word with the title: prefix only matches the titles of the documents.
+    // A wildcard pattern is returned as it is, it is expanded into terms against the index, so it
+    // is not stemmed. None is returned for a word which is split into more than one term.
+    pub fn analyze_query_word(&self, word: &str) -> Option<String> {
+        let mut query_terms = self.analyze_query_terms(word);
+        if query_terms.len() == 1 {
+            query_terms.pop()
+        } else {
+            None
+        }
     }
 
     pub fn tokenize_query(
@@ -58,25 +398,81 @@ impl Parser {
         }
 
         let mut unigram_tokens: Vec<Token> = Vec::new();
+        let mut positional_queries: Vec<PositionalQuery> = Vec::new();
         let mut position = 0;
 
+        let mut in_phrase = false;
+        let mut phrase_tokens: Vec<Token> = Vec::new();
+        let mut near_distance: Option<u32> = None;
+        let mut previous_token: Option<Token> = None;
+
         for word in sentences.split_whitespace() {
-            let cleaned_word = clean_word(word);
-            if !cleaned_word.is_empty()
-                && !self.stop_word_set.contains(&cleaned_word)
-                && is_valid_token(&cleaned_word)
-            {
-                unigram_tokens.push(Token {
-                    position,
-                    word: cleaned_word,
-                });
+            // The NEAR operator does not take up a position as it is not part of the text
+            if !in_phrase && let Some(distance) = parse_near_operator(word) {
+                near_distance = Some(distance);
+                continue;
             }
 
-            position += 1;
+            let mut word = word;
+            if !in_phrase && word.starts_with('"') {
+                in_phrase = true;
+                phrase_tokens.clear();
+                word = &word[1..];
+            }
+            let mut closes_phrase = false;
+            if in_phrase && word.ends_with('"') {
+                closes_phrase = true;
+                word = &word[..word.len() - 1];
+            }
+
+            self.analyze_query_word_into(word, &mut |query_term| {
+                if let Some(query_term) = query_term {
+                    let token = Token {
+                        position,
+                        word: query_term,
+                    };
+                    if in_phrase {
+                        phrase_tokens.push(token.clone());
+                    }
+                    if let (Some(slop), Some(previous)) =
+                        (near_distance.take(), previous_token.take())
+                    {
+                        positional_queries.push(PositionalQuery {
+                            terms: vec![previous, token.clone()],
+                            slop,
+                            ordered: false,
+                        });
+                    }
+                    previous_token = Some(token.clone());
+                    unigram_tokens.push(token);
+                }
+                position += 1;
+            });
+
+            if closes_phrase {
+                in_phrase = false;
+                if phrase_tokens.len() > 1 {
+                    positional_queries.push(PositionalQuery {
+                        terms: std::mem::take(&mut phrase_tokens),
+                        slop: 0,
+                        ordered: true,
+                    });
+                }
+            }
+        }
+
+        // A phrase which is never closed runs till the end of the query
+        if in_phrase && phrase_tokens.len() > 1 {
+            positional_queries.push(PositionalQuery {
+                terms: phrase_tokens,
+                slop: 0,
+                ordered: true,
+            });
         }
 
         Ok(TokenizeQueryResult {
             unigram: unigram_tokens,
+            positional: positional_queries,
         })
     }
 
@@ -88,24 +484,23 @@ impl Parser {
         let mut position = 0;
 
         for word in sentences.split_whitespace() {
-            let cleaned_word = clean_word(word);
-            if !cleaned_word.is_empty()
-                && !self.stop_word_set.contains(&cleaned_word)
-                && is_valid_token(&cleaned_word)
-            {
-                tokens.push(Token {
-                    position,
-                    word: cleaned_word,
-                });
-            }
-
-            position += 1;
+            self.analyze_word(clean_word(word), |term| {
+                if let Some(term) = term {
+                    tokens.push(Token {
+                        position,
+                        word: term,
+                    });
+                }
+                position += 1;
+            });
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::parser::synonyms::SynonymRule;
+
     use super::*;
 
     #[test]
@@ -121,6 +516,270 @@ mod tests {
         println!("{}", is_valid_token(&cleaned_word));
     }
 
+    #[test]
+    fn test_tokenize_query_phrase() {
+        let parser = Parser::new().unwrap();
+        let result = parser
+            .tokenize_query("\"lord of the rings\" movie")
+            .unwrap();
+
+        assert_eq!(result.unigram.len(), 3);
+        assert_eq!(result.positional.len(), 1);
+        let phrase = &result.positional[0];
+        assert!(phrase.ordered);
+        assert_eq!(phrase.slop, 0);
+        assert_eq!(phrase.terms[0].word, "lord");
+        assert_eq!(phrase.terms[0].position, 0);
+        assert_eq!(phrase.terms[1].word, "rings");
+        assert_eq!(phrase.terms[1].position, 3);
+    }
+
+    #[test]
+    fn test_tokenize_query_near() {
+        let parser = Parser::new().unwrap();
+        let result = parser.tokenize_query("misery NEAR/5 movie").unwrap();
+
+        assert_eq!(result.unigram.len(), 2);
+        assert_eq!(result.unigram[1].position, 1);
+        assert_eq!(result.positional.len(), 1);
+        let near = &result.positional[0];
+        assert!(!near.ordered);
+        assert_eq!(near.slop, 5);
+        assert_eq!(near.terms[0].word, "misery");
+        assert_eq!(near.terms[1].word, "movie");
+    }
+
+    #[test]
+    fn test_tokenize_query_without_operators() {
+        let parser = Parser::new().unwrap();
+        let result = parser.tokenize_query("misery movie").unwrap();
+
+        assert_eq!(result.unigram.len(), 2);
+        assert!(result.positional.is_empty());
+    }
+
+    #[test]
+    fn test_tokenize_query_title_prefix() {
+        let parser = Parser::new().unwrap();
+        let result = parser
+            .tokenize_query("title:Misery king title:the")
+            .unwrap();
+
+        let words: Vec<&str> = result.unigram.iter().map(|t| t.word.as_str()).collect();
+        assert_eq!(words, vec!["title:misery", "king"]);
+        assert_eq!(result.unigram[1].position, 1);
+    }
+
+    #[test]
+    fn test_tokenize_query_wildcards() {
+        let parser = Parser::new().unwrap();
+        let result = parser
+            .tokenize_query("Astro* col?r title:king* color? a* *ing")
+            .unwrap();
+
+        let words: Vec<&str> = result.unigram.iter().map(|t| t.word.as_str()).collect();
+        assert_eq!(words, vec!["astro*", "col?r", "title:king*", "color"]);
+    }
+
+    #[test]
+    fn test_tokenize_with_analyzer() {
+        let parser = Parser::with_config(AnalyzerConfig {
+            stemmer: Stemmer::Porter,
+            min_token_length: 3,
+            split_hyphens: true,
+            ..AnalyzerConfig::default()
+        });
+        let mut tokens = Vec::new();
+        parser.tokenize("Running state-of-the-art go-karts", &mut tokens);
+        let tokens: Vec<(u32, &str)> = tokens
+            .iter()
+            .map(|t| (t.position, t.word.as_str()))
+            .collect();
+        assert_eq!(
+            tokens,
+            vec![(0, "run"), (1, "state"), (4, "art"), (6, "kart")]
+        );
+
+        let result = parser.tokenize_query("\"state-of-the-art\" runs*").unwrap();
+        let words: Vec<&str> = result.unigram.iter().map(|t| t.word.as_str()).collect();
+        assert_eq!(words, vec!["state", "art", "runs*"]);
+        assert_eq!(result.positional[0].terms[1].position, 3);
+        assert_eq!(parser.analyze_query_word("co-op"), None);
+        assert_eq!(
+            parser.analyze_query_terms("title:go-karts"),
+            vec!["title:kart"]
+        );
+    }
+
+    #[test]
+    fn test_expand_synonyms() {
+        let mut parser = Parser::with_config(AnalyzerConfig {
+            stemmer: Stemmer::Porter,
+            ..AnalyzerConfig::default()
+        });
+        parser.set_synonyms(SynonymDictionary {
+            rules: vec![
+                SynonymRule::equivalent(&["car", "automobile", "motor car"]),
+                SynonymRule::one_way(&["New York City"], &["nyc"]),
+            ],
+            weight: 0.6,
+        });
+        // the runs of words which have synonyms along with the terms and offsets of the synonyms
+        let expand = |query: &str| {
+            let tokens = parser.tokenize_query(query).unwrap().unigram;
+            parser
+                .expand_synonyms(&tokens)
+                .into_iter()
+                .map(|expansion| {
+                    assert_eq!(expansion.weight, 0.6);
+                    (
+                        expansion.first_token,
+                        expansion.no_of_tokens,
+                        expansion.synonyms,
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+        let synonym = |terms: &[(&str, u32)]| -> Vec<(String, u32)> {
+            terms
+                .iter()
+                .map(|&(term, offset)| (term.to_string(), offset))
+                .collect()
+        };
+        assert_eq!(
+            expand("cars"),
+            vec![(
+                0,
+                1,
+                vec![
+                    synonym(&[("automobil", 0)]),
+                    synonym(&[("motor", 0), ("car", 1)])
+                ]
+            )]
+        );
+        assert_eq!(
+            expand("title:automobiles car"),
+            vec![
+                (
+                    0,
+                    1,
+                    vec![synonym(&[("car", 0)]), synonym(&[("motor", 0), ("car", 1)])]
+                ),
+                (
+                    1,
+                    1,
+                    vec![
+                        synonym(&[("automobil", 0)]),
+                        synonym(&[("motor", 0), ("car", 1)])
+                    ]
+                ),
+            ]
+        );
+        // a single term synonym which is a word of the query is scored as that word
+        assert_eq!(
+            expand("car automobile"),
+            vec![
+                (0, 1, vec![synonym(&[("motor", 0), ("car", 1)])]),
+                (1, 1, vec![synonym(&[("motor", 0), ("car", 1)])]),
+            ]
+        );
+        assert_eq!(
+            expand("new york city"),
+            vec![(0, 3, vec![synonym(&[("nyc", 0)])])]
+        );
+        assert!(expand("new york").is_empty());
+        assert!(expand("car*").is_empty());
+    }
+
+    #[test]
+    fn test_tokenize_unicode() {
+        let tokenize = |analyzer_config: AnalyzerConfig, text: &str| {
+            let mut tokens = Vec::new();
+            Parser::with_config(analyzer_config).tokenize(text, &mut tokens);
+            tokens
+                .into_iter()
+                .map(|t| (t.position, t.word))
+                .collect::<Vec<(u32, String)>>()
+        };
+        let expected = |tokens: &[(u32, &str)]| {
+            tokens
+                .iter()
+                .map(|&(position, word)| (position, word.to_string()))
+                .collect::<Vec<(u32, String)>>()
+        };
+        let text = "Café Zürich, 東京都 co-op Москва the";
+
+        // the default tokenizer drops every word which is not ASCII
+        assert_eq!(
+            tokenize(AnalyzerConfig::default(), text),
+            expected(&[(3, "co-op")])
+        );
+        let unicode_config = AnalyzerConfig {
+            tokenizer: Tokenizer::Unicode,
+            ..AnalyzerConfig::default()
+        };
+        assert_eq!(
+            tokenize(unicode_config.clone(), text),
+            expected(&[
+                (0, "café"),
+                (1, "zürich"),
+                (2, "東京"),
+                (3, "京都"),
+                (4, "co"),
+                (5, "op"),
+                (6, "москва")
+            ])
+        );
+        assert_eq!(
+            tokenize(
+                AnalyzerConfig {
+                    fold_diacritics: true,
+                    ..unicode_config.clone()
+                },
+                text
+            ),
+            expected(&[
+                (0, "cafe"),
+                (1, "zurich"),
+                (2, "東京"),
+                (3, "京都"),
+                (4, "co"),
+                (5, "op"),
+                (6, "moskva")
+            ])
+        );
+        assert_eq!(
+            tokenize(
+                AnalyzerConfig {
+                    fold_diacritics: true,
+                    ..AnalyzerConfig::default()
+                },
+                text
+            ),
+            expected(&[(0, "cafe"), (1, "zurich"), (3, "co-op"), (4, "moskva")])
+        );
+        // the bigrams do not run over punctuation, a single character is kept on its own
+        assert_eq!(
+            tokenize(unicode_config.clone(), "日本。語 don't"),
+            expected(&[(0, "日本"), (1, "語"), (2, "don't")])
+        );
+
+        // a colon splits the word, so a document can not add to the scores of the title terms
+        assert_eq!(
+            tokenize(unicode_config.clone(), "title:misery a:b:c"),
+            expected(&[(0, "title"), (1, "misery"), (3, "b"), (4, "c")])
+        );
+
+        let parser = Parser::with_config(unicode_config);
+        let result = parser.tokenize_query("\"東京都\" zür*").unwrap();
+        let words: Vec<&str> = result.unigram.iter().map(|t| t.word.as_str()).collect();
+        assert_eq!(words, vec!["東京", "京都", "zür*"]);
+        assert_eq!(result.positional[0].terms.len(), 2);
+        // the title: prefix of a query word still picks the field
+        let result = parser.tokenize_query("title:misery").unwrap();
+        assert_eq!(result.unigram[0].word, "title:misery");
+    }
+
     // #[test]
     // fn test_punctuation_handling() {
     //     let tokenizer = create_test_tokenizer();
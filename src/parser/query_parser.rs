@@ -0,0 +1,483 @@
+use crate::{
+    parser::parser::{Parser, PositionalQuery, Token, TokenizationError, strip_field},
+    utils::{field::Field, wildcard::is_wildcard_pattern},
+};
+
+// The boolean query language supports
+// - AND, OR and NOT operators (in uppercase), terms next to each other are implicitly joined by AND
+// - parentheses for grouping
+// - "..." for phrases
+// - -term to exclude a term and +term to require it
+// A word which has synonyms becomes an OR of the word and its synonyms.
+// NOT binds tighter than AND which binds tighter than OR.
+#[derive(Debug, Clone)]
+pub enum QueryNode {
+    Term(String),
+    Phrase(PositionalQuery),
+    And(Vec<QueryNode>),
+    Or(Vec<QueryNode>),
+    Not(Box<QueryNode>),
+}
+
+impl QueryNode {
+    // All the distinct terms which are present in the query, the terms
+    // which are inside a NOT are included as well since they need to be looked up.
+    pub fn get_terms(&self) -> Vec<String> {
+        let mut terms = Vec::new();
+        self.collect_terms(&mut terms);
+        terms
+    }
+
+    fn collect_terms(&self, terms: &mut Vec<String>) {
+        match self {
+            QueryNode::Term(term) => {
+                if !terms.contains(term) {
+                    terms.push(term.clone());
+                }
+            }
+            QueryNode::Phrase(phrase) => {
+                for token in &phrase.terms {
+                    if !terms.contains(&token.word) {
+                        terms.push(token.word.clone());
+                    }
+                }
+            }
+            QueryNode::And(children) | QueryNode::Or(children) => {
+                for child in children {
+                    child.collect_terms(terms);
+                }
+            }
+            QueryNode::Not(child) => child.collect_terms(terms),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum QueryToken {
+    LeftParen,
+    RightParen,
+    And,
+    Or,
+    Not,
+    Required,
+    Excluded,
+    Word(String),
+    Phrase(Vec<String>),
+}
+
+fn lex_query(query: &str) -> Vec<QueryToken> {
+    let chars: Vec<char> = query.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '(' {
+            tokens.push(QueryToken::LeftParen);
+            i += 1;
+        } else if c == ')' {
+            tokens.push(QueryToken::RightParen);
+            i += 1;
+        } else if c == '"' {
+            let start = i + 1;
+            i = start;
+            while i < chars.len() && chars[i] != '"' {
+                i += 1;
+            }
+            let phrase: String = chars[start..i].iter().collect();
+            tokens.push(QueryToken::Phrase(
+                phrase.split_whitespace().map(String::from).collect(),
+            ));
+            // skip the closing quote
+            i += 1;
+        } else if (c == '-' || c == '+') && i + 1 < chars.len() && !chars[i + 1].is_whitespace() {
+            tokens.push(if c == '-' {
+                QueryToken::Excluded
+            } else {
+                QueryToken::Required
+            });
+            i += 1;
+        } else {
+            let start = i;
+            while i < chars.len()
+                && !chars[i].is_whitespace()
+                && !matches!(chars[i], '(' | ')' | '"')
+            {
+                i += 1;
+            }
+            let word: String = chars[start..i].iter().collect();
+            tokens.push(match word.as_str() {
+                "AND" => QueryToken::And,
+                "OR" => QueryToken::Or,
+                "NOT" => QueryToken::Not,
+                _ => QueryToken::Word(word),
+            });
+        }
+    }
+    tokens
+}
+
+struct BooleanQueryParser<'a> {
+    parser: &'a Parser,
+    tokens: Vec<QueryToken>,
+    index: usize,
+}
+
+impl<'a> BooleanQueryParser<'a> {
+    fn peek(&self) -> Option<&QueryToken> {
+        self.tokens.get(self.index)
+    }
+
+    fn parse_or(&mut self) -> Result<Option<QueryNode>, TokenizationError> {
+        let mut children = Vec::new();
+        children.extend(self.parse_and()?);
+        while self.peek() == Some(&QueryToken::Or) {
+            self.index += 1;
+            children.extend(self.parse_and()?);
+        }
+        Ok(match children.len() {
+            0 => None,
+            1 => children.pop(),
+            _ => Some(QueryNode::Or(children)),
+        })
+    }
+
+    fn parse_and(&mut self) -> Result<Option<QueryNode>, TokenizationError> {
+        let mut children = Vec::new();
+        children.extend(self.parse_unary()?);
+        loop {
+            match self.peek() {
+                Some(QueryToken::And) => {
+                    self.index += 1;
+                    children.extend(self.parse_unary()?);
+                }
+                Some(QueryToken::RightParen) | Some(QueryToken::Or) | None => break,
+                // two operands next to each other are joined by an implicit AND
+                Some(_) => children.extend(self.parse_unary()?),
+            }
+        }
+        Ok(match children.len() {
+            0 => None,
+            1 => children.pop(),
+            _ => Some(QueryNode::And(children)),
+        })
+    }
+
+    fn parse_unary(&mut self) -> Result<Option<QueryNode>, TokenizationError> {
+        match self.peek() {
+            Some(QueryToken::Not) | Some(QueryToken::Excluded) => {
+                self.index += 1;
+                Ok(self
+                    .parse_unary()?
+                    .map(|node| QueryNode::Not(Box::new(node))))
+            }
+            Some(QueryToken::Required) => {
+                self.index += 1;
+                self.parse_primary()
+            }
+            _ => self.parse_primary(),
+        }
+    }
+
+    fn parse_primary(&mut self) -> Result<Option<QueryNode>, TokenizationError> {
+        let Some(token) = self.tokens.get(self.index).cloned() else {
+            return Err(TokenizationError::MissingOperand);
+        };
+        self.index += 1;
+        match token {
+            QueryToken::LeftParen => {
+                let node = self.parse_or()?;
+                if self.peek() != Some(&QueryToken::RightParen) {
+                    return Err(TokenizationError::UnbalancedParentheses);
+                }
+                self.index += 1;
+                Ok(node)
+            }
+            QueryToken::Word(word) => Ok(self.parse_word(&word)),
+            QueryToken::Phrase(words) => {
+                let mut phrase_tokens = Vec::with_capacity(words.len());
+                let mut position = 0;
+                for word in &words {
+                    self.parser.analyze_query_word_into(word, &mut |word| {
+                        if let Some(word) = word {
+                            phrase_tokens.push(Token { position, word });
+                        }
+                        position += 1;
+                    });
+                }
+                Ok(match phrase_tokens.len() {
+                    0 => None,
+                    1 => phrase_tokens.pop().map(|token| QueryNode::Term(token.word)),
+                    _ => Some(QueryNode::Phrase(PositionalQuery {
+                        terms: phrase_tokens,
+                        slop: 0,
+                        ordered: true,
+                    })),
+                })
+            }
+            QueryToken::RightParen => Err(TokenizationError::UnbalancedParentheses),
+            _ => Err(TokenizationError::MissingOperand),
+        }
+    }
+
+    // A word is looked up along with the words right after it which it is implicitly ANDed with,
+    // the longest run of them which has synonyms becomes an OR of the run and its synonyms. A
+    // synonym made of more than one word has to match as a phrase.
+    fn parse_word(&mut self, word: &str) -> Option<QueryNode> {
+        let synonym_map = self.parser.get_synonym_map();
+        let mut terms = self.analyze_word(word);
+        // the no of terms up to the end of every word of the run
+        let mut word_ends = vec![terms.len()];
+        let mut next = self.index;
+        while !synonym_map.is_empty() && terms.len() < synonym_map.get_max_no_of_terms() {
+            let Some(QueryToken::Word(next_word)) = self.tokens.get(next) else {
+                break;
+            };
+            terms.extend(self.analyze_word(next_word));
+            word_ends.push(terms.len());
+            next += 1;
+        }
+
+        let field = terms.first().map(|term| Field::from_term(term));
+        let matched = (1..=word_ends.len()).rev().find_map(|no_of_words| {
+            let run = &terms[..word_ends[no_of_words - 1]];
+            if run.is_empty()
+                || run
+                    .iter()
+                    .any(|term| Some(Field::from_term(term)) != field || is_wildcard_pattern(term))
+            {
+                return None;
+            }
+            let run: Vec<String> = run
+                .iter()
+                .map(|term| strip_field(term).to_string())
+                .collect();
+            synonym_map
+                .get_synonyms(&run)
+                .map(|synonyms| (no_of_words, synonyms))
+        });
+        let Some((no_of_words, synonyms)) = matched else {
+            terms.truncate(word_ends[0]);
+            return and_node(terms);
+        };
+        let field = field.unwrap();
+
+        self.index += no_of_words - 1;
+        terms.truncate(word_ends[no_of_words - 1]);
+        let mut children: Vec<QueryNode> = and_node(terms).into_iter().collect();
+        for synonym in synonyms {
+            let mut tokens: Vec<Token> = synonym
+                .iter()
+                .map(|token| Token {
+                    position: token.position,
+                    word: field.get_field_term(&token.word),
+                })
+                .collect();
+            children.push(match tokens.len() {
+                1 => QueryNode::Term(tokens.pop().unwrap().word),
+                _ => QueryNode::Phrase(PositionalQuery {
+                    terms: tokens,
+                    slop: 0,
+                    ordered: true,
+                }),
+            });
+        }
+        Some(QueryNode::Or(children))
+    }
+
+    // words are cleaned in the same way as during indexing, stop words are dropped
+    fn analyze_word(&self, word: &str) -> Vec<String> {
+        self.parser.analyze_query_terms(word)
+    }
+}
+
+// the parts of a hyphenated word all have to match
+fn and_node(mut terms: Vec<String>) -> Option<QueryNode> {
+    match terms.len() {
+        0 | 1 => terms.pop().map(QueryNode::Term),
+        _ => Some(QueryNode::And(
+            terms.into_iter().map(QueryNode::Term).collect(),
+        )),
+    }
+}
+
+impl Parser {
+    // Parses a boolean query into its syntax tree, None is returned if the query
+    // only consists of stop words.
+    pub fn parse_boolean_query(&self, query: &str) -> Result<Option<QueryNode>, TokenizationError> {
+        if query.trim().is_empty() {
+            return Err(TokenizationError::EmptyInput);
+        }
+        let mut boolean_query_parser = BooleanQueryParser {
+            parser: self,
+            tokens: lex_query(query),
+            index: 0,
+        };
+        let node = boolean_query_parser.parse_or()?;
+        if boolean_query_parser.index < boolean_query_parser.tokens.len() {
+            return Err(TokenizationError::UnbalancedParentheses);
+        }
+        Ok(node)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::{
+        analyzer::AnalyzerConfig,
+        synonyms::{SynonymDictionary, SynonymRule},
+    };
+
+    use super::*;
+
+    fn describe(node: &QueryNode) -> String {
+        match node {
+            QueryNode::Term(term) => term.clone(),
+            QueryNode::Phrase(phrase) => format!(
+                "\"{}\"",
+                phrase
+                    .terms
+                    .iter()
+                    .map(|t| t.word.as_str())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            ),
+            QueryNode::And(children) => format!(
+                "AND({})",
+                children.iter().map(describe).collect::<Vec<_>>().join(",")
+            ),
+            QueryNode::Or(children) => format!(
+                "OR({})",
+                children.iter().map(describe).collect::<Vec<_>>().join(",")
+            ),
+            QueryNode::Not(child) => format!("NOT({})", describe(child)),
+        }
+    }
+
+    fn parse(query: &str) -> String {
+        let parser = Parser::new().unwrap();
+        describe(&parser.parse_boolean_query(query).unwrap().unwrap())
+    }
+
+    #[test]
+    fn test_implicit_and() {
+        assert_eq!(parse("misery movie"), "AND(misery,movie)");
+    }
+
+    #[test]
+    fn test_operator_precedence() {
+        assert_eq!(
+            parse("stephen AND king OR tolkien NOT hobbit"),
+            "OR(AND(stephen,king),AND(tolkien,NOT(hobbit)))"
+        );
+    }
+
+    #[test]
+    fn test_grouping() {
+        assert_eq!(
+            parse("(king OR tolkien) AND novel"),
+            "AND(OR(king,tolkien),novel)"
+        );
+    }
+
+    #[test]
+    fn test_required_and_excluded_terms() {
+        assert_eq!(parse("+king -horror novel"), "AND(king,NOT(horror),novel)");
+    }
+
+    #[test]
+    fn test_title_prefix() {
+        assert_eq!(
+            parse("title:misery AND -title:movie"),
+            "AND(title:misery,NOT(title:movie))"
+        );
+    }
+
+    #[test]
+    fn test_phrase_and_stop_words() {
+        assert_eq!(
+            parse("\"lord of the rings\" AND the movie"),
+            "AND(\"lord rings\",movie)"
+        );
+    }
+
+    #[test]
+    fn test_hyphenated_word_is_not_excluded() {
+        assert_eq!(parse("co-op"), "co-op");
+    }
+
+    #[test]
+    fn test_split_hyphenated_word() {
+        let parser = Parser::with_config(AnalyzerConfig {
+            split_hyphens: true,
+            ..AnalyzerConfig::default()
+        });
+        let node = parser
+            .parse_boolean_query("co-op \"state-of-the-art\"")
+            .unwrap();
+        assert_eq!(describe(&node.unwrap()), "AND(AND(co,op),\"state art\")");
+    }
+
+    #[test]
+    fn test_synonyms() {
+        let mut parser = Parser::new().unwrap();
+        parser.set_synonyms(SynonymDictionary {
+            rules: vec![
+                SynonymRule::equivalent(&["car", "automobile"]),
+                SynonymRule::equivalent(&["usa", "united states of america"]),
+                SynonymRule::one_way(&["tv"], &["television"]),
+            ],
+            ..SynonymDictionary::default()
+        });
+        let parse = |query: &str| describe(&parser.parse_boolean_query(query).unwrap().unwrap());
+        assert_eq!(
+            parse("car -tv"),
+            "AND(OR(car,automobile),NOT(OR(tv,television)))"
+        );
+        assert_eq!(parse("title:automobile"), "OR(title:automobile,title:car)");
+        assert_eq!(parse("television"), "television");
+        assert_eq!(
+            parse("usa OR cars"),
+            "OR(OR(usa,\"united states america\"),cars)"
+        );
+        // the longest run of words is expanded, the words after it are parsed as usual
+        assert_eq!(
+            parse("united states of america car"),
+            "AND(OR(AND(united,states,america),usa),OR(car,automobile))"
+        );
+        assert_eq!(parse("united states"), "AND(united,states)");
+        assert_eq!(
+            parse("united AND states of america"),
+            "AND(united,states,america)"
+        );
+    }
+
+    #[test]
+    fn test_only_stop_words() {
+        let parser = Parser::new().unwrap();
+        assert!(parser.parse_boolean_query("the AND of").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_unbalanced_parentheses() {
+        let parser = Parser::new().unwrap();
+        assert!(matches!(
+            parser.parse_boolean_query("(king OR tolkien"),
+            Err(TokenizationError::UnbalancedParentheses)
+        ));
+        assert!(matches!(
+            parser.parse_boolean_query("king OR tolkien)"),
+            Err(TokenizationError::UnbalancedParentheses)
+        ));
+    }
+
+    #[test]
+    fn test_missing_operand() {
+        let parser = Parser::new().unwrap();
+        assert!(matches!(
+            parser.parse_boolean_query("king AND"),
+            Err(TokenizationError::MissingOperand)
+        ));
+    }
+}
@@ -0,0 +1,465 @@
+use std::{fmt, str::FromStr};
+
+use rust_stemmers::Algorithm;
+
+// The stemmers which reduce the words of the documents and of the queries to a common stem, so
+// that a query for "running" also finds "runs". Only words made of the letters a to z are stemmed,
+// the words with digits or hyphens are kept as they are.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Stemmer {
+    #[default]
+    None,
+    // the original algorithm of Martin Porter (1980)
+    Porter,
+    // the English stemmer of Snowball, also known as Porter2, which stems fewer words wrongly
+    Snowball,
+}
+
+impl Stemmer {
+    pub fn get_id(&self) -> u8 {
+        match self {
+            Stemmer::None => 0,
+            Stemmer::Porter => 1,
+            Stemmer::Snowball => 2,
+        }
+    }
+
+    pub fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(Stemmer::None),
+            1 => Some(Stemmer::Porter),
+            2 => Some(Stemmer::Snowball),
+            _ => None,
+        }
+    }
+
+    pub fn stem(&self, word: &str) -> String {
+        if *self == Stemmer::None || !word.bytes().all(|byte| byte.is_ascii_lowercase()) {
+            return word.to_string();
+        }
+        match self {
+            Stemmer::None => word.to_string(),
+            Stemmer::Porter => porter_stem(word),
+            Stemmer::Snowball => snowball_stem(word),
+        }
+    }
+}
+
+impl fmt::Display for Stemmer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Stemmer::None => "none",
+            Stemmer::Porter => "porter",
+            Stemmer::Snowball => "snowball",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for Stemmer {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Stemmer::None),
+            "porter" => Ok(Stemmer::Porter),
+            "snowball" | "porter2" => Ok(Stemmer::Snowball),
+            _ => Err(format!("unknown stemmer {}", s)),
+        }
+    }
+}
+
+// The word being stemmed by the Porter stemmer, the stem is word[..=k] and j marks where the
+// suffix which was matched last starts
+struct PorterWord {
+    word: Vec<u8>,
+    k: isize,
+    j: isize,
+}
+
+impl PorterWord {
+    fn is_consonant(&self, i: isize) -> bool {
+        match self.word[i as usize] {
+            b'a' | b'e' | b'i' | b'o' | b'u' => false,
+            b'y' => i == 0 || !self.is_consonant(i - 1),
+            _ => true,
+        }
+    }
+
+    // the no of vowel consonant sequences in word[..=j]
+    fn measure(&self) -> usize {
+        let mut n = 0;
+        let mut i = 0;
+        loop {
+            if i > self.j {
+                return n;
+            }
+            if !self.is_consonant(i) {
+                break;
+            }
+            i += 1;
+        }
+        i += 1;
+        loop {
+            loop {
+                if i > self.j {
+                    return n;
+                }
+                if self.is_consonant(i) {
+                    break;
+                }
+                i += 1;
+            }
+            i += 1;
+            n += 1;
+            loop {
+                if i > self.j {
+                    return n;
+                }
+                if !self.is_consonant(i) {
+                    break;
+                }
+                i += 1;
+            }
+            i += 1;
+        }
+    }
+
+    fn has_vowel_in_stem(&self) -> bool {
+        (0..=self.j).any(|i| !self.is_consonant(i))
+    }
+
+    fn ends_with_double_consonant(&self, i: isize) -> bool {
+        i >= 1 && self.word[i as usize] == self.word[i as usize - 1] && self.is_consonant(i)
+    }
+
+    // consonant vowel consonant where the last consonant is not w, x or y, like hop
+    fn ends_with_cvc(&self, i: isize) -> bool {
+        i >= 2
+            && self.is_consonant(i)
+            && !self.is_consonant(i - 1)
+            && self.is_consonant(i - 2)
+            && !matches!(self.word[i as usize], b'w' | b'x' | b'y')
+    }
+
+    fn ends_with(&mut self, suffix: &str) -> bool {
+        let length = suffix.len() as isize;
+        if length > self.k + 1 || !self.word[..=self.k as usize].ends_with(suffix.as_bytes()) {
+            return false;
+        }
+        self.j = self.k - length;
+        true
+    }
+
+    fn set_to(&mut self, suffix: &str) {
+        self.word.truncate((self.j + 1) as usize);
+        self.word.extend_from_slice(suffix.as_bytes());
+        self.k = self.j + suffix.len() as isize;
+    }
+
+    fn replace_if_measured(&mut self, suffix: &str) {
+        if self.measure() > 0 {
+            self.set_to(suffix);
+        }
+    }
+
+    // the first matching suffix is replaced if the stem before it has a measure above 0
+    fn replace_suffixes(&mut self, rules: &[(&str, &str)]) {
+        for (suffix, replacement) in rules {
+            if self.ends_with(suffix) {
+                self.replace_if_measured(replacement);
+                return;
+            }
+        }
+    }
+
+    // plurals and -ed or -ing, caresses -> caress, ponies -> poni, motoring -> motor
+    fn step_1ab(&mut self) {
+        if self.word[self.k as usize] == b's' {
+            if self.ends_with("sses") {
+                self.k -= 2;
+            } else if self.ends_with("ies") {
+                self.set_to("i");
+            } else if self.word[self.k as usize - 1] != b's' {
+                self.k -= 1;
+            }
+        }
+        if self.ends_with("eed") {
+            if self.measure() > 0 {
+                self.k -= 1;
+            }
+        } else if (self.ends_with("ed") || self.ends_with("ing")) && self.has_vowel_in_stem() {
+            self.k = self.j;
+            if self.ends_with("at") {
+                self.set_to("ate");
+            } else if self.ends_with("bl") {
+                self.set_to("ble");
+            } else if self.ends_with("iz") {
+                self.set_to("ize");
+            } else if self.ends_with_double_consonant(self.k) {
+                if !matches!(self.word[self.k as usize], b'l' | b's' | b'z') {
+                    self.k -= 1;
+                }
+            } else {
+                self.j = self.k;
+                if self.measure() == 1 && self.ends_with_cvc(self.k) {
+                    self.set_to("e");
+                }
+            }
+        }
+    }
+
+    // a y after a vowel in the stem becomes an i, happy -> happi
+    fn step_1c(&mut self) {
+        if self.ends_with("y") && self.has_vowel_in_stem() {
+            self.word[self.k as usize] = b'i';
+        }
+    }
+
+    // double suffixes are mapped to single ones, relational -> relate
+    fn step_2(&mut self) {
+        if self.k < 1 {
+            return;
+        }
+        let rules: &[(&str, &str)] = match self.word[self.k as usize - 1] {
+            b'a' => &[("ational", "ate"), ("tional", "tion")],
+            b'c' => &[("enci", "ence"), ("anci", "ance")],
+            b'e' => &[("izer", "ize")],
+            b'l' => &[
+                ("bli", "ble"),
+                ("alli", "al"),
+                ("entli", "ent"),
+                ("eli", "e"),
+                ("ousli", "ous"),
+            ],
+            b'o' => &[("ization", "ize"), ("ation", "ate"), ("ator", "ate")],
+            b's' => &[
+                ("alism", "al"),
+                ("iveness", "ive"),
+                ("fulness", "ful"),
+                ("ousness", "ous"),
+            ],
+            b't' => &[("aliti", "al"), ("iviti", "ive"), ("biliti", "ble")],
+            b'g' => &[("logi", "log")],
+            _ => &[],
+        };
+        self.replace_suffixes(rules);
+    }
+
+    // -ic-, -full and -ness, hopeful -> hope
+    fn step_3(&mut self) {
+        let rules: &[(&str, &str)] = match self.word[self.k as usize] {
+            b'e' => &[("icate", "ic"), ("ative", ""), ("alize", "al")],
+            b'i' => &[("iciti", "ic")],
+            b'l' => &[("ical", "ic"), ("ful", "")],
+            b's' => &[("ness", "")],
+            _ => &[],
+        };
+        self.replace_suffixes(rules);
+    }
+
+    // the suffixes are removed from stems with a measure above 1, revival -> reviv
+    fn step_4(&mut self) {
+        if self.k < 1 {
+            return;
+        }
+        let suffixes: &[&str] = match self.word[self.k as usize - 1] {
+            b'a' => &["al"],
+            b'c' => &["ance", "ence"],
+            b'e' => &["er"],
+            b'i' => &["ic"],
+            b'l' => &["able", "ible"],
+            b'n' => &["ant", "ement", "ment", "ent"],
+            b'o' => &["ion", "ou"],
+            b's' => &["ism"],
+            b't' => &["ate", "iti"],
+            b'u' => &["ous"],
+            b'v' => &["ive"],
+            b'z' => &["ize"],
+            _ => &[],
+        };
+        let Some(&suffix) = suffixes.iter().find(|suffix| self.ends_with(suffix)) else {
+            return;
+        };
+        // -ion is only removed after an s or a t, adoption -> adopt
+        if suffix == "ion" && (self.j < 0 || !matches!(self.word[self.j as usize], b's' | b't')) {
+            return;
+        }
+        if self.measure() > 1 {
+            self.k = self.j;
+        }
+    }
+
+    // a final -e is removed and -ll becomes -l on long stems, probate -> probat, controll -> control
+    fn step_5(&mut self) {
+        self.j = self.k;
+        if self.word[self.k as usize] == b'e' {
+            let measure = self.measure();
+            if measure > 1 || measure == 1 && !self.ends_with_cvc(self.k - 1) {
+                self.k -= 1;
+            }
+        }
+        if self.word[self.k as usize] == b'l'
+            && self.ends_with_double_consonant(self.k)
+            && self.measure() > 1
+        {
+            self.k -= 1;
+        }
+    }
+}
+
+pub fn porter_stem(word: &str) -> String {
+    if word.len() <= 2 {
+        return word.to_string();
+    }
+    let mut porter_word = PorterWord {
+        word: word.as_bytes().to_vec(),
+        k: word.len() as isize - 1,
+        j: 0,
+    };
+    porter_word.step_1ab();
+    if porter_word.k > 0 {
+        porter_word.step_1c();
+        porter_word.step_2();
+        porter_word.step_3();
+        porter_word.step_4();
+        porter_word.step_5();
+    }
+    porter_word.word.truncate((porter_word.k + 1) as usize);
+    String::from_utf8(porter_word.word).unwrap()
+}
+
+// The English stemmer of the Snowball project as it is generated from the Snowball sources, which
+// are checked against the vocabulary of the project
+pub fn snowball_stem(word: &str) -> String {
+    rust_stemmers::Stemmer::create(Algorithm::English)
+        .stem(word)
+        .into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_porter_stem() {
+        for (word, stem) in [
+            ("caresses", "caress"),
+            ("ponies", "poni"),
+            ("ties", "ti"),
+            ("cats", "cat"),
+            ("feed", "feed"),
+            ("agreed", "agre"),
+            ("plastered", "plaster"),
+            ("bled", "bled"),
+            ("motoring", "motor"),
+            ("sing", "sing"),
+            ("conflated", "conflat"),
+            ("troubled", "troubl"),
+            ("sized", "size"),
+            ("hopping", "hop"),
+            ("falling", "fall"),
+            ("hissing", "hiss"),
+            ("fizzed", "fizz"),
+            ("filing", "file"),
+            ("happy", "happi"),
+            ("sky", "sky"),
+            ("relational", "relat"),
+            ("conditional", "condit"),
+            ("rational", "ration"),
+            ("digitizer", "digit"),
+            ("vietnamization", "vietnam"),
+            ("operator", "oper"),
+            ("decisiveness", "decis"),
+            ("hopefulness", "hope"),
+            ("sensibiliti", "sensibl"),
+            ("triplicate", "triplic"),
+            ("formative", "form"),
+            ("electrical", "electr"),
+            ("goodness", "good"),
+            ("revival", "reviv"),
+            ("allowance", "allow"),
+            ("airliner", "airlin"),
+            ("adjustable", "adjust"),
+            ("replacement", "replac"),
+            ("adoption", "adopt"),
+            ("communism", "commun"),
+            ("effective", "effect"),
+            ("bowdlerize", "bowdler"),
+            ("probate", "probat"),
+            ("rate", "rate"),
+            ("controll", "control"),
+            ("roll", "roll"),
+            ("generalizations", "gener"),
+            ("oscillators", "oscil"),
+            ("running", "run"),
+            ("runs", "run"),
+        ] {
+            assert_eq!(porter_stem(word), stem, "{}", word);
+        }
+    }
+
+    #[test]
+    fn test_snowball_stem() {
+        for (word, stem) in [
+            ("consign", "consign"),
+            ("consigned", "consign"),
+            ("consignment", "consign"),
+            ("consistency", "consist"),
+            ("consistently", "consist"),
+            ("consolation", "consol"),
+            ("consolatory", "consolatori"),
+            ("consoles", "consol"),
+            ("consolidating", "consolid"),
+            ("consolingly", "consol"),
+            ("consonant", "conson"),
+            ("conspicuously", "conspicu"),
+            ("conspiracy", "conspiraci"),
+            ("conspirators", "conspir"),
+            ("constable", "constabl"),
+            ("constancy", "constanc"),
+            ("constant", "constant"),
+            ("knackeries", "knackeri"),
+            ("knaves", "knave"),
+            ("kneaded", "knead"),
+            ("knees", "knee"),
+            ("knightly", "knight"),
+            ("knitting", "knit"),
+            ("knives", "knive"),
+            ("knocker", "knocker"),
+            ("generously", "generous"),
+            ("generate", "generat"),
+            ("cries", "cri"),
+            ("ties", "tie"),
+            ("gaps", "gap"),
+            ("gas", "gas"),
+            ("skies", "sky"),
+            ("dying", "die"),
+            ("only", "onli"),
+            ("news", "news"),
+            ("cosmos", "cosmos"),
+            ("succeeded", "succeed"),
+            ("hopping", "hop"),
+            ("hoped", "hope"),
+            ("saying", "say"),
+            ("running", "run"),
+            ("runs", "run"),
+        ] {
+            assert_eq!(snowball_stem(word), stem, "{}", word);
+        }
+    }
+
+    #[test]
+    fn test_stemmer() {
+        assert_eq!(Stemmer::None.stem("running"), "running");
+        assert_eq!(Stemmer::Porter.stem("mp3s"), "mp3s");
+        assert_eq!(
+            Stemmer::Snowball.stem("state-of-the-art"),
+            "state-of-the-art"
+        );
+        for stemmer in [Stemmer::None, Stemmer::Porter, Stemmer::Snowball] {
+            assert_eq!(stemmer.to_string().parse::<Stemmer>(), Ok(stemmer));
+            assert_eq!(Stemmer::from_id(stemmer.get_id()), Some(stemmer));
+        }
+    }
+}
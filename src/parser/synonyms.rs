@@ -0,0 +1,280 @@
+use std::{fs, io, path::Path};
+
+use rustc_hash::FxHashMap;
+
+use crate::parser::parser::Token;
+
+// the score of a synonym is multiplied by this, so that the documents which contain the words of
+// the query itself are ranked above the ones which only contain their synonyms
+pub const DEFAULT_SYNONYM_WEIGHT: f32 = 0.5;
+
+// A rule of a synonym dictionary, every one of its words is expanded into all of its synonyms.
+// A word or a synonym can be made of more than one word, such as united states.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SynonymRule {
+    pub words: Vec<String>,
+    pub synonyms: Vec<String>,
+}
+
+impl SynonymRule {
+    // car, automobile, auto - every word is a synonym of the others
+    pub fn equivalent(words: &[&str]) -> Self {
+        let words: Vec<String> = words.iter().map(|word| word.to_string()).collect();
+        Self {
+            synonyms: words.clone(),
+            words,
+        }
+    }
+
+    // tv => television - a query for tv also finds television but not the other way round
+    pub fn one_way(words: &[&str], synonyms: &[&str]) -> Self {
+        Self {
+            words: words.iter().map(|word| word.to_string()).collect(),
+            synonyms: synonyms.iter().map(|synonym| synonym.to_string()).collect(),
+        }
+    }
+}
+
+// The synonyms which the words of a query are expanded with. The words of the query are kept, a
+// synonym is looked up along with them and its score is weighted by the weight of the dictionary.
+// The synonyms are only applied to the queries, so they can be changed without rebuilding the
+// index.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SynonymDictionary {
+    pub rules: Vec<SynonymRule>,
+    pub weight: f32,
+}
+
+impl Default for SynonymDictionary {
+    fn default() -> Self {
+        Self {
+            rules: Vec::new(),
+            weight: DEFAULT_SYNONYM_WEIGHT,
+        }
+    }
+}
+
+impl SynonymDictionary {
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.weight.is_finite() || self.weight < 0.0 {
+            return Err(format!(
+                "the synonym weight has to be a non negative number, not {}",
+                self.weight
+            ));
+        }
+        Ok(())
+    }
+
+    // Parses a synonym file, which has a rule per line. The words of a rule are separated by
+    // commas, the words before a => are expanded into the ones after it while the words of a rule
+    // without one are all expanded into each other. The empty lines and the lines starting with #
+    // are skipped.
+    //
+    // # equivalent
+    // car, automobile, auto
+    // usa, united states, united states of america
+    // # one way
+    // tv, telly => television
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut rules = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let rule = match line.split_once("=>") {
+                Some((words, synonyms)) => SynonymRule {
+                    words: parse_words(words),
+                    synonyms: parse_words(synonyms),
+                },
+                None => SynonymRule {
+                    words: parse_words(line),
+                    synonyms: parse_words(line),
+                },
+            };
+            let is_valid = !rule.words.is_empty()
+                && !rule.synonyms.is_empty()
+                && !rule.synonyms.iter().any(|synonym| synonym.contains("=>"))
+                && (line.contains("=>") || rule.words.len() > 1);
+            if !is_valid {
+                return Err(format!("line {} is not a synonym rule: {}", i + 1, line));
+            }
+            rules.push(rule);
+        }
+        Ok(Self {
+            rules,
+            ..Self::default()
+        })
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        Self::parse(&fs::read_to_string(path)?).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: {}", path.display(), e),
+            )
+        })
+    }
+}
+
+fn parse_words(words: &str) -> Vec<String> {
+    words
+        .split(',')
+        .map(|word| word.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|word| !word.is_empty())
+        .collect()
+}
+
+// The rules of a synonym dictionary analyzed into terms in the same way as the queries, so a rule
+// matches whichever form of its words the query has. The terms of a synonym keep their positions
+// so a synonym made of more than one word can be looked up as a phrase.
+#[derive(Debug, Default)]
+pub struct SynonymMap {
+    dictionary: SynonymDictionary,
+    synonyms: FxHashMap<Vec<String>, Vec<Vec<Token>>>,
+    // the most terms which the words of a rule have, longer runs of the query are not looked up
+    max_no_of_terms: usize,
+}
+
+impl SynonymMap {
+    pub fn new(dictionary: SynonymDictionary, analyze: impl Fn(&str) -> Vec<Token>) -> Self {
+        let mut synonyms: FxHashMap<Vec<String>, Vec<Vec<Token>>> = FxHashMap::default();
+        let mut max_no_of_terms = 0;
+        for rule in &dictionary.rules {
+            let rule_synonyms: Vec<Vec<Token>> = rule
+                .synonyms
+                .iter()
+                .map(|synonym| analyze(synonym))
+                .filter(|tokens| !tokens.is_empty())
+                .collect();
+            for word in &rule.words {
+                // a word which is only made of stop words can not be part of a query
+                let terms: Vec<String> =
+                    analyze(word).into_iter().map(|token| token.word).collect();
+                if terms.is_empty() {
+                    continue;
+                }
+                max_no_of_terms = max_no_of_terms.max(terms.len());
+                let word_synonyms = synonyms.entry(terms.clone()).or_default();
+                for tokens in &rule_synonyms {
+                    let is_word = tokens.iter().map(|token| &token.word).eq(terms.iter());
+                    let is_present = word_synonyms.iter().any(|word_synonym| {
+                        word_synonym
+                            .iter()
+                            .map(|token| &token.word)
+                            .eq(tokens.iter().map(|token| &token.word))
+                    });
+                    if !is_word && !is_present {
+                        word_synonyms.push(tokens.clone());
+                    }
+                }
+            }
+        }
+        synonyms.retain(|_, word_synonyms| !word_synonyms.is_empty());
+        Self {
+            dictionary,
+            synonyms,
+            max_no_of_terms,
+        }
+    }
+
+    pub fn get_dictionary(&self) -> &SynonymDictionary {
+        &self.dictionary
+    }
+
+    pub fn get_weight(&self) -> f32 {
+        self.dictionary.weight
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.synonyms.is_empty()
+    }
+
+    pub fn get_max_no_of_terms(&self) -> usize {
+        self.max_no_of_terms
+    }
+
+    pub fn get_synonyms(&self, terms: &[String]) -> Option<&[Vec<Token>]> {
+        self.synonyms.get(terms).map(Vec::as_slice)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_synonyms() {
+        let dictionary = SynonymDictionary::parse(
+            "# vehicles\ncar, automobile,auto\n\n  tv , telly =>  television \nusa, united   states\n",
+        )
+        .unwrap();
+        assert_eq!(
+            dictionary.rules,
+            vec![
+                SynonymRule::equivalent(&["car", "automobile", "auto"]),
+                SynonymRule::one_way(&["tv", "telly"], &["television"]),
+                SynonymRule::equivalent(&["usa", "united states"]),
+            ]
+        );
+        assert_eq!(dictionary.weight, DEFAULT_SYNONYM_WEIGHT);
+
+        for text in [
+            "car",
+            "car =>",
+            "=> automobile",
+            "car => auto => automobile",
+            " , ",
+        ] {
+            assert!(SynonymDictionary::parse(text).is_err(), "{}", text);
+        }
+
+        let temp_dir = TempDir::new().unwrap();
+        let synonyms_path = temp_dir.path().join("synonyms.txt");
+        fs::write(&synonyms_path, "car, automobile\nbus\n").unwrap();
+        let e = SynonymDictionary::load(&synonyms_path).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+        assert!(e.to_string().contains("line 2"));
+    }
+
+    #[test]
+    fn test_synonym_map() {
+        let dictionary = SynonymDictionary {
+            rules: vec![
+                SynonymRule::equivalent(&["car", "automobile"]),
+                SynonymRule::one_way(&["United States"], &["usa", "us"]),
+            ],
+            weight: 0.8,
+        };
+        let synonym_map = SynonymMap::new(dictionary, |words| {
+            words
+                .split_whitespace()
+                .enumerate()
+                .map(|(position, word)| Token {
+                    position: position as u32,
+                    word: word.to_lowercase(),
+                })
+                .collect()
+        });
+        let get_synonyms = |terms: &[&str]| -> Vec<Vec<String>> {
+            let terms: Vec<String> = terms.iter().map(|term| term.to_string()).collect();
+            synonym_map
+                .get_synonyms(&terms)
+                .unwrap_or_default()
+                .iter()
+                .map(|tokens| tokens.iter().map(|token| token.word.clone()).collect())
+                .collect()
+        };
+        assert_eq!(get_synonyms(&["car"]), vec![vec!["automobile"]]);
+        assert_eq!(get_synonyms(&["automobile"]), vec![vec!["car"]]);
+        assert_eq!(
+            get_synonyms(&["united", "states"]),
+            vec![vec!["usa"], vec!["us"]]
+        );
+        assert!(get_synonyms(&["usa"]).is_empty());
+        assert_eq!(synonym_map.get_max_no_of_terms(), 2);
+        assert_eq!(synonym_map.get_weight(), 0.8);
+    }
+}
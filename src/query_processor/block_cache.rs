@@ -0,0 +1,77 @@
+use std::{
+    hash::{Hash, Hasher},
+    sync::Mutex,
+};
+
+use rustc_hash::FxHasher;
+use search_engine_cache::CacheType;
+
+const NO_OF_SHARDS: usize = 16;
+
+type Shard<V> = Mutex<CacheType<(u32, u32), V>>;
+
+// The cache of the blocks which is shared by every query running at the same time. The cache is
+// split into shards, each behind its own lock, so that queries reading different blocks rarely
+// wait on each other. Every shard evicts its least frequently used blocks on its own.
+pub struct BlockCache<V> {
+    shards: Box<[Shard<V>]>,
+}
+
+impl<V: Clone> BlockCache<V> {
+    pub fn new(capacity: usize) -> Self {
+        let shard_capacity = capacity.div_ceil(NO_OF_SHARDS);
+        Self {
+            shards: (0..NO_OF_SHARDS)
+                .map(|_| Mutex::new(CacheType::new_lfu(shard_capacity)))
+                .collect(),
+        }
+    }
+
+    fn get_shard(&self, key: &(u32, u32)) -> &Shard<V> {
+        let mut hasher = FxHasher::default();
+        key.hash(&mut hasher);
+        &self.shards[hasher.finish() as usize % NO_OF_SHARDS]
+    }
+
+    // the key is the id of the inverted index and the id of the block in it
+    pub fn get(&self, key: &(u32, u32)) -> Option<V> {
+        self.get_shard(key).lock().unwrap().get(key).cloned()
+    }
+
+    pub fn put(&self, key: (u32, u32), value: V) {
+        self.get_shard(&key).lock().unwrap().put(key, value, 1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, thread};
+
+    use crate::utils::block::Block;
+
+    use super::*;
+
+    #[test]
+    fn test_concurrent_get_and_put() {
+        let block_cache = BlockCache::new(16_000);
+        thread::scope(|scope| {
+            for segment_id in 0..4 {
+                let block_cache = &block_cache;
+                scope.spawn(move || {
+                    for block_id in 0..50 {
+                        block_cache
+                            .put((segment_id, block_id), Arc::new(Block::new(block_id, None)));
+                    }
+                });
+            }
+        });
+
+        for segment_id in 0..4 {
+            for block_id in 0..50 {
+                let block = block_cache.get(&(segment_id, block_id)).unwrap();
+                assert_eq!(block.block_id, block_id);
+            }
+        }
+        assert!(block_cache.get(&(4, 0)).is_none());
+    }
+}
@@ -0,0 +1,249 @@
+use crate::{
+    query_processor::{
+        query_processor::ScoreCombination,
+        synonym_iterator::UnionIterator,
+        term_iterator::{PostingIterator, TermIterator},
+    },
+    scoring::{bm_25::compute_idf, bm_25f::BM25F, scorer::Scorer},
+    utils::field::DocumentLengths,
+};
+
+// The terms a word is indexed as in every field, scored together with BM25F. The term iterators
+// are weighted by their fields only, the weight of the query term multiplies the whole score.
+// The idf is the one of the word, every term iterator keeps the idf of its own term since its max
+// scores were computed with it. A max score of a field bounds the normalized tf of the field, so
+// the bounds of the fields are weighted, added up and saturated like the tfs of a document are.
+#[derive(Debug)]
+pub struct FieldsIterator<'a> {
+    union_iterator: UnionIterator<TermIterator<'a>>,
+    bm25f: BM25F,
+    idf: f32,
+    term_idfs: Vec<f32>,
+    query_weight: f32,
+}
+
+impl<'a> FieldsIterator<'a> {
+    pub fn new(
+        term_iterators: Vec<TermIterator<'a>>,
+        bm25f: BM25F,
+        no_of_docs: u32,
+        idf: f32,
+        query_weight: f32,
+    ) -> Self {
+        let term_idfs = term_iterators
+            .iter()
+            .map(|term_iterator| compute_idf(no_of_docs, term_iterator.term_frequency))
+            .collect();
+        Self {
+            union_iterator: UnionIterator::new(term_iterators, ScoreCombination::Sum),
+            bm25f,
+            idf,
+            term_idfs,
+            query_weight,
+        }
+    }
+
+    // The score of a document with the tf bounds of the fields, a field with a weight of 0 adds
+    // nothing to the tf of any document
+    fn get_score_bound(&self, max_scores: impl Iterator<Item = (usize, f32)>) -> f32 {
+        if self.idf <= 0.0 {
+            return 0.0;
+        }
+        let term_iterators = self.union_iterator.get_iterators();
+        let max_tf: f32 = max_scores
+            .filter(|&(i, _)| term_iterators[i].weight > 0.0)
+            .map(|(i, max_score)| {
+                let weight = term_iterators[i].weight;
+                weight
+                    * self
+                        .bm25f
+                        .get_max_normalized_tf(max_score / weight, self.term_idfs[i])
+            })
+            .sum();
+        self.query_weight * self.idf * self.bm25f.saturate(max_tf)
+    }
+}
+
+impl PostingIterator for FieldsIterator<'_> {
+    fn get_current_doc_id(&self) -> u64 {
+        self.union_iterator.get_current_doc_id()
+    }
+
+    fn next(&mut self) -> bool {
+        self.union_iterator.next()
+    }
+
+    fn advance(&mut self, doc_id: u32) {
+        self.union_iterator.advance(doc_id)
+    }
+
+    fn is_complete(&mut self) -> bool {
+        self.union_iterator.is_complete()
+    }
+
+    // the weighted tfs of the fields which have the term are added up before they are saturated,
+    // the scorer only knows about a single field so it is not used
+    fn get_current_doc_score(
+        &self,
+        document_lengths: &DocumentLengths,
+        _scorer: &dyn Scorer,
+    ) -> f32 {
+        let doc_id = self.get_current_doc_id();
+        let tf: f32 = self
+            .union_iterator
+            .get_iterators()
+            .iter()
+            .filter(|term_iterator| term_iterator.get_current_doc_id() == doc_id)
+            .map(|term_iterator| {
+                term_iterator.weight
+                    * self.bm25f.get_normalized_tf(
+                        term_iterator.get_current_doc_frequency(),
+                        document_lengths.get_length(term_iterator.field, doc_id as u32),
+                        document_lengths.get_average_length(term_iterator.field),
+                    )
+            })
+            .sum();
+        self.query_weight * self.idf * self.bm25f.saturate(tf)
+    }
+
+    fn get_max_score(&self) -> f32 {
+        self.get_score_bound(
+            self.union_iterator
+                .get_iterators()
+                .iter()
+                .map(|term_iterator| term_iterator.get_max_score())
+                .enumerate(),
+        )
+    }
+
+    fn move_block_max_iterator(&mut self, doc_id: u32) {
+        self.union_iterator.move_block_max_iterator(doc_id)
+    }
+
+    // the fields which are complete have no documents left to bound
+    fn get_block_max_score(&mut self) -> f32 {
+        let mut block_max_scores = Vec::new();
+        for (i, term_iterator) in self
+            .union_iterator
+            .get_iterators_mut()
+            .iter_mut()
+            .enumerate()
+        {
+            if !term_iterator.is_complete() {
+                block_max_scores.push((i, term_iterator.get_block_max_score()));
+            }
+        }
+        self.get_score_bound(block_max_scores.into_iter())
+    }
+
+    fn get_block_max_last_doc_id(&mut self) -> u64 {
+        self.union_iterator.get_block_max_last_doc_id()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        compressor::compressor::CompressionAlgorithm,
+        scoring::{
+            bm_25::{BM25, BM25Params},
+            scorer::TermStatistics,
+        },
+        utils::{chunk::Chunk, chunk_block_max_metadata::ChunkBlockMaxMetadata, field::Field},
+    };
+
+    const DOCUMENT_LENGTHS: DocumentLengths = DocumentLengths {
+        body_lengths: &[10, 10, 10, 10, 10, 10, 10, 10],
+        average_body_length: 10.0,
+        title_lengths: &[2, 2, 2, 2, 2, 2, 2, 2],
+        average_title_length: 2.0,
+    };
+
+    // a term iterator with the max score BM25 gives its postings, weighted by the field weight
+    fn create_term_iterator(
+        term: &str,
+        postings: &[(u32, u32)],
+        weight: f32,
+    ) -> TermIterator<'static> {
+        let mut chunk = Chunk::new(0, CompressionAlgorithm::VarByte);
+        for &(doc_id, frequency) in postings {
+            chunk.add_doc_id(doc_id);
+            chunk.add_doc_frequency(frequency);
+            chunk.add_doc_positions((0..frequency).collect());
+        }
+        let encoded = chunk.encode().leak();
+        let mut decoded_chunk = Chunk::new(0, CompressionAlgorithm::VarByte);
+        decoded_chunk.decode(&encoded[4..]);
+        let field = Field::from_term(term);
+        let term_statistics = TermStatistics {
+            no_of_docs: DOCUMENT_LENGTHS.get_no_of_docs(),
+            document_frequency: postings.len() as u32,
+            collection_frequency: postings.len() as u64,
+            average_document_length: DOCUMENT_LENGTHS.get_average_length(field),
+        };
+        let max_score = weight
+            * postings
+                .iter()
+                .map(|&(doc_id, frequency)| {
+                    BM25::default().score(
+                        frequency,
+                        DOCUMENT_LENGTHS.get_length(field, doc_id),
+                        &term_statistics,
+                    )
+                })
+                .fold(0.0, f32::max);
+        let mut term_iterator = TermIterator::new(
+            term.to_string(),
+            0,
+            postings.len() as u32,
+            vec![decoded_chunk],
+            max_score,
+            vec![ChunkBlockMaxMetadata::new(
+                postings.last().unwrap().0,
+                max_score,
+            )],
+        );
+        term_iterator.set_weight(weight);
+        term_iterator.init();
+        term_iterator
+    }
+
+    #[test]
+    fn test_fields_iterator() {
+        let bm25f = BM25F {
+            params: BM25Params::default(),
+        };
+        let idf = compute_idf(8, 2);
+        let mut fields_iterator = FieldsIterator::new(
+            vec![
+                create_term_iterator("title:misery", &[(1, 1)], 2.0),
+                create_term_iterator("misery", &[(1, 1), (2, 5)], 1.0),
+            ],
+            bm25f,
+            8,
+            idf,
+            1.0,
+        );
+        let max_score = fields_iterator.get_max_score();
+        let block_max_score = fields_iterator.get_block_max_score();
+
+        let mut doc_scores = Vec::new();
+        while !fields_iterator.is_complete() {
+            doc_scores.push((
+                fields_iterator.get_current_doc_id(),
+                fields_iterator.get_current_doc_score(&DOCUMENT_LENGTHS, &BM25::default()),
+            ));
+            fields_iterator.next();
+        }
+        assert_eq!(doc_scores.len(), 2);
+        // the title and the body of the first document are saturated together
+        let tf = 2.0 * bm25f.get_normalized_tf(1, 2, 2.0) + bm25f.get_normalized_tf(1, 10, 10.0);
+        assert!((doc_scores[0].1 - idf * bm25f.saturate(tf)).abs() < 1e-4);
+        for &(_, score) in &doc_scores {
+            assert!(score <= max_score + 1e-4);
+            assert!(score <= block_max_score + 1e-4);
+        }
+        assert!(max_score < idf * (bm25f.params.k1 + 1.0));
+    }
+}
@@ -1,4 +1,7 @@
+mod block_cache;
+mod fields_iterator;
 pub mod query_processor;
 pub mod retrieval_algorithms;
+mod synonym_iterator;
 mod term_iterator;
 mod utils;
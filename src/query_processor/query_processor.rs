@@ -1,32 +1,149 @@
 use std::{
+    cell::RefCell,
+    fmt,
     fs::File,
-    io::{self, BufReader},
-    path::PathBuf,
-    u32,
+    io,
+    path::{Path, PathBuf},
+    sync::{
+        Arc, RwLock,
+        atomic::{AtomicU32, Ordering},
+    },
 };
 
-use search_engine_cache::CacheType;
+use memmap2::Mmap;
+use rustc_hash::FxHashMap;
 
 use crate::{
     compressor::compressor::CompressionAlgorithm,
+    parser::query_parser::QueryNode,
     query_processor::{
+        block_cache::BlockCache,
+        fields_iterator::FieldsIterator,
         retrieval_algorithms::{
-            QueryAlgorithm, binary_merge::holistic_binary_merge,
-            block_max_max_score::block_max_max_score, block_max_wand::block_max_wand,
-            max_score::max_score, wand::wand,
+            QueryAlgorithm,
+            binary_merge::holistic_binary_merge,
+            block_max_max_score::block_max_max_score,
+            block_max_wand::block_max_wand,
+            boolean::boolean_retrieval,
+            max_score::max_score,
+            positional::{PositionalConstraint, positional_merge},
+            wand::wand,
         },
-        term_iterator::TermIterator,
+        synonym_iterator::{PhraseIterator, UnionIterator},
+        term_iterator::{PostingIterator, TermIterator},
+    },
+    scoring::{
+        ScoringModel,
+        bm_25::{BM25Params, compute_idf},
+        bm_25f::BM25F,
+        scorer::{Scorer, TermStatistics},
     },
+    segment::segment::Segment,
     utils::{
-        block::Block, in_memory_term_metadata::InMemoryTermMetadata, paths::get_inverted_index_path,
+        bitmap::Bitmap,
+        block::BlockView,
+        chunk::Chunk,
+        chunk_block_max_metadata::ChunkBlockMaxMetadata,
+        chunk_iterator::{BlockChecker, ChunkIterator, ChunkLoader},
+        field::{DEFAULT_TITLE_WEIGHT, DocumentLengths, Field},
+        in_memory_term_metadata::InMemoryTermMetadata,
+        index_format::InvertedIndexFooter,
+        paths::{get_inverted_index_path, get_segment_directory_path},
     },
 };
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScoreCombination {
+    Sum,
+    // a word and its synonyms are scored with the best of them, so a document with several
+    // synonyms of a word does not score more than a document with the word itself
+    Max,
+}
+
+impl ScoreCombination {
+    pub fn combine(&self, scores: impl Iterator<Item = f32>) -> f32 {
+        match self {
+            ScoreCombination::Sum => scores.sum(),
+            ScoreCombination::Max => scores.reduce(f32::max).unwrap_or(0.0),
+        }
+    }
+}
+
+// A term of a ranked query along with the weight its scores are multiplied by. A phrase only
+// matches the documents which have its terms one after the other, the offsets of the terms are
+// their positions relative to the first one and the weight is the weight of every term. A group
+// scores a document with the sum or the best of the scores of its members. The fields are the
+// terms a word is indexed as in every field, BM25F scores them together while the other models
+// add up the scores of the fields.
+#[derive(Debug, Clone, PartialEq)]
+pub enum QueryTerm {
+    Term(String, f32),
+    Phrase(Vec<(String, u32)>, f32),
+    Group(Vec<QueryTerm>, ScoreCombination),
+    Fields(Vec<String>, f32),
+}
+
+impl QueryTerm {
+    // the terms which are looked up for the query term, each of them once
+    fn collect_terms(&self, terms: &mut Vec<String>) {
+        match self {
+            QueryTerm::Term(term, _) => {
+                if !terms.contains(term) {
+                    terms.push(term.clone());
+                }
+            }
+            QueryTerm::Phrase(phrase_terms, _) => {
+                for (term, _) in phrase_terms {
+                    if !terms.contains(term) {
+                        terms.push(term.clone());
+                    }
+                }
+            }
+            QueryTerm::Group(members, _) => {
+                for member in members {
+                    member.collect_terms(terms);
+                }
+            }
+            QueryTerm::Fields(field_terms, _) => {
+                for term in field_terms {
+                    if !terms.contains(term) {
+                        terms.push(term.clone());
+                    }
+                }
+            }
+        }
+    }
+}
+
+// the no of blocks which are remembered as checked, which covers an index of 6.4 GB
+const DEFAULT_CHECKED_BLOCKS_CAPACITY: usize = 100_000;
+
+// A query is answered by every segment of the index on its own, the results of the segments are
+// then merged. Since the segments hold disjoint ranges of doc ids no document is returned twice.
+// The query processor is shared by the queries running on different threads. The inverted index
+// files are memory mapped and the chunks borrow their bytes straight from the mapping, so a query
+// never copies a block. The blocks are checked against their checksums the first time a query
+// decodes them, the block cache remembers the blocks which were checked already.
 pub struct QueryProcessor {
-    block_cache: CacheType<u32, Block>,
-    inverted_index_file: File,
+    inverted_indexes: RwLock<FxHashMap<u32, Arc<MappedInvertedIndex>>>,
+    checked_blocks: BlockCache<()>,
+    next_mapping_id: AtomicU32,
+    index_directory_path: PathBuf,
     compression_algorithm: CompressionAlgorithm,
     query_algorithm: QueryAlgorithm,
+    scoring_model: ScoringModel,
+    bm25_params: BM25Params,
+    scorer: Box<dyn Scorer>,
+    title_weight: f32,
+}
+
+// The mapping of the inverted index of a segment along with its footer, which is None for an index
+// saved before it had checksums. Every mapping has an id of its own, so a segment which is mapped
+// again after a rebuild does not share the checked blocks of its old files.
+pub struct MappedInvertedIndex {
+    mapping_id: u32,
+    mmap: Mmap,
+    footer: Option<InvertedIndexFooter>,
 }
 
 impl QueryProcessor {
@@ -34,84 +151,707 @@ impl QueryProcessor {
         index_directory_path: PathBuf,
         compression_algorithm: CompressionAlgorithm,
         query_algorithm: QueryAlgorithm,
+        scoring_model: ScoringModel,
+        bm25_params: BM25Params,
     ) -> io::Result<Self> {
-        let inverted_index_path = get_inverted_index_path(index_directory_path.clone());
-        let inverted_index_file = File::open(inverted_index_path)?;
-
         Ok(Self {
-            block_cache: CacheType::new_lfu(1000),
-            inverted_index_file,
+            inverted_indexes: RwLock::new(FxHashMap::default()),
+            checked_blocks: BlockCache::new(DEFAULT_CHECKED_BLOCKS_CAPACITY),
+            next_mapping_id: AtomicU32::new(0),
+            index_directory_path,
             compression_algorithm,
             query_algorithm,
+            scoring_model,
+            bm25_params,
+            scorer: scoring_model.get_scorer(bm25_params),
+            title_weight: DEFAULT_TITLE_WEIGHT,
         })
     }
 
-    pub fn process_query(
-        &mut self,
-        query_terms: Vec<String>,
-        query_metadata: Vec<InMemoryTermMetadata>,
-        document_lengths: &Box<[u32]>,
-        average_document_length: f32,
-    ) -> Vec<(u32, f32)> {
-        let mut term_iterators: Vec<TermIterator> = Vec::with_capacity(query_terms.len());
-        let mut reader: BufReader<&mut File> = BufReader::new(&mut self.inverted_index_file);
+    // The inverted index files of the segments are mapped when they are first queried, a damaged
+    // footer is reported by the query
+    pub fn open_segment(&self, segment_id: u32) -> io::Result<Arc<MappedInvertedIndex>> {
+        if let Some(inverted_index) = self.inverted_indexes.read().unwrap().get(&segment_id) {
+            return Ok(inverted_index.clone());
+        }
+        let segment_directory_path =
+            get_segment_directory_path(&self.index_directory_path, segment_id);
+        let mapping_id = self.next_mapping_id.fetch_add(1, Ordering::Relaxed);
+        let inverted_index = Arc::new(map_inverted_index(
+            &get_inverted_index_path(segment_directory_path),
+            mapping_id,
+        )?);
+        // another query may have mapped the segment in the meantime
+        Ok(self
+            .inverted_indexes
+            .write()
+            .unwrap()
+            .entry(segment_id)
+            .or_insert(inverted_index)
+            .clone())
+    }
+
+    pub fn set_scorer(&mut self, scoring_model: ScoringModel, bm25_params: BM25Params) {
+        self.scoring_model = scoring_model;
+        self.bm25_params = bm25_params;
+        self.scorer = scoring_model.get_scorer(bm25_params);
+    }
+
+    // The scores of the title terms are multiplied by the title weight when they are added to the
+    // scores of the body terms. The max scores are weighted in the same way when the term iterators
+    // are created, so the weight can be changed without rebuilding the index.
+    pub fn set_title_weight(&mut self, title_weight: f32) {
+        self.title_weight = title_weight;
+    }
+
+    fn get_field_weight(&self, field: Field) -> f32 {
+        match field {
+            Field::Body => 1.0,
+            Field::Title => self.title_weight,
+        }
+    }
+
+    // The mapping stays alive until the queries which are still reading the segment are done
+    pub fn close_segment(&self, segment_id: u32) {
+        self.inverted_indexes.write().unwrap().remove(&segment_id);
+    }
+
+    // Every segment scores its documents with the number of documents containing the term and
+    // the number of its occurrences across all the segments, otherwise the scores of different
+    // segments could not be compared. The average length is the one of the field of the term.
+    fn get_term_statistics(
+        query_terms: &[String],
+        segments: &[Segment],
+        document_lengths: &DocumentLengths,
+    ) -> Vec<TermStatistics> {
+        query_terms
+            .iter()
+            .map(|query_term| {
+                let mut term_statistics = TermStatistics {
+                    no_of_docs: document_lengths.get_no_of_docs(),
+                    document_frequency: 0,
+                    collection_frequency: 0,
+                    average_document_length: document_lengths
+                        .get_average_length(Field::from_term(query_term)),
+                };
+                for term_metadata in segments.iter().filter_map(|segment| {
+                    segment
+                        .in_memory_index_metadata
+                        .get_term_metadata(query_term)
+                }) {
+                    term_statistics.document_frequency += term_metadata.term_frequency;
+                    term_statistics.collection_frequency += term_metadata.collection_frequency;
+                }
+                term_statistics
+            })
+            .collect()
+    }
+
+    // Creates a term iterator over the chunks of the term in the segment. The term statistics are
+    // the ones across all the segments.
+    // The chunks of a block are only checked and decoded once the iterator reaches them, unless the
+    // index was written without the no of chunks in each block, in which case all of them are
+    // checked and decoded up front.
+    fn create_term_iterator<'a>(
+        &self,
+        segment_blocks: &'a SegmentBlocks<'a>,
+        query_term: String,
+        query_metadata: &InMemoryTermMetadata,
+        term_statistics: &TermStatistics,
+        rescale_max_score: impl Fn(f32) -> f32,
+    ) -> io::Result<TermIterator<'a>> {
+        let original_chunk_block_max_metadata = query_metadata.chunk_block_max_metadata.unwrap();
+        let chunk_block_max_metadata = original_chunk_block_max_metadata
+            .iter()
+            .map(|chunk_metadata| {
+                ChunkBlockMaxMetadata::new(
+                    chunk_metadata.chunk_last_doc_id,
+                    rescale_max_score(chunk_metadata.chunk_max_term_score),
+                )
+            })
+            .collect();
+        let max_score = rescale_max_score(query_metadata.max_score);
+
+        let mut term_iterator = match query_metadata.block_chunk_counts {
+            Some(block_chunk_counts) => {
+                let blocks = query_metadata
+                    .block_ids
+                    .iter()
+                    .map(|&block_id| BlockView::new(&segment_blocks.inverted_index.mmap, block_id))
+                    .collect::<io::Result<Vec<_>>>()?;
+                let chunk_loader = ChunkLoader::new(
+                    blocks,
+                    query_metadata.block_ids.to_vec(),
+                    segment_blocks,
+                    query_metadata.term_id,
+                    block_chunk_counts,
+                    self.compression_algorithm.clone(),
+                );
+                TermIterator::from_chunk_iterator(
+                    query_term,
+                    query_metadata.term_id,
+                    term_statistics.document_frequency,
+                    ChunkIterator::new_lazy(
+                        chunk_loader,
+                        original_chunk_block_max_metadata,
+                        query_metadata.term_frequency,
+                    ),
+                    max_score,
+                    chunk_block_max_metadata,
+                )
+            }
+            None => TermIterator::new(
+                query_term,
+                query_metadata.term_id,
+                term_statistics.document_frequency,
+                self.decode_term_chunks(
+                    &query_metadata
+                        .block_ids
+                        .iter()
+                        .map(|&block_id| segment_blocks.read_block(block_id))
+                        .collect::<io::Result<Vec<_>>>()?,
+                    query_metadata,
+                ),
+                max_score,
+                chunk_block_max_metadata,
+            ),
+        };
+        term_iterator.set_collection_frequency(term_statistics.collection_frequency);
+        term_iterator.init();
+        Ok(term_iterator)
+    }
+
+    fn decode_term_chunks<'a>(
+        &self,
+        blocks: &[BlockView<'a>],
+        query_metadata: &InMemoryTermMetadata,
+    ) -> Vec<Chunk<'a>> {
+        let mut chunks = Vec::new();
+        for block in blocks {
+            let term_index = block.check_if_term_exists(query_metadata.term_id);
+            if term_index == -1 {
+                continue;
+            }
+            chunks.extend(block.decode_chunks_for_term(
+                query_metadata.term_id,
+                term_index as usize,
+                self.compression_algorithm.clone(),
+            ));
+        }
+        chunks
+    }
 
-        for i in 0..query_metadata.len() {
-            let mut chunks = Vec::new();
-            for block_id in query_metadata[i].block_ids {
-                if let Some(block) = self.block_cache.get(block_id) {
-                    let term_index = block.check_if_term_exists(query_metadata[i].term_id);
+    // Creates the term iterator of a query term if it is present in the segment. The max scores are
+    // weighted by the field of the term and by the weight of the query term, so the upper bounds of
+    // WAND and MaxScore still hold for the weighted scores.
+    fn create_weighted_term_iterator<'a>(
+        &self,
+        segment_blocks: &'a SegmentBlocks<'a>,
+        segment: &Segment,
+        query_term: &str,
+        query_weight: f32,
+        term_statistics: &TermStatistics,
+    ) -> io::Result<Option<TermIterator<'a>>> {
+        let Some(term_metadata) = segment
+            .in_memory_index_metadata
+            .get_term_metadata(query_term)
+        else {
+            return Ok(None);
+        };
+        // the max scores were computed with the statistics of the segment when it was written
+        let segment_term_statistics = TermStatistics {
+            no_of_docs: segment.collection_no_of_docs,
+            document_frequency: term_metadata.term_frequency,
+            collection_frequency: term_metadata.collection_frequency,
+            average_document_length: segment.get_average_length(Field::from_term(query_term)),
+        };
+        let weight = self.get_field_weight(Field::from_term(query_term)) * query_weight;
+        let mut term_iterator = self.create_term_iterator(
+            segment_blocks,
+            query_term.to_string(),
+            &term_metadata,
+            term_statistics,
+            |max_score| {
+                weight
+                    * self.scorer.rescale_max_score(
+                        max_score,
+                        &segment_term_statistics,
+                        term_statistics,
+                    )
+            },
+        )?;
+        term_iterator.set_weight(weight);
+        Ok(Some(term_iterator))
+    }
 
-                    if term_index == -1 {
-                        continue;
+    // Creates the term iterators of the query terms which are present in the segment, along with
+    // the index of their query term.
+    fn create_segment_term_iterators<'a>(
+        &self,
+        segment_blocks: &'a SegmentBlocks<'a>,
+        segment: &Segment,
+        query_terms: &[String],
+        term_statistics: &[TermStatistics],
+    ) -> io::Result<(Vec<TermIterator<'a>>, Vec<usize>)> {
+        let mut term_iterators = Vec::with_capacity(query_terms.len());
+        let mut term_indexes = Vec::with_capacity(query_terms.len());
+        for (i, query_term) in query_terms.iter().enumerate() {
+            if let Some(term_iterator) = self.create_weighted_term_iterator(
+                segment_blocks,
+                segment,
+                query_term,
+                1.0,
+                &term_statistics[i],
+            )? {
+                term_iterators.push(term_iterator);
+                term_indexes.push(i);
+            }
+        }
+        Ok((term_iterators, term_indexes))
+    }
+
+    // Creates the iterator of a term, a phrase or a group of the query. A phrase which is missing
+    // any of its terms in the segment has no documents in it, and a group only keeps the members
+    // which have documents in the segment.
+    fn create_posting_iterator<'a>(
+        &self,
+        segment_blocks: &'a SegmentBlocks<'a>,
+        segment: &Segment,
+        query_term: &QueryTerm,
+        term_statistics: &FxHashMap<String, TermStatistics>,
+    ) -> io::Result<Option<Box<dyn PostingIterator + 'a>>> {
+        Ok(match query_term {
+            QueryTerm::Term(term, weight) => self
+                .create_weighted_term_iterator(
+                    segment_blocks,
+                    segment,
+                    term,
+                    *weight,
+                    &term_statistics[term],
+                )?
+                .map(|term_iterator| Box::new(term_iterator) as Box<dyn PostingIterator>),
+            QueryTerm::Phrase(terms, weight) => {
+                let mut term_iterators = Vec::with_capacity(terms.len());
+                for (term, _) in terms {
+                    match self.create_weighted_term_iterator(
+                        segment_blocks,
+                        segment,
+                        term,
+                        *weight,
+                        &term_statistics[term],
+                    )? {
+                        Some(term_iterator) => term_iterators.push(term_iterator),
+                        None => return Ok(None),
                     }
-                    chunks.extend(block.decode_chunks_for_term(
-                        query_metadata[i].term_id,
-                        term_index as usize,
-                        self.compression_algorithm.clone(),
-                    ));
+                }
+                let offsets = terms.iter().map(|&(_, offset)| offset).collect();
+                Some(Box::new(PhraseIterator::new(term_iterators, offsets)))
+            }
+            QueryTerm::Group(members, score_combination) => {
+                let mut iterators = Vec::with_capacity(members.len());
+                for member in members {
+                    if let Some(iterator) = self.create_posting_iterator(
+                        segment_blocks,
+                        segment,
+                        member,
+                        term_statistics,
+                    )? {
+                        iterators.push(iterator);
+                    }
+                }
+                if iterators.len() > 1 {
+                    Some(Box::new(UnionIterator::new(iterators, *score_combination)))
                 } else {
-                    let mut new_block = Block::new(*block_id, None);
-                    new_block.decode(&mut reader).unwrap();
-                    let term_index = new_block.check_if_term_exists(query_metadata[i].term_id);
+                    iterators.pop()
+                }
+            }
+            QueryTerm::Fields(terms, weight) if self.scoring_model == ScoringModel::BM25F => {
+                // the term iterators are only weighted by their fields
+                let mut term_iterators = Vec::with_capacity(terms.len());
+                for term in terms {
+                    term_iterators.extend(self.create_weighted_term_iterator(
+                        segment_blocks,
+                        segment,
+                        term,
+                        1.0,
+                        &term_statistics[term],
+                    )?);
+                }
+                if term_iterators.is_empty() {
+                    return Ok(None);
+                }
+                // a word is in at least as many documents as any of its fields, the idf of the word
+                // is the same in every segment
+                let no_of_docs = term_statistics[&terms[0]].no_of_docs;
+                let document_frequency = terms
+                    .iter()
+                    .map(|term| term_statistics[term].document_frequency)
+                    .max()
+                    .unwrap_or(0);
+                Some(Box::new(FieldsIterator::new(
+                    term_iterators,
+                    BM25F {
+                        params: self.bm25_params,
+                    },
+                    no_of_docs,
+                    compute_idf(no_of_docs, document_frequency),
+                    *weight,
+                )))
+            }
+            QueryTerm::Fields(terms, weight) => self.create_posting_iterator(
+                segment_blocks,
+                segment,
+                &QueryTerm::Group(
+                    terms
+                        .iter()
+                        .map(|term| QueryTerm::Term(term.clone(), *weight))
+                        .collect(),
+                    ScoreCombination::Sum,
+                ),
+                term_statistics,
+            )?,
+        })
+    }
 
-                    if term_index == -1 {
-                        continue;
-                    }
+    fn rank<I: PostingIterator>(
+        &self,
+        iterators: Vec<I>,
+        document_lengths: &DocumentLengths,
+        deleted_documents: &Bitmap,
+        top_k: usize,
+    ) -> Vec<(u32, f32)> {
+        let scorer = self.scorer.as_ref();
+        match self.query_algorithm {
+            QueryAlgorithm::BlockMaxMaxScore => block_max_max_score(
+                iterators,
+                document_lengths,
+                scorer,
+                deleted_documents,
+                top_k,
+            ),
+            QueryAlgorithm::BlockMaxWand => block_max_wand(
+                iterators,
+                document_lengths,
+                scorer,
+                deleted_documents,
+                top_k,
+            ),
+            QueryAlgorithm::MaxScore => max_score(
+                iterators,
+                document_lengths,
+                scorer,
+                deleted_documents,
+                top_k,
+            ),
+            // the boolean queries are answered by process_boolean_query, a ranked query only gets
+            // here with the boolean algorithm when it has groups which a conjunction can not take
+            QueryAlgorithm::Wand | QueryAlgorithm::Boolean => wand(
+                iterators,
+                document_lengths,
+                scorer,
+                deleted_documents,
+                top_k,
+            ),
+        }
+    }
 
-                    chunks.extend(new_block.decode_chunks_for_term(
-                        query_metadata[i].term_id,
-                        term_index as usize,
-                        self.compression_algorithm.clone(),
+    // Every segment keeps its own top k + offset documents, the results of all the segments are
+    // then merged and the first offset documents are skipped so that the results can be paged through.
+    // Every query term has a weight which its scores are multiplied by, such as the weight of a
+    // synonym. The queries with only plain terms keep their term iterators unboxed, the fields of
+    // a word are plain terms as well unless they are scored with BM25F.
+    pub fn process_query(
+        &self,
+        query_terms: &[QueryTerm],
+        segments: &[Segment],
+        document_lengths: &DocumentLengths,
+        deleted_documents: &Bitmap,
+        k: usize,
+        offset: usize,
+    ) -> io::Result<Vec<(u32, f32)>> {
+        let top_k = get_top_k(k, offset, document_lengths, deleted_documents);
+        if top_k == 0 || query_terms.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut leaf_terms = Vec::new();
+        for query_term in query_terms {
+            query_term.collect_terms(&mut leaf_terms);
+        }
+        let term_statistics: FxHashMap<String, TermStatistics> = leaf_terms
+            .iter()
+            .cloned()
+            .zip(Self::get_term_statistics(
+                &leaf_terms,
+                segments,
+                document_lengths,
+            ))
+            .collect();
+        let has_groups = query_terms.iter().any(|query_term| match query_term {
+            QueryTerm::Term(..) => false,
+            QueryTerm::Fields(..) => self.scoring_model == ScoringModel::BM25F,
+            _ => true,
+        });
+        let mut result_docs = Vec::new();
+        for segment in segments {
+            if segment.no_of_docs == 0 {
+                continue;
+            }
+            let inverted_index = self.open_segment(segment.segment_id)?;
+            let segment_blocks = SegmentBlocks::new(self, &inverted_index);
+            if has_groups {
+                let mut iterators = Vec::with_capacity(query_terms.len());
+                for query_term in query_terms {
+                    iterators.extend(self.create_posting_iterator(
+                        &segment_blocks,
+                        segment,
+                        query_term,
+                        &term_statistics,
+                    )?);
+                }
+                if !iterators.is_empty() {
+                    result_docs.extend(self.rank(
+                        iterators,
+                        document_lengths,
+                        deleted_documents,
+                        top_k,
                     ));
-                    self.block_cache.put(*block_id, new_block, 1);
                 }
+                segment_blocks.check()?;
+                continue;
+            }
+            let mut term_iterators = Vec::with_capacity(query_terms.len());
+            for query_term in query_terms {
+                let (terms, weight) = match query_term {
+                    QueryTerm::Term(term, weight) => (std::slice::from_ref(term), *weight),
+                    QueryTerm::Fields(terms, weight) => (terms.as_slice(), *weight),
+                    _ => continue,
+                };
+                for term in terms {
+                    term_iterators.extend(self.create_weighted_term_iterator(
+                        &segment_blocks,
+                        segment,
+                        term,
+                        weight,
+                        &term_statistics[term],
+                    )?);
+                }
+            }
+            if term_iterators.is_empty() {
+                continue;
             }
+            result_docs.extend(if self.query_algorithm == QueryAlgorithm::Boolean {
+                holistic_binary_merge(term_iterators, deleted_documents)
+            } else {
+                self.rank(term_iterators, document_lengths, deleted_documents, top_k)
+            });
+            segment_blocks.check()?;
+        }
+        Ok(merge_segment_results(result_docs, k, offset))
+    }
 
-            term_iterators.push(TermIterator::new(
-                query_terms[i].clone(),
-                query_metadata[i].term_id,
-                query_metadata[i].term_frequency,
-                chunks,
-                query_metadata[i].max_score,
-                query_metadata[i].chunk_block_max_metadata.unwrap().to_vec(),
-            ));
+    // Phrase and proximity queries are always evaluated through a positional merge
+    // irrespective of the query algorithm, since the ranked algorithms cannot check positions.
+    // The constraints refer to the query terms by their index, a segment which is missing any of
+    // the terms of a constraint cannot have a match.
+    #[allow(
+        clippy::too_many_arguments,
+        reason = "the constraints come on top of the arguments of process_query"
+    )]
+    pub fn process_positional_query(
+        &self,
+        query_terms: &[String],
+        constraints: &[PositionalConstraint],
+        segments: &[Segment],
+        document_lengths: &DocumentLengths,
+        deleted_documents: &Bitmap,
+        k: usize,
+        offset: usize,
+    ) -> io::Result<Vec<(u32, f32)>> {
+        let top_k = get_top_k(k, offset, document_lengths, deleted_documents);
+        if top_k == 0 {
+            return Ok(Vec::new());
         }
-        for term_iterator in &mut term_iterators {
-            term_iterator.init();
+        let term_statistics = Self::get_term_statistics(query_terms, segments, document_lengths);
+        let mut result_docs = Vec::new();
+        'segments: for segment in segments {
+            if segment.no_of_docs == 0 {
+                continue;
+            }
+            let inverted_index = self.open_segment(segment.segment_id)?;
+            let segment_blocks = SegmentBlocks::new(self, &inverted_index);
+            let (term_iterators, term_indexes) = self.create_segment_term_iterators(
+                &segment_blocks,
+                segment,
+                query_terms,
+                &term_statistics,
+            )?;
+
+            let mut segment_constraints = Vec::with_capacity(constraints.len());
+            for constraint in constraints {
+                let mut segment_constraint = constraint.clone();
+                for term_index in segment_constraint.term_indexes.iter_mut() {
+                    match term_indexes.iter().position(|i| i == term_index) {
+                        Some(iterator_index) => *term_index = iterator_index,
+                        None => continue 'segments,
+                    }
+                }
+                segment_constraints.push(segment_constraint);
+            }
+
+            result_docs.extend(positional_merge(
+                term_iterators,
+                &segment_constraints,
+                document_lengths,
+                self.scorer.as_ref(),
+                deleted_documents,
+                top_k,
+            ));
+            segment_blocks.check()?;
         }
-        match self.query_algorithm {
-            QueryAlgorithm::BlockMaxMaxScore => {
-                block_max_max_score(term_iterators, document_lengths, average_document_length)
+        Ok(merge_segment_results(result_docs, k, offset))
+    }
+
+    // The boolean query is evaluated over the term iterators of all the terms in the query which
+    // are present in a segment, the doc ids of the segment are needed to evaluate NOT.
+    // The results are unranked so they are paged through in the order of their doc ids.
+    #[allow(
+        clippy::too_many_arguments,
+        reason = "the query tree comes on top of the arguments of process_query"
+    )]
+    pub fn process_boolean_query(
+        &self,
+        query: &QueryNode,
+        query_terms: &[String],
+        segments: &[Segment],
+        document_lengths: &DocumentLengths,
+        deleted_documents: &Bitmap,
+        k: usize,
+        offset: usize,
+    ) -> io::Result<Vec<(u32, f32)>> {
+        let term_statistics = Self::get_term_statistics(query_terms, segments, document_lengths);
+        let mut result_docs = Vec::new();
+        for segment in segments {
+            if segment.no_of_docs == 0 {
+                continue;
             }
-            QueryAlgorithm::BlockMaxWand => {
-                block_max_wand(term_iterators, document_lengths, average_document_length)
+            let inverted_index = self.open_segment(segment.segment_id)?;
+            let segment_blocks = SegmentBlocks::new(self, &inverted_index);
+            let (term_iterators, _) = self.create_segment_term_iterators(
+                &segment_blocks,
+                segment,
+                query_terms,
+                &term_statistics,
+            )?;
+            result_docs.extend(boolean_retrieval(
+                query,
+                term_iterators,
+                segment.get_doc_id_range(),
+                deleted_documents,
+            ));
+            segment_blocks.check()?;
+        }
+        result_docs.sort_unstable_by_key(|&(doc_id, _)| doc_id);
+        Ok(result_docs.into_iter().skip(offset).take(k).collect())
+    }
+}
+
+// The blocks of the inverted index of a segment which a query reads. The blocks with a footer are
+// checked against their checksums unless they were checked already, a damaged block is kept until
+// the query is done with the segment and then becomes the error of the query.
+struct SegmentBlocks<'a> {
+    checked_blocks: &'a BlockCache<()>,
+    inverted_index: &'a MappedInvertedIndex,
+    damaged_block: RefCell<Option<io::Error>>,
+}
+
+impl<'a> SegmentBlocks<'a> {
+    fn new(query_processor: &'a QueryProcessor, inverted_index: &'a MappedInvertedIndex) -> Self {
+        Self {
+            checked_blocks: &query_processor.checked_blocks,
+            inverted_index,
+            damaged_block: RefCell::new(None),
+        }
+    }
+
+    fn read_block(&self, block_id: u32) -> io::Result<BlockView<'a>> {
+        let key = (self.inverted_index.mapping_id, block_id);
+        match &self.inverted_index.footer {
+            Some(footer) if self.checked_blocks.get(&key).is_none() => {
+                let block = BlockView::new_checked(&self.inverted_index.mmap, block_id, footer)?;
+                self.checked_blocks.put(key, ());
+                Ok(block)
             }
-            QueryAlgorithm::MaxScore => {
-                max_score(term_iterators, document_lengths, average_document_length)
+            _ => BlockView::new(&self.inverted_index.mmap, block_id),
+        }
+    }
+
+    fn check(&self) -> io::Result<()> {
+        match self.damaged_block.take() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+}
+
+impl BlockChecker for SegmentBlocks<'_> {
+    fn check_block(&self, block_id: u32) -> bool {
+        match self.read_block(block_id) {
+            Ok(_) => true,
+            Err(e) => {
+                self.damaged_block.borrow_mut().get_or_insert(e);
+                false
             }
-            QueryAlgorithm::Wand => wand(term_iterators, document_lengths, average_document_length),
-            QueryAlgorithm::Boolean => holistic_binary_merge(term_iterators),
         }
     }
 }
+
+impl fmt::Debug for SegmentBlocks<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SegmentBlocks")
+            .field("mapping_id", &self.inverted_index.mapping_id)
+            .field("damaged_block", &self.damaged_block)
+            .finish_non_exhaustive()
+    }
+}
+
+// The files of a segment are never written again once the segment is complete, a rebuild of the
+// base segment happens with a mutable reference to the search engine so no query can be reading
+// it, and the query processor is recreated afterwards.
+fn map_inverted_index(
+    inverted_index_path: &Path,
+    mapping_id: u32,
+) -> io::Result<MappedInvertedIndex> {
+    let file = File::open(inverted_index_path)?;
+    // SAFETY: see above, the mapped file is not modified while it is mapped
+    let mmap = unsafe { Mmap::map(&file)? };
+    Ok(MappedInvertedIndex {
+        mapping_id,
+        mmap,
+        footer: InvertedIndexFooter::read_from_file(inverted_index_path)?,
+    })
+}
+
+// The no of documents every segment keeps, k and offset come from the caller so they can be as
+// large as usize allows, but there are never more results than live documents
+fn get_top_k(
+    k: usize,
+    offset: usize,
+    document_lengths: &DocumentLengths,
+    deleted_documents: &Bitmap,
+) -> usize {
+    let no_of_live_docs = document_lengths
+        .get_no_of_docs()
+        .saturating_sub(deleted_documents.len());
+    k.saturating_add(offset).min(no_of_live_docs as usize)
+}
+
+// The results of every segment are already sorted, the sort is stable so documents
+// with the same score keep their order.
+fn merge_segment_results(
+    mut result_docs: Vec<(u32, f32)>,
+    k: usize,
+    offset: usize,
+) -> Vec<(u32, f32)> {
+    result_docs.sort_by(|a, b| b.1.total_cmp(&a.1));
+    result_docs.into_iter().skip(offset).take(k).collect()
+}
@@ -1,4 +1,4 @@
-use crate::query_processor::term_iterator::TermIterator;
+use crate::{query_processor::term_iterator::TermIterator, utils::bitmap::Bitmap};
 
 // these are both conjunctive algorithms
 
@@ -23,8 +23,11 @@ use crate::query_processor::term_iterator::TermIterator;
 // }
 
 // supposedly faster
-pub fn holistic_binary_merge(mut term_iterators: Vec<TermIterator>) -> Vec<(u32, f32)> {
-    term_iterators.sort_by(|a, b| a.get_no_of_postings().cmp(&b.get_no_of_postings()));
+pub fn holistic_binary_merge(
+    mut term_iterators: Vec<TermIterator>,
+    deleted_documents: &Bitmap,
+) -> Vec<(u32, f32)> {
+    term_iterators.sort_by_key(|a| a.get_no_of_postings());
     let mut current = term_iterators[0].get_current_doc_id();
     let mut i = 1;
     let mut doc_ids = Vec::new();
@@ -47,7 +50,9 @@ pub fn holistic_binary_merge(mut term_iterators: Vec<TermIterator>) -> Vec<(u32,
         }
 
         if i == term_iterators.len() {
-            doc_ids.push((current as u32, 0.0));
+            if !deleted_documents.contains(current as u32) {
+                doc_ids.push((current as u32, 0.0));
+            }
             term_iterators[0].next();
             current = term_iterators[0].get_current_doc_id();
             i = 1;
@@ -1,17 +1,20 @@
-use std::{cmp::Reverse, collections::BinaryHeap, u32};
+use std::{cmp::Reverse, collections::BinaryHeap};
 
 use crate::{
     query_processor::{
-        retrieval_algorithms::utils::{DocData, FloatDoc},
-        term_iterator::TermIterator,
+        retrieval_algorithms::utils::{DocData, FloatDoc, get_top_k_docs},
+        term_iterator::PostingIterator,
     },
-    scoring::bm_25::BM25Params,
+    scoring::scorer::Scorer,
+    utils::{bitmap::Bitmap, field::DocumentLengths},
 };
 
-pub fn block_max_max_score(
-    mut term_iterators: Vec<TermIterator>,
-    doc_lengths: &Box<[u32]>,
-    average_doc_length: f32,
+pub fn block_max_max_score<I: PostingIterator>(
+    mut term_iterators: Vec<I>,
+    document_lengths: &DocumentLengths,
+    scorer: &dyn Scorer,
+    deleted_documents: &Bitmap,
+    k: usize,
 ) -> Vec<(u32, f32)> {
     term_iterators.sort_by(|a, b| a.get_max_score().total_cmp(&b.get_max_score()));
     let n = term_iterators.len();
@@ -22,32 +25,29 @@ pub fn block_max_max_score(
     }
     let mut pivot = 0;
     let mut threshold = 0.0;
-    let max_size = 20;
-    let mut pq: BinaryHeap<Reverse<FloatDoc>> = BinaryHeap::with_capacity(max_size);
+    let mut pq: BinaryHeap<Reverse<FloatDoc>> = BinaryHeap::new();
     let mut current = u64::MAX;
     for term_iterator in &term_iterators {
         current = current.min(term_iterator.get_current_doc_id());
     }
-    let params = BM25Params::default();
 
     while pivot < n && current != u64::MAX {
         let mut score = 0.0;
         let mut next = u64::MAX;
 
-        for i in pivot..n {
-            if term_iterators[i].get_current_doc_id() == current {
-                score += term_iterators[i].get_current_doc_score(
-                    &doc_lengths[current as usize - 1],
-                    average_doc_length,
-                    &params,
-                    doc_lengths.len() as u32,
-                );
-                term_iterators[i].next();
+        for term_iterator in &mut term_iterators[pivot..n] {
+            if term_iterator.get_current_doc_id() == current {
+                score += term_iterator.get_current_doc_score(document_lengths, scorer);
+                term_iterator.next();
             }
-            if term_iterators[i].get_current_doc_id() < next {
-                next = term_iterators[i].get_current_doc_id();
+            if term_iterator.get_current_doc_id() < next {
+                next = term_iterator.get_current_doc_id();
             }
         }
+        if deleted_documents.contains(current as u32) {
+            current = next;
+            continue;
+        }
         if score + ub[pivot] > threshold {
             let mut bub = vec![0.0; term_iterators.len()];
             term_iterators[0].move_block_max_iterator(current as u32);
@@ -62,16 +62,11 @@ pub fn block_max_max_score(
                 }
                 term_iterators[i].advance(current as u32);
                 if term_iterators[i].get_current_doc_id() == current {
-                    score += term_iterators[i].get_current_doc_score(
-                        &doc_lengths[current as usize - 1],
-                        average_doc_length,
-                        &params,
-                        doc_lengths.len() as u32,
-                    );
+                    score += term_iterators[i].get_current_doc_score(document_lengths, scorer);
                 }
             }
 
-            let does_length_exceed = pq.len() >= max_size;
+            let does_length_exceed = pq.len() >= k;
             if does_length_exceed {
                 let does_score_exceed = score > pq.peek().unwrap().0.0.score;
                 if does_score_exceed {
@@ -95,11 +90,5 @@ pub fn block_max_max_score(
         current = next;
     }
 
-    let mut doc_ids = Vec::with_capacity(max_size);
-    while !pq.is_empty() {
-        if let Some(doc) = pq.pop() {
-            doc_ids.push((doc.0.0.docid, doc.0.0.score));
-        }
-    }
-    doc_ids
+    get_top_k_docs(pq)
 }
@@ -1,23 +1,24 @@
 use std::cmp::Reverse;
 use std::collections::BinaryHeap;
-use std::u32;
 
 use crate::query_processor::retrieval_algorithms::utils::{
-    DocData, FloatDoc, sort_by_doc_id, swap_down,
+    DocData, FloatDoc, get_top_k_docs, sort_by_doc_id, swap_down,
 };
-use crate::query_processor::term_iterator::TermIterator;
-use crate::scoring::bm_25::BM25Params;
+use crate::query_processor::term_iterator::PostingIterator;
+use crate::scoring::scorer::Scorer;
+use crate::utils::bitmap::Bitmap;
+use crate::utils::field::DocumentLengths;
 
-pub fn block_max_wand(
-    mut term_iterators: Vec<TermIterator>,
-    doc_lengths: &Box<[u32]>,
-    average_doc_length: f32,
+pub fn block_max_wand<I: PostingIterator>(
+    mut term_iterators: Vec<I>,
+    document_lengths: &DocumentLengths,
+    scorer: &dyn Scorer,
+    deleted_documents: &Bitmap,
+    k: usize,
 ) -> Vec<(u32, f32)> {
-    let max_size = 20;
-    let mut pq: BinaryHeap<Reverse<FloatDoc>> = BinaryHeap::with_capacity(max_size);
+    let mut pq: BinaryHeap<Reverse<FloatDoc>> = BinaryHeap::new();
     let mut threshold = 0.0;
     sort_by_doc_id(&mut term_iterators);
-    let params = BM25Params::default();
 
     loop {
         let mut score: f32 = 0.0;
@@ -44,49 +45,41 @@ pub fn block_max_wand(
         }
         let mut pivot_score = 0.0;
         let mut next = u64::MAX;
-        for i in 0..pivot + 1 {
+        for term_iterator in &mut term_iterators[..=pivot] {
             // Shallow move
-            term_iterators[i]
-                .block_max_iterator
-                .advance(pivot_id as u32);
-            pivot_score += term_iterators[i].get_block_max_score();
-            if (term_iterators[i].get_block_max_last_doc_id()) < next {
-                next = term_iterators[i].get_block_max_last_doc_id();
+            term_iterator.move_block_max_iterator(pivot_id as u32);
+            pivot_score += term_iterator.get_block_max_score();
+            if (term_iterator.get_block_max_last_doc_id()) < next {
+                next = term_iterator.get_block_max_last_doc_id();
             }
         }
         if pivot_score >= threshold {
             if pivot_id == term_iterators[0].get_current_doc_id() {
                 let mut score = 0.0;
-                for i in 0..pivot + 1 {
-                    score += term_iterators[i].get_current_doc_score(
-                        &doc_lengths[pivot_id as usize - 1],
-                        average_doc_length,
-                        &params,
-                        doc_lengths.len() as u32,
-                    );
-                    pivot_score = pivot_score - term_iterators[i].get_block_max_score()
-                        + term_iterators[i].get_current_doc_score(
-                            &doc_lengths[pivot_id as usize - 1],
-                            average_doc_length,
-                            &params,
-                            doc_lengths.len() as u32,
-                        );
+                for term_iterator in &mut term_iterators[..=pivot] {
+                    score += term_iterator.get_current_doc_score(document_lengths, scorer);
+                    pivot_score = pivot_score - term_iterator.get_block_max_score()
+                        + term_iterator.get_current_doc_score(document_lengths, scorer);
                     if pivot_score <= threshold {
                         break;
                     }
                 }
 
-                for i in 0..pivot + 1 {
-                    term_iterators[i].next();
+                for term_iterator in &mut term_iterators[..=pivot] {
+                    term_iterator.next();
                 }
-                pq.push(Reverse(FloatDoc(DocData {
-                    docid: pivot_id as u32,
-                    score,
-                })));
-                if pq.len() > max_size {
-                    pq.pop();
+                if !deleted_documents.contains(pivot_id as u32) {
+                    pq.push(Reverse(FloatDoc(DocData {
+                        docid: pivot_id as u32,
+                        score,
+                    })));
+                    if pq.len() > k {
+                        pq.pop();
+                    }
+                    if pq.len() == k {
+                        threshold = pq.peek().unwrap().0.0.score;
+                    }
                 }
-                threshold = pq.peek().unwrap().0.0.score;
                 sort_by_doc_id(&mut term_iterators);
             } else {
                 while term_iterators[pivot].get_current_doc_id() == pivot_id {
@@ -109,11 +102,5 @@ pub fn block_max_wand(
             swap_down(&mut term_iterators, pivot);
         }
     }
-    let mut doc_ids = Vec::with_capacity(max_size);
-    while !pq.is_empty() {
-        if let Some(doc) = pq.pop() {
-            doc_ids.push((doc.0.0.docid, doc.0.0.score));
-        }
-    }
-    doc_ids
+    get_top_k_docs(pq)
 }
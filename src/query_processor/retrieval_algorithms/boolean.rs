@@ -0,0 +1,343 @@
+use std::ops::RangeInclusive;
+
+use rustc_hash::FxHashMap;
+
+use crate::{
+    parser::query_parser::QueryNode,
+    query_processor::{
+        retrieval_algorithms::positional::{PositionalConstraint, matches_constraint},
+        term_iterator::TermIterator,
+    },
+    utils::bitmap::Bitmap,
+};
+
+// Evaluates the syntax tree of a boolean query bottom up, every node produces the sorted doc ids
+// which satisfy it. Conjunctions over terms do not materialize the posting lists of all the terms,
+// the candidates are taken from the shortest list and the rest of the terms are advanced over them.
+// Boolean retrieval is unranked so every document gets a score of 0.
+pub fn boolean_retrieval(
+    query: &QueryNode,
+    mut term_iterators: Vec<TermIterator>,
+    doc_ids: RangeInclusive<u32>,
+    deleted_documents: &Bitmap,
+) -> Vec<(u32, f32)> {
+    let term_indexes: FxHashMap<String, usize> = term_iterators
+        .iter()
+        .enumerate()
+        .map(|(i, term_iterator)| (term_iterator.get_term().clone(), i))
+        .collect();
+    let mut evaluator = BooleanEvaluator {
+        term_iterators: &mut term_iterators,
+        term_indexes,
+        doc_ids,
+    };
+    evaluator
+        .evaluate(query)
+        .into_iter()
+        .filter(|&doc_id| !deleted_documents.contains(doc_id))
+        .map(|doc_id| (doc_id, 0.0))
+        .collect()
+}
+
+struct BooleanEvaluator<'a, 'b> {
+    term_iterators: &'a mut Vec<TermIterator<'b>>,
+    term_indexes: FxHashMap<String, usize>,
+    // every document which can be part of the result, NOT excludes documents from these
+    doc_ids: RangeInclusive<u32>,
+}
+
+impl BooleanEvaluator<'_, '_> {
+    fn evaluate(&mut self, node: &QueryNode) -> Vec<u32> {
+        match node {
+            QueryNode::Term(term) => match self.term_indexes.get(term) {
+                Some(&i) => {
+                    self.term_iterators[i].reset();
+                    self.term_iterators[i].get_all_doc_ids()
+                }
+                None => Vec::new(),
+            },
+            QueryNode::Phrase(_) => self.evaluate_and(std::slice::from_ref(node)),
+            QueryNode::And(children) => self.evaluate_and(children),
+            QueryNode::Or(children) => {
+                let mut doc_ids = Vec::new();
+                for child in children {
+                    doc_ids = union(&doc_ids, &self.evaluate(child));
+                }
+                doc_ids
+            }
+            QueryNode::Not(child) => {
+                let excluded = self.evaluate(child);
+                difference(&self.doc_ids.clone().collect::<Vec<u32>>(), &excluded)
+            }
+        }
+    }
+
+    fn evaluate_and(&mut self, children: &[QueryNode]) -> Vec<u32> {
+        let mut terms: Vec<&String> = Vec::new();
+        let mut phrases = Vec::new();
+        let mut excluded: Vec<&QueryNode> = Vec::new();
+        let mut others: Vec<&QueryNode> = Vec::new();
+        for child in children {
+            match child {
+                QueryNode::Term(term) => terms.push(term),
+                QueryNode::Phrase(phrase) => {
+                    terms.extend(phrase.terms.iter().map(|token| &token.word));
+                    phrases.push(phrase);
+                }
+                QueryNode::Not(inner) => excluded.push(inner),
+                _ => others.push(child),
+            }
+        }
+
+        // a term which is not present in the index makes the conjunction empty
+        let mut term_ids = Vec::with_capacity(terms.len());
+        for term in terms {
+            match self.term_indexes.get(term) {
+                Some(&i) => {
+                    if !term_ids.contains(&i) {
+                        term_ids.push(i)
+                    }
+                }
+                None => return Vec::new(),
+            }
+        }
+        term_ids.sort_by_key(|&i| self.term_iterators[i].get_no_of_postings());
+
+        let mut candidates: Option<Vec<u32>> = None;
+        for other in others {
+            let doc_ids = self.evaluate(other);
+            candidates = Some(match candidates {
+                Some(candidates) => intersection(&candidates, &doc_ids),
+                None => doc_ids,
+            });
+        }
+        let mut remaining_terms = term_ids.as_slice();
+        if candidates.is_none() && !term_ids.is_empty() {
+            self.term_iterators[term_ids[0]].reset();
+            candidates = Some(self.term_iterators[term_ids[0]].get_all_doc_ids());
+            remaining_terms = &term_ids[1..];
+        }
+        // a conjunction which only excludes documents starts from all the documents
+        let mut candidates = candidates.unwrap_or_else(|| self.doc_ids.clone().collect());
+
+        for &i in remaining_terms {
+            candidates = self.filter_by_term(candidates, i, true);
+        }
+
+        if !phrases.is_empty() {
+            let constraints: Vec<PositionalConstraint> = phrases
+                .iter()
+                .map(|phrase| PositionalConstraint {
+                    term_indexes: phrase
+                        .terms
+                        .iter()
+                        .map(|token| self.term_indexes[&token.word])
+                        .collect(),
+                    offsets: phrase
+                        .terms
+                        .iter()
+                        .map(|token| token.position - phrase.terms[0].position)
+                        .collect(),
+                    slop: phrase.slop,
+                    ordered: phrase.ordered,
+                })
+                .collect();
+            candidates = self.filter_by_constraints(candidates, &term_ids, &constraints);
+        }
+
+        for inner in excluded {
+            candidates = match inner {
+                QueryNode::Term(term) => match self.term_indexes.get(term) {
+                    Some(&i) => self.filter_by_term(candidates, i, false),
+                    None => candidates,
+                },
+                _ => difference(&candidates, &self.evaluate(inner)),
+            };
+        }
+        candidates
+    }
+
+    // Keeps the candidates which are (or are not) present in the posting list of the term
+    fn filter_by_term(&mut self, candidates: Vec<u32>, i: usize, keep_present: bool) -> Vec<u32> {
+        let term_iterator = &mut self.term_iterators[i];
+        term_iterator.reset();
+        candidates
+            .into_iter()
+            .filter(|&doc_id| {
+                if term_iterator.get_current_doc_id() < doc_id as u64 {
+                    term_iterator.advance(doc_id);
+                }
+                (term_iterator.get_current_doc_id() == doc_id as u64) == keep_present
+            })
+            .collect()
+    }
+
+    // All the candidates are present in the posting lists of the terms, the term iterators are
+    // moved to each of them so that the positions can be checked.
+    fn filter_by_constraints(
+        &mut self,
+        candidates: Vec<u32>,
+        term_ids: &[usize],
+        constraints: &[PositionalConstraint],
+    ) -> Vec<u32> {
+        for &i in term_ids {
+            self.term_iterators[i].reset();
+        }
+        candidates
+            .into_iter()
+            .filter(|&doc_id| {
+                for &i in term_ids {
+                    if self.term_iterators[i].get_current_doc_id() < doc_id as u64 {
+                        self.term_iterators[i].advance(doc_id);
+                    }
+                }
+                constraints
+                    .iter()
+                    .all(|constraint| matches_constraint(self.term_iterators, constraint))
+            })
+            .collect()
+    }
+}
+
+fn intersection(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut result = Vec::with_capacity(a.len().min(b.len()));
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        if a[i] == b[j] {
+            result.push(a[i]);
+            i += 1;
+            j += 1;
+        } else if a[i] < b[j] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    result
+}
+
+fn union(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut result = Vec::with_capacity(a.len() + b.len());
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        if a[i] == b[j] {
+            result.push(a[i]);
+            i += 1;
+            j += 1;
+        } else if a[i] < b[j] {
+            result.push(a[i]);
+            i += 1;
+        } else {
+            result.push(b[j]);
+            j += 1;
+        }
+    }
+    result.extend_from_slice(&a[i..]);
+    result.extend_from_slice(&b[j..]);
+    result
+}
+
+fn difference(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut result = Vec::with_capacity(a.len());
+    let mut j = 0;
+    for &doc_id in a {
+        while j < b.len() && b[j] < doc_id {
+            j += 1;
+        }
+        if j == b.len() || b[j] != doc_id {
+            result.push(doc_id);
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        compressor::compressor::CompressionAlgorithm,
+        parser::parser::Parser,
+        utils::{chunk::Chunk, chunk_block_max_metadata::ChunkBlockMaxMetadata},
+    };
+
+    fn create_term_iterator(term: &str, postings: Vec<(u32, Vec<u32>)>) -> TermIterator<'static> {
+        let mut chunk = Chunk::new(1, CompressionAlgorithm::VarByte);
+        for (doc_id, positions) in &postings {
+            chunk.add_doc_id(*doc_id);
+            chunk.add_doc_frequency(positions.len() as u32);
+            chunk.add_doc_positions(positions.clone());
+        }
+        // the decoded chunk borrows the encoded bytes for the rest of the test
+        let encoded = chunk.encode().leak();
+        let mut decoded_chunk = Chunk::new(1, CompressionAlgorithm::VarByte);
+        decoded_chunk.decode(&encoded[4..]);
+        let last_doc_id = postings.last().unwrap().0;
+        let mut term_iterator = TermIterator::new(
+            term.to_string(),
+            1,
+            postings.len() as u32,
+            vec![decoded_chunk],
+            1.0,
+            vec![ChunkBlockMaxMetadata::new(last_doc_id, 1.0)],
+        );
+        term_iterator.init();
+        term_iterator
+    }
+
+    fn run(query: &str) -> Vec<u32> {
+        let term_iterators = vec![
+            create_term_iterator("king", vec![(1, vec![0]), (2, vec![4]), (4, vec![1])]),
+            create_term_iterator("stephen", vec![(2, vec![3]), (3, vec![0]), (4, vec![5])]),
+            create_term_iterator("horror", vec![(4, vec![2]), (5, vec![2])]),
+        ];
+        let parser = Parser::new().unwrap();
+        let node = parser.parse_boolean_query(query).unwrap().unwrap();
+        boolean_retrieval(&node, term_iterators, 1..=6, &Bitmap::new())
+            .into_iter()
+            .map(|(doc_id, _)| doc_id)
+            .collect()
+    }
+
+    #[test]
+    fn test_and() {
+        assert_eq!(run("king AND stephen"), vec![2, 4]);
+        assert_eq!(run("king stephen horror"), vec![4]);
+    }
+
+    #[test]
+    fn test_or() {
+        assert_eq!(run("king OR horror"), vec![1, 2, 4, 5]);
+    }
+
+    #[test]
+    fn test_not() {
+        assert_eq!(run("king -horror"), vec![1, 2]);
+        assert_eq!(run("king AND NOT (stephen OR horror)"), vec![1]);
+        assert_eq!(run("NOT king"), vec![3, 5, 6]);
+    }
+
+    #[test]
+    fn test_grouping() {
+        assert_eq!(run("(king OR horror) AND stephen"), vec![2, 4]);
+    }
+
+    #[test]
+    fn test_phrase() {
+        assert_eq!(run("\"stephen king\""), vec![2]);
+        assert_eq!(run("\"stephen king\" OR horror"), vec![2, 4, 5]);
+    }
+
+    #[test]
+    fn test_unknown_term() {
+        assert_eq!(run("king AND tolkien"), Vec::<u32>::new());
+        assert_eq!(run("king OR tolkien"), vec![1, 2, 4]);
+        assert_eq!(run("king -tolkien"), vec![1, 2, 4]);
+    }
+
+    #[test]
+    fn test_set_operations() {
+        assert_eq!(intersection(&[1, 3, 5, 7], &[3, 4, 7]), vec![3, 7]);
+        assert_eq!(union(&[1, 3, 5], &[2, 3, 6]), vec![1, 2, 3, 5, 6]);
+        assert_eq!(difference(&[1, 2, 3, 4], &[2, 4, 9]), vec![1, 3]);
+    }
+}
@@ -1,10 +1,14 @@
 pub mod binary_merge;
 pub mod block_max_max_score;
 pub mod block_max_wand;
+pub mod boolean;
 pub mod max_score;
+pub mod positional;
 mod utils;
 pub mod wand;
 
+use std::{fmt, str::FromStr};
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum QueryAlgorithm {
     Wand,
@@ -14,14 +18,31 @@ pub enum QueryAlgorithm {
     Boolean,
 }
 
-impl QueryAlgorithm {
-    pub fn to_string(&self) -> String {
-        match self {
-            QueryAlgorithm::BlockMaxMaxScore => String::from("Block Max Max Score (BMMS)"),
-            QueryAlgorithm::BlockMaxWand => String::from("Block Max Wand (BMW)"),
-            QueryAlgorithm::Wand => String::from("WAND"),
-            QueryAlgorithm::Boolean => String::from("Boolean"),
-            QueryAlgorithm::MaxScore => String::from("Max Score (MS)"),
+impl fmt::Display for QueryAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            QueryAlgorithm::BlockMaxMaxScore => "Block Max Max Score (BMMS)",
+            QueryAlgorithm::BlockMaxWand => "Block Max Wand (BMW)",
+            QueryAlgorithm::Wand => "WAND",
+            QueryAlgorithm::Boolean => "Boolean",
+            QueryAlgorithm::MaxScore => "Max Score (MS)",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// the names which are used for the algorithms in the config file
+impl FromStr for QueryAlgorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "boolean" => Ok(QueryAlgorithm::Boolean),
+            "bmw" => Ok(QueryAlgorithm::BlockMaxWand),
+            "bmms" => Ok(QueryAlgorithm::BlockMaxMaxScore),
+            "wand" => Ok(QueryAlgorithm::Wand),
+            "ms" => Ok(QueryAlgorithm::MaxScore),
+            _ => Err(format!("unknown query algorithm {}", s)),
         }
     }
 }
@@ -0,0 +1,250 @@
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+use crate::{
+    query_processor::{
+        retrieval_algorithms::utils::{DocData, FloatDoc, get_top_k_docs},
+        term_iterator::TermIterator,
+    },
+    scoring::scorer::Scorer,
+    utils::{bitmap::Bitmap, field::DocumentLengths},
+};
+
+// A phrase or proximity constraint over the query terms.
+// The terms are referred to by the index of their term iterator and every term has an offset
+// which is its position relative to the first term of the constraint in the query.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PositionalConstraint {
+    pub term_indexes: Vec<usize>,
+    pub offsets: Vec<u32>,
+    pub slop: u32,
+    pub ordered: bool,
+}
+
+// For a phrase every term has to be present at the position of the first term plus its offset.
+pub(crate) fn matches_phrase(positions: &[Vec<u32>], offsets: &[u32]) -> bool {
+    positions[0].iter().any(|&start| {
+        positions
+            .iter()
+            .zip(offsets)
+            .skip(1)
+            .all(|(term_positions, offset)| term_positions.binary_search(&(start + offset)).is_ok())
+    })
+}
+
+// For a proximity query two positions of the terms have to be within slop of each other.
+// Since both the lists are sorted we can walk them together.
+fn matches_near(first: &[u32], second: &[u32], slop: u32) -> bool {
+    let (mut i, mut j) = (0, 0);
+    while i < first.len() && j < second.len() {
+        if first[i].abs_diff(second[j]) <= slop {
+            return true;
+        }
+        if first[i] < second[j] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    false
+}
+
+pub(crate) fn matches_constraint(
+    term_iterators: &[TermIterator],
+    constraint: &PositionalConstraint,
+) -> bool {
+    let positions: Vec<Vec<u32>> = constraint
+        .term_indexes
+        .iter()
+        .map(|&i| term_iterators[i].get_current_positions())
+        .collect();
+    if positions.iter().any(|p| p.is_empty()) {
+        return false;
+    }
+    if constraint.ordered {
+        matches_phrase(&positions, &constraint.offsets)
+    } else {
+        positions
+            .windows(2)
+            .all(|pair| matches_near(&pair[0], &pair[1], constraint.slop))
+    }
+}
+
+// The terms which are part of a constraint are intersected in the same way as a
+// conjunctive query, the positions are only decoded once all of them point to the same document.
+// The remaining terms of the query do not filter documents, they only add to the score.
+pub fn positional_merge(
+    mut term_iterators: Vec<TermIterator>,
+    constraints: &[PositionalConstraint],
+    document_lengths: &DocumentLengths,
+    scorer: &dyn Scorer,
+    deleted_documents: &Bitmap,
+    k: usize,
+) -> Vec<(u32, f32)> {
+    let mut pq: BinaryHeap<Reverse<FloatDoc>> = BinaryHeap::new();
+
+    let mut required: Vec<usize> = constraints
+        .iter()
+        .flat_map(|c| c.term_indexes.iter().copied())
+        .collect();
+    required.sort_unstable();
+    required.dedup();
+    if required.is_empty() {
+        return Vec::new();
+    }
+
+    loop {
+        let mut candidate = 0;
+        for &i in &required {
+            candidate = candidate.max(term_iterators[i].get_current_doc_id());
+        }
+        if candidate == u64::MAX {
+            break;
+        }
+
+        let mut all_match = true;
+        for &i in &required {
+            if term_iterators[i].get_current_doc_id() < candidate {
+                term_iterators[i].advance(candidate as u32);
+            }
+            if term_iterators[i].get_current_doc_id() != candidate {
+                all_match = false;
+            }
+        }
+        if !all_match {
+            continue;
+        }
+
+        if !deleted_documents.contains(candidate as u32)
+            && constraints
+                .iter()
+                .all(|c| matches_constraint(&term_iterators, c))
+        {
+            let mut score = 0.0;
+            for term_iterator in &mut term_iterators {
+                if term_iterator.get_current_doc_id() < candidate {
+                    term_iterator.advance(candidate as u32);
+                }
+                if term_iterator.get_current_doc_id() == candidate {
+                    score += term_iterator.get_current_doc_score(document_lengths, scorer);
+                }
+            }
+            pq.push(Reverse(FloatDoc(DocData {
+                docid: candidate as u32,
+                score,
+            })));
+            if pq.len() > k {
+                pq.pop();
+            }
+        }
+        term_iterators[required[0]].next();
+    }
+
+    get_top_k_docs(pq)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        compressor::compressor::CompressionAlgorithm,
+        scoring::bm_25::BM25,
+        utils::{chunk::Chunk, chunk_block_max_metadata::ChunkBlockMaxMetadata},
+    };
+
+    fn create_term_iterator(term_id: u32, postings: Vec<(u32, Vec<u32>)>) -> TermIterator<'static> {
+        let mut chunk = Chunk::new(term_id, CompressionAlgorithm::VarByte);
+        for (doc_id, positions) in &postings {
+            chunk.add_doc_id(*doc_id);
+            chunk.add_doc_frequency(positions.len() as u32);
+            chunk.add_doc_positions(positions.clone());
+        }
+        // the decoded chunk borrows the encoded bytes for the rest of the test
+        let encoded = chunk.encode().leak();
+        let mut decoded_chunk = Chunk::new(term_id, CompressionAlgorithm::VarByte);
+        decoded_chunk.decode(&encoded[4..]);
+        let last_doc_id = postings.last().unwrap().0;
+        let mut term_iterator = TermIterator::new(
+            term_id.to_string(),
+            term_id,
+            postings.len() as u32,
+            vec![decoded_chunk],
+            1.0,
+            vec![ChunkBlockMaxMetadata::new(last_doc_id, 1.0)],
+        );
+        term_iterator.init();
+        term_iterator
+    }
+
+    #[test]
+    fn test_matches_phrase() {
+        assert!(matches_phrase(&[vec![1, 10], vec![4, 11]], &[0, 1]));
+        assert!(!matches_phrase(&[vec![1, 10], vec![4, 12]], &[0, 1]));
+        assert!(matches_phrase(&[vec![5], vec![8]], &[0, 3]));
+    }
+
+    #[test]
+    fn test_matches_near() {
+        assert!(matches_near(&[1, 20], &[25], 5));
+        assert!(matches_near(&[30], &[25], 5));
+        assert!(!matches_near(&[1, 40], &[25], 5));
+    }
+
+    #[test]
+    fn test_positional_merge_phrase() {
+        let document_lengths = DocumentLengths {
+            body_lengths: &[10; 5],
+            average_body_length: 10.0,
+            title_lengths: &[],
+            average_title_length: 0.0,
+        };
+        let misery = create_term_iterator(1, vec![(1, vec![0]), (2, vec![3]), (4, vec![7])]);
+        let movie = create_term_iterator(2, vec![(1, vec![5]), (2, vec![4]), (3, vec![1])]);
+        let constraints = vec![PositionalConstraint {
+            term_indexes: vec![0, 1],
+            offsets: vec![0, 1],
+            slop: 0,
+            ordered: true,
+        }];
+
+        let results = positional_merge(
+            vec![misery, movie],
+            &constraints,
+            &document_lengths,
+            &BM25::default(),
+            &Bitmap::new(),
+            10,
+        );
+        let doc_ids: Vec<u32> = results.iter().map(|r| r.0).collect();
+        assert_eq!(doc_ids, vec![2]);
+    }
+
+    #[test]
+    fn test_positional_merge_near() {
+        let document_lengths = DocumentLengths {
+            body_lengths: &[10; 5],
+            average_body_length: 10.0,
+            title_lengths: &[],
+            average_title_length: 0.0,
+        };
+        let misery = create_term_iterator(1, vec![(1, vec![0]), (2, vec![9]), (4, vec![7])]);
+        let movie = create_term_iterator(2, vec![(1, vec![5]), (2, vec![1]), (4, vec![6])]);
+        let constraints = vec![PositionalConstraint {
+            term_indexes: vec![0, 1],
+            offsets: vec![0, 1],
+            slop: 5,
+            ordered: false,
+        }];
+
+        let results = positional_merge(
+            vec![misery, movie],
+            &constraints,
+            &document_lengths,
+            &BM25::default(),
+            &Bitmap::new(),
+            10,
+        );
+        let mut doc_ids: Vec<u32> = results.iter().map(|r| r.0).collect();
+        doc_ids.sort();
+        assert_eq!(doc_ids, vec![1, 4]);
+    }
+}
@@ -1,6 +1,9 @@
-use std::cmp::Ordering;
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::BinaryHeap,
+};
 
-use crate::query_processor::term_iterator::TermIterator;
+use crate::query_processor::term_iterator::PostingIterator;
 
 #[derive(Debug, PartialEq)]
 pub struct DocData {
@@ -28,10 +31,19 @@ impl PartialOrd for FloatDoc {
     }
 }
 
-pub fn sort_by_doc_id(term_iterators: &mut Vec<TermIterator>) {
-    term_iterators.sort_by(|a, b| a.get_current_doc_id().cmp(&b.get_current_doc_id()));
+// The heap only holds the top k documents with the lowest score at the top,
+// the documents are returned in decreasing order of their scores.
+pub fn get_top_k_docs(pq: BinaryHeap<Reverse<FloatDoc>>) -> Vec<(u32, f32)> {
+    pq.into_sorted_vec()
+        .into_iter()
+        .map(|doc| (doc.0.0.docid, doc.0.0.score))
+        .collect()
 }
-pub fn swap_down(term_iterators: &mut Vec<TermIterator>, pivot: usize) {
+
+pub fn sort_by_doc_id<I: PostingIterator>(term_iterators: &mut [I]) {
+    term_iterators.sort_by_key(|a| a.get_current_doc_id());
+}
+pub fn swap_down<I: PostingIterator>(term_iterators: &mut [I], pivot: usize) {
     let mut temp = pivot;
     while temp + 1 < term_iterators.len()
         && term_iterators[temp].get_current_doc_id() > term_iterators[temp + 1].get_current_doc_id()
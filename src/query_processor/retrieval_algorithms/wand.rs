@@ -1,23 +1,26 @@
 use crate::{
     query_processor::{
-        retrieval_algorithms::utils::{DocData, FloatDoc, sort_by_doc_id, swap_down},
-        term_iterator::TermIterator,
+        retrieval_algorithms::utils::{
+            DocData, FloatDoc, get_top_k_docs, sort_by_doc_id, swap_down,
+        },
+        term_iterator::PostingIterator,
     },
-    scoring::bm_25::BM25Params,
+    scoring::scorer::Scorer,
+    utils::{bitmap::Bitmap, field::DocumentLengths},
 };
 use std::collections::BinaryHeap;
 use std::{cmp::Reverse, f32};
 
-pub fn wand(
-    mut term_iterators: Vec<TermIterator>,
-    doc_lengths: &Box<[u32]>,
-    average_doc_length: f32,
+pub fn wand<I: PostingIterator>(
+    mut term_iterators: Vec<I>,
+    document_lengths: &DocumentLengths,
+    scorer: &dyn Scorer,
+    deleted_documents: &Bitmap,
+    k: usize,
 ) -> Vec<(u32, f32)> {
-    let max_docs = 50;
-    let mut pq: BinaryHeap<Reverse<FloatDoc>> = BinaryHeap::with_capacity(max_docs as usize);
+    let mut pq: BinaryHeap<Reverse<FloatDoc>> = BinaryHeap::new();
     let mut threshold = 0.0;
     sort_by_doc_id(&mut term_iterators);
-    let params = BM25Params::default();
 
     loop {
         let mut score: f32 = 0.0;
@@ -40,26 +43,27 @@ pub fn wand(
         if pivot_id == term_iterators[0].get_current_doc_id() {
             let mut pivot_score = 0.0;
 
-            for i in 0..term_iterators.len() {
-                if term_iterators[i].get_current_doc_id() != pivot_id {
+            for term_iterator in term_iterators.iter_mut() {
+                if term_iterator.get_current_doc_id() != pivot_id {
                     break;
                 }
-                pivot_score += term_iterators[i].get_current_doc_score(
-                    &doc_lengths[pivot_id as usize - 1],
-                    average_doc_length,
-                    &params,
-                    doc_lengths.len() as u32,
-                );
-                term_iterators[i].next();
+                pivot_score += term_iterator.get_current_doc_score(document_lengths, scorer);
+                term_iterator.next();
             }
-            pq.push(Reverse(FloatDoc(DocData {
-                docid: pivot_id as u32,
-                score: pivot_score,
-            })));
-            if pq.len() > max_docs {
-                pq.pop();
+            // the postings of deleted documents are only removed during a merge
+            if !deleted_documents.contains(pivot_id as u32) {
+                pq.push(Reverse(FloatDoc(DocData {
+                    docid: pivot_id as u32,
+                    score: pivot_score,
+                })));
+                if pq.len() > k {
+                    pq.pop();
+                }
+                // the threshold can only be raised once the heap holds k documents
+                if pq.len() == k {
+                    threshold = pq.peek().unwrap().0.0.score;
+                }
             }
-            threshold = pq.peek().unwrap().0.0.score;
             sort_by_doc_id(&mut term_iterators);
         } else {
             while pivot > 0 && term_iterators[pivot].get_current_doc_id() == pivot_id {
@@ -71,11 +75,5 @@ pub fn wand(
         }
     }
 
-    let mut doc_ids = Vec::with_capacity(pq.len());
-    while !pq.is_empty() {
-        if let Some(doc) = pq.pop() {
-            doc_ids.push((doc.0.0.docid, doc.0.0.score));
-        }
-    }
-    doc_ids
+    get_top_k_docs(pq)
 }
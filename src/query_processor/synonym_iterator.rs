@@ -0,0 +1,388 @@
+use crate::{
+    query_processor::{
+        query_processor::ScoreCombination,
+        retrieval_algorithms::positional::matches_phrase,
+        term_iterator::{PostingIterator, TermIterator},
+    },
+    scoring::scorer::Scorer,
+    utils::field::DocumentLengths,
+};
+
+// The union of the postings of a group of iterators, a document is scored with the sum or the max
+// of the scores of the iterators which contain it. The max scores and block max scores are
+// combined in the same way, so they stay upper bounds of the combined scores.
+#[derive(Debug)]
+pub struct UnionIterator<I> {
+    iterators: Vec<I>,
+    combination: ScoreCombination,
+    current_doc_id: u64,
+}
+
+impl<I: PostingIterator> UnionIterator<I> {
+    pub fn new(iterators: Vec<I>, combination: ScoreCombination) -> Self {
+        let mut union_iterator = Self {
+            iterators,
+            combination,
+            current_doc_id: u64::MAX,
+        };
+        union_iterator.update_current_doc_id();
+        union_iterator
+    }
+
+    pub fn get_iterators(&self) -> &[I] {
+        &self.iterators
+    }
+
+    pub fn get_iterators_mut(&mut self) -> &mut [I] {
+        &mut self.iterators
+    }
+
+    fn update_current_doc_id(&mut self) {
+        self.current_doc_id = self
+            .iterators
+            .iter()
+            .map(|iterator| iterator.get_current_doc_id())
+            .min()
+            .unwrap_or(u64::MAX);
+    }
+}
+
+impl<I: PostingIterator> PostingIterator for UnionIterator<I> {
+    fn get_current_doc_id(&self) -> u64 {
+        self.current_doc_id
+    }
+
+    fn next(&mut self) -> bool {
+        if self.current_doc_id == u64::MAX {
+            return false;
+        }
+        for iterator in &mut self.iterators {
+            if iterator.get_current_doc_id() == self.current_doc_id {
+                iterator.next();
+            }
+        }
+        self.update_current_doc_id();
+        self.current_doc_id != u64::MAX
+    }
+
+    fn advance(&mut self, doc_id: u32) {
+        for iterator in &mut self.iterators {
+            if iterator.get_current_doc_id() < doc_id as u64 {
+                iterator.advance(doc_id);
+            }
+        }
+        self.update_current_doc_id();
+    }
+
+    fn is_complete(&mut self) -> bool {
+        self.current_doc_id == u64::MAX
+    }
+
+    fn get_current_doc_score(
+        &self,
+        document_lengths: &DocumentLengths,
+        scorer: &dyn Scorer,
+    ) -> f32 {
+        self.combination.combine(
+            self.iterators
+                .iter()
+                .filter(|iterator| iterator.get_current_doc_id() == self.current_doc_id)
+                .map(|iterator| iterator.get_current_doc_score(document_lengths, scorer)),
+        )
+    }
+
+    fn get_max_score(&self) -> f32 {
+        self.combination.combine(
+            self.iterators
+                .iter()
+                .map(|iterator| iterator.get_max_score()),
+        )
+    }
+
+    // the iterators which are complete have no documents left to bound
+    fn move_block_max_iterator(&mut self, doc_id: u32) {
+        for iterator in &mut self.iterators {
+            if !iterator.is_complete() {
+                iterator.move_block_max_iterator(doc_id);
+            }
+        }
+    }
+
+    fn get_block_max_score(&mut self) -> f32 {
+        let mut block_max_scores = Vec::with_capacity(self.iterators.len());
+        for iterator in &mut self.iterators {
+            if !iterator.is_complete() {
+                block_max_scores.push(iterator.get_block_max_score());
+            }
+        }
+        self.combination.combine(block_max_scores.into_iter())
+    }
+
+    // the block max score holds until the first of the blocks ends
+    fn get_block_max_last_doc_id(&mut self) -> u64 {
+        let mut last_doc_id = u64::MAX;
+        for iterator in &mut self.iterators {
+            if !iterator.is_complete() {
+                last_doc_id = last_doc_id.min(iterator.get_block_max_last_doc_id());
+            }
+        }
+        last_doc_id
+    }
+}
+
+// The documents which have the terms of a synonym made of more than one word one after the other,
+// the offsets are the positions of the terms relative to the first one. A document is scored with
+// the sum of the scores of the terms. Like the phrases of a positional query, a document without
+// the positions of the terms does not match, the searcher does not expand a query into phrases on
+// an index without positions.
+#[derive(Debug)]
+pub struct PhraseIterator<'a> {
+    term_iterators: Vec<TermIterator<'a>>,
+    offsets: Vec<u32>,
+    current_doc_id: u64,
+}
+
+impl<'a> PhraseIterator<'a> {
+    pub fn new(term_iterators: Vec<TermIterator<'a>>, offsets: Vec<u32>) -> Self {
+        let mut phrase_iterator = Self {
+            term_iterators,
+            offsets,
+            current_doc_id: u64::MAX,
+        };
+        phrase_iterator.find_match();
+        phrase_iterator
+    }
+
+    // Moves the term iterators to the first document from where they are which has the phrase
+    fn find_match(&mut self) {
+        loop {
+            let candidate = self
+                .term_iterators
+                .iter()
+                .map(|term_iterator| term_iterator.get_current_doc_id())
+                .max()
+                .unwrap_or(u64::MAX);
+            if candidate == u64::MAX {
+                self.current_doc_id = u64::MAX;
+                return;
+            }
+            let mut all_match = true;
+            for term_iterator in &mut self.term_iterators {
+                if term_iterator.get_current_doc_id() < candidate {
+                    term_iterator.advance(candidate as u32);
+                }
+                if term_iterator.get_current_doc_id() != candidate {
+                    all_match = false;
+                }
+            }
+            if !all_match {
+                continue;
+            }
+            if self.matches_phrase() {
+                self.current_doc_id = candidate;
+                return;
+            }
+            self.term_iterators[0].next();
+        }
+    }
+
+    fn matches_phrase(&self) -> bool {
+        let positions: Vec<Vec<u32>> = self
+            .term_iterators
+            .iter()
+            .map(|term_iterator| term_iterator.get_current_positions())
+            .collect();
+        !positions.iter().any(|positions| positions.is_empty())
+            && matches_phrase(&positions, &self.offsets)
+    }
+}
+
+impl PostingIterator for PhraseIterator<'_> {
+    fn get_current_doc_id(&self) -> u64 {
+        self.current_doc_id
+    }
+
+    fn next(&mut self) -> bool {
+        if self.current_doc_id == u64::MAX {
+            return false;
+        }
+        self.term_iterators[0].next();
+        self.find_match();
+        self.current_doc_id != u64::MAX
+    }
+
+    fn advance(&mut self, doc_id: u32) {
+        if self.current_doc_id >= doc_id as u64 {
+            return;
+        }
+        for term_iterator in &mut self.term_iterators {
+            if term_iterator.get_current_doc_id() < doc_id as u64 {
+                term_iterator.advance(doc_id);
+            }
+        }
+        self.find_match();
+    }
+
+    fn is_complete(&mut self) -> bool {
+        self.current_doc_id == u64::MAX
+    }
+
+    fn get_current_doc_score(
+        &self,
+        document_lengths: &DocumentLengths,
+        scorer: &dyn Scorer,
+    ) -> f32 {
+        self.term_iterators
+            .iter()
+            .map(|term_iterator| term_iterator.get_current_doc_score(document_lengths, scorer))
+            .sum()
+    }
+
+    fn get_max_score(&self) -> f32 {
+        self.term_iterators
+            .iter()
+            .map(|term_iterator| term_iterator.get_max_score())
+            .sum()
+    }
+
+    fn move_block_max_iterator(&mut self, doc_id: u32) {
+        for term_iterator in &mut self.term_iterators {
+            term_iterator.move_block_max_iterator(doc_id);
+        }
+    }
+
+    fn get_block_max_score(&mut self) -> f32 {
+        self.term_iterators
+            .iter_mut()
+            .map(|term_iterator| term_iterator.get_block_max_score())
+            .sum()
+    }
+
+    fn get_block_max_last_doc_id(&mut self) -> u64 {
+        self.term_iterators
+            .iter_mut()
+            .map(|term_iterator| term_iterator.get_block_max_last_doc_id())
+            .min()
+            .unwrap_or(u64::MAX)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        compressor::compressor::CompressionAlgorithm,
+        scoring::bm_25::BM25,
+        utils::{chunk::Chunk, chunk_block_max_metadata::ChunkBlockMaxMetadata},
+    };
+
+    fn create_term_iterator(term_id: u32, postings: Vec<(u32, Vec<u32>)>) -> TermIterator<'static> {
+        let mut chunk = Chunk::new(term_id, CompressionAlgorithm::VarByte);
+        for (doc_id, positions) in &postings {
+            chunk.add_doc_id(*doc_id);
+            chunk.add_doc_frequency(positions.len() as u32);
+            chunk.add_doc_positions(positions.clone());
+        }
+        // the decoded chunk borrows the encoded bytes for the rest of the test
+        let encoded = chunk.encode().leak();
+        let mut decoded_chunk = Chunk::new(term_id, CompressionAlgorithm::VarByte);
+        decoded_chunk.decode(&encoded[4..]);
+        let last_doc_id = postings.last().unwrap().0;
+        let mut term_iterator = TermIterator::new(
+            term_id.to_string(),
+            term_id,
+            postings.len() as u32,
+            vec![decoded_chunk],
+            1.0,
+            vec![ChunkBlockMaxMetadata::new(last_doc_id, 1.0)],
+        );
+        term_iterator.init();
+        term_iterator
+    }
+
+    fn get_doc_scores(iterator: &mut dyn PostingIterator) -> Vec<(u64, f32)> {
+        let document_lengths = DocumentLengths {
+            body_lengths: &[10; 5],
+            average_body_length: 10.0,
+            title_lengths: &[],
+            average_title_length: 0.0,
+        };
+        let mut doc_scores = Vec::new();
+        while !iterator.is_complete() {
+            doc_scores.push((
+                iterator.get_current_doc_id(),
+                iterator.get_current_doc_score(&document_lengths, &BM25::default()),
+            ));
+            iterator.next();
+        }
+        doc_scores
+    }
+
+    fn create_union_iterator(
+        score_combination: ScoreCombination,
+    ) -> UnionIterator<TermIterator<'static>> {
+        UnionIterator::new(
+            vec![
+                create_term_iterator(1, vec![(1, vec![0]), (3, vec![2])]),
+                create_term_iterator(2, vec![(2, vec![1]), (3, vec![5])]),
+            ],
+            score_combination,
+        )
+    }
+
+    #[test]
+    fn test_union_iterator() {
+        let sum = get_doc_scores(&mut create_union_iterator(ScoreCombination::Sum));
+        let max = get_doc_scores(&mut create_union_iterator(ScoreCombination::Max));
+        let doc_ids: Vec<u64> = max.iter().map(|&(doc_id, _)| doc_id).collect();
+        assert_eq!(doc_ids, vec![1, 2, 3]);
+        // the document with both the terms only gets the score of one of them with max
+        assert!(sum[2].1 > max[2].1);
+        assert!((max[2].1 - max[0].1.max(max[1].1)).abs() < 1e-4);
+
+        let mut union_iterator = create_union_iterator(ScoreCombination::Max);
+        union_iterator.advance(2);
+        assert_eq!(union_iterator.get_current_doc_id(), 2);
+        assert_eq!(union_iterator.get_max_score(), 1.0);
+        assert!(union_iterator.next());
+        assert_eq!(union_iterator.get_current_doc_id(), 3);
+        assert!(!union_iterator.next());
+    }
+
+    #[test]
+    fn test_phrase_iterator() {
+        let new_york = |york_postings| {
+            PhraseIterator::new(
+                vec![
+                    create_term_iterator(1, vec![(1, vec![0]), (2, vec![4]), (4, vec![2])]),
+                    create_term_iterator(2, york_postings),
+                ],
+                vec![0, 1],
+            )
+        };
+        let mut phrase_iterator = new_york(vec![(1, vec![1]), (2, vec![7]), (4, vec![3])]);
+        let doc_ids: Vec<u64> = get_doc_scores(&mut phrase_iterator)
+            .into_iter()
+            .map(|(doc_id, _)| doc_id)
+            .collect();
+        assert_eq!(doc_ids, vec![1, 4]);
+
+        let mut phrase_iterator = new_york(vec![(1, vec![1]), (2, vec![7]), (4, vec![3])]);
+        phrase_iterator.advance(2);
+        assert_eq!(phrase_iterator.get_current_doc_id(), 4);
+        assert_eq!(phrase_iterator.get_max_score(), 2.0);
+
+        let mut phrase_iterator = new_york(vec![(3, vec![1])]);
+        assert!(phrase_iterator.is_complete());
+
+        // the terms of a document without positions can not be told to be one after the other
+        let mut phrase_iterator = PhraseIterator::new(
+            vec![
+                create_term_iterator(1, vec![(1, vec![]), (2, vec![])]),
+                create_term_iterator(2, vec![(1, vec![]), (2, vec![])]),
+            ],
+            vec![0, 1],
+        );
+        assert!(phrase_iterator.is_complete());
+    }
+}
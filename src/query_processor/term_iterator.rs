@@ -1,38 +1,86 @@
-use std::u32;
+use std::fmt::Debug;
 
 use crate::{
     query_processor::utils::BlockMaxIterator,
-    scoring::bm_25::{BM25Params, compute_term_score},
+    scoring::scorer::{Scorer, TermStatistics},
     utils::{
-        chunk::Chunk, chunk_block_max_metadata::ChunkBlockMaxMetadata,
+        chunk::Chunk,
+        chunk_block_max_metadata::ChunkBlockMaxMetadata,
         chunk_iterator::ChunkIterator,
+        field::{DocumentLengths, Field},
     },
 };
+// The postings which the ranked retrieval algorithms iterate over, the postings of a single term or
+// the ones of a group of terms which are scored together such as a word and its synonyms. An
+// iterator which is complete is at u64::MAX.
+pub trait PostingIterator: Debug {
+    fn get_current_doc_id(&self) -> u64;
+    fn next(&mut self) -> bool;
+    // moves to the first document which is at least doc_id
+    fn advance(&mut self, doc_id: u32);
+    fn is_complete(&mut self) -> bool;
+    fn get_current_doc_score(&self, document_lengths: &DocumentLengths, scorer: &dyn Scorer)
+    -> f32;
+    fn get_max_score(&self) -> f32;
+    fn move_block_max_iterator(&mut self, doc_id: u32);
+    fn get_block_max_score(&mut self) -> f32;
+    fn get_block_max_last_doc_id(&mut self) -> u64;
+}
+
 #[derive(Debug)]
-pub struct TermIterator {
+pub struct TermIterator<'a> {
     pub term: String,
+    #[allow(dead_code, reason = "only the tests read the term id back")]
     pub term_id: u32,
     pub term_frequency: u32,
-    pub chunk_iterator: ChunkIterator,
+    pub collection_frequency: u64,
+    pub field: Field,
+    // the scores of the term are multiplied by the weight of its field, the max scores which the
+    // iterator is created with have to be weighted already
+    pub weight: f32,
+    pub chunk_iterator: ChunkIterator<'a>,
     pub max_score: f32,
     pub block_max_iterator: BlockMaxIterator,
     pub is_complete: bool,
 }
 
-impl TermIterator {
+impl<'a> TermIterator<'a> {
     pub fn new(
         term: String,
         term_id: u32,
         term_frequency: u32,
-        chunks: Vec<Chunk>,
+        chunks: Vec<Chunk<'a>>,
+        max_score: f32,
+        chunk_metadata: Vec<ChunkBlockMaxMetadata>,
+    ) -> Self {
+        Self::from_chunk_iterator(
+            term,
+            term_id,
+            term_frequency,
+            ChunkIterator::new(chunks),
+            max_score,
+            chunk_metadata,
+        )
+    }
+
+    pub fn from_chunk_iterator(
+        term: String,
+        term_id: u32,
+        term_frequency: u32,
+        chunk_iterator: ChunkIterator<'a>,
         max_score: f32,
         chunk_metadata: Vec<ChunkBlockMaxMetadata>,
     ) -> Self {
         Self {
+            field: Field::from_term(&term),
             term,
             term_id,
             term_frequency,
-            chunk_iterator: ChunkIterator::new(chunks),
+            // the document frequency is the smallest the collection frequency can be, it is set
+            // by the query processor for the scoring models which need it
+            collection_frequency: term_frequency as u64,
+            weight: 1.0,
+            chunk_iterator,
             max_score,
             block_max_iterator: BlockMaxIterator::new(chunk_metadata),
             is_complete: false,
@@ -51,6 +99,7 @@ impl TermIterator {
         &self.term
     }
 
+    #[cfg(test)]
     pub fn get_term_id(&self) -> u32 {
         self.term_id
     }
@@ -73,6 +122,7 @@ impl TermIterator {
         self.chunk_iterator.has_next()
     }
 
+    #[cfg(test)]
     pub fn contains_doc_id(&self, doc_id: u32) -> bool {
         self.chunk_iterator.contains_doc_id(doc_id)
     }
@@ -101,21 +151,37 @@ impl TermIterator {
     pub fn get_current_doc_frequency(&self) -> u32 {
         self.chunk_iterator.get_doc_frequency()
     }
+
+    // the positions are only present if the index was built with positions
+    pub fn get_current_positions(&self) -> Vec<u32> {
+        self.chunk_iterator.get_posting_list()
+    }
+    pub fn set_collection_frequency(&mut self, collection_frequency: u64) {
+        self.collection_frequency = collection_frequency;
+    }
+
+    pub fn set_weight(&mut self, weight: f32) {
+        self.weight = weight;
+    }
+
+    // The current document is scored with the length of the field the term belongs to
     pub fn get_current_doc_score(
         &self,
-        current_doc_length: &u32,
-        avg_doc_length: f32,
-        params: &BM25Params,
-        n: u32,
+        document_lengths: &DocumentLengths,
+        scorer: &dyn Scorer,
     ) -> f32 {
-        compute_term_score(
-            self.get_current_doc_frequency(),
-            *current_doc_length,
-            avg_doc_length,
-            n,
-            self.term_frequency,
-            params,
-        )
+        let term_statistics = TermStatistics {
+            no_of_docs: document_lengths.get_no_of_docs(),
+            document_frequency: self.term_frequency,
+            collection_frequency: self.collection_frequency,
+            average_document_length: document_lengths.get_average_length(self.field),
+        };
+        self.weight
+            * scorer.score(
+                self.get_current_doc_frequency(),
+                document_lengths.get_length(self.field, self.get_current_doc_id() as u32),
+                &term_statistics,
+            )
     }
 
     pub fn get_max_score(&self) -> f32 {
@@ -135,9 +201,96 @@ impl TermIterator {
     }
 }
 
+impl PostingIterator for TermIterator<'_> {
+    fn get_current_doc_id(&self) -> u64 {
+        TermIterator::get_current_doc_id(self)
+    }
+
+    fn next(&mut self) -> bool {
+        TermIterator::next(self)
+    }
+
+    fn advance(&mut self, doc_id: u32) {
+        TermIterator::advance(self, doc_id)
+    }
+
+    fn is_complete(&mut self) -> bool {
+        TermIterator::is_complete(self)
+    }
+
+    fn get_current_doc_score(
+        &self,
+        document_lengths: &DocumentLengths,
+        scorer: &dyn Scorer,
+    ) -> f32 {
+        TermIterator::get_current_doc_score(self, document_lengths, scorer)
+    }
+
+    fn get_max_score(&self) -> f32 {
+        TermIterator::get_max_score(self)
+    }
+
+    fn move_block_max_iterator(&mut self, doc_id: u32) {
+        TermIterator::move_block_max_iterator(self, doc_id)
+    }
+
+    fn get_block_max_score(&mut self) -> f32 {
+        TermIterator::get_block_max_score(self)
+    }
+
+    fn get_block_max_last_doc_id(&mut self) -> u64 {
+        TermIterator::get_block_max_last_doc_id(self)
+    }
+}
+
+impl<T: PostingIterator + ?Sized> PostingIterator for Box<T> {
+    fn get_current_doc_id(&self) -> u64 {
+        (**self).get_current_doc_id()
+    }
+
+    fn next(&mut self) -> bool {
+        (**self).next()
+    }
+
+    fn advance(&mut self, doc_id: u32) {
+        (**self).advance(doc_id)
+    }
+
+    fn is_complete(&mut self) -> bool {
+        (**self).is_complete()
+    }
+
+    fn get_current_doc_score(
+        &self,
+        document_lengths: &DocumentLengths,
+        scorer: &dyn Scorer,
+    ) -> f32 {
+        (**self).get_current_doc_score(document_lengths, scorer)
+    }
+
+    fn get_max_score(&self) -> f32 {
+        (**self).get_max_score()
+    }
+
+    fn move_block_max_iterator(&mut self, doc_id: u32) {
+        (**self).move_block_max_iterator(doc_id)
+    }
+
+    fn get_block_max_score(&mut self) -> f32 {
+        (**self).get_block_max_score()
+    }
+
+    fn get_block_max_last_doc_id(&mut self) -> u64 {
+        (**self).get_block_max_last_doc_id()
+    }
+}
+
 #[cfg(test)]
 mod term_iterator_tests {
-    use crate::compressor::compressor::CompressionAlgorithm;
+    use crate::{
+        compressor::compressor::CompressionAlgorithm,
+        scoring::bm_25::{BM25, BM25Params},
+    };
 
     use super::*;
 
@@ -146,7 +299,7 @@ mod term_iterator_tests {
         doc_ids: Vec<u32>,
         frequencies: Vec<u32>,
         positions: Vec<Vec<u32>>,
-    ) -> Chunk {
+    ) -> Chunk<'static> {
         let mut chunk = Chunk::new(term, CompressionAlgorithm::VarByte);
 
         for (i, &doc_id) in doc_ids.iter().enumerate() {
@@ -166,9 +319,10 @@ mod term_iterator_tests {
         doc_ids: Vec<u32>,
         frequencies: Vec<u32>,
         positions: Vec<Vec<u32>>,
-    ) -> Chunk {
+    ) -> Chunk<'static> {
         let mut chunk = create_test_chunk(term_id, doc_ids, frequencies, positions);
-        let encoded = chunk.encode();
+        // the decoded chunk borrows the encoded bytes for the rest of the test
+        let encoded = chunk.encode().leak();
         let mut decoded_chunk = Chunk::new(term_id, CompressionAlgorithm::VarByte);
         decoded_chunk.decode(&encoded[4..]);
         decoded_chunk
@@ -400,10 +554,50 @@ mod term_iterator_tests {
         let mut iterator = TermIterator::new("test".to_string(), 1, 10, vec![chunk], 0.5, metadata);
         iterator.init();
 
-        let params = BM25Params { k1: 1.2, b: 0.75 };
-
-        let score = iterator.get_current_doc_score(&100, 100.0, &params, 1000);
+        let scorer = BM25 {
+            params: BM25Params { k1: 1.2, b: 0.75 },
+        };
+
+        let document_lengths = DocumentLengths {
+            body_lengths: &[100; 1000],
+            average_body_length: 100.0,
+            title_lengths: &[2; 1000],
+            average_title_length: 2.0,
+        };
+        let score = iterator.get_current_doc_score(&document_lengths, &scorer);
         assert!(score > 0.0);
+
+        // a title term is scored with the length of the title and weighted
+        let chunk = create_decoded_chunk(1, vec![100], vec![3], vec![vec![1, 2, 3]]);
+        let metadata = create_test_block_max_metadata(vec![100], vec![0.5]);
+        let mut title_iterator =
+            TermIterator::new("title:test".to_string(), 2, 10, vec![chunk], 0.5, metadata);
+        title_iterator.init();
+        assert_eq!(title_iterator.field, Field::Title);
+        let title_score = title_iterator.get_current_doc_score(&document_lengths, &scorer);
+        title_iterator.set_weight(2.0);
+        assert_eq!(
+            title_iterator.get_current_doc_score(&document_lengths, &scorer),
+            2.0 * title_score
+        );
+    }
+
+    #[test]
+    fn test_get_current_positions() {
+        let chunk = create_decoded_chunk(
+            1,
+            vec![100, 200],
+            vec![2, 3],
+            vec![vec![4, 9], vec![1, 2, 7]],
+        );
+        let metadata = create_test_block_max_metadata(vec![200], vec![0.5]);
+
+        let mut iterator = TermIterator::new("test".to_string(), 1, 10, vec![chunk], 0.5, metadata);
+        iterator.init();
+
+        assert_eq!(iterator.get_current_positions(), vec![4, 9]);
+        iterator.next();
+        assert_eq!(iterator.get_current_positions(), vec![1, 2, 7]);
     }
 
     #[test]
@@ -1,5 +1,3 @@
-use std::u64;
-
 use crate::utils::chunk_block_max_metadata::ChunkBlockMaxMetadata;
 #[derive(Debug)]
 pub struct BlockMaxIterator {
@@ -5,6 +5,7 @@ BM25 = Σ(t∈q) log((N - f_t + 0.5) / (f_t + 0.5)) * TF_BM25
 where TF_BM25 = (f_t,d * (k1 + 1)) / (f_t,d + k1 * ((1 - b) + (b * ℓ_d / ℓ_avg)))
 */
 /// BM25 parameters
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct BM25Params {
     pub k1: f32, // Term frequency saturation parameter (typical: 1.2)
     pub b: f32,  // Length normalization parameter (typical: 0.75)
@@ -16,6 +17,25 @@ impl Default for BM25Params {
     }
 }
 
+impl BM25Params {
+    // k1 can be any non negative number while b is the fraction of the length normalization
+    // which is applied, so it has to be between 0 and 1
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.k1.is_finite() && self.k1 >= 0.0) {
+            return Err(format!(
+                "k1 has to be a non negative number, not {}",
+                self.k1
+            ));
+        }
+        if !(0.0..=1.0).contains(&self.b) {
+            return Err(format!("b has to be between 0 and 1, not {}", self.b));
+        }
+        Ok(())
+    }
+}
+
+use crate::scoring::scorer::{Scorer, TermStatistics, get_length_ratio, rescale_by_idf};
+
 /* Compute IDF component for a term
 IDF = log((N - f_t + 0.5) / (f_t + 0.5))
 # Arguments
@@ -62,3 +82,105 @@ pub fn compute_term_score(
     let tf = compute_tf_bm25(f_td, doc_len, avg_doc_len, params);
     idf * tf
 }
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct BM25 {
+    pub params: BM25Params,
+}
+
+impl Scorer for BM25 {
+    fn score(&self, f_td: u32, doc_len: u32, term_statistics: &TermStatistics) -> f32 {
+        compute_term_score(
+            f_td,
+            doc_len,
+            term_statistics.average_document_length,
+            term_statistics.no_of_docs,
+            term_statistics.document_frequency,
+            &self.params,
+        )
+    }
+
+    // TF_BM25 is always below k1 + 1
+    fn rescale_max_score(
+        &self,
+        max_score: f32,
+        old_term_statistics: &TermStatistics,
+        new_term_statistics: &TermStatistics,
+    ) -> f32 {
+        rescale_by_idf(
+            max_score,
+            compute_idf(
+                old_term_statistics.no_of_docs,
+                old_term_statistics.document_frequency,
+            ),
+            compute_idf(
+                new_term_statistics.no_of_docs,
+                new_term_statistics.document_frequency,
+            ),
+            get_length_ratio(old_term_statistics, new_term_statistics),
+            self.params.k1 + 1.0,
+        )
+    }
+}
+
+/* BM25+ lower bounds the contribution of a term so that long documents containing the term
+are not scored below short documents which do not contain it, its idf is never negative.
+BM25+ = Σ(t∈q) log((N + 1) / f_t) * (TF_BM25 + δ)
+*/
+#[derive(Debug, Clone, Copy)]
+pub struct BM25Plus {
+    pub params: BM25Params,
+    pub delta: f32,
+}
+
+impl Default for BM25Plus {
+    fn default() -> Self {
+        BM25Plus {
+            params: BM25Params::default(),
+            delta: 1.0,
+        }
+    }
+}
+
+impl BM25Plus {
+    fn compute_idf(n: u32, f_t: u32) -> f32 {
+        ((n as f32 + 1.0) / f_t as f32).ln()
+    }
+}
+
+impl Scorer for BM25Plus {
+    fn score(&self, f_td: u32, doc_len: u32, term_statistics: &TermStatistics) -> f32 {
+        let idf = Self::compute_idf(
+            term_statistics.no_of_docs,
+            term_statistics.document_frequency,
+        );
+        let tf = compute_tf_bm25(
+            f_td,
+            doc_len,
+            term_statistics.average_document_length,
+            &self.params,
+        );
+        idf * (tf + self.delta)
+    }
+
+    fn rescale_max_score(
+        &self,
+        max_score: f32,
+        old_term_statistics: &TermStatistics,
+        new_term_statistics: &TermStatistics,
+    ) -> f32 {
+        rescale_by_idf(
+            max_score,
+            Self::compute_idf(
+                old_term_statistics.no_of_docs,
+                old_term_statistics.document_frequency,
+            ),
+            Self::compute_idf(
+                new_term_statistics.no_of_docs,
+                new_term_statistics.document_frequency,
+            ),
+            get_length_ratio(old_term_statistics, new_term_statistics),
+            self.params.k1 + 1.0 + self.delta,
+        )
+    }
+}
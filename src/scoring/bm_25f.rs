@@ -0,0 +1,92 @@
+/* BM25F scoring implementation
+
+The frequencies of a term in the fields of a document are normalized by the lengths of their
+fields, weighted by the fields and added up, the sum is then saturated once.
+BM25F = Σ(t∈q) log((N - f_t + 0.5) / (f_t + 0.5)) * (tf * (k1 + 1)) / (k1 + tf)
+where tf = Σ(f) w_f * f_t,f / ((1 - b) + (b * ℓ_f / ℓ_avg,f))
+*/
+use crate::scoring::{
+    bm_25::{BM25, BM25Params},
+    scorer::{Scorer, TermStatistics},
+};
+
+// The title and the body are indexed as separate terms, so the scorer only ever sees one field
+// and scores it like BM25, which is what the max scores of the index are computed with. The fields
+// of a word are combined at query time by the BM25F iterator with the helpers below, every field
+// is normalized with its own lengths and the b of the parameters.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct BM25F {
+    pub params: BM25Params,
+}
+
+impl BM25F {
+    pub fn get_normalized_tf(&self, f_tf: u32, field_len: u32, avg_field_len: f32) -> f32 {
+        let b = self.params.b;
+        f_tf as f32 / ((1.0 - b) + (b * field_len as f32 / avg_field_len))
+    }
+
+    // an infinite tf saturates at k1 + 1 instead of the NaN of infinity over infinity
+    pub fn saturate(&self, tf: f32) -> f32 {
+        let k1 = self.params.k1;
+        if tf.is_infinite() {
+            return k1 + 1.0;
+        }
+        (tf * (k1 + 1.0)) / (k1 + tf)
+    }
+
+    // The largest normalized tf which a max score of a single field allows, the inverse of the
+    // saturation. A score which can not be inverted, since its idf is not positive or it is as
+    // large as the saturation gets, does not bound the tf at all.
+    pub fn get_max_normalized_tf(&self, max_score: f32, idf: f32) -> f32 {
+        let k1 = self.params.k1;
+        if idf <= 0.0 {
+            return f32::INFINITY;
+        }
+        let max_saturated_tf = max_score / idf;
+        if max_saturated_tf >= k1 + 1.0 {
+            f32::INFINITY
+        } else if max_saturated_tf <= 0.0 {
+            0.0
+        } else {
+            k1 * max_saturated_tf / (k1 + 1.0 - max_saturated_tf)
+        }
+    }
+}
+
+impl Scorer for BM25F {
+    fn score(&self, f_td: u32, doc_len: u32, term_statistics: &TermStatistics) -> f32 {
+        BM25 {
+            params: self.params,
+        }
+        .score(f_td, doc_len, term_statistics)
+    }
+
+    fn rescale_max_score(
+        &self,
+        max_score: f32,
+        old_term_statistics: &TermStatistics,
+        new_term_statistics: &TermStatistics,
+    ) -> f32 {
+        BM25 {
+            params: self.params,
+        }
+        .rescale_max_score(max_score, old_term_statistics, new_term_statistics)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_max_normalized_tf_inverts_the_saturation() {
+        let bm25f = BM25F::default();
+        let idf = 1.5;
+        for tf in [0.0, 0.3, 1.0, 4.0, 25.0] {
+            let max_tf = bm25f.get_max_normalized_tf(idf * bm25f.saturate(tf), idf);
+            assert!((max_tf - tf).abs() < 1e-3 * tf.max(1.0));
+        }
+        assert_eq!(bm25f.get_max_normalized_tf(1.0, 0.0), f32::INFINITY);
+        assert_eq!(bm25f.saturate(f32::INFINITY), bm25f.params.k1 + 1.0);
+    }
+}
@@ -0,0 +1,61 @@
+/* DFR (divergence from randomness) scoring implementation with the InL2 model
+
+The informative content of a term is measured by how much its occurrences in a document diverge
+from a random distribution of the term, normalized by the length of the document.
+InL2 = Σ(t∈q) tfn / (tfn + 1) * log2((N + 1) / (f_t + 0.5))
+where tfn = f_t,d * log2(1 + c * ℓ_avg / ℓ_d)
+*/
+use crate::scoring::scorer::{Scorer, TermStatistics, get_length_ratio, rescale_by_idf};
+
+#[derive(Debug, Clone, Copy)]
+pub struct Dfr {
+    // the length normalization parameter, a larger c favours shorter documents
+    pub c: f32,
+}
+
+impl Default for Dfr {
+    fn default() -> Self {
+        Dfr { c: 1.0 }
+    }
+}
+
+impl Dfr {
+    fn compute_idf(n: u32, f_t: u32) -> f32 {
+        ((n as f32 + 1.0) / (f_t as f32 + 0.5)).log2()
+    }
+}
+
+impl Scorer for Dfr {
+    fn score(&self, f_td: u32, doc_len: u32, term_statistics: &TermStatistics) -> f32 {
+        let tfn = f_td as f32
+            * (1.0 + self.c * term_statistics.average_document_length / doc_len as f32).log2();
+        let idf = Self::compute_idf(
+            term_statistics.no_of_docs,
+            term_statistics.document_frequency,
+        );
+        tfn / (tfn + 1.0) * idf
+    }
+
+    // tfn / (tfn + 1) is always below 1, log2(1 + c * r * ℓ_avg / ℓ_d) is at most r times
+    // log2(1 + c * ℓ_avg / ℓ_d) so tfn grows by at most the length ratio
+    fn rescale_max_score(
+        &self,
+        max_score: f32,
+        old_term_statistics: &TermStatistics,
+        new_term_statistics: &TermStatistics,
+    ) -> f32 {
+        rescale_by_idf(
+            max_score,
+            Self::compute_idf(
+                old_term_statistics.no_of_docs,
+                old_term_statistics.document_frequency,
+            ),
+            Self::compute_idf(
+                new_term_statistics.no_of_docs,
+                new_term_statistics.document_frequency,
+            ),
+            get_length_ratio(old_term_statistics, new_term_statistics),
+            1.0,
+        )
+    }
+}
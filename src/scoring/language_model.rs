@@ -0,0 +1,61 @@
+/* Query likelihood scoring implementation with Dirichlet smoothing
+
+Documents are ranked by the likelihood of their language model generating the query, the model of
+a document is smoothed with the model of the collection.
+LM = Σ(t∈q) log(1 + f_t,d / (μ * p_t)) + log(μ / (ℓ_d + μ))
+where p_t = cf_t / (N * ℓ_avg) is the probability of the term in the collection
+
+The score of a term is clamped at 0 so that a document never loses score by containing a term,
+which keeps the max scores usable by the pruning algorithms.
+*/
+use crate::scoring::scorer::{Scorer, TermStatistics};
+
+#[derive(Debug, Clone, Copy)]
+pub struct DirichletLM {
+    pub mu: f32,
+}
+
+impl Default for DirichletLM {
+    fn default() -> Self {
+        DirichletLM { mu: 2000.0 }
+    }
+}
+
+impl DirichletLM {
+    fn compute_collection_probability(term_statistics: &TermStatistics) -> f32 {
+        let collection_length =
+            term_statistics.no_of_docs as f64 * term_statistics.average_document_length as f64;
+        (term_statistics.collection_frequency as f64 / collection_length) as f32
+    }
+}
+
+impl Scorer for DirichletLM {
+    fn score(&self, f_td: u32, doc_len: u32, term_statistics: &TermStatistics) -> f32 {
+        let collection_probability = Self::compute_collection_probability(term_statistics);
+        if collection_probability.is_nan() || collection_probability <= 0.0 {
+            return 0.0;
+        }
+        let score = (1.0 + f_td as f32 / (self.mu * collection_probability)).ln()
+            + (self.mu / (doc_len as f32 + self.mu)).ln();
+        score.max(0.0)
+    }
+
+    // The idf is not a factor of the score so the max score cannot be rescaled exactly. If the term
+    // became r times less likely in the collection, 1 + f / (μ * p / r) <= r * (1 + f / (μ * p)),
+    // so no score grew by more than log(r). The average document length only changes the score
+    // through p, a longer average length makes the term less likely.
+    fn rescale_max_score(
+        &self,
+        max_score: f32,
+        old_term_statistics: &TermStatistics,
+        new_term_statistics: &TermStatistics,
+    ) -> f32 {
+        let ratio = Self::compute_collection_probability(old_term_statistics)
+            / Self::compute_collection_probability(new_term_statistics);
+        if ratio > 1.0 {
+            max_score + ratio.ln()
+        } else {
+            max_score
+        }
+    }
+}
@@ -1 +1,112 @@
 pub mod bm_25;
+pub mod bm_25f;
+pub mod dfr;
+pub mod language_model;
+pub mod scorer;
+pub mod tf_idf;
+
+use std::{fmt, str::FromStr};
+
+use crate::scoring::{
+    bm_25::{BM25, BM25Params, BM25Plus},
+    bm_25f::BM25F,
+    dfr::Dfr,
+    language_model::DirichletLM,
+    scorer::Scorer,
+    tf_idf::TfIdf,
+};
+
+// The scoring model is chosen when the index is built since the max scores are computed with it,
+// an index can only be queried with the model and the BM25 parameters it was built with.
+// BM25, BM25+ and BM25F take k1 and b from the BM25 parameters, the other models ignore them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScoringModel {
+    BM25,
+    BM25Plus,
+    BM25F,
+    DirichletLM,
+    Dfr,
+    TfIdf,
+}
+
+impl ScoringModel {
+    pub fn get_scorer(&self, bm25_params: BM25Params) -> Box<dyn Scorer> {
+        match self {
+            ScoringModel::BM25 => Box::new(BM25 {
+                params: bm25_params,
+            }),
+            ScoringModel::BM25Plus => Box::new(BM25Plus {
+                params: bm25_params,
+                ..BM25Plus::default()
+            }),
+            ScoringModel::BM25F => Box::new(BM25F {
+                params: bm25_params,
+            }),
+            ScoringModel::DirichletLM => Box::new(DirichletLM::default()),
+            ScoringModel::Dfr => Box::new(Dfr::default()),
+            ScoringModel::TfIdf => Box::new(TfIdf),
+        }
+    }
+
+    pub fn uses_bm25_params(&self) -> bool {
+        matches!(
+            self,
+            ScoringModel::BM25 | ScoringModel::BM25Plus | ScoringModel::BM25F
+        )
+    }
+
+    // the id which is saved along with the term metadata
+    pub fn get_id(&self) -> u8 {
+        match self {
+            ScoringModel::BM25 => 0,
+            ScoringModel::BM25Plus => 1,
+            ScoringModel::BM25F => 2,
+            ScoringModel::DirichletLM => 3,
+            ScoringModel::Dfr => 4,
+            ScoringModel::TfIdf => 5,
+        }
+    }
+
+    pub fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(ScoringModel::BM25),
+            1 => Some(ScoringModel::BM25Plus),
+            2 => Some(ScoringModel::BM25F),
+            3 => Some(ScoringModel::DirichletLM),
+            4 => Some(ScoringModel::Dfr),
+            5 => Some(ScoringModel::TfIdf),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ScoringModel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ScoringModel::BM25 => "BM25",
+            ScoringModel::BM25Plus => "BM25+",
+            ScoringModel::BM25F => "BM25F",
+            ScoringModel::DirichletLM => "Query Likelihood (Dirichlet LM)",
+            ScoringModel::Dfr => "DFR (InL2)",
+            ScoringModel::TfIdf => "TF-IDF",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// the names which are used for the models in the config file
+impl FromStr for ScoringModel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bm25" => Ok(ScoringModel::BM25),
+            "bm25plus" => Ok(ScoringModel::BM25Plus),
+            "bm25f" => Ok(ScoringModel::BM25F),
+            "dirichlet" => Ok(ScoringModel::DirichletLM),
+            "dfr" => Ok(ScoringModel::Dfr),
+            "tfidf" => Ok(ScoringModel::TfIdf),
+            _ => Err(format!("unknown scoring model {}", s)),
+        }
+    }
+}
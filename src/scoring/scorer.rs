@@ -0,0 +1,192 @@
+// The statistics of a term over the whole collection which the score of a document depends on
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TermStatistics {
+    pub no_of_docs: u32,
+    // the no of documents containing the term
+    pub document_frequency: u32,
+    // the no of occurrences of the term across all the documents
+    pub collection_frequency: u64,
+    pub average_document_length: f32,
+}
+
+// A scoring model scores a document for a single query term, the score of a document for a query
+// is the sum of the scores of its terms. The max scores and block max scores which the pruning
+// algorithms rely on are computed with the same scorer when the index is written.
+pub trait Scorer: Send + Sync {
+    // f_td is the no of occurrences of the term in the document
+    fn score(&self, f_td: u32, doc_len: u32, term_statistics: &TermStatistics) -> f32;
+
+    // The max scores of a segment were computed with the statistics at the time it was written,
+    // this brings a max score up to date with the current statistics. The result has to stay
+    // an upper bound of the current scores of the documents, otherwise documents can be skipped.
+    // The average document length changes along with the rest of the statistics, an old average
+    // length of 0 means it is not known.
+    fn rescale_max_score(
+        &self,
+        max_score: f32,
+        old_term_statistics: &TermStatistics,
+        new_term_statistics: &TermStatistics,
+    ) -> f32;
+}
+
+// The length normalization of BM25 and DFR scales the tf of a document by at most r once the
+// average length grew r times, since their tf is concave and 0 for no occurrences. An average
+// length which shrank only lowers the scores, an unknown one can scale them without bound.
+pub fn get_length_ratio(
+    old_term_statistics: &TermStatistics,
+    new_term_statistics: &TermStatistics,
+) -> f32 {
+    let ratio =
+        new_term_statistics.average_document_length / old_term_statistics.average_document_length;
+    if ratio.is_nan() {
+        f32::INFINITY
+    } else {
+        ratio.max(1.0)
+    }
+}
+
+// For the models where the idf is a factor of every score the max scores are rescaled by swapping
+// the old idf for the current one and scaling the rest of the score by the length ratio. An old
+// idf which is not positive cannot be divided out, so the largest score the term could possibly
+// have is used instead, max_tf_score is the largest value the rest of the score can take.
+pub fn rescale_by_idf(
+    max_score: f32,
+    old_idf: f32,
+    new_idf: f32,
+    length_ratio: f32,
+    max_tf_score: f32,
+) -> f32 {
+    if old_idf == new_idf && length_ratio == 1.0 {
+        max_score
+    } else if new_idf <= 0.0 {
+        0.0
+    } else if old_idf > 0.0 {
+        let max_tf = max_score / old_idf;
+        if length_ratio == 1.0 {
+            max_tf * new_idf
+        } else {
+            // f32::min ignores the NaN of 0 times an infinite ratio
+            (max_tf * length_ratio).min(max_tf_score) * new_idf
+        }
+    } else {
+        new_idf * max_tf_score
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::scoring::{ScoringModel, bm_25::BM25Params};
+
+    use super::*;
+
+    fn statistics(
+        no_of_docs: u32,
+        document_frequency: u32,
+        collection_frequency: u64,
+    ) -> TermStatistics {
+        TermStatistics {
+            no_of_docs,
+            document_frequency,
+            collection_frequency,
+            average_document_length: 100.0,
+        }
+    }
+
+    fn statistics_with_length(
+        no_of_docs: u32,
+        document_frequency: u32,
+        collection_frequency: u64,
+        average_document_length: f32,
+    ) -> TermStatistics {
+        TermStatistics {
+            average_document_length,
+            ..statistics(no_of_docs, document_frequency, collection_frequency)
+        }
+    }
+
+    const SCORING_MODELS: [ScoringModel; 6] = [
+        ScoringModel::BM25,
+        ScoringModel::BM25Plus,
+        ScoringModel::BM25F,
+        ScoringModel::DirichletLM,
+        ScoringModel::Dfr,
+        ScoringModel::TfIdf,
+    ];
+
+    #[test]
+    fn test_scores_grow_with_term_frequency() {
+        let term_statistics = statistics(1000, 10, 40);
+        for scoring_model in SCORING_MODELS {
+            let scorer = scoring_model.get_scorer(BM25Params::default());
+            let mut previous_score = 0.0;
+            for f_td in [1, 2, 5, 20] {
+                let score = scorer.score(f_td, 100, &term_statistics);
+                assert!(score > previous_score, "{}", scoring_model);
+                previous_score = score;
+            }
+            // a longer document with the same no of occurrences is never better
+            assert!(
+                scorer.score(5, 400, &term_statistics) <= scorer.score(5, 50, &term_statistics),
+                "{}",
+                scoring_model
+            );
+        }
+    }
+
+    // The documents are scored with the old statistics to get the max score, the rescaled max score
+    // has to be at least the score of every document with the new statistics
+    #[test]
+    fn test_rescaled_max_score_is_an_upper_bound() {
+        let documents = [(1, 20), (3, 100), (8, 150), (2, 400), (1, 1), (40, 3000)];
+        let changes = [
+            (statistics(1000, 10, 40), statistics(1200, 30, 90)),
+            (statistics(1000, 10, 40), statistics(5000, 11, 41)),
+            (statistics(10, 6, 20), statistics(40, 6, 20)),
+            (statistics(40, 3, 3), statistics(40, 30, 200)),
+            // the average length grows when long documents are added, which raises the scores
+            // of the documents of the old segments
+            (
+                statistics_with_length(1000, 10, 40, 50.0),
+                statistics_with_length(1200, 10, 40, 400.0),
+            ),
+            (
+                statistics_with_length(1000, 10, 40, 20.0),
+                statistics_with_length(3000, 30, 90, 150.0),
+            ),
+            (
+                statistics_with_length(10, 6, 20, 10.0),
+                statistics_with_length(40, 6, 20, 1000.0),
+            ),
+            (
+                statistics_with_length(1000, 10, 40, 400.0),
+                statistics_with_length(1200, 10, 40, 50.0),
+            ),
+            // an unknown average length
+            (
+                statistics_with_length(1000, 10, 40, 0.0),
+                statistics_with_length(1200, 10, 40, 100.0),
+            ),
+        ];
+        for scoring_model in SCORING_MODELS {
+            let scorer = scoring_model.get_scorer(BM25Params::default());
+            for (old_term_statistics, new_term_statistics) in &changes {
+                let max_score = documents
+                    .iter()
+                    .map(|&(f_td, doc_len)| scorer.score(f_td, doc_len, old_term_statistics))
+                    .fold(f32::MIN, f32::max);
+                let rescaled_max_score =
+                    scorer.rescale_max_score(max_score, old_term_statistics, new_term_statistics);
+                for &(f_td, doc_len) in &documents {
+                    let score = scorer.score(f_td, doc_len, new_term_statistics);
+                    assert!(
+                        score <= rescaled_max_score + 1e-4,
+                        "{} {} > {}",
+                        scoring_model,
+                        score,
+                        rescaled_max_score
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,47 @@
+/* TF-IDF scoring implementation with a logarithmic term frequency
+
+TF-IDF = Σ(t∈q) (1 + log(f_t,d)) * log(1 + N / f_t)
+*/
+use crate::scoring::scorer::{Scorer, TermStatistics, rescale_by_idf};
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TfIdf;
+
+impl TfIdf {
+    fn compute_idf(n: u32, f_t: u32) -> f32 {
+        (1.0 + n as f32 / f_t as f32).ln()
+    }
+}
+
+impl Scorer for TfIdf {
+    fn score(&self, f_td: u32, _doc_len: u32, term_statistics: &TermStatistics) -> f32 {
+        let tf = 1.0 + (f_td as f32).ln();
+        tf * Self::compute_idf(
+            term_statistics.no_of_docs,
+            term_statistics.document_frequency,
+        )
+    }
+
+    // the idf is always positive so the max score never has to fall back to a bound of the tf
+    fn rescale_max_score(
+        &self,
+        max_score: f32,
+        old_term_statistics: &TermStatistics,
+        new_term_statistics: &TermStatistics,
+    ) -> f32 {
+        rescale_by_idf(
+            max_score,
+            Self::compute_idf(
+                old_term_statistics.no_of_docs,
+                old_term_statistics.document_frequency,
+            ),
+            Self::compute_idf(
+                new_term_statistics.no_of_docs,
+                new_term_statistics.document_frequency,
+            ),
+            // the length of a document does not matter
+            1.0,
+            f32::MAX,
+        )
+    }
+}
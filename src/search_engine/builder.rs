@@ -0,0 +1,451 @@
+use std::{path::PathBuf, sync::Arc};
+
+use crate::{
+    compressor::compressor::CompressionAlgorithm,
+    indexer::document_source::document_source::DocumentSource,
+    parser::{analyzer::AnalyzerConfig, synonyms::SynonymDictionary},
+    query_processor::retrieval_algorithms::QueryAlgorithm,
+    scoring::{ScoringModel, bm_25::BM25Params},
+    search_engine::{error::SearchEngineError, search_engine::SearchEngine},
+    utils::field::DEFAULT_TITLE_WEIGHT,
+};
+
+// Configures a SearchEngine over an index directory. The dataset directory is only needed
+// to build the index, an index which has already been saved can be opened without it.
+//
+// let mut search_engine = SearchEngineBuilder::new("index")
+//     .dataset_directory_path("wikipedia")
+//     .query_algorithm(QueryAlgorithm::BlockMaxWand)
+//     .index()?;
+// let results = search_engine.query("stephen king", 10, 0)?;
+#[derive(Debug, Clone)]
+pub struct SearchEngineBuilder {
+    index_directory_path: PathBuf,
+    dataset_directory_path: Option<PathBuf>,
+    compression_algorithm: CompressionAlgorithm,
+    query_algorithm: QueryAlgorithm,
+    scoring_model: ScoringModel,
+    bm25_params: BM25Params,
+    title_weight: f32,
+    include_positions: bool,
+    merge_factor: usize,
+    document_source: Option<Arc<dyn DocumentSource>>,
+    analyzer_config: AnalyzerConfig,
+    synonyms: SynonymDictionary,
+}
+
+impl SearchEngineBuilder {
+    pub fn new<P: Into<PathBuf>>(index_directory_path: P) -> Self {
+        Self {
+            index_directory_path: index_directory_path.into(),
+            dataset_directory_path: None,
+            compression_algorithm: CompressionAlgorithm::Simple16,
+            query_algorithm: QueryAlgorithm::Wand,
+            scoring_model: ScoringModel::BM25,
+            bm25_params: BM25Params::default(),
+            title_weight: DEFAULT_TITLE_WEIGHT,
+            include_positions: false,
+            merge_factor: 10,
+            document_source: None,
+            analyzer_config: AnalyzerConfig::default(),
+            synonyms: SynonymDictionary::default(),
+        }
+    }
+
+    pub fn dataset_directory_path<P: Into<PathBuf>>(mut self, dataset_directory_path: P) -> Self {
+        self.dataset_directory_path = Some(dataset_directory_path.into());
+        self
+    }
+
+    // The compression algorithm has to be the same one which the index was built with
+    pub fn compression_algorithm(mut self, compression_algorithm: CompressionAlgorithm) -> Self {
+        self.compression_algorithm = compression_algorithm;
+        self
+    }
+
+    pub fn query_algorithm(mut self, query_algorithm: QueryAlgorithm) -> Self {
+        self.query_algorithm = query_algorithm;
+        self
+    }
+
+    // The scoring model has to be the same one which the index was built with
+    pub fn scoring_model(mut self, scoring_model: ScoringModel) -> Self {
+        self.scoring_model = scoring_model;
+        self
+    }
+
+    // k1 controls how quickly the score of a term saturates with its frequency in a document and b
+    // how much the length of a document is normalized, they are fixed when the index is built.
+    // The parameters are checked when the search engine is built.
+    pub fn bm25_params(mut self, k1: f32, b: f32) -> Self {
+        self.bm25_params = BM25Params { k1, b };
+        self
+    }
+
+    // How much a match in the title of a document counts relative to a match in its body, it is
+    // only applied at query time so an index can be opened with any title weight
+    pub fn title_weight(mut self, title_weight: f32) -> Self {
+        self.title_weight = title_weight;
+        self
+    }
+
+    pub fn include_positions(mut self, include_positions: bool) -> Self {
+        self.include_positions = include_positions;
+        self
+    }
+
+    pub fn merge_factor(mut self, merge_factor: usize) -> Self {
+        self.merge_factor = merge_factor;
+        self
+    }
+
+    // The source which reads the documents of the dataset, such as
+    // DocumentFormat::Csv.get_source(FieldMapping::default()). It is only needed to build an index.
+    pub fn document_source(mut self, document_source: Arc<dyn DocumentSource>) -> Self {
+        self.document_source = Some(document_source);
+        self
+    }
+
+    // How the text is split into terms, such as the stemmer and the stop words. The analyzer is
+    // saved with the index, an index is always opened with the analyzer it was built with.
+    pub fn analyzer(mut self, analyzer_config: AnalyzerConfig) -> Self {
+        self.analyzer_config = analyzer_config;
+        self
+    }
+
+    // The synonyms which the words of the queries are expanded with, such as the ones read with
+    // SynonymDictionary::load. They are not saved with the index.
+    pub fn synonyms(mut self, synonyms: SynonymDictionary) -> Self {
+        self.synonyms = synonyms;
+        self
+    }
+
+    // Creates the search engine without building or loading an index
+    pub fn build(self) -> Result<SearchEngine, SearchEngineError> {
+        let dataset_directory_path = self.dataset_directory_path.unwrap_or_default();
+        let mut search_engine = SearchEngine::new(
+            dataset_directory_path.to_string_lossy().into_owned(),
+            self.compression_algorithm,
+            self.query_algorithm,
+            self.index_directory_path.to_string_lossy().into_owned(),
+        )?;
+        search_engine.set_scoring_model(self.scoring_model);
+        search_engine.set_bm25_params(self.bm25_params)?;
+        search_engine.set_title_weight(self.title_weight)?;
+        search_engine.set_include_positions(self.include_positions);
+        search_engine.set_merge_factor(self.merge_factor)?;
+        search_engine.set_analyzer_config(self.analyzer_config)?;
+        search_engine.set_synonyms(self.synonyms)?;
+        if let Some(document_source) = self.document_source {
+            search_engine.set_document_source(document_source);
+        }
+        Ok(search_engine)
+    }
+
+    // Builds the index from the dataset and saves it, so that it can be opened later
+    pub fn index(self) -> Result<SearchEngine, SearchEngineError> {
+        let mut search_engine = self.build()?;
+        search_engine.build_index()?;
+        search_engine.save_index()?;
+        Ok(search_engine)
+    }
+
+    // Opens an index which has been saved before
+    pub fn open(self) -> Result<SearchEngine, SearchEngineError> {
+        let mut search_engine = self.build()?;
+        search_engine.load_index()?;
+        Ok(search_engine)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use crate::{
+        indexer::document_source::document_source::{DocumentFormat, FieldMapping},
+        parser::{analyzer::Tokenizer, stemmer::Stemmer},
+        utils::test_utils::write_test_dataset,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_index_and_open() {
+        let temp_dir = TempDir::new().unwrap();
+        let dataset_dir = temp_dir.path().join("dataset");
+        let index_dir = temp_dir.path().join("index");
+        write_test_dataset(
+            &dataset_dir,
+            &[
+                ("Misery", "misery is a novel by stephen king"),
+                ("River", "a quiet river flows past the old mill"),
+                ("Garden", "the garden was full of roses in spring"),
+            ],
+        );
+
+        assert!(matches!(
+            SearchEngineBuilder::new(&index_dir).open(),
+            Err(SearchEngineError::IndexNotFound(_))
+        ));
+        assert!(matches!(
+            SearchEngineBuilder::new(&index_dir)
+                .dataset_directory_path(temp_dir.path().join("missing"))
+                .build(),
+            Err(SearchEngineError::DatasetNotFound(_))
+        ));
+
+        SearchEngineBuilder::new(&index_dir)
+            .dataset_directory_path(&dataset_dir)
+            .index()
+            .unwrap();
+
+        let mut search_engine = SearchEngineBuilder::new(&index_dir).open().unwrap();
+        let results = search_engine.query("stephen king", 10, 0).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].doc_id, 1);
+        assert_eq!(results[0].document.doc_name, "Misery");
+        assert_eq!(search_engine.get_index_metadata().unwrap().no_of_docs, 3);
+    }
+
+    #[test]
+    fn test_scoring_model() {
+        let temp_dir = TempDir::new().unwrap();
+        let dataset_dir = temp_dir.path().join("dataset");
+        let index_dir = temp_dir.path().join("index");
+        write_test_dataset(
+            &dataset_dir,
+            &[
+                ("Misery", "misery is a novel by stephen king"),
+                ("Carrie", "carrie is the first novel by stephen king"),
+                ("River", "a quiet river flows past the old mill"),
+            ],
+        );
+
+        SearchEngineBuilder::new(&index_dir)
+            .dataset_directory_path(&dataset_dir)
+            .scoring_model(ScoringModel::DirichletLM)
+            .query_algorithm(QueryAlgorithm::BlockMaxWand)
+            .index()
+            .unwrap();
+
+        let mut search_engine = SearchEngineBuilder::new(&index_dir)
+            .scoring_model(ScoringModel::DirichletLM)
+            .open()
+            .unwrap();
+        let results = search_engine.query("misery novel", 10, 0).unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].document.doc_name, "Misery");
+        assert_eq!(
+            search_engine.get_index_metadata().unwrap().scoring_model,
+            ScoringModel::DirichletLM.to_string()
+        );
+
+        // the max scores of the index were computed with a different model
+        assert!(matches!(
+            SearchEngineBuilder::new(&index_dir).open(),
+            Err(SearchEngineError::IncompatibleIndex(_))
+        ));
+    }
+
+    #[test]
+    fn test_bm25_params() {
+        let temp_dir = TempDir::new().unwrap();
+        let dataset_dir = temp_dir.path().join("dataset");
+        let index_dir = temp_dir.path().join("index");
+        write_test_dataset(
+            &dataset_dir,
+            &[
+                ("Misery", "misery is a novel by stephen king"),
+                ("River", "a quiet river flows past the old mill"),
+                ("Garden", "the garden was full of roses in spring"),
+            ],
+        );
+
+        assert!(matches!(
+            SearchEngineBuilder::new(&index_dir)
+                .bm25_params(1.2, 1.5)
+                .build(),
+            Err(SearchEngineError::InvalidConfiguration(_))
+        ));
+
+        SearchEngineBuilder::new(&index_dir)
+            .dataset_directory_path(&dataset_dir)
+            .bm25_params(0.9, 0.4)
+            .index()
+            .unwrap();
+
+        let mut search_engine = SearchEngineBuilder::new(&index_dir)
+            .bm25_params(0.9, 0.4)
+            .open()
+            .unwrap();
+        assert_eq!(search_engine.query("misery", 10, 0).unwrap().len(), 1);
+        assert_eq!(search_engine.get_index_metadata().unwrap().bm25_k1, 0.9);
+
+        // the block max scores were computed with k1 = 0.9 and b = 0.4
+        assert!(matches!(
+            SearchEngineBuilder::new(&index_dir).open(),
+            Err(SearchEngineError::IncompatibleIndex(_))
+        ));
+        // the parameters do not matter to the other models, but the model does
+        assert!(matches!(
+            SearchEngineBuilder::new(&index_dir)
+                .scoring_model(ScoringModel::Dfr)
+                .open(),
+            Err(SearchEngineError::IncompatibleIndex(_))
+        ));
+    }
+
+    #[test]
+    fn test_merge_factor() {
+        let temp_dir = TempDir::new().unwrap();
+        let index_dir = temp_dir.path().join("index");
+        for merge_factor in [0, 1] {
+            assert!(matches!(
+                SearchEngineBuilder::new(&index_dir)
+                    .merge_factor(merge_factor)
+                    .build(),
+                Err(SearchEngineError::InvalidConfiguration(_))
+            ));
+        }
+        let mut search_engine = SearchEngineBuilder::new(&index_dir)
+            .merge_factor(2)
+            .build()
+            .unwrap();
+        assert!(search_engine.set_merge_factor(1).is_err());
+    }
+
+    #[test]
+    fn test_document_source() {
+        let temp_dir = TempDir::new().unwrap();
+        let dataset_dir = temp_dir.path().join("dataset");
+        let index_dir = temp_dir.path().join("index");
+        std::fs::create_dir_all(&dataset_dir).unwrap();
+        std::fs::write(
+            dataset_dir.join("books.csv"),
+            "name,link,summary\n\
+             Misery,https://example.com/misery,\"misery is a novel by stephen king\"\n\
+             River,https://example.com/river,a quiet river flows past the old mill\n\
+             Garden,https://example.com/garden,the garden was full of roses in spring\n",
+        )
+        .unwrap();
+        // the files of the other formats are skipped
+        std::fs::write(dataset_dir.join("notes.txt"), "stephen king wrote carrie").unwrap();
+
+        let field_mapping = FieldMapping {
+            title: "name".to_string(),
+            url: "link".to_string(),
+            text: "summary".to_string(),
+        };
+        let mut search_engine = SearchEngineBuilder::new(&index_dir)
+            .dataset_directory_path(&dataset_dir)
+            .document_source(DocumentFormat::Csv.get_source(field_mapping))
+            .index()
+            .unwrap();
+        let results = search_engine.query("stephen king", 10, 0).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].document.doc_name, "Misery");
+        assert_eq!(results[0].document.doc_url, "https://example.com/misery");
+        assert_eq!(search_engine.get_index_metadata().unwrap().no_of_docs, 3);
+    }
+
+    #[test]
+    fn test_analyzer() {
+        let temp_dir = TempDir::new().unwrap();
+        let dataset_dir = temp_dir.path().join("dataset");
+        let index_dir = temp_dir.path().join("index");
+        write_test_dataset(
+            &dataset_dir,
+            &[
+                ("Marathon", "she runs a marathon every year"),
+                ("Art", "a state-of-the-art telescope"),
+                ("River", "a quiet river flows past the old mill"),
+            ],
+        );
+
+        assert!(matches!(
+            SearchEngineBuilder::new(&index_dir)
+                .analyzer(AnalyzerConfig {
+                    min_token_length: 0,
+                    ..AnalyzerConfig::default()
+                })
+                .build(),
+            Err(SearchEngineError::InvalidConfiguration(_))
+        ));
+
+        SearchEngineBuilder::new(&index_dir)
+            .dataset_directory_path(&dataset_dir)
+            .include_positions(true)
+            .analyzer(AnalyzerConfig {
+                stemmer: Stemmer::Porter,
+                stop_words: vec!["a".to_string(), "every".to_string()],
+                split_hyphens: true,
+                ..AnalyzerConfig::default()
+            })
+            .index()
+            .unwrap();
+
+        // the index is opened with the analyzer it was built with rather than the default one
+        let mut search_engine = SearchEngineBuilder::new(&index_dir).open().unwrap();
+        assert_eq!(search_engine.get_analyzer_config().stemmer, Stemmer::Porter);
+        assert_eq!(
+            search_engine.get_index_metadata().unwrap().stemmer,
+            "porter"
+        );
+        let results = search_engine.query("running marathons", 10, 0).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].document.doc_name, "Marathon");
+        assert_eq!(search_engine.get_document_frequency("running"), Some(1));
+        assert_eq!(search_engine.get_document_frequency("runs"), Some(1));
+
+        // the parts of a hyphenated word are indexed at positions of their own
+        let results = search_engine.query("\"state of the art\"", 10, 0).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].document.doc_name, "Art");
+        let results = search_engine.query("art", 10, 0).unwrap();
+        assert_eq!(results.len(), 1);
+    }
+
+    #[test]
+    fn test_unicode_tokenizer() {
+        let temp_dir = TempDir::new().unwrap();
+        let dataset_dir = temp_dir.path().join("dataset");
+        let index_dir = temp_dir.path().join("index");
+        write_test_dataset(
+            &dataset_dir,
+            &[
+                ("Zürich", "Zürich is the largest city of Switzerland"),
+                ("Tokyo", "東京都は日本の首都です"),
+                ("Moscow", "Москва is the capital of Russia"),
+                ("River", "a quiet river flows past the old mill"),
+            ],
+        );
+
+        SearchEngineBuilder::new(&index_dir)
+            .dataset_directory_path(&dataset_dir)
+            .analyzer(AnalyzerConfig {
+                tokenizer: Tokenizer::Unicode,
+                fold_diacritics: true,
+                ..AnalyzerConfig::default()
+            })
+            .index()
+            .unwrap();
+
+        let mut search_engine = SearchEngineBuilder::new(&index_dir).open().unwrap();
+        assert_eq!(
+            search_engine.get_index_metadata().unwrap().tokenizer,
+            "unicode"
+        );
+        for (query, doc_name) in [
+            ("zurich", "Zürich"),
+            ("ZÜRICH", "Zürich"),
+            ("日本", "Tokyo"),
+            ("首都", "Tokyo"),
+            ("moskva", "Moscow"),
+        ] {
+            let results = search_engine.query(query, 10, 0).unwrap();
+            assert_eq!(results.len(), 1, "{}", query);
+            assert_eq!(results[0].document.doc_name, doc_name, "{}", query);
+        }
+    }
+}
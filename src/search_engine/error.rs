@@ -0,0 +1,54 @@
+use std::{error::Error, fmt, io, path::PathBuf};
+
+#[derive(Debug)]
+pub enum SearchEngineError {
+    // the dataset directory the index is built from does not exist
+    DatasetNotFound(PathBuf),
+    // a file of the saved index is missing, the index has to be built and saved first
+    IndexNotFound(PathBuf),
+    // the saved index can not be used with the configuration of the search engine
+    IncompatibleIndex(String),
+    // a file of the saved index is truncated or does not match its checksum
+    CorruptIndex(String),
+    // a setting of the search engine is out of its range
+    InvalidConfiguration(String),
+    InvalidQuery(String),
+    Io(io::Error),
+}
+
+impl fmt::Display for SearchEngineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SearchEngineError::DatasetNotFound(path) => {
+                write!(f, "the dataset {:?} does not exist", path)
+            }
+            SearchEngineError::IndexNotFound(path) => {
+                write!(f, "the index file {:?} does not exist", path)
+            }
+            SearchEngineError::IncompatibleIndex(message) => {
+                write!(f, "incompatible index: {}", message)
+            }
+            SearchEngineError::CorruptIndex(message) => write!(f, "corrupt index: {}", message),
+            SearchEngineError::InvalidConfiguration(message) => {
+                write!(f, "invalid configuration: {}", message)
+            }
+            SearchEngineError::InvalidQuery(message) => write!(f, "invalid query: {}", message),
+            SearchEngineError::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for SearchEngineError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SearchEngineError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for SearchEngineError {
+    fn from(e: io::Error) -> Self {
+        SearchEngineError::Io(e)
+    }
+}
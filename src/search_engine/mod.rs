@@ -1 +1,4 @@
+pub mod builder;
+pub mod error;
 pub mod search_engine;
+pub mod searcher;
@@ -1,31 +1,68 @@
 use std::{
     fs::{self, File},
-    io::{self, BufReader, BufWriter, Error, ErrorKind},
+    io::{self, BufReader, BufWriter, Error, ErrorKind, Write},
     path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    thread::JoinHandle,
 };
 
 use search_engine_cache::CacheType;
 
 use crate::{
     compressor::compressor::CompressionAlgorithm,
-    in_memory_index_metadata::in_memory_index_metadata::InMemoryIndexMetadata,
-    indexer::indexer::Indexer,
-    parser::parser::Parser,
+    in_memory_index_metadata::in_memory_index_metadata::{
+        InMemoryIndexMetadata, merge_sorted_terms,
+    },
+    indexer::{
+        build_manifest::BuildReport,
+        document_source::document_source::{DocumentFormat, DocumentSource, FieldMapping},
+        indexer::Indexer,
+    },
+    parser::{analyzer::AnalyzerConfig, parser::Parser, synonyms::SynonymDictionary},
     query_processor::{query_processor::QueryProcessor, retrieval_algorithms::QueryAlgorithm},
+    scoring::{ScoringModel, bm_25::BM25Params},
+    search_engine::{
+        error::SearchEngineError,
+        searcher::{QueryCache, Searcher},
+    },
+    segment::{
+        merge_policy::TieredMergePolicy,
+        segment::{
+            Segment, load_base_segment_metadata, load_segment_ids, load_terms, save_segment_ids,
+        },
+        segment_merger::SegmentMerger,
+        segment_verifier::{IndexReport, SegmentReport, rebuild_segment, verify_segment},
+    },
     utils::{
-        paths::{get_inverted_index_path, get_save_doc_metadata_path, get_save_term_metadata_path},
-        types::{DocumentMetadata, SearchEngineMetadata},
+        block::DEFAULT_MAX_BLOCK_SIZE,
+        field::DEFAULT_TITLE_WEIGHT,
+        index_format::InvertedIndexFooter,
+        paths::{
+            get_inverted_index_path, get_save_analyzer_path, get_save_bk_tree_path,
+            get_save_deleted_documents_path, get_save_doc_metadata_path,
+            get_save_segment_metadata_path, get_save_term_metadata_path, get_save_terms_path,
+            get_segment_directory_path, get_segments_directory_path,
+        },
+        types::{SearchEngineMetadata, SearchResult},
     },
 };
 
 pub struct SearchEngine {
-    query_cache: CacheType<String, Vec<(u32, f32)>>,
+    query_cache: QueryCache,
     query_processor: QueryProcessor,
     parser: Parser,
     indexer: Indexer,
-    in_memory_index_metadata: InMemoryIndexMetadata,
+    // the base segment always comes first, the rest are in the order of their doc ids
+    segments: Vec<Segment>,
+    next_segment_id: u32,
+    merge_policy: TieredMergePolicy,
+    // the ids of the segments which are being merged along with the thread merging them
+    segment_merge: Option<(Vec<u32>, JoinHandle<io::Result<Segment>>)>,
     compression_algorithm: CompressionAlgorithm,
     query_algorithm: QueryAlgorithm,
+    scoring_model: ScoringModel,
+    bm25_params: BM25Params,
+    title_weight: f32,
     dataset_directory_path: PathBuf,
     index_directory_path: PathBuf,
 }
@@ -36,30 +73,22 @@ impl SearchEngine {
         compression_algorithm: CompressionAlgorithm,
         query_algorithm: QueryAlgorithm,
         index_directory_path: String,
-    ) -> Result<Self, Error> {
+    ) -> Result<Self, SearchEngineError> {
+        // an empty dataset path is allowed so that a saved index can be opened without its dataset,
+        // a dataset can also be a single file such as a dump of Wikipedia
         let dataset_path = Path::new(&dataset_directory_path).to_path_buf();
-        if !dataset_path.exists() || !dataset_path.is_dir() {
-            return Err(Error::new(
-                ErrorKind::Other,
-                "index directory path does not exist, please initialize it ",
-            ));
+        if !dataset_directory_path.is_empty() && !dataset_path.exists() {
+            return Err(SearchEngineError::DatasetNotFound(dataset_path));
         }
         let index_path = Path::new(&index_directory_path).to_path_buf();
-        if !index_path.exists() {
-            fs::create_dir_all(index_path.clone()).unwrap();
-        } else if index_path.is_file() {
-            fs::create_dir_all(index_path.clone()).unwrap();
-        }
+        // an index path which is a file can not be created as a directory and is reported
+        fs::create_dir_all(&index_path)?;
         let inverted_index_path = get_inverted_index_path(index_directory_path);
-        if !inverted_index_path.exists() {
-            if let Err(e) = File::create_new(inverted_index_path) {
-                if e.kind() != ErrorKind::AlreadyExists {
-                    return Err(io::Error::new(
-                        io::ErrorKind::Other,
-                        format!("Error creating inverted index file: {}", e),
-                    ));
-                }
-            }
+        if !inverted_index_path.exists()
+            && let Err(e) = File::create_new(inverted_index_path)
+            && e.kind() != ErrorKind::AlreadyExists
+        {
+            return Err(SearchEngineError::Io(e));
         }
         let parser = Parser::new()?;
         let mut indexer = Indexer::new(
@@ -73,99 +102,770 @@ impl SearchEngine {
             index_path.clone(),
             compression_algorithm.clone(),
             query_algorithm.clone(),
+            ScoringModel::BM25,
+            BM25Params::default(),
         )?;
 
         Ok(Self {
-            query_cache: CacheType::new_landlord(10000),
+            query_cache: Mutex::new(CacheType::new_landlord(10000)),
             query_processor,
             parser,
-            in_memory_index_metadata: InMemoryIndexMetadata::new(),
+            segments: vec![Segment::new(0, 1, 0, InMemoryIndexMetadata::new())],
+            next_segment_id: 1,
+            merge_policy: TieredMergePolicy::new(10),
+            segment_merge: None,
             indexer,
             compression_algorithm,
             query_algorithm,
+            scoring_model: ScoringModel::BM25,
+            bm25_params: BM25Params::default(),
+            title_weight: DEFAULT_TITLE_WEIGHT,
             dataset_directory_path: dataset_path,
             index_directory_path: index_path,
         })
     }
 
-    pub fn merge_spimi_files(&mut self) -> io::Result<()> {
-        self.in_memory_index_metadata = self.indexer.merge_spimi_files()?;
+    pub fn merge_spimi_files(&mut self) -> Result<(), SearchEngineError> {
+        self.wait_for_segment_merges()?;
+        let in_memory_index_metadata = self.indexer.merge_spimi_files()?;
+        self.reset_segments(in_memory_index_metadata)?;
         Ok(())
     }
-    pub fn build_index(&mut self) -> io::Result<()> {
-        self.in_memory_index_metadata = self.indexer.index()?;
+    // the report tells whether a build which was stopped has been resumed
+    pub fn build_index(&mut self) -> Result<BuildReport, SearchEngineError> {
+        if !self.dataset_directory_path.exists() {
+            return Err(SearchEngineError::DatasetNotFound(
+                self.dataset_directory_path.clone(),
+            ));
+        }
+        self.wait_for_segment_merges()?;
+        let (in_memory_index_metadata, build_report) = self.indexer.index()?;
+        self.reset_segments(in_memory_index_metadata)?;
+        Ok(build_report)
+    }
+
+    // Builds the index straight from a MediaWiki XML dump such as
+    // enwiki-latest-pages-articles.xml.bz2, the markup of the articles is stripped while the dump
+    // is decompressed so no preprocessing is needed. The dump becomes the dataset of the index.
+    pub fn index_wikipedia_dump(
+        &mut self,
+        dump_path: PathBuf,
+    ) -> Result<BuildReport, SearchEngineError> {
+        if !dump_path.is_file() {
+            return Err(SearchEngineError::DatasetNotFound(dump_path));
+        }
+        self.set_dataset_directory_path(dump_path);
+        self.set_document_source(DocumentFormat::MediaWiki.get_source(FieldMapping::default()));
+        self.build_index()
+    }
+
+    // A full build assigns the doc ids again, so the index is left with only the base segment
+    fn reset_segments(
+        &mut self,
+        in_memory_index_metadata: InMemoryIndexMetadata,
+    ) -> io::Result<()> {
+        let segments_directory_path = get_segments_directory_path(&self.index_directory_path);
+        if segments_directory_path.exists() {
+            fs::remove_dir_all(&segments_directory_path)?;
+        }
+        match fs::remove_file(get_save_segment_metadata_path(&self.index_directory_path)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+        self.segments = vec![Segment::new(
+            0,
+            1,
+            self.indexer.get_no_of_docs(),
+            in_memory_index_metadata,
+        )];
+        self.segments[0].set_average_lengths(&self.indexer.get_document_lengths());
+        self.next_segment_id = 1;
+        self.reopen_segments()
+    }
+
+    // The cached blocks and query results are no longer valid once the segments change
+    fn reopen_segments(&mut self) -> io::Result<()> {
+        self.query_processor = QueryProcessor::new(
+            self.index_directory_path.clone(),
+            self.compression_algorithm.clone(),
+            self.query_algorithm.clone(),
+            self.scoring_model,
+            self.bm25_params,
+        )?;
+        self.query_processor.set_title_weight(self.title_weight);
+        self.query_cache = Mutex::new(CacheType::new_landlord(10000));
         Ok(())
     }
 
-    pub fn load_document_metadata(&mut self) -> io::Result<()> {
-        let doc_save_path = get_save_doc_metadata_path(Path::new(&self.index_directory_path));
-        if !doc_save_path.as_path().exists() {
-            return Err(Error::new(
-                ErrorKind::InvalidFilename,
-                "The document metadata save file does not exist",
-            ));
+    // Number of segments of a tier which are merged together, a smaller merge factor keeps fewer
+    // segments around at the cost of merging more often. At least 2 segments have to be merged
+    // together, otherwise a merge would never reduce the no of segments.
+    pub fn set_merge_factor(&mut self, merge_factor: usize) -> Result<(), SearchEngineError> {
+        if merge_factor < 2 {
+            return Err(SearchEngineError::InvalidConfiguration(format!(
+                "the merge factor has to be at least 2, not {}",
+                merge_factor
+            )));
         }
-        let file = File::open(&doc_save_path).unwrap();
-        let reader = BufReader::new(file);
-        self.indexer.load_document_metadata(reader)?;
+        let base_segment_deleted_ratio = self.merge_policy.get_base_segment_deleted_ratio();
+        self.merge_policy = TieredMergePolicy::new(merge_factor);
+        self.merge_policy
+            .set_base_segment_deleted_ratio(base_segment_deleted_ratio);
         Ok(())
     }
 
-    pub fn load_term_metadata(&mut self) -> io::Result<()> {
-        let term_save_path = get_save_term_metadata_path(Path::new(&self.index_directory_path));
-        if !term_save_path.as_path().exists() {
-            return Err(Error::new(
-                ErrorKind::InvalidFilename,
-                "The term metadata save file does not exist",
-            ));
+    // The fraction of the documents of the base segment which can be deleted before it is merged
+    // to purge their postings, a ratio of 1 or more keeps the base segment until the next build
+    pub fn set_base_segment_deleted_ratio(&mut self, base_segment_deleted_ratio: f32) {
+        self.merge_policy
+            .set_base_segment_deleted_ratio(base_segment_deleted_ratio);
+    }
+
+    pub fn get_segment_ids(&self) -> Vec<u32> {
+        self.segments
+            .iter()
+            .map(|segment| segment.segment_id)
+            .collect()
+    }
+
+    fn save_segment_ids(&self) -> io::Result<()> {
+        save_segment_ids(
+            &self.index_directory_path,
+            &self.get_segment_ids()[1..],
+            self.next_segment_id,
+        )
+    }
+
+    // Adds the documents in the batch files (or directories of batch files) to the index as a new
+    // segment, which may start a merge of the smaller segments in the background.
+    // The segment is saved along with the document metadata and the deleted documents, the list of
+    // segments is written last so a segment is only loaded once its documents are known.
+    pub fn add_documents(&mut self, files: Vec<PathBuf>) -> Result<(), SearchEngineError> {
+        self.finish_segment_merge(false)?;
+        if let Some(mut segment) = self.indexer.add_documents(files, self.next_segment_id)? {
+            segment.set_average_lengths(&self.indexer.get_document_lengths());
+            self.next_segment_id += 1;
+            self.segments.push(segment);
+            self.save_document_metadata()?;
+            self.save_deleted_documents()?;
+            self.save_segment_ids()?;
+            self.query_cache = Mutex::new(CacheType::new_landlord(10000));
+        }
+        self.start_segment_merge()
+    }
+
+    // Only one merge runs at a time. The base segment is only merged on its own, to purge the
+    // postings of its deleted documents, and the merge reads it from the disk so the index is
+    // saved before it starts.
+    fn start_segment_merge(&mut self) -> Result<(), SearchEngineError> {
+        if self.segment_merge.is_some() {
+            return Ok(());
+        }
+        let Some(segment_ids) = self
+            .merge_policy
+            .find_merge(&self.segments, &self.indexer.deleted_documents)
+        else {
+            return Ok(());
+        };
+        if segment_ids == [0] {
+            self.save_document_metadata()?;
+            self.save_deleted_documents()?;
+            self.save_term_metadata()?;
+            self.parser
+                .get_analyzer_config()
+                .save(&self.index_directory_path)?;
+        }
+        let merged_segment_id = self.next_segment_id;
+        self.next_segment_id += 1;
+
+        let mut segment_merger = SegmentMerger::new(
+            self.index_directory_path.clone(),
+            segment_ids.clone(),
+            merged_segment_id,
+            self.indexer.document_lengths.clone(),
+            self.indexer.get_avg_doc_length(),
+            self.indexer.title_lengths.clone(),
+            self.indexer.get_avg_title_length(),
+            self.indexer.deleted_documents.clone(),
+            self.indexer.get_include_positions(),
+            self.compression_algorithm.clone(),
+            self.scoring_model,
+            self.bm25_params,
+        );
+        segment_merger.set_base_segment_no_of_docs(self.segments[0].no_of_docs);
+        self.segment_merge = Some((segment_ids, segment_merger.spawn()));
+        Ok(())
+    }
+
+    // Replaces the merged segments with the result of the merge once it is complete, the merged
+    // segments are only removed after the new list of segments has been saved.
+    // If wait is false and the merge is still running nothing happens.
+    fn finish_segment_merge(&mut self, wait: bool) -> Result<(), SearchEngineError> {
+        match &self.segment_merge {
+            Some((_, handle)) if wait || handle.is_finished() => {}
+            _ => return Ok(()),
         }
-        let file = File::open(&term_save_path).unwrap();
-        let reader = BufReader::new(file);
-        self.in_memory_index_metadata.load_term_metadata(reader)?;
+        let (segment_ids, handle) = self.segment_merge.take().unwrap();
+        let merged_segment = handle
+            .join()
+            .map_err(|_| Error::other("the segment merge thread panicked"))??;
+
+        if segment_ids == [0] {
+            self.replace_base_segment(merged_segment)?;
+        } else {
+            let position = self
+                .segments
+                .iter()
+                .position(|segment| segment.segment_id == segment_ids[0])
+                .unwrap();
+            self.segments
+                .retain(|segment| !segment_ids.contains(&segment.segment_id));
+            self.segments.insert(position, merged_segment);
+            self.save_segment_ids()?;
+
+            for segment_id in segment_ids {
+                self.query_processor.close_segment(segment_id);
+                fs::remove_dir_all(get_segment_directory_path(
+                    &self.index_directory_path,
+                    segment_id,
+                ))?;
+            }
+        }
+        self.query_cache = Mutex::new(CacheType::new_landlord(10000));
+        self.start_segment_merge()
+    }
+
+    // The merged base segment is moved from its own directory to the root of the index. The
+    // documents which were deleted while it was merged are saved first, since the deleted
+    // documents which it no longer has postings for must not come back if the index is loaded.
+    fn replace_base_segment(
+        &mut self,
+        mut merged_segment: Segment,
+    ) -> Result<(), SearchEngineError> {
+        self.save_deleted_documents()?;
+        let merged_segment_directory_path =
+            get_segment_directory_path(&self.index_directory_path, merged_segment.segment_id);
+        self.query_processor.close_segment(0);
+        move_base_segment_files(&merged_segment_directory_path, &self.index_directory_path)?;
+        merged_segment.segment_id = 0;
+        merged_segment.save_metadata(&self.index_directory_path)?;
+        fs::remove_dir_all(&merged_segment_directory_path)?;
+        self.segments[0] = merged_segment;
         Ok(())
     }
 
-    pub fn save_document_metadata(&mut self) -> io::Result<()> {
+    // Blocks until every pending merge, including the ones started by a finished merge, is done
+    pub fn wait_for_segment_merges(&mut self) -> Result<(), SearchEngineError> {
+        while self.segment_merge.is_some() {
+            self.finish_segment_merge(true)?;
+        }
+        Ok(())
+    }
+
+    // Deletes every document with the given name and returns the number of deleted documents.
+    // The documents stop showing up in the results immediately but their postings stay in
+    // the segments until they are merged, which may start a merge in the background.
+    pub fn delete_documents_by_name(&mut self, doc_name: &str) -> Result<u32, SearchEngineError> {
+        self.finish_segment_merge(false)?;
+        let mut no_of_deleted_docs = 0;
+        for doc_id in self.indexer.get_doc_ids_by_name(doc_name) {
+            if self.indexer.delete_document(doc_id) {
+                no_of_deleted_docs += 1;
+            }
+        }
+        if no_of_deleted_docs > 0 {
+            self.query_cache = Mutex::new(CacheType::new_landlord(10000));
+            self.start_segment_merge()?;
+        }
+        Ok(no_of_deleted_docs)
+    }
+
+    pub fn load_document_metadata(&mut self) -> Result<(), SearchEngineError> {
+        load_document_metadata(&mut self.indexer, &self.index_directory_path)
+    }
+
+    pub fn load_deleted_documents(&mut self) -> Result<(), SearchEngineError> {
+        load_deleted_documents(&mut self.indexer, &self.index_directory_path)
+    }
+
+    // The document metadata and the deleted documents of the saved index, which verify_index and
+    // repair_index check the saved segments against. They are loaded into an indexer of their own
+    // so the documents of the loaded index are left as they are.
+    fn load_saved_documents(&self) -> Result<Indexer, SearchEngineError> {
+        let mut saved_indexer = Indexer::new(
+            self.parser.clone(),
+            self.compression_algorithm.clone(),
+            self.index_directory_path.clone(),
+        )?;
+        saved_indexer.set_include_positions(self.indexer.get_include_positions());
+        load_document_metadata(&mut saved_indexer, &self.index_directory_path)?;
+        load_deleted_documents(&mut saved_indexer, &self.index_directory_path)?;
+        Ok(saved_indexer)
+    }
+
+    pub fn load_term_metadata(&mut self) -> Result<(), SearchEngineError> {
+        let index_directory_path = Path::new(&self.index_directory_path);
+        let terms_path = get_save_terms_path(index_directory_path);
+        if !terms_path.exists() && !get_save_term_metadata_path(index_directory_path).exists() {
+            return Err(SearchEngineError::IndexNotFound(terms_path));
+        }
+        let in_memory_index_metadata =
+            load_terms(index_directory_path).map_err(|e| get_load_error(&terms_path, e))?;
+        self.segments.truncate(1);
+        self.segments[0].no_of_docs = self.indexer.get_no_of_docs();
+        self.segments[0].collection_no_of_docs = self.indexer.get_no_of_docs();
+        self.segments[0].set_average_lengths(&self.indexer.get_document_lengths());
+        self.segments[0].in_memory_index_metadata = in_memory_index_metadata;
+        self.check_scoring(&self.segments[0])?;
+        // the documents which are added to the index and the merged segments keep the positions
+        // of the saved index whatever is configured
+        if let Some(include_positions) = self.check_inverted_index(&self.segments[0])? {
+            self.indexer.set_include_positions(include_positions);
+        }
+        // an index which was saved before the BK-tree was kept can not suggest corrections
+        let bk_tree_path = get_save_bk_tree_path(Path::new(&self.index_directory_path));
+        if bk_tree_path.exists() {
+            self.segments[0]
+                .in_memory_index_metadata
+                .load_bk_tree(&fs::read(bk_tree_path)?)?;
+        }
+        Ok(())
+    }
+
+    // The queries are analyzed with the analyzer which the index was built with rather than the
+    // configured one, an index which was saved before the analyzer was recorded was built with
+    // the default analyzer
+    pub fn load_analyzer_config(&mut self) -> Result<(), SearchEngineError> {
+        let analyzer_config = AnalyzerConfig::load(&self.index_directory_path)
+            .map_err(|e| get_load_error(&get_save_analyzer_path(&self.index_directory_path), e))?
+            .unwrap_or_default();
+        self.set_analyzer_config(analyzer_config)
+    }
+
+    // The max scores of a segment are only upper bounds of the scores of the model and the
+    // parameters which they were computed with, so the segment can not be queried with any others
+    fn check_scoring(&self, segment: &Segment) -> Result<(), SearchEngineError> {
+        let scoring_model = segment.in_memory_index_metadata.scoring_model;
+        if scoring_model != self.scoring_model {
+            return Err(SearchEngineError::IncompatibleIndex(format!(
+                "segment {} was built with the {} scoring model but {} is configured",
+                segment.segment_id, scoring_model, self.scoring_model
+            )));
+        }
+        let bm25_params = segment.in_memory_index_metadata.bm25_params;
+        if scoring_model.uses_bm25_params() && bm25_params != self.bm25_params {
+            return Err(SearchEngineError::IncompatibleIndex(format!(
+                "segment {} was built with k1 = {} and b = {} but k1 = {} and b = {} are configured",
+                segment.segment_id,
+                bm25_params.k1,
+                bm25_params.b,
+                self.bm25_params.k1,
+                self.bm25_params.b
+            )));
+        }
+        Ok(())
+    }
+
+    // The footer of the inverted index records how its blocks were written, an inverted index
+    // which was saved before it had a footer is used as it is. Returns whether the segment has
+    // positions if its footer says so.
+    fn check_inverted_index(&self, segment: &Segment) -> Result<Option<bool>, SearchEngineError> {
+        let inverted_index_path = get_inverted_index_path(get_segment_directory_path(
+            &self.index_directory_path,
+            segment.segment_id,
+        ));
+        let Some(footer) = InvertedIndexFooter::read_from_file(&inverted_index_path)
+            .map_err(|e| get_load_error(&inverted_index_path, e))?
+        else {
+            return Ok(None);
+        };
+        if footer.compression_algorithm != self.compression_algorithm {
+            return Err(SearchEngineError::IncompatibleIndex(format!(
+                "segment {} was compressed with {} but {} is configured",
+                segment.segment_id, footer.compression_algorithm, self.compression_algorithm
+            )));
+        }
+        let block_size = DEFAULT_MAX_BLOCK_SIZE as u32 * 1000;
+        if footer.block_size != block_size {
+            return Err(SearchEngineError::IncompatibleIndex(format!(
+                "segment {} has blocks of {} bytes but blocks of {} bytes are read",
+                segment.segment_id, footer.block_size, block_size
+            )));
+        }
+        let no_of_blocks = segment.in_memory_index_metadata.no_of_blocks;
+        if footer.get_no_of_blocks() != no_of_blocks {
+            return Err(SearchEngineError::CorruptIndex(format!(
+                "the inverted index of segment {} has {} blocks but its term metadata has {}",
+                segment.segment_id,
+                footer.get_no_of_blocks(),
+                no_of_blocks
+            )));
+        }
+        Ok(Some(footer.include_positions))
+    }
+
+    // Segments which were added after the document metadata was last saved are dropped, since
+    // their documents are unknown. The base segment holds every document before the first segment.
+    // The directories of the dropped segments and of the segments which were never listed, because
+    // the search engine stopped while they were written or merged, are removed.
+    pub fn load_segments(&mut self) -> Result<(), SearchEngineError> {
+        self.wait_for_segment_merges()?;
+        let (segment_ids, next_segment_id) = load_segment_ids(&self.index_directory_path)?;
+        self.segments.truncate(1);
+        for segment_id in segment_ids {
+            let segment = Segment::load(&self.index_directory_path, segment_id).map_err(|e| {
+                get_load_error(
+                    &get_segment_directory_path(&self.index_directory_path, segment_id),
+                    e,
+                )
+            })?;
+            self.check_scoring(&segment)?;
+            if segment.first_doc_id + segment.no_of_docs - 1 > self.indexer.get_no_of_docs() {
+                break;
+            }
+            if let Some(include_positions) = self.check_inverted_index(&segment)?
+                && include_positions != self.indexer.get_include_positions()
+            {
+                return Err(SearchEngineError::IncompatibleIndex(format!(
+                    "segment {} was written {} positions unlike the base segment",
+                    segment.segment_id,
+                    if include_positions { "with" } else { "without" }
+                )));
+            }
+            self.segments.push(segment);
+        }
+        let base_segment_no_of_docs = match self.segments.get(1) {
+            Some(segment) => segment.first_doc_id - 1,
+            None => self.indexer.get_no_of_docs(),
+        };
+        let base_segment =
+            load_base_segment_metadata(&self.index_directory_path, base_segment_no_of_docs)
+                .map_err(|e| {
+                    get_load_error(
+                        &get_save_segment_metadata_path(&self.index_directory_path),
+                        e,
+                    )
+                })?;
+        self.segments[0].no_of_docs = base_segment.no_of_docs;
+        self.segments[0].collection_no_of_docs = base_segment.collection_no_of_docs;
+        self.segments[0].no_of_purged_docs = base_segment.no_of_purged_docs;
+        let document_lengths = self.indexer.get_document_lengths();
+        for segment in &mut self.segments {
+            segment.set_average_lengths(&document_lengths);
+        }
+        self.next_segment_id = next_segment_id;
+        self.remove_orphaned_segments()?;
+        self.reopen_segments()?;
+        Ok(())
+    }
+
+    fn remove_orphaned_segments(&self) -> io::Result<()> {
+        let segments_directory_path = get_segments_directory_path(&self.index_directory_path);
+        if !segments_directory_path.exists() {
+            return Ok(());
+        }
+        for entry in fs::read_dir(segments_directory_path)? {
+            let entry = entry?;
+            let Some(segment_id) = entry
+                .file_name()
+                .to_str()
+                .and_then(|name| name.parse::<u32>().ok())
+            else {
+                continue;
+            };
+            let is_loaded = self
+                .segments
+                .iter()
+                .any(|segment| segment.segment_id == segment_id);
+            if !is_loaded && entry.file_type()?.is_dir() {
+                fs::remove_dir_all(entry.path())?;
+            }
+        }
+        Ok(())
+    }
+
+    // The metadata is written to a temporary file which is renamed over the old one, so the saved
+    // metadata is never left half written
+    pub fn save_document_metadata(&mut self) -> Result<(), SearchEngineError> {
         let doc_save_path = get_save_doc_metadata_path(Path::new(&self.index_directory_path));
-        let file = File::create(&doc_save_path)?;
-        let doc_writer = BufWriter::new(file);
-        self.indexer.save_document_metadata(doc_writer)?;
+        let temp_path = doc_save_path.with_extension("sidx.tmp");
+        let mut doc_writer = BufWriter::new(File::create(&temp_path)?);
+        self.indexer.save_document_metadata(&mut doc_writer)?;
+        doc_writer.flush()?;
+        drop(doc_writer);
+        fs::rename(temp_path, doc_save_path)?;
         Ok(())
     }
 
-    pub fn save_term_metadata(&mut self) -> io::Result<()> {
-        let term_save_path = get_save_term_metadata_path(Path::new(&self.index_directory_path));
-        let file = File::create(&term_save_path)?;
-        let term_writer = BufWriter::new(file);
-        self.in_memory_index_metadata
-            .save_term_metadata(term_writer)?;
+    pub fn save_deleted_documents(&mut self) -> Result<(), SearchEngineError> {
+        let deleted_save_path =
+            get_save_deleted_documents_path(Path::new(&self.index_directory_path));
+        let temp_path = deleted_save_path.with_extension("sidx.tmp");
+        let mut deleted_writer = BufWriter::new(File::create(&temp_path)?);
+        self.indexer.save_deleted_documents(&mut deleted_writer)?;
+        deleted_writer.flush()?;
+        drop(deleted_writer);
+        fs::rename(temp_path, deleted_save_path)?;
         Ok(())
     }
 
-    pub fn save_index(&mut self) -> io::Result<()> {
+    pub fn save_term_metadata(&mut self) -> Result<(), SearchEngineError> {
+        let index_directory_path = Path::new(&self.index_directory_path);
+        self.segments[0]
+            .in_memory_index_metadata
+            .save_terms_file(&get_save_terms_path(index_directory_path))?;
+        // the term metadata of an index saved before terms.sidx is replaced by it
+        match fs::remove_file(get_save_term_metadata_path(index_directory_path)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+        let bk_tree_path = get_save_bk_tree_path(Path::new(&self.index_directory_path));
+        self.segments[0]
+            .in_memory_index_metadata
+            .save_bk_tree(BufWriter::new(File::create(bk_tree_path)?))?;
+        Ok(())
+    }
+
+    // The segments other than the base segment are saved as soon as they are written
+    pub fn save_index(&mut self) -> Result<(), SearchEngineError> {
+        self.wait_for_segment_merges()?;
         self.save_document_metadata()?;
+        self.save_deleted_documents()?;
         self.save_term_metadata()?;
+        self.parser
+            .get_analyzer_config()
+            .save(&self.index_directory_path)?;
+        // a saved index no longer needs the runs it was merged from
+        self.indexer.remove_build_files()?;
         Ok(())
     }
 
-    pub fn load_index(&mut self) -> io::Result<()> {
+    pub fn load_index(&mut self) -> Result<(), SearchEngineError> {
+        self.load_analyzer_config()?;
         self.load_document_metadata()?;
+        self.load_deleted_documents()?;
         self.load_term_metadata()?;
+        self.load_segments()?;
         Ok(())
     }
 
+    // Checks every segment of the saved index for damage, see verify_segment for what is checked.
+    // The document metadata is read from the index directory, so an index has to be saved before
+    // it is verified.
+    pub fn verify_index(&mut self) -> Result<IndexReport, SearchEngineError> {
+        self.wait_for_segment_merges()?;
+        let saved_indexer = self.load_saved_documents()?;
+        Ok(self.verify_saved_index(&saved_indexer))
+    }
+
+    fn verify_saved_index(&self, saved_indexer: &Indexer) -> IndexReport {
+        let mut report = IndexReport::default();
+        let no_of_docs = saved_indexer.get_no_of_docs();
+
+        let segment_ids = match load_segment_ids(&self.index_directory_path) {
+            Ok((segment_ids, _)) => segment_ids,
+            Err(e) => {
+                report
+                    .problems
+                    .push(format!("the segment ids can not be read: {}", e));
+                Vec::new()
+            }
+        };
+        for segment_id in segment_ids {
+            match Segment::load_metadata(&self.index_directory_path, segment_id) {
+                // the segments which were added after the document metadata was saved are left
+                // out when the index is loaded, so they are not checked either
+                Ok(segment) if segment.first_doc_id + segment.no_of_docs - 1 > no_of_docs => break,
+                Ok(segment) => report.segments.push(SegmentReport {
+                    segment_id,
+                    first_doc_id: segment.first_doc_id,
+                    no_of_docs: segment.no_of_docs,
+                    collection_no_of_docs: segment.collection_no_of_docs,
+                    ..Default::default()
+                }),
+                Err(e) => report.problems.push(format!(
+                    "the metadata of segment {} can not be read: {}",
+                    segment_id, e
+                )),
+            }
+        }
+        let base_segment_no_of_docs = match report.segments.first() {
+            Some(segment_report) => segment_report.first_doc_id - 1,
+            None => no_of_docs,
+        };
+        let base_segment_collection_no_of_docs =
+            match load_base_segment_metadata(&self.index_directory_path, base_segment_no_of_docs) {
+                Ok(base_segment) => base_segment.collection_no_of_docs,
+                Err(e) => {
+                    report.problems.push(format!(
+                        "the metadata of the base segment can not be read: {}",
+                        e
+                    ));
+                    base_segment_no_of_docs
+                }
+            };
+        report.segments.insert(
+            0,
+            SegmentReport {
+                segment_id: 0,
+                first_doc_id: 1,
+                no_of_docs: base_segment_no_of_docs,
+                collection_no_of_docs: base_segment_collection_no_of_docs,
+                ..Default::default()
+            },
+        );
+
+        let document_lengths = saved_indexer.get_document_lengths();
+        for segment_report in &mut report.segments {
+            if segment_report.collection_no_of_docs > no_of_docs {
+                segment_report.problems.push(format!(
+                    "it was written with {} documents but the document metadata only has {}",
+                    segment_report.collection_no_of_docs, no_of_docs
+                ));
+                continue;
+            }
+            verify_segment(
+                &get_segment_directory_path(&self.index_directory_path, segment_report.segment_id),
+                segment_report,
+                &document_lengths.get_prefix(segment_report.collection_no_of_docs),
+                self.compression_algorithm.clone(),
+            );
+        }
+        report
+    }
+
+    // Rebuilds every segment which verify_index finds a problem in from the postings which can
+    // still be read, the postings in damaged blocks are lost. A segment which is not repairable
+    // is left as it is, the index then has to be built again from the dataset.
+    // Returns the report of the index from before the repair, a fully repaired index is loaded.
+    pub fn repair_index(&mut self) -> Result<IndexReport, SearchEngineError> {
+        self.wait_for_segment_merges()?;
+        let saved_indexer = self.load_saved_documents()?;
+        let report = self.verify_saved_index(&saved_indexer);
+        let (mut segment_ids, mut next_segment_id) = load_segment_ids(&self.index_directory_path)?;
+        let document_lengths = saved_indexer.get_document_lengths();
+
+        for segment_report in &report.segments {
+            if segment_report.is_ok() || !segment_report.repairable {
+                continue;
+            }
+            let segment_id = segment_report.segment_id;
+            let segment_directory_path =
+                get_segment_directory_path(&self.index_directory_path, segment_id);
+            let rebuilt_segment_id = next_segment_id;
+            next_segment_id += 1;
+            let rebuilt_segment_directory_path =
+                get_segment_directory_path(&self.index_directory_path, rebuilt_segment_id);
+
+            let in_memory_index_metadata = rebuild_segment(
+                &segment_directory_path,
+                &load_terms(&segment_directory_path)?,
+                &segment_report.damaged_block_ids,
+                &rebuilt_segment_directory_path,
+                &document_lengths.get_prefix(segment_report.collection_no_of_docs),
+                &saved_indexer.deleted_documents,
+                saved_indexer.get_include_positions(),
+                self.compression_algorithm.clone(),
+            )?;
+
+            if segment_id == 0 {
+                // The base segment lives at the root of the index, its rebuilt files are moved
+                // over the damaged ones. An index which is left with some of each by a crash is
+                // still damaged and can be repaired again.
+                in_memory_index_metadata
+                    .save_terms_file(&get_save_terms_path(&rebuilt_segment_directory_path))?;
+                in_memory_index_metadata.save_bk_tree(BufWriter::new(File::create(
+                    get_save_bk_tree_path(&rebuilt_segment_directory_path),
+                )?))?;
+                move_base_segment_files(&rebuilt_segment_directory_path, &segment_directory_path)?;
+                fs::remove_dir_all(&rebuilt_segment_directory_path)?;
+            } else {
+                // the same as replacing segments with their merge
+                let mut rebuilt_segment = Segment::new(
+                    rebuilt_segment_id,
+                    segment_report.first_doc_id,
+                    segment_report.no_of_docs,
+                    in_memory_index_metadata,
+                );
+                rebuilt_segment.collection_no_of_docs = segment_report.collection_no_of_docs;
+                rebuilt_segment.save(&self.index_directory_path)?;
+                let position = segment_ids.iter().position(|&id| id == segment_id).unwrap();
+                segment_ids[position] = rebuilt_segment_id;
+                save_segment_ids(&self.index_directory_path, &segment_ids, next_segment_id)?;
+                fs::remove_dir_all(&segment_directory_path)?;
+            }
+        }
+        save_segment_ids(&self.index_directory_path, &segment_ids, next_segment_id)?;
+
+        // an index which is still damaged can not be loaded, an index which was not damaged is
+        // left loaded as it is
+        if !report.is_ok() && report.is_repairable() {
+            self.load_index()?;
+        }
+        Ok(report)
+    }
+
     pub fn set_dataset_directory_path(&mut self, dataset_directory_path: PathBuf) {
+        self.indexer
+            .set_dataset_directory_path(dataset_directory_path.clone());
         self.dataset_directory_path = dataset_directory_path;
     }
     pub fn get_dataset_directory_path(&self) -> &str {
-        &self.dataset_directory_path.as_os_str().to_str().unwrap()
+        self.dataset_directory_path.as_os_str().to_str().unwrap()
     }
 
     pub fn set_index_directory_path(&mut self, index_directory_path: PathBuf) {
         self.index_directory_path = index_directory_path;
     }
     pub fn get_index_directory_path(&self) -> &str {
-        &self.index_directory_path.as_os_str().to_str().unwrap()
+        self.index_directory_path.as_os_str().to_str().unwrap()
+    }
+
+    pub fn set_include_positions(&mut self, include_positions: bool) {
+        self.indexer.set_include_positions(include_positions);
+    }
+
+    // The analyzer has to be set before the index is built, it is saved along with the index and
+    // replaces the configured one when the index is loaded
+    pub fn set_analyzer_config(
+        &mut self,
+        analyzer_config: AnalyzerConfig,
+    ) -> Result<(), SearchEngineError> {
+        analyzer_config
+            .validate()
+            .map_err(SearchEngineError::InvalidConfiguration)?;
+        // the synonyms are analyzed again with the new analyzer
+        let synonyms = self.parser.get_synonyms().clone();
+        self.parser = Parser::with_config(analyzer_config);
+        self.parser.set_synonyms(synonyms);
+        self.indexer.set_parser(self.parser.clone());
+        self.query_cache = Mutex::new(CacheType::new_landlord(10000));
+        Ok(())
+    }
+
+    pub fn get_analyzer_config(&self) -> &AnalyzerConfig {
+        self.parser.get_analyzer_config()
+    }
+
+    // The synonyms which the words of the queries are expanded with, they are only applied at
+    // query time so they can be changed at any point without rebuilding the index
+    pub fn set_synonyms(&mut self, synonyms: SynonymDictionary) -> Result<(), SearchEngineError> {
+        synonyms
+            .validate()
+            .map_err(SearchEngineError::InvalidConfiguration)?;
+        self.parser.set_synonyms(synonyms);
+        self.query_cache = Mutex::new(CacheType::new_landlord(10000));
+        Ok(())
+    }
+
+    pub fn get_synonyms(&self) -> &SynonymDictionary {
+        self.parser.get_synonyms()
+    }
+
+    // The format which the dataset and the files of add_documents are read in, the json lines of
+    // the preprocessing script by default
+    pub fn set_document_source(&mut self, document_source: Arc<dyn DocumentSource>) {
+        self.indexer.set_document_source(document_source);
     }
 
     pub fn set_compression_algorithm(&mut self, compression_algorithm: CompressionAlgorithm) {
@@ -184,84 +884,1716 @@ impl SearchEngine {
         &self.query_algorithm
     }
 
-    pub fn get_index_metadata(&self) -> SearchEngineMetadata {
-        let size_of_index = fs::metadata(get_inverted_index_path(self.get_index_directory_path()))
-            .unwrap()
-            .len() as f64
-            / 1_000_000_000.0;
-        SearchEngineMetadata {
+    // Has to be set before the index is built or loaded, an index which was built with a different
+    // scoring model can not be loaded
+    pub fn set_scoring_model(&mut self, scoring_model: ScoringModel) {
+        self.scoring_model = scoring_model;
+        self.indexer.set_scoring_model(scoring_model);
+        self.query_processor
+            .set_scorer(self.scoring_model, self.bm25_params);
+    }
+
+    pub fn get_scoring_model(&self) -> ScoringModel {
+        self.scoring_model
+    }
+
+    // Same as the scoring model, the parameters have to be set before the index is built or loaded
+    pub fn set_bm25_params(&mut self, bm25_params: BM25Params) -> Result<(), SearchEngineError> {
+        bm25_params
+            .validate()
+            .map_err(SearchEngineError::InvalidConfiguration)?;
+        self.bm25_params = bm25_params;
+        self.indexer.set_bm25_params(bm25_params);
+        self.query_processor
+            .set_scorer(self.scoring_model, self.bm25_params);
+        Ok(())
+    }
+
+    pub fn get_bm25_params(&self) -> BM25Params {
+        self.bm25_params
+    }
+
+    // The weight of a match in the title relative to a match in the body. Unlike the scoring model
+    // it is only applied at query time, so it can be changed at any point.
+    pub fn set_title_weight(&mut self, title_weight: f32) -> Result<(), SearchEngineError> {
+        if !title_weight.is_finite() || title_weight < 0.0 {
+            return Err(SearchEngineError::InvalidConfiguration(format!(
+                "the title weight has to be a non negative number, not {}",
+                title_weight
+            )));
+        }
+        self.title_weight = title_weight;
+        self.query_processor.set_title_weight(title_weight);
+        self.query_cache = Mutex::new(CacheType::new_landlord(10000));
+        Ok(())
+    }
+
+    pub fn get_title_weight(&self) -> f32 {
+        self.title_weight
+    }
+
+    // the size of the index is read from the files of the segments, which may have been removed
+    // from under the search engine
+    pub fn get_index_metadata(&self) -> Result<SearchEngineMetadata, SearchEngineError> {
+        let mut size_of_index = 0.0;
+        let mut no_of_blocks = 0;
+        for segment in &self.segments {
+            let segment_directory_path =
+                get_segment_directory_path(&self.index_directory_path, segment.segment_id);
+            size_of_index += fs::metadata(get_inverted_index_path(segment_directory_path))?.len()
+                as f64
+                / 1_000_000_000.0;
+            no_of_blocks += segment.in_memory_index_metadata.no_of_blocks;
+        }
+        Ok(SearchEngineMetadata {
             no_of_docs: self.indexer.get_no_of_docs(),
-            no_of_terms: self.in_memory_index_metadata.no_of_terms,
-            no_of_blocks: self.in_memory_index_metadata.no_of_blocks,
-            size_of_index: size_of_index,
+            no_of_deleted_docs: self.indexer.deleted_documents.len(),
+            no_of_terms: self.get_no_of_terms(),
+            no_of_blocks,
+            no_of_segments: self.segments.len() as u32,
+            size_of_index,
             dataset_directory_path: self.get_dataset_directory_path().to_string(),
             index_directory_path: self.get_index_directory_path().to_string(),
             compression_algorithm: self.get_compression_algorithm().to_string(),
             query_algorithm: self.get_query_algorithm().to_string(),
+            scoring_model: self.get_scoring_model().to_string(),
+            bm25_k1: self.bm25_params.k1,
+            bm25_b: self.bm25_params.b,
+            title_weight: self.title_weight,
+            tokenizer: self.get_analyzer_config().tokenizer.to_string(),
+            stemmer: self.get_analyzer_config().stemmer.to_string(),
+        })
+    }
+
+    // The distinct terms of the index in lexicographic order, a term can be present in more than
+    // one segment so the sorted terms of the segments are merged as they are iterated over
+    pub fn get_terms(&self) -> impl Iterator<Item = String> + '_ {
+        merge_sorted_terms(
+            self.segments
+                .iter()
+                .map(|segment| segment.in_memory_index_metadata.iter_sorted_terms())
+                .collect(),
+        )
+    }
+
+    // the count saved with a single segment is exact, the terms of several segments overlap
+    pub fn get_no_of_terms(&self) -> u32 {
+        if self.segments.len() == 1 {
+            return self.segments[0]
+                .in_memory_index_metadata
+                .get_no_of_saved_terms() as u32;
         }
+        self.get_terms().count() as u32
     }
 
-    pub fn get_terms(&self) -> Vec<&str> {
-        self.in_memory_index_metadata.get_all_terms()
+    // The no of documents containing the term summed over all the segments, the term is analyzed
+    // the same way as the query terms. Deleted documents are counted until their segment is merged.
+    pub fn get_document_frequency(&self, term: &str) -> Option<u32> {
+        let term = self.parser.analyze_query_word(term)?;
+        let mut document_frequency = None;
+        for segment in &self.segments {
+            if let Some(term_metadata) = segment.in_memory_index_metadata.get_term_metadata(&term) {
+                *document_frequency.get_or_insert(0) += term_metadata.term_frequency;
+            }
+        }
+        document_frequency
     }
 
-    pub fn handle_query(
+    // A searcher answers queries with a shared reference to the search engine, so any number of
+    // threads can query the index at once. Segment merges which finished in the background are
+    // only picked up by query, since replacing the segments needs a mutable reference.
+    pub fn searcher(&self) -> Searcher<'_> {
+        Searcher::new(
+            &self.query_cache,
+            &self.query_processor,
+            &self.parser,
+            &self.indexer,
+            &self.segments,
+            &self.query_algorithm,
+        )
+    }
+
+    // Returns k results of the query starting from offset, in decreasing order of their scores.
+    // The same query with a different k or offset is cached separately.
+    pub fn query(
         &mut self,
-        query: String,
-    ) -> Result<Vec<(DocumentMetadata, f32)>, io::Error> {
-        let mut result_metadata = Vec::new();
-        println!("started processing");
-        if let Some(result_docs) = self.query_cache.get(&query) {
-            for doc in result_docs {
-                if let Some(metadata) = self.indexer.get_doc_metadata(doc.0) {
-                    result_metadata.push((metadata, doc.1));
-                }
-            }
-        } else {
-            let token_query_result = self.parser.tokenize_query(&query);
-            if token_query_result.is_err() {
-                return Err(io::Error::new(io::ErrorKind::Unsupported, "error"));
-            }
+        query: &str,
+        k: usize,
+        offset: usize,
+    ) -> Result<Vec<SearchResult>, SearchEngineError> {
+        self.finish_segment_merge(false)?;
+        self.searcher().query(query, k, offset)
+    }
 
-            let tokens = token_query_result.unwrap();
-            let mut query_terms = Vec::with_capacity(tokens.unigram.len());
-            let mut query_metadata = Vec::with_capacity(tokens.unigram.len());
-            for token in tokens.unigram {
-                if let Some(term_metadata) =
-                    self.in_memory_index_metadata.get_term_metadata(&token.word)
-                {
-                    query_metadata.push(term_metadata);
-                    query_terms.push(token.word);
-                }
-            }
+    // Returns the query with its misspelled words corrected, or None if it looks fine
+    pub fn suggest_query(&self, query: &str) -> Option<String> {
+        self.searcher().suggest_query(query)
+    }
+}
 
-            let result_docs = self.query_processor.process_query(
-                query_terms,
-                query_metadata,
-                &self.indexer.document_lengths,
-                self.indexer.get_avg_doc_length(),
-            );
+// Moves the files of a segment which replaces the base segment over the ones of the base segment.
+// An index which is left with some of each by a crash is damaged and can be repaired.
+fn move_base_segment_files(
+    segment_directory_path: &Path,
+    index_directory_path: &Path,
+) -> io::Result<()> {
+    fs::rename(
+        get_inverted_index_path(segment_directory_path),
+        get_inverted_index_path(index_directory_path),
+    )?;
+    fs::rename(
+        get_save_terms_path(segment_directory_path),
+        get_save_terms_path(index_directory_path),
+    )?;
+    fs::rename(
+        get_save_bk_tree_path(segment_directory_path),
+        get_save_bk_tree_path(index_directory_path),
+    )?;
+    // the term metadata of an index saved before terms.sidx is replaced by it
+    match fs::remove_file(get_save_term_metadata_path(index_directory_path)) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
 
-            for doc in &result_docs {
-                if let Some(metadata) = self.indexer.get_doc_metadata(doc.0) {
-                    result_metadata.push((metadata, doc.1));
-                }
-            }
-            self.query_cache.put(query, result_docs, 0);
-        }
+fn load_document_metadata(
+    indexer: &mut Indexer,
+    index_directory_path: &Path,
+) -> Result<(), SearchEngineError> {
+    let doc_save_path = get_save_doc_metadata_path(index_directory_path);
+    if !doc_save_path.as_path().exists() {
+        return Err(SearchEngineError::IndexNotFound(doc_save_path));
+    }
+    let file = File::open(&doc_save_path)?;
+    let reader = BufReader::new(file);
+    indexer
+        .load_document_metadata(reader)
+        .map_err(|e| get_load_error(&doc_save_path, e))?;
+    Ok(())
+}
+
+// An index which was saved before documents could be deleted has no deleted documents file
+fn load_deleted_documents(
+    indexer: &mut Indexer,
+    index_directory_path: &Path,
+) -> Result<(), SearchEngineError> {
+    let deleted_save_path = get_save_deleted_documents_path(index_directory_path);
+    if !deleted_save_path.as_path().exists() {
+        indexer.deleted_documents.clear();
+        return Ok(());
+    }
+    let file = File::open(&deleted_save_path)?;
+    let reader = BufReader::new(file);
+    indexer.load_deleted_documents(reader)?;
+    Ok(())
+}
 
-        Ok(result_metadata)
+// A file which can not be read because it is damaged makes the index corrupt, and a file which
+// was saved by a newer version makes it incompatible
+fn get_load_error(path: &Path, e: io::Error) -> SearchEngineError {
+    match e.kind() {
+        ErrorKind::InvalidData | ErrorKind::UnexpectedEof => {
+            SearchEngineError::CorruptIndex(format!("{:?}: {}", path, e))
+        }
+        ErrorKind::Unsupported => SearchEngineError::IncompatibleIndex(e.to_string()),
+        _ => SearchEngineError::Io(e),
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::{
+        collections::BTreeMap,
+        fs::{self, File},
+        io::Write,
+        thread,
+    };
+
+    use tempfile::TempDir;
+
     use crate::{
         compressor::compressor::CompressionAlgorithm,
-        query_processor::retrieval_algorithms::QueryAlgorithm,
-        search_engine::search_engine::SearchEngine,
+        indexer::build_manifest::{BuildManifest, BuildReport, FileProgress},
+        parser::synonyms::SynonymDictionary,
+        query_processor::{query_processor::QueryProcessor, retrieval_algorithms::QueryAlgorithm},
+        scoring::{ScoringModel, bm_25::BM25Params},
+        search_engine::{error::SearchEngineError, search_engine::SearchEngine},
+        utils::{
+            field::DEFAULT_TITLE_WEIGHT,
+            paths::{
+                get_build_manifest_path, get_run_path, get_segment_directory_path,
+                get_unfinished_run_path,
+            },
+            test_utils::{write_test_batch, write_test_dataset},
+            types::SearchResult,
+        },
     };
 
+    fn create_test_search_engine(
+        temp_dir: &TempDir,
+        documents: &[(&str, &str)],
+        include_positions: bool,
+    ) -> SearchEngine {
+        let dataset_dir = temp_dir.path().join("dataset");
+        let index_dir = temp_dir.path().join("index");
+        write_test_dataset(&dataset_dir, documents);
+
+        let mut search_engine = SearchEngine::new(
+            dataset_dir.to_str().unwrap().to_string(),
+            CompressionAlgorithm::Simple16,
+            QueryAlgorithm::Wand,
+            index_dir.to_str().unwrap().to_string(),
+        )
+        .unwrap();
+        search_engine.set_include_positions(include_positions);
+        search_engine.build_index().unwrap();
+        search_engine
+    }
+
+    const TEST_DOCUMENTS: &[(&str, &str)] = &[
+        (
+            "Misery",
+            "misery is a movie based on the novel by stephen king",
+        ),
+        ("Movie", "a movie about misery and loss in a small town"),
+        ("Novel", "the novel misery movie adaptation won an award"),
+        ("Town", "a small town with a famous movie theater"),
+    ];
+
+    #[test]
+    fn test_phrase_query() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut search_engine = create_test_search_engine(&temp_dir, TEST_DOCUMENTS, true);
+
+        let results = search_engine.query("\"misery movie\"", 10, 0).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].document.doc_name, "Novel");
+
+        let results = search_engine
+            .query("\"misery movie\"", usize::MAX, usize::MAX - 1)
+            .unwrap();
+        assert!(results.is_empty());
+    }
+
+    #[test]
+    fn test_proximity_query() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut search_engine = create_test_search_engine(&temp_dir, TEST_DOCUMENTS, true);
+
+        let results = search_engine.query("misery NEAR/2 movie", 10, 0).unwrap();
+        let mut names: Vec<String> = results.into_iter().map(|r| r.document.doc_name).collect();
+        names.sort();
+        assert_eq!(names, vec!["Movie", "Novel"]);
+
+        let results = search_engine.query("small NEAR/1 theater", 10, 0).unwrap();
+        assert!(results.is_empty());
+    }
+
+    #[test]
+    fn test_phrase_query_without_positions() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut search_engine = create_test_search_engine(&temp_dir, TEST_DOCUMENTS, false);
+
+        for query in ["\"misery movie\"", "misery NEAR/2 movie"] {
+            assert!(matches!(
+                search_engine.query(query, 10, 0),
+                Err(SearchEngineError::InvalidQuery(_))
+            ));
+        }
+        search_engine.set_query_algorithm(QueryAlgorithm::Boolean);
+        assert!(matches!(
+            search_engine.query("novel AND \"misery movie\"", 10, 0),
+            Err(SearchEngineError::InvalidQuery(_))
+        ));
+    }
+
+    #[test]
+    fn test_positions_are_saved_with_the_index() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut search_engine = create_test_search_engine(&temp_dir, TEST_DOCUMENTS, true);
+        search_engine.save_index().unwrap();
+
+        // the index has positions even though the loading search engine is configured without them
+        let mut loaded_search_engine = SearchEngine::new(
+            String::new(),
+            CompressionAlgorithm::Simple16,
+            QueryAlgorithm::Wand,
+            temp_dir.path().join("index").to_str().unwrap().to_string(),
+        )
+        .unwrap();
+        loaded_search_engine.set_include_positions(false);
+        loaded_search_engine.load_index().unwrap();
+        let results = loaded_search_engine
+            .query("\"misery movie\"", 10, 0)
+            .unwrap();
+        assert_eq!(results[0].document.doc_name, "Novel");
+
+        // the documents which are added afterwards have positions as well
+        let batch_dir = temp_dir.path().join("new_batch");
+        write_test_dataset(&batch_dir, &[("Sequel", "the misery movie sequel")]);
+        loaded_search_engine
+            .add_documents(vec![batch_dir.join("batch_0")])
+            .unwrap();
+        let mut names: Vec<String> = loaded_search_engine
+            .query("\"misery movie\"", 10, 0)
+            .unwrap()
+            .into_iter()
+            .map(|r| r.document.doc_name)
+            .collect();
+        names.sort();
+        assert_eq!(names, vec!["Novel", "Sequel"]);
+    }
+
+    #[test]
+    fn test_title_field() {
+        let documents = [
+            (
+                "Misery",
+                "a novel by stephen king about misery and an author held captive",
+            ),
+            (
+                "Sorrow",
+                "misery misery misery and the misery of a long winter",
+            ),
+            ("River", "a quiet river flows past the old mill"),
+            ("Garden", "the garden was full of roses in spring"),
+            ("Train", "the night train leaves the station at ten"),
+            ("Ocean", "waves crashed against the rocky shore"),
+        ];
+        let temp_dir = TempDir::new().unwrap();
+        let mut search_engine = create_test_search_engine(&temp_dir, &documents, false);
+
+        // the article named after the query ranks first even though the other one mentions it more
+        let names = |results: Vec<SearchResult>| -> Vec<String> {
+            results.into_iter().map(|r| r.document.doc_name).collect()
+        };
+        assert_eq!(
+            names(search_engine.query("misery", 10, 0).unwrap()),
+            vec!["Misery", "Sorrow"]
+        );
+        assert_eq!(
+            names(search_engine.query("title:river", 10, 0).unwrap()),
+            vec!["River"]
+        );
+        assert!(
+            search_engine
+                .query("title:winter", 10, 0)
+                .unwrap()
+                .is_empty()
+        );
+        assert_eq!(
+            search_engine.get_document_frequency("title:misery"),
+            Some(1)
+        );
+
+        // without the title the body decides the ranking
+        search_engine.set_title_weight(0.0).unwrap();
+        assert_eq!(
+            names(search_engine.query("misery", 10, 0).unwrap()),
+            vec!["Sorrow", "Misery"]
+        );
+        assert!(matches!(
+            search_engine.set_title_weight(-1.0),
+            Err(SearchEngineError::InvalidConfiguration(_))
+        ));
+
+        // the title lengths are saved along with the document metadata
+        search_engine.save_index().unwrap();
+        let mut loaded_search_engine = SearchEngine::new(
+            String::new(),
+            CompressionAlgorithm::Simple16,
+            QueryAlgorithm::MaxScore,
+            temp_dir.path().join("index").to_str().unwrap().to_string(),
+        )
+        .unwrap();
+        loaded_search_engine.load_index().unwrap();
+        assert_eq!(
+            names(loaded_search_engine.query("misery", 10, 0).unwrap()),
+            vec!["Misery", "Sorrow"]
+        );
+    }
+
+    #[test]
+    fn test_bm25f() {
+        // every body has the same length, and the fillers keep the idf of misery positive
+        let documents = [
+            (
+                "Misery",
+                "misery river garden castle dragon forest meadow valley",
+            ),
+            (
+                "Sorrow",
+                "misery misery misery misery misery harbor lantern quarry",
+            ),
+            (
+                "Train",
+                "night train leaves station ten sharp every evening",
+            ),
+            (
+                "Ocean",
+                "waves crashed against rocky shore under grey skies",
+            ),
+            (
+                "Forge",
+                "blacksmith hammered glowing iron until dawn broke slowly",
+            ),
+            (
+                "Orchard",
+                "apples ripened early across hillside orchard rows today",
+            ),
+            (
+                "Comet",
+                "bright comet crossed northern sky above silent hills",
+            ),
+            (
+                "Bakery",
+                "fresh bread cooled near window while bakers rested",
+            ),
+        ];
+        let names = |search_engine: &mut SearchEngine| -> Vec<String> {
+            search_engine
+                .query("misery", 10, 0)
+                .unwrap()
+                .into_iter()
+                .map(|r| r.document.doc_name)
+                .collect()
+        };
+        let create_search_engine = |temp_dir: &TempDir, scoring_model| {
+            let dataset_dir = temp_dir.path().join("dataset");
+            write_test_dataset(&dataset_dir, &documents);
+            let mut search_engine = SearchEngine::new(
+                dataset_dir.to_str().unwrap().to_string(),
+                CompressionAlgorithm::Simple16,
+                QueryAlgorithm::Wand,
+                temp_dir.path().join("index").to_str().unwrap().to_string(),
+            )
+            .unwrap();
+            search_engine.set_scoring_model(scoring_model);
+            search_engine.build_index().unwrap();
+            search_engine
+        };
+
+        // BM25 adds up the saturated scores of the fields, so the title match alone outweighs the
+        // body which repeats the word
+        let temp_dir = TempDir::new().unwrap();
+        let mut search_engine = create_search_engine(&temp_dir, ScoringModel::BM25);
+        assert_eq!(names(&mut search_engine), vec!["Misery", "Sorrow"]);
+
+        // BM25F saturates the weighted frequencies of the fields once, a title match counts as
+        // title weight occurrences in the body
+        let temp_dir = TempDir::new().unwrap();
+        let mut search_engine = create_search_engine(&temp_dir, ScoringModel::BM25F);
+        for query_algorithm in [
+            QueryAlgorithm::Wand,
+            QueryAlgorithm::BlockMaxWand,
+            QueryAlgorithm::MaxScore,
+            QueryAlgorithm::BlockMaxMaxScore,
+        ] {
+            search_engine.set_query_algorithm(query_algorithm);
+            search_engine
+                .set_title_weight(DEFAULT_TITLE_WEIGHT)
+                .unwrap();
+            assert_eq!(names(&mut search_engine), vec!["Sorrow", "Misery"]);
+            search_engine.set_title_weight(10.0).unwrap();
+            assert_eq!(names(&mut search_engine), vec!["Misery", "Sorrow"]);
+        }
+    }
+
+    #[test]
+    fn test_query_pagination() {
+        // BM25 gives a negative idf to terms present in more than half of the documents,
+        // so the test documents are padded with unrelated ones.
+        let mut documents = TEST_DOCUMENTS.to_vec();
+        documents.extend_from_slice(&[
+            ("River", "a quiet river flows past the old mill"),
+            ("Garden", "the garden was full of roses in spring"),
+            ("Train", "the night train leaves the station at ten"),
+            ("Ocean", "waves crashed against the rocky shore"),
+            ("Bakery", "fresh bread is baked every morning"),
+            ("Library", "the library opens early on weekdays"),
+        ]);
+        let temp_dir = TempDir::new().unwrap();
+        let mut search_engine = create_test_search_engine(&temp_dir, &documents, false);
+
+        let all_results = search_engine.query("misery town", 10, 0).unwrap();
+        assert_eq!(all_results.len(), 4);
+        assert!(
+            all_results
+                .windows(2)
+                .all(|pair| pair[0].score >= pair[1].score)
+        );
+
+        let first_page = search_engine.query("misery town", 2, 0).unwrap();
+        let second_page = search_engine.query("misery town", 2, 2).unwrap();
+        let paged_names: Vec<String> = first_page
+            .into_iter()
+            .chain(second_page)
+            .map(|r| r.document.doc_name)
+            .collect();
+        let all_names: Vec<String> = all_results
+            .into_iter()
+            .map(|r| r.document.doc_name)
+            .collect();
+        assert_eq!(paged_names, all_names);
+
+        let last_page = search_engine.query("misery town", 2, 4).unwrap();
+        assert!(last_page.is_empty());
+
+        // k and offset are not trusted to be small, they can not overflow or allocate up front
+        let results = search_engine.query("misery town", usize::MAX, 0).unwrap();
+        assert_eq!(results.len(), 4);
+        assert!(
+            search_engine
+                .query("misery town", usize::MAX, usize::MAX)
+                .unwrap()
+                .is_empty()
+        );
+        search_engine.set_query_algorithm(QueryAlgorithm::MaxScore);
+        assert_eq!(
+            search_engine
+                .query("misery town", usize::MAX, 1)
+                .unwrap()
+                .len(),
+            3
+        );
+    }
+
+    #[test]
+    fn test_add_documents() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut search_engine = create_test_search_engine(&temp_dir, &TEST_DOCUMENTS[..2], true);
+        assert_eq!(search_engine.indexer.get_no_of_docs(), 2);
+
+        let new_batch_dir = temp_dir.path().join("new_batch");
+        write_test_dataset(&new_batch_dir, &TEST_DOCUMENTS[2..]);
+        search_engine
+            .add_documents(vec![new_batch_dir.join("batch_0")])
+            .unwrap();
+        assert_eq!(search_engine.indexer.get_no_of_docs(), 4);
+        assert_eq!(search_engine.indexer.document_names[2], "Novel");
+
+        // the postings of the existing documents and the new documents are merged
+        search_engine.set_query_algorithm(QueryAlgorithm::Boolean);
+        let mut run = |query: &str| {
+            let mut names: Vec<String> = search_engine
+                .query(query, 10, 0)
+                .unwrap()
+                .into_iter()
+                .map(|r| r.document.doc_name)
+                .collect();
+            names.sort();
+            names
+        };
+        assert_eq!(run("misery"), vec!["Misery", "Movie", "Novel"]);
+        assert_eq!(run("theater"), vec!["Town"]);
+        assert_eq!(run("small town"), vec!["Movie", "Town"]);
+        assert_eq!(run("\"misery movie\""), vec!["Novel"]);
+    }
+
+    #[test]
+    fn test_add_documents_without_saving() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut search_engine = create_test_search_engine(&temp_dir, &TEST_DOCUMENTS[..2], true);
+        search_engine.save_index().unwrap();
+        let new_batch_dir = temp_dir.path().join("new_batch");
+        write_test_dataset(&new_batch_dir, &TEST_DOCUMENTS[2..]);
+        search_engine
+            .add_documents(vec![new_batch_dir.join("batch_0")])
+            .unwrap();
+        // a segment directory which was written but never listed
+        let orphaned_segment_path = get_segment_directory_path(temp_dir.path().join("index"), 7);
+        fs::create_dir_all(&orphaned_segment_path).unwrap();
+        drop(search_engine);
+
+        // the added segment is loaded without save_index having been called after it
+        let mut search_engine = SearchEngine::new(
+            temp_dir
+                .path()
+                .join("dataset")
+                .to_str()
+                .unwrap()
+                .to_string(),
+            CompressionAlgorithm::Simple16,
+            QueryAlgorithm::Wand,
+            temp_dir.path().join("index").to_str().unwrap().to_string(),
+        )
+        .unwrap();
+        search_engine.load_index().unwrap();
+        assert_eq!(search_engine.indexer.get_no_of_docs(), 4);
+        assert_eq!(search_engine.get_segment_ids(), vec![0, 1]);
+        assert!(!orphaned_segment_path.exists());
+        let results = search_engine.query("theater", 10, 0).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].document.doc_name, "Town");
+    }
+
+    #[test]
+    fn test_index_path_is_a_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let index_path = temp_dir.path().join("index");
+        fs::write(&index_path, b"not an index").unwrap();
+        let result = SearchEngine::new(
+            String::new(),
+            CompressionAlgorithm::Simple16,
+            QueryAlgorithm::Wand,
+            index_path.to_str().unwrap().to_string(),
+        );
+        assert!(matches!(result, Err(SearchEngineError::Io(_))));
+    }
+
+    #[test]
+    fn test_resume_stopped_build() {
+        let temp_dir = TempDir::new().unwrap();
+        let dataset_dir = temp_dir.path().join("dataset");
+        let index_dir = temp_dir.path().join("index");
+        let open_search_engine = || {
+            SearchEngine::new(
+                dataset_dir.to_str().unwrap().to_string(),
+                CompressionAlgorithm::Simple16,
+                QueryAlgorithm::Wand,
+                index_dir.to_str().unwrap().to_string(),
+            )
+            .unwrap()
+        };
+        let scores = |search_engine: &mut SearchEngine| {
+            let mut scores: Vec<(String, f32)> = search_engine
+                .query("stephen award theater", 10, 0)
+                .unwrap()
+                .into_iter()
+                .map(|r| (r.document.doc_name, r.score))
+                .collect();
+            scores.sort_by(|a, b| a.0.cmp(&b.0));
+            scores
+        };
+
+        // the build stops after the run of the first batch while the second run is being written
+        write_test_batch(&dataset_dir, "batch_0", &TEST_DOCUMENTS[..2]);
+        let build_report = open_search_engine().build_index().unwrap();
+        assert!(!build_report.is_resumed());
+        write_test_batch(&dataset_dir, "batch_1", &TEST_DOCUMENTS[2..]);
+        fs::write(get_unfinished_run_path(&index_dir, 1), b"half a run").unwrap();
+
+        // only the second batch is indexed when the build is started again
+        let mut search_engine = open_search_engine();
+        let build_report = search_engine.build_index().unwrap();
+        assert_eq!(
+            build_report,
+            BuildReport {
+                no_of_resumed_docs: 2,
+                no_of_resumed_runs: 1,
+            }
+        );
+        assert_eq!(search_engine.indexer.get_no_of_docs(), 4);
+        let manifest = BuildManifest::load(&index_dir).unwrap().unwrap();
+        assert_eq!(manifest.get_no_of_runs(), 2);
+        assert!(get_run_path(&index_dir, 1).exists());
+        assert!(!get_unfinished_run_path(&index_dir, 1).exists());
+        let resumed_scores = scores(&mut search_engine);
+        assert_eq!(resumed_scores.len(), 3);
+
+        // the runs can be merged after a restart, the document lengths are read from the manifest
+        let mut restarted_search_engine = open_search_engine();
+        restarted_search_engine.merge_spimi_files().unwrap();
+        assert_eq!(restarted_search_engine.indexer.get_no_of_docs(), 4);
+        assert_eq!(scores(&mut restarted_search_engine), resumed_scores);
+
+        restarted_search_engine.save_index().unwrap();
+        assert!(!get_build_manifest_path(&index_dir).exists());
+        assert!(!get_run_path(&index_dir, 0).exists());
+
+        // the documents are scored as if they had been indexed in one go
+        let fresh_temp_dir = TempDir::new().unwrap();
+        let mut fresh_search_engine =
+            create_test_search_engine(&fresh_temp_dir, TEST_DOCUMENTS, false);
+        assert_eq!(scores(&mut fresh_search_engine), resumed_scores);
+    }
+
+    #[test]
+    fn test_resume_partly_read_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let dataset_dir = temp_dir.path().join("dataset");
+        let index_dir = temp_dir.path().join("index");
+        let open_search_engine = || {
+            SearchEngine::new(
+                dataset_dir.to_str().unwrap().to_string(),
+                CompressionAlgorithm::Simple16,
+                QueryAlgorithm::Wand,
+                index_dir.to_str().unwrap().to_string(),
+            )
+            .unwrap()
+        };
+
+        // the build stopped after a run with the first two documents of the file
+        write_test_batch(&dataset_dir, "batch_0", &TEST_DOCUMENTS[..2]);
+        open_search_engine().build_index().unwrap();
+        let documents = BuildManifest::load(&index_dir)
+            .unwrap()
+            .unwrap()
+            .load_documents()
+            .unwrap();
+        let mut manifest = BuildManifest::new(&index_dir, &dataset_dir, 0);
+        let file_progress = FileProgress {
+            documents_read: 2,
+            is_finished: false,
+        };
+        let batch_file = dataset_dir.join("batch_0").join("batch_0.zst");
+        manifest
+            .add_run(BTreeMap::from([(batch_file, file_progress)]), &documents)
+            .unwrap();
+        write_test_batch(&dataset_dir, "batch_0", TEST_DOCUMENTS);
+
+        // the file is read again after the documents which are in the run
+        let mut search_engine = open_search_engine();
+        search_engine.build_index().unwrap();
+        assert_eq!(search_engine.indexer.get_no_of_docs(), 4);
+        assert_eq!(
+            BuildManifest::load(&index_dir)
+                .unwrap()
+                .unwrap()
+                .get_no_of_runs(),
+            2
+        );
+        let results = search_engine.query("stephen award theater", 10, 0).unwrap();
+
+        let fresh_temp_dir = TempDir::new().unwrap();
+        let mut fresh_search_engine =
+            create_test_search_engine(&fresh_temp_dir, TEST_DOCUMENTS, false);
+        let fresh_results = fresh_search_engine
+            .query("stephen award theater", 10, 0)
+            .unwrap();
+        assert_eq!(results.len(), 3);
+        for (result, fresh_result) in results.iter().zip(&fresh_results) {
+            assert_eq!(result.doc_id, fresh_result.doc_id);
+            assert_eq!(result.score, fresh_result.score);
+        }
+    }
+
+    #[test]
+    fn test_index_wikipedia_dump() {
+        let dump = r#"<mediawiki xml:lang="en">
+  <siteinfo>
+    <base>https://en.wikipedia.org/wiki/Main_Page</base>
+  </siteinfo>
+  <page>
+    <title>Misery (novel)</title>
+    <ns>0</ns>
+    <revision>
+      <text xml:space="preserve">{{Infobox book|author=[[Stephen King]]|publisher=Viking}}
+'''''Misery''''' is a [[psychological horror|horror]] novel by [[Stephen King]].
+== Plot ==
+[[Annie Wilkes]] keeps the writer Paul Sheldon in her house.&lt;ref&gt;{{cite web|publisher=Viking}}&lt;/ref&gt;
+== References ==
+* Viking Press catalogue
+[[Category:Novels by Stephen King]]</text>
+    </revision>
+  </page>
+  <page>
+    <title>Viking</title>
+    <ns>0</ns>
+    <redirect title="Vikings" />
+    <revision>
+      <text xml:space="preserve">#REDIRECT [[Vikings]]</text>
+    </revision>
+  </page>
+  <page>
+    <title>River</title>
+    <ns>0</ns>
+    <revision>
+      <text xml:space="preserve">A '''river''' flows past the old mill.</text>
+    </revision>
+  </page>
+  <page>
+    <title>Garden</title>
+    <ns>0</ns>
+    <revision>
+      <text xml:space="preserve">The [[garden]] was full of roses in spring.</text>
+    </revision>
+  </page>
+  <page>
+    <title>Template:Infobox book</title>
+    <ns>10</ns>
+    <revision>
+      <text xml:space="preserve">{| class="infobox" |}</text>
+    </revision>
+  </page>
+</mediawiki>
+"#;
+        let temp_dir = TempDir::new().unwrap();
+        let dump_path = temp_dir.path().join("enwiki-latest-pages-articles.xml.bz2");
+        let mut encoder = bzip2::write::BzEncoder::new(
+            File::create(&dump_path).unwrap(),
+            bzip2::Compression::fast(),
+        );
+        encoder.write_all(dump.as_bytes()).unwrap();
+        encoder.finish().unwrap();
+
+        let mut search_engine = SearchEngine::new(
+            String::new(),
+            CompressionAlgorithm::Simple16,
+            QueryAlgorithm::Wand,
+            temp_dir.path().join("index").to_str().unwrap().to_string(),
+        )
+        .unwrap();
+        assert!(matches!(
+            search_engine.index_wikipedia_dump(temp_dir.path().join("missing.xml.bz2")),
+            Err(SearchEngineError::DatasetNotFound(_))
+        ));
+        search_engine.index_wikipedia_dump(dump_path).unwrap();
+        // the redirect and the template are not articles
+        assert_eq!(search_engine.indexer.get_no_of_docs(), 3);
+
+        let results = search_engine.query("annie wilkes horror", 10, 0).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].document.doc_name, "Misery (novel)");
+        assert_eq!(
+            results[0].document.doc_url,
+            "https://en.wikipedia.org/wiki/Misery_(novel)"
+        );
+        // the infobox, the citations, the references and the categories are not indexed
+        for term in ["viking", "infobox", "category", "catalogue", "ref"] {
+            assert_eq!(search_engine.get_document_frequency(term), None, "{}", term);
+        }
+    }
+
+    #[test]
+    fn test_wildcard_query() {
+        let documents = [
+            ("Astronomy", "astronomy is the study of stars and planets"),
+            ("Astronaut", "an astronaut travels to space in a rocket"),
+            ("Colour", "the colour of the sky is blue"),
+            ("Color", "color theory explains how colors mix"),
+            ("River", "a quiet river flows past the old mill"),
+            ("Garden", "the garden was full of roses in spring"),
+            ("Train", "the night train leaves the station at ten"),
+            ("Ocean", "waves crashed against the rocky shore"),
+        ];
+        let temp_dir = TempDir::new().unwrap();
+        let mut search_engine = create_test_search_engine(&temp_dir, &documents, true);
+        let run = |search_engine: &mut SearchEngine, query: &str| {
+            let mut names: Vec<String> = search_engine
+                .query(query, 10, 0)
+                .unwrap()
+                .into_iter()
+                .map(|r| r.document.doc_name)
+                .collect();
+            names.sort();
+            names
+        };
+        assert_eq!(
+            run(&mut search_engine, "astro*"),
+            vec!["Astronaut", "Astronomy"]
+        );
+        assert_eq!(run(&mut search_engine, "col?r"), vec!["Color"]);
+        assert_eq!(run(&mut search_engine, "colo*r"), vec!["Color", "Colour"]);
+        assert_eq!(
+            run(&mut search_engine, "title:ast*"),
+            vec!["Astronaut", "Astronomy"]
+        );
+        assert!(run(&mut search_engine, "zebra*").is_empty());
+        assert!(matches!(
+            search_engine.query("\"astro* study\"", 10, 0),
+            Err(SearchEngineError::InvalidQuery(_))
+        ));
+
+        search_engine.set_query_algorithm(QueryAlgorithm::Boolean);
+        assert_eq!(run(&mut search_engine, "astro* -rocket"), vec!["Astronomy"]);
+        assert_eq!(
+            run(&mut search_engine, "col?r OR riv*"),
+            vec!["Color", "River"]
+        );
+        assert!(run(&mut search_engine, "zebra* OR zoo*").is_empty());
+    }
+
+    #[test]
+    fn test_suggest_query() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut search_engine = create_test_search_engine(&temp_dir, &TEST_DOCUMENTS[..2], false);
+        let new_batch_dir = temp_dir.path().join("new_batch");
+        write_test_dataset(&new_batch_dir, &TEST_DOCUMENTS[2..]);
+        search_engine
+            .add_documents(vec![new_batch_dir.join("batch_0")])
+            .unwrap();
+
+        assert_eq!(
+            search_engine.suggest_query("misry mvie"),
+            Some("misery movie".to_string())
+        );
+        assert_eq!(
+            search_engine.suggest_query("\"stephn king\""),
+            Some("\"stephen king\"".to_string())
+        );
+        assert_eq!(
+            search_engine.suggest_query("title:misry"),
+            Some("title:misery".to_string())
+        );
+        // theater is only in the segment of the added documents
+        assert_eq!(
+            search_engine.suggest_query("famous theatr"),
+            Some("famous theater".to_string())
+        );
+        assert_eq!(search_engine.suggest_query("misery movie"), None);
+        assert_eq!(search_engine.suggest_query("xyzzy"), None);
+
+        // the BK-trees are saved along with the segments
+        search_engine.save_index().unwrap();
+        let mut loaded_search_engine = SearchEngine::new(
+            String::new(),
+            CompressionAlgorithm::Simple16,
+            QueryAlgorithm::Wand,
+            temp_dir.path().join("index").to_str().unwrap().to_string(),
+        )
+        .unwrap();
+        loaded_search_engine.load_index().unwrap();
+        assert_eq!(
+            loaded_search_engine.suggest_query("misry theatr"),
+            Some("misery theater".to_string())
+        );
+    }
+
+    #[test]
+    fn test_load_legacy_term_metadata() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut search_engine = create_test_search_engine(&temp_dir, TEST_DOCUMENTS, false);
+        search_engine.save_index().unwrap();
+        let index_dir = temp_dir.path().join("index");
+        assert!(index_dir.join("terms.sidx").exists());
+
+        // an index saved before terms.sidx only has term_metadata.sidx
+        let file = File::create(index_dir.join("term_metadata.sidx")).unwrap();
+        search_engine.segments[0]
+            .in_memory_index_metadata
+            .save_term_metadata(std::io::BufWriter::new(file))
+            .unwrap();
+        std::fs::remove_file(index_dir.join("terms.sidx")).unwrap();
+
+        let mut loaded_search_engine = SearchEngine::new(
+            String::new(),
+            CompressionAlgorithm::Simple16,
+            QueryAlgorithm::Wand,
+            index_dir.to_str().unwrap().to_string(),
+        )
+        .unwrap();
+        loaded_search_engine.load_index().unwrap();
+        let results = loaded_search_engine.query("stephen king", 10, 0).unwrap();
+        assert_eq!(results[0].document.doc_name, "Misery");
+
+        // saving the index again moves it over to terms.sidx
+        loaded_search_engine.save_index().unwrap();
+        assert!(index_dir.join("terms.sidx").exists());
+        assert!(!index_dir.join("term_metadata.sidx").exists());
+        loaded_search_engine.load_index().unwrap();
+        let results = loaded_search_engine.query("stephen king", 10, 0).unwrap();
+        assert_eq!(results[0].document.doc_name, "Misery");
+    }
+
+    #[test]
+    fn test_corrupt_index() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut search_engine = create_test_search_engine(&temp_dir, TEST_DOCUMENTS, false);
+        search_engine.save_index().unwrap();
+        let index_dir = temp_dir.path().join("index");
+        let open = |compression_algorithm: CompressionAlgorithm| {
+            let mut search_engine = SearchEngine::new(
+                String::new(),
+                compression_algorithm,
+                QueryAlgorithm::Wand,
+                index_dir.to_str().unwrap().to_string(),
+            )
+            .unwrap();
+            search_engine.load_index().map(|_| search_engine)
+        };
+        let mut loaded_search_engine = open(CompressionAlgorithm::Simple16).unwrap();
+        let results = loaded_search_engine.query("stephen king", 10, 0).unwrap();
+        assert_eq!(results[0].document.doc_name, "Misery");
+
+        // the compression algorithm is saved with the index
+        assert!(matches!(
+            open(CompressionAlgorithm::VarByte),
+            Err(SearchEngineError::IncompatibleIndex(_))
+        ));
+
+        let damage = |file_name: &str, damage: &dyn Fn(&mut Vec<u8>)| {
+            let path = index_dir.join(file_name);
+            let bytes = std::fs::read(&path).unwrap();
+            let mut damaged_bytes = bytes.clone();
+            damage(&mut damaged_bytes);
+            std::fs::write(&path, &damaged_bytes).unwrap();
+            let result = open(CompressionAlgorithm::Simple16);
+            std::fs::write(&path, &bytes).unwrap();
+            result
+        };
+        assert!(matches!(
+            damage("document_metadata.sidx", &|bytes| bytes[20] ^= 1),
+            Err(SearchEngineError::CorruptIndex(_))
+        ));
+        assert!(matches!(
+            damage("inverted_index.idx", &|bytes| bytes
+                .truncate(bytes.len() - 1)),
+            Err(SearchEngineError::CorruptIndex(_))
+        ));
+        assert!(matches!(
+            damage("terms.sidx", &|bytes| bytes[12] ^= 1),
+            Err(SearchEngineError::CorruptIndex(_))
+        ));
+        // an index saved by a newer version can not be read
+        assert!(matches!(
+            damage("terms.sidx", &|bytes| bytes[4] = 2),
+            Err(SearchEngineError::IncompatibleIndex(_))
+        ));
+        assert!(open(CompressionAlgorithm::Simple16).is_ok());
+
+        // the blocks are only checked by the queries which decode them
+        let path = index_dir.join("inverted_index.idx");
+        let bytes = std::fs::read(&path).unwrap();
+        let mut damaged_bytes = bytes.clone();
+        damaged_bytes[100] ^= 1;
+        std::fs::write(&path, &damaged_bytes).unwrap();
+        let mut damaged_search_engine = open(CompressionAlgorithm::Simple16).unwrap();
+        assert!(matches!(
+            damaged_search_engine.query("stephen king", 10, 0),
+            Err(SearchEngineError::Io(_))
+        ));
+        std::fs::write(&path, &bytes).unwrap();
+    }
+
+    #[test]
+    fn test_index_metadata_of_a_removed_index() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut search_engine = create_test_search_engine(&temp_dir, TEST_DOCUMENTS, false);
+        search_engine.save_index().unwrap();
+        let metadata = search_engine.get_index_metadata().unwrap();
+        assert_eq!(metadata.no_of_docs, TEST_DOCUMENTS.len() as u32);
+        assert!(metadata.size_of_index > 0.0);
+
+        // the files of the index were removed from under the search engine
+        std::fs::remove_file(temp_dir.path().join("index").join("inverted_index.idx")).unwrap();
+        assert!(matches!(
+            search_engine.get_index_metadata(),
+            Err(SearchEngineError::Io(_))
+        ));
+    }
+
+    // verify_index and repair_index read the saved index, the documents which were added and
+    // deleted since it was saved are still searchable and deleted afterwards
+    #[test]
+    fn test_verify_index_keeps_loaded_documents() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut search_engine = create_test_search_engine(&temp_dir, TEST_DOCUMENTS, true);
+        search_engine.save_index().unwrap();
+        let batch_dir = temp_dir.path().join("new_batch");
+        write_test_dataset(
+            &batch_dir,
+            &[
+                ("Hope", "a story about hope and misery"),
+                ("Faith", "a story about faith"),
+            ],
+        );
+        search_engine
+            .add_documents(vec![batch_dir.join("batch_0")])
+            .unwrap();
+        search_engine.set_base_segment_deleted_ratio(1.0);
+        assert_eq!(search_engine.delete_documents_by_name("Faith").unwrap(), 1);
+        assert_eq!(search_engine.delete_documents_by_name("Misery").unwrap(), 1);
+
+        assert!(search_engine.verify_index().unwrap().is_ok());
+        assert!(search_engine.repair_index().unwrap().is_ok());
+        assert!(search_engine.query("faith", 10, 0).unwrap().is_empty());
+        assert!(search_engine.query("stephen", 10, 0).unwrap().is_empty());
+        let results = search_engine.query("hope", 10, 0).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].document.doc_name, "Hope");
+    }
+
+    #[test]
+    fn test_verify_and_repair_index() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut search_engine = create_test_search_engine(&temp_dir, TEST_DOCUMENTS, true);
+        let batch_dir = temp_dir.path().join("new_batch");
+        write_test_dataset(
+            &batch_dir,
+            &[
+                ("Hope", "a story about hope and misery"),
+                ("Faith", "a story about faith"),
+            ],
+        );
+        search_engine
+            .add_documents(vec![batch_dir.join("batch_0")])
+            .unwrap();
+        search_engine.save_index().unwrap();
+
+        // the max scores are computed again with the statistics each segment was written with
+        let report = search_engine.verify_index().unwrap();
+        assert!(report.is_ok(), "{:?}", report);
+        assert_eq!(report.segments.len(), 2);
+        assert_eq!(report.segments[1].first_doc_id, 5);
+        assert!(
+            report
+                .segments
+                .iter()
+                .all(|segment| segment.no_of_postings > 0)
+        );
+
+        // a damaged block is found by its checksum, the rest of the index is still checked
+        let index_dir = temp_dir.path().join("index");
+        let inverted_index_path = index_dir.join("inverted_index.idx");
+        let mut bytes = std::fs::read(&inverted_index_path).unwrap();
+        bytes[100] ^= 1;
+        std::fs::write(&inverted_index_path, &bytes).unwrap();
+        // a query reading the damaged block fails instead of panicking
+        assert!(search_engine.query("misery", 10, 0).is_err());
+        let report = search_engine.verify_index().unwrap();
+        assert!(!report.is_ok());
+        assert_eq!(report.segments[0].damaged_block_ids, vec![0]);
+        assert!(report.segments[1].is_ok());
+        assert!(report.is_repairable());
+
+        // the base segment only has a single block so all of its postings are lost
+        let report = search_engine.repair_index().unwrap();
+        assert!(!report.is_ok());
+        assert!(search_engine.verify_index().unwrap().is_ok());
+        let results = search_engine.query("misery", 10, 0).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].document.doc_name, "Hope");
+
+        // a segment whose term metadata is damaged can only be built again
+        let segment_id = search_engine.get_segment_ids()[1];
+        let terms_path = index_dir
+            .join("segments")
+            .join(segment_id.to_string())
+            .join("terms.sidx");
+        let mut bytes = std::fs::read(&terms_path).unwrap();
+        bytes[12] ^= 1;
+        std::fs::write(&terms_path, &bytes).unwrap();
+        let report = search_engine.repair_index().unwrap();
+        assert!(!report.segments[1].repairable);
+        assert!(!report.is_repairable());
+        assert!(matches!(
+            search_engine.load_index(),
+            Err(SearchEngineError::CorruptIndex(_))
+        ));
+    }
+
+    #[test]
+    fn test_add_documents_without_positions() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut search_engine = create_test_search_engine(&temp_dir, &TEST_DOCUMENTS[..2], false);
+
+        let new_batch_dir = temp_dir.path().join("new_batch");
+        write_test_dataset(&new_batch_dir, &TEST_DOCUMENTS[2..]);
+        search_engine
+            .add_documents(vec![new_batch_dir.join("batch_0").join("batch_0.zst")])
+            .unwrap();
+
+        let results = search_engine.query("stephen theater", 10, 0).unwrap();
+        let mut names: Vec<String> = results.into_iter().map(|r| r.document.doc_name).collect();
+        names.sort();
+        assert_eq!(names, vec!["Misery", "Town"]);
+    }
+
+    #[test]
+    fn test_delete_documents() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut search_engine = create_test_search_engine(&temp_dir, TEST_DOCUMENTS, true);
+        search_engine.set_query_algorithm(QueryAlgorithm::Boolean);
+        // the base segment is kept as it is, so the postings of the deleted document stay in it
+        search_engine.set_base_segment_deleted_ratio(1.0);
+        let names = |search_engine: &mut SearchEngine, query: &str| {
+            let mut names: Vec<String> = search_engine
+                .query(query, 10, 0)
+                .unwrap()
+                .into_iter()
+                .map(|r| r.document.doc_name)
+                .collect();
+            names.sort();
+            names
+        };
+        assert_eq!(
+            names(&mut search_engine, "misery"),
+            vec!["Misery", "Movie", "Novel"]
+        );
+
+        assert_eq!(search_engine.delete_documents_by_name("Movie").unwrap(), 1);
+        assert_eq!(search_engine.delete_documents_by_name("Movie").unwrap(), 0);
+        assert_eq!(names(&mut search_engine, "misery"), vec!["Misery", "Novel"]);
+        assert_eq!(
+            names(&mut search_engine, "NOT stephen"),
+            vec!["Novel", "Town"]
+        );
+
+        // the deleted documents are persisted with the rest of the document metadata
+        search_engine.save_index().unwrap();
+        search_engine.indexer.deleted_documents.clear();
+        search_engine.load_index().unwrap();
+        assert!(search_engine.indexer.deleted_documents.contains(2));
+
+        // the new documents are written to a segment of their own, the postings of the
+        // deleted document stay in the base segment until it is rebuilt
+        let new_batch_dir = temp_dir.path().join("new_batch");
+        write_test_dataset(&new_batch_dir, &[("Loss", "a story about loss")]);
+        search_engine
+            .add_documents(vec![new_batch_dir.join("batch_0")])
+            .unwrap();
+        assert_eq!(search_engine.get_segment_ids(), vec![0, 1]);
+        let term_metadata = search_engine.segments[0]
+            .in_memory_index_metadata
+            .get_term_metadata("misery")
+            .unwrap();
+        assert_eq!(term_metadata.term_frequency, 3);
+        let term_metadata = search_engine.segments[1]
+            .in_memory_index_metadata
+            .get_term_metadata("loss")
+            .unwrap();
+        assert_eq!(term_metadata.term_frequency, 1);
+        assert_eq!(names(&mut search_engine, "misery"), vec!["Misery", "Novel"]);
+        assert_eq!(names(&mut search_engine, "loss"), vec!["Loss"]);
+    }
+
+    #[test]
+    fn test_concurrent_queries() {
+        fn assert_send_sync<T: Send + Sync>(_: &T) {}
+
+        let temp_dir = TempDir::new().unwrap();
+        let mut search_engine = create_test_search_engine(&temp_dir, TEST_DOCUMENTS, true);
+        let new_batch_dir = temp_dir.path().join("new_batch");
+        write_test_dataset(
+            &new_batch_dir,
+            &[("Theater", "the old theater in town closed")],
+        );
+        search_engine
+            .add_documents(vec![new_batch_dir.join("batch_0")])
+            .unwrap();
+
+        let queries = ["stephen", "theater", "\"misery movie\"", "small town"];
+        let doc_ids = |results: Vec<SearchResult>| -> Vec<u32> {
+            results.into_iter().map(|result| result.doc_id).collect()
+        };
+        let expected_results: Vec<Vec<u32>> = queries
+            .iter()
+            .map(|query| doc_ids(search_engine.query(query, 10, 0).unwrap()))
+            .collect();
+        search_engine.reopen_segments().unwrap();
+
+        let searcher = search_engine.searcher();
+        assert_send_sync(&searcher);
+        thread::scope(|scope| {
+            let handles: Vec<_> = (0..8)
+                .map(|i| {
+                    scope.spawn(move || {
+                        (0..queries.len())
+                            .map(|j| {
+                                let query = queries[(i + j) % queries.len()];
+                                (query, doc_ids(searcher.query(query, 10, 0).unwrap()))
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            for handle in handles {
+                for (query, results) in handle.join().unwrap() {
+                    let i = queries.iter().position(|q| *q == query).unwrap();
+                    assert_eq!(results, expected_results[i]);
+                }
+            }
+        });
+    }
+
+    #[test]
+    fn test_segment_merge() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut search_engine = create_test_search_engine(&temp_dir, TEST_DOCUMENTS, true);
+        search_engine.set_merge_factor(2).unwrap();
+        search_engine.set_query_algorithm(QueryAlgorithm::Boolean);
+        let names = |search_engine: &mut SearchEngine, query: &str| {
+            let mut names: Vec<String> = search_engine
+                .query(query, 10, 0)
+                .unwrap()
+                .into_iter()
+                .map(|r| r.document.doc_name)
+                .collect();
+            names.sort();
+            names
+        };
+
+        let batches = [
+            vec![
+                ("Grief", "a story about grief"),
+                ("Loss", "a story about loss"),
+            ],
+            vec![
+                ("Hope", "a story about hope and misery"),
+                ("Faith", "a story about faith"),
+            ],
+        ];
+        for (i, batch) in batches.iter().enumerate() {
+            let batch_dir = temp_dir.path().join(format!("new_batch_{}", i));
+            write_test_dataset(&batch_dir, batch);
+            search_engine
+                .add_documents(vec![batch_dir.join("batch_0")])
+                .unwrap();
+            if i == 0 {
+                assert_eq!(search_engine.delete_documents_by_name("Loss").unwrap(), 1);
+            }
+        }
+
+        // the two added segments are in the same tier so they are merged into a new segment
+        // which covers all of their doc ids, the deleted document is purged by the merge
+        search_engine.wait_for_segment_merges().unwrap();
+        assert_eq!(search_engine.get_segment_ids(), vec![0, 3]);
+        assert_eq!(search_engine.segments[1].get_doc_id_range(), 5..=8);
+        let merged_metadata = &search_engine.segments[1].in_memory_index_metadata;
+        assert!(merged_metadata.get_term_metadata("loss").is_none());
+        assert_eq!(
+            merged_metadata
+                .get_term_metadata("story")
+                .unwrap()
+                .term_frequency,
+            3
+        );
+        assert!(!temp_dir.path().join("index/segments/1").exists());
+
+        assert_eq!(
+            names(&mut search_engine, "story"),
+            vec!["Faith", "Grief", "Hope"]
+        );
+        assert_eq!(
+            names(&mut search_engine, "misery"),
+            vec!["Hope", "Misery", "Movie", "Novel"]
+        );
+        assert_eq!(
+            names(&mut search_engine, "story AND NOT hope"),
+            vec!["Faith", "Grief"]
+        );
+
+        // ranked queries are merged across the segments
+        search_engine.set_query_algorithm(QueryAlgorithm::Wand);
+        search_engine.query_processor = QueryProcessor::new(
+            temp_dir.path().join("index"),
+            CompressionAlgorithm::Simple16,
+            QueryAlgorithm::Wand,
+            ScoringModel::BM25,
+            BM25Params::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            names(&mut search_engine, "stephen hope"),
+            vec!["Hope", "Misery"]
+        );
+
+        // the segments are loaded back along with the rest of the index
+        search_engine.save_index().unwrap();
+        let mut loaded_search_engine = SearchEngine::new(
+            temp_dir
+                .path()
+                .join("dataset")
+                .to_str()
+                .unwrap()
+                .to_string(),
+            CompressionAlgorithm::Simple16,
+            QueryAlgorithm::Boolean,
+            temp_dir.path().join("index").to_str().unwrap().to_string(),
+        )
+        .unwrap();
+        loaded_search_engine.load_index().unwrap();
+        assert_eq!(loaded_search_engine.get_segment_ids(), vec![0, 3]);
+        assert_eq!(loaded_search_engine.segments[0].no_of_docs, 4);
+        assert_eq!(
+            names(&mut loaded_search_engine, "story OR town"),
+            vec!["Faith", "Grief", "Hope", "Movie", "Town"]
+        );
+    }
+
+    #[test]
+    fn test_base_segment_merge() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut search_engine = create_test_search_engine(&temp_dir, TEST_DOCUMENTS, true);
+        search_engine.set_query_algorithm(QueryAlgorithm::Boolean);
+        search_engine.save_index().unwrap();
+
+        // a quarter of the base segment is deleted, which is over the default deleted ratio
+        assert_eq!(search_engine.delete_documents_by_name("Novel").unwrap(), 1);
+        search_engine.wait_for_segment_merges().unwrap();
+        assert_eq!(search_engine.get_segment_ids(), vec![0]);
+        assert_eq!(search_engine.segments[0].no_of_purged_docs, 1);
+        let base_metadata = &search_engine.segments[0].in_memory_index_metadata;
+        assert!(base_metadata.get_term_metadata("award").is_none());
+        assert_eq!(
+            base_metadata
+                .get_term_metadata("misery")
+                .unwrap()
+                .term_frequency,
+            2
+        );
+        assert!(!temp_dir.path().join("index/segments/1").exists());
+
+        let names = |search_engine: &mut SearchEngine, query: &str| -> Vec<String> {
+            search_engine
+                .query(query, 10, 0)
+                .unwrap()
+                .into_iter()
+                .map(|r| r.document.doc_name)
+                .collect()
+        };
+        assert!(names(&mut search_engine, "award").is_empty());
+        let mut misery = names(&mut search_engine, "misery");
+        misery.sort();
+        assert_eq!(misery, vec!["Misery", "Movie"]);
+
+        // the purged documents are loaded back so the base segment is not merged again
+        let mut loaded_search_engine = SearchEngine::new(
+            temp_dir
+                .path()
+                .join("dataset")
+                .to_str()
+                .unwrap()
+                .to_string(),
+            CompressionAlgorithm::Simple16,
+            QueryAlgorithm::Boolean,
+            temp_dir.path().join("index").to_str().unwrap().to_string(),
+        )
+        .unwrap();
+        loaded_search_engine.load_index().unwrap();
+        assert_eq!(loaded_search_engine.segments[0].no_of_purged_docs, 1);
+        assert_eq!(loaded_search_engine.segments[0].no_of_docs, 4);
+        assert!(names(&mut loaded_search_engine, "award").is_empty());
+        assert_eq!(names(&mut loaded_search_engine, "theater"), vec!["Town"]);
+        assert!(loaded_search_engine.verify_index().unwrap().is_ok());
+
+        // a full build starts over with a base segment which has nothing purged
+        loaded_search_engine.build_index().unwrap();
+        assert_eq!(loaded_search_engine.segments[0].no_of_purged_docs, 0);
+        assert!(!temp_dir.path().join("index/segment_metadata.sidx").exists());
+    }
+
+    // The documents which are added grow the average length, which raises the scores of the
+    // documents of the base segment above the max scores it was written with
+    #[test]
+    fn test_top_k_after_average_length_grows() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut search_engine = create_test_search_engine(
+            &temp_dir,
+            &[
+                ("Misery", "misery king"),
+                ("Novel", "a novel by stephen king about misery and a writer"),
+                ("King", "king king king of the misery"),
+            ],
+            true,
+        );
+        let long_text = "a long story about nothing at all ".repeat(40);
+        let new_batch_dir = temp_dir.path().join("new_batch");
+        let long_documents: Vec<(String, &str)> = (0..6)
+            .map(|i| (format!("Long {}", i), long_text.as_str()))
+            .collect();
+        let long_documents: Vec<(&str, &str)> = long_documents
+            .iter()
+            .map(|(name, text)| (name.as_str(), *text))
+            .collect();
+        write_test_dataset(&new_batch_dir, &long_documents);
+        search_engine
+            .add_documents(vec![new_batch_dir.join("batch_0")])
+            .unwrap();
+
+        for query_algorithm in [
+            QueryAlgorithm::Wand,
+            QueryAlgorithm::BlockMaxWand,
+            QueryAlgorithm::MaxScore,
+            QueryAlgorithm::BlockMaxMaxScore,
+        ] {
+            search_engine.set_query_algorithm(query_algorithm.clone());
+            let results = search_engine.query("misery king", 10, 0).unwrap();
+            assert_eq!(results.len(), 3, "{}", query_algorithm);
+            for k in 1..=3 {
+                let top_k = search_engine.query("misery king", k, 0).unwrap();
+                let names: Vec<&str> = top_k.iter().map(|r| r.document.doc_name.as_str()).collect();
+                let expected_names: Vec<&str> = results[..k]
+                    .iter()
+                    .map(|r| r.document.doc_name.as_str())
+                    .collect();
+                assert_eq!(names, expected_names, "{}", query_algorithm);
+            }
+        }
+    }
+
+    #[test]
+    fn test_boolean_query() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut search_engine = create_test_search_engine(&temp_dir, TEST_DOCUMENTS, true);
+        search_engine.set_query_algorithm(QueryAlgorithm::Boolean);
+
+        let mut run = |query: &str| {
+            let mut names: Vec<String> = search_engine
+                .query(query, 10, 0)
+                .unwrap()
+                .into_iter()
+                .map(|r| r.document.doc_name)
+                .collect();
+            names.sort();
+            names
+        };
+        assert_eq!(run("misery AND NOT novel"), vec!["Movie"]);
+        assert_eq!(run("(stephen OR town) AND small"), vec!["Movie", "Town"]);
+        assert_eq!(run("movie -misery"), vec!["Town"]);
+        assert_eq!(run("\"misery movie\" OR theater"), vec!["Novel", "Town"]);
+
+        assert!(search_engine.query("(misery OR movie", 10, 0).is_err());
+    }
+
+    #[test]
+    fn test_synonym_query() {
+        let documents = [
+            ("Parking", "the automobile was parked outside the house"),
+            ("Road", "a red car drove down the road"),
+            ("Wheels", "the car is an automobile with four wheels"),
+            ("Apple", "the big apple is another name for new york city"),
+            ("Subway", "the nyc subway runs all night"),
+            ("Garden", "the garden was full of roses"),
+        ];
+        let temp_dir = TempDir::new().unwrap();
+        let mut search_engine = create_test_search_engine(&temp_dir, &documents, true);
+        let run = |search_engine: &mut SearchEngine, query: &str| -> Vec<(String, f32)> {
+            search_engine
+                .query(query, 10, 0)
+                .unwrap()
+                .into_iter()
+                .map(|r| (r.document.doc_name, r.score))
+                .collect()
+        };
+        assert_eq!(run(&mut search_engine, "car").len(), 2);
+
+        let synonyms = SynonymDictionary::parse(
+            "car, automobile
+nyc, new york city
+",
+        )
+        .unwrap();
+        assert!(matches!(
+            search_engine.set_synonyms(SynonymDictionary {
+                weight: -1.0,
+                ..synonyms.clone()
+            }),
+            Err(SearchEngineError::InvalidConfiguration(_))
+        ));
+        search_engine.set_synonyms(synonyms).unwrap();
+
+        // a document which only has the synonym is ranked below the ones which have the word, and
+        // every ranked algorithm scores the synonyms the same way
+        let results = run(&mut search_engine, "car");
+        let names: Vec<&str> = results.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names.len(), 3);
+        assert_eq!(names[2], "Parking");
+        for query_algorithm in [
+            QueryAlgorithm::BlockMaxWand,
+            QueryAlgorithm::MaxScore,
+            QueryAlgorithm::BlockMaxMaxScore,
+        ] {
+            search_engine.set_query_algorithm(query_algorithm);
+            let algorithm_results = run(&mut search_engine, "car");
+            assert_eq!(algorithm_results.len(), results.len());
+            for ((name, score), (expected_name, expected_score)) in
+                algorithm_results.iter().zip(&results)
+            {
+                assert_eq!(name, expected_name);
+                assert!((score - expected_score).abs() < 1e-4);
+            }
+        }
+        let results = run(&mut search_engine, "new york city");
+        assert_eq!(results[0].0, "Apple");
+        assert_eq!(results[1].0, "Subway");
+
+        // a synonym made of more than one word is a phrase in a boolean query
+        search_engine.set_query_algorithm(QueryAlgorithm::Boolean);
+        let mut names = |query: &str| -> Vec<String> {
+            let mut names: Vec<String> = run(&mut search_engine, query)
+                .into_iter()
+                .map(|(name, _)| name)
+                .collect();
+            names.sort();
+            names
+        };
+        assert_eq!(names("car -wheels"), vec!["Parking", "Road"]);
+        assert_eq!(names("nyc"), vec!["Apple", "Subway"]);
+        assert_eq!(names("new york city"), vec!["Apple", "Subway"]);
+    }
+
+    #[test]
+    fn test_phrase_synonym_without_positions() {
+        let documents = [
+            ("Apple", "the big apple is another name for new york city"),
+            ("Subway", "the nyc subway runs all night"),
+            ("Road", "a red car drove down the road"),
+        ];
+        let temp_dir = TempDir::new().unwrap();
+        let mut search_engine = create_test_search_engine(&temp_dir, &documents, false);
+        search_engine
+            .set_synonyms(
+                SynonymDictionary::parse("car, automobile\nnyc, new york city\n").unwrap(),
+            )
+            .unwrap();
+
+        // the synonyms of a single word still work, a synonym made of more than one word would
+        // have to be matched as a phrase
+        assert_eq!(search_engine.query("car", 10, 0).unwrap().len(), 1);
+        for query_algorithm in [QueryAlgorithm::Wand, QueryAlgorithm::Boolean] {
+            search_engine.set_query_algorithm(query_algorithm);
+            assert!(matches!(
+                search_engine.query("nyc", 10, 0),
+                Err(SearchEngineError::InvalidQuery(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_synonym_scoring() {
+        let documents = [
+            ("Exact", "a car parked in the garage"),
+            (
+                "Several",
+                "an automobile auto motorcar parked in the garage",
+            ),
+            ("Apple", "the big apple is another name for new york city"),
+            (
+                "Scattered",
+                "a new library opened in york near the city hall",
+            ),
+            ("Subway", "the nyc subway runs all night"),
+            ("Garden", "the garden was full of roses"),
+            ("Lake", "a quiet lake between the hills"),
+        ];
+        let temp_dir = TempDir::new().unwrap();
+        let mut search_engine = create_test_search_engine(&temp_dir, &documents, true);
+        search_engine
+            .set_synonyms(SynonymDictionary {
+                weight: 0.9,
+                ..SynonymDictionary::parse("car, automobile, auto, motorcar\nnyc, new york city\n")
+                    .unwrap()
+            })
+            .unwrap();
+        let names = |search_engine: &mut SearchEngine, query: &str| -> Vec<String> {
+            search_engine
+                .query(query, 10, 0)
+                .unwrap()
+                .into_iter()
+                .map(|r| r.document.doc_name)
+                .collect()
+        };
+
+        // a word and its synonyms are scored with the best of them, a document with several
+        // synonyms of the word does not outrank the one with the word itself
+        for query_algorithm in [
+            QueryAlgorithm::Wand,
+            QueryAlgorithm::BlockMaxWand,
+            QueryAlgorithm::MaxScore,
+            QueryAlgorithm::BlockMaxMaxScore,
+        ] {
+            search_engine.set_query_algorithm(query_algorithm);
+            assert_eq!(names(&mut search_engine, "car"), vec!["Exact", "Several"]);
+            // the synonym made of more than one word only matches the documents which have its
+            // words one after the other
+            let mut nyc_names = names(&mut search_engine, "nyc");
+            nyc_names.sort();
+            assert_eq!(nyc_names, vec!["Apple", "Subway"]);
+        }
+    }
+
     #[test]
     fn test_create_index() {
         let mut search_engine = SearchEngine::new(
@@ -314,7 +2646,7 @@ mod tests {
         .unwrap();
 
         search_engine.load_index().unwrap();
-        println!("{:?}", search_engine.get_index_metadata());
+        println!("{:?}", search_engine.get_index_metadata().unwrap());
     }
 
     #[test]
@@ -329,7 +2661,7 @@ mod tests {
 
         search_engine.load_index().unwrap();
         let query_string = "misery movie".to_string();
-        let results = search_engine.handle_query(query_string).unwrap();
+        let results = search_engine.query(&query_string, 10, 0).unwrap();
         println!("{:?}", results);
     }
 }
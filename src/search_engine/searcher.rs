@@ -0,0 +1,528 @@
+use std::{io, sync::Mutex};
+
+use rustc_hash::FxHashMap;
+use search_engine_cache::CacheType;
+
+use crate::{
+    indexer::indexer::Indexer,
+    parser::{
+        parser::{Parser, SynonymExpansion, Token, TokenizeQueryResult, strip_field},
+        query_parser::QueryNode,
+    },
+    query_processor::{
+        query_processor::{QueryProcessor, QueryTerm, ScoreCombination},
+        retrieval_algorithms::{QueryAlgorithm, positional::PositionalConstraint},
+    },
+    search_engine::error::SearchEngineError,
+    segment::segment::Segment,
+    utils::{
+        field::{Field, TITLE_PREFIX},
+        types::SearchResult,
+        wildcard::{WildcardPattern, is_wildcard_pattern},
+    },
+};
+
+// a word of the query which is present in fewer documents than this is checked for a misspelling
+const RARE_TERM_DOCUMENT_FREQUENCY: u32 = 5;
+// a correction has to be present in at least this many times as many documents as the word
+const CORRECTION_DOCUMENT_FREQUENCY_RATIO: u32 = 10;
+
+// a wildcard pattern is expanded into at most this many terms, the ones present in the most
+// documents are kept
+const MAX_WILDCARD_EXPANSIONS: usize = 64;
+
+// the results of a query are cached along with the k and offset they were requested with
+pub type QueryCache = Mutex<CacheType<(String, usize, usize), Vec<(u32, f32)>>>;
+
+// A read only view of a loaded index which answers queries, it is Send + Sync so it can be shared
+// by as many threads as needed. The search engine can not add, delete or merge anything while a
+// searcher borrows it.
+//
+// let searcher = search_engine.searcher();
+// thread::scope(|scope| {
+//     scope.spawn(|| searcher.query("stephen king", 10, 0));
+//     scope.spawn(|| searcher.query("misery", 10, 0));
+// });
+#[derive(Clone, Copy)]
+pub struct Searcher<'a> {
+    query_cache: &'a QueryCache,
+    query_processor: &'a QueryProcessor,
+    parser: &'a Parser,
+    indexer: &'a Indexer,
+    segments: &'a [Segment],
+    query_algorithm: &'a QueryAlgorithm,
+}
+
+impl<'a> Searcher<'a> {
+    pub fn new(
+        query_cache: &'a QueryCache,
+        query_processor: &'a QueryProcessor,
+        parser: &'a Parser,
+        indexer: &'a Indexer,
+        segments: &'a [Segment],
+        query_algorithm: &'a QueryAlgorithm,
+    ) -> Self {
+        Self {
+            query_cache,
+            query_processor,
+            parser,
+            indexer,
+            segments,
+            query_algorithm,
+        }
+    }
+
+    // Returns k results of the query starting from offset, in decreasing order of their scores.
+    // The same query with a different k or offset is cached separately.
+    pub fn query(
+        &self,
+        query: &str,
+        k: usize,
+        offset: usize,
+    ) -> Result<Vec<SearchResult>, SearchEngineError> {
+        let cache_key = (query.to_string(), k, offset);
+        // the cache is not locked while the query is processed, two threads running the same
+        // query at once both process it
+        let cached_result_docs = self.query_cache.lock().unwrap().get(&cache_key).cloned();
+        let result_docs = if let Some(result_docs) = cached_result_docs {
+            result_docs
+        } else {
+            let query = &cache_key.0;
+            let result_docs = if *self.query_algorithm == QueryAlgorithm::Boolean {
+                self.handle_boolean_query(query, k, offset)?
+            } else {
+                let mut tokens = self
+                    .parser
+                    .tokenize_query(query)
+                    .map_err(|e| SearchEngineError::InvalidQuery(format!("{:?}", e)))?;
+                check_positional_wildcards(
+                    tokens.positional.iter().flat_map(|query| &query.terms),
+                )?;
+                if !tokens.positional.is_empty() {
+                    self.check_positions()?;
+                }
+                if tokens.positional.is_empty() {
+                    // the terms which a pattern expands to have no synonyms
+                    let synonyms = self.parser.expand_synonyms(&tokens.unigram);
+                    let query_terms = self.get_ranked_query_terms(tokens.unigram, synonyms)?;
+
+                    self.query_processor.process_query(
+                        &query_terms,
+                        self.segments,
+                        &self.indexer.get_document_lengths(),
+                        &self.indexer.deleted_documents,
+                        k,
+                        offset,
+                    )?
+                } else {
+                    tokens.unigram = self.expand_wildcard_tokens(tokens.unigram);
+                    self.handle_positional_query(tokens, k, offset)?
+                }
+            };
+
+            self.query_cache
+                .lock()
+                .unwrap()
+                .put(cache_key, result_docs.clone(), 0);
+            result_docs
+        };
+
+        Ok(result_docs
+            .into_iter()
+            .filter_map(|(doc_id, score)| {
+                let document = self.indexer.get_doc_metadata(doc_id)?;
+                Some(SearchResult {
+                    doc_id,
+                    document,
+                    score,
+                })
+            })
+            .collect())
+    }
+
+    // The query is parsed into a syntax tree which is evaluated over the terms present in the index
+    fn handle_boolean_query(
+        &self,
+        query: &str,
+        k: usize,
+        offset: usize,
+    ) -> Result<Vec<(u32, f32)>, SearchEngineError> {
+        let query_node = match self.parser.parse_boolean_query(query) {
+            Ok(Some(query_node)) => self.expand_wildcard_node(query_node)?,
+            Ok(None) => return Ok(Vec::new()),
+            Err(e) => return Err(SearchEngineError::InvalidQuery(format!("{:?}", e))),
+        };
+
+        Ok(self.query_processor.process_boolean_query(
+            &query_node,
+            &query_node.get_terms(),
+            self.segments,
+            &self.indexer.get_document_lengths(),
+            &self.indexer.deleted_documents,
+            k,
+            offset,
+        )?)
+    }
+
+    // An index without positions can not tell where its terms are in a document, a phrase or
+    // proximity query would match nothing instead of what it asks for
+    fn check_positions(&self) -> Result<(), SearchEngineError> {
+        if self.indexer.get_include_positions() {
+            return Ok(());
+        }
+        Err(SearchEngineError::InvalidQuery(
+            "the index was built without positions, it can not answer phrase or NEAR queries"
+                .to_string(),
+        ))
+    }
+
+    // The constraints refer to the terms of the query by their index
+    fn handle_positional_query(
+        &self,
+        tokens: TokenizeQueryResult,
+        k: usize,
+        offset: usize,
+    ) -> io::Result<Vec<(u32, f32)>> {
+        let mut query_terms: Vec<String> = Vec::with_capacity(tokens.unigram.len());
+        for token in tokens.unigram {
+            if !query_terms.contains(&token.word) {
+                query_terms.push(token.word);
+            }
+        }
+
+        let mut constraints = Vec::with_capacity(tokens.positional.len());
+        for positional_query in &tokens.positional {
+            let mut term_indexes = Vec::with_capacity(positional_query.terms.len());
+            let mut offsets = Vec::with_capacity(positional_query.terms.len());
+            for token in &positional_query.terms {
+                match query_terms.iter().position(|term| term == &token.word) {
+                    Some(index) => term_indexes.push(index),
+                    None => return Ok(Vec::new()),
+                }
+                offsets.push(token.position - positional_query.terms[0].position);
+            }
+            constraints.push(PositionalConstraint {
+                term_indexes,
+                offsets,
+                slop: positional_query.slop,
+                ordered: positional_query.ordered,
+            });
+        }
+
+        self.query_processor.process_positional_query(
+            &query_terms,
+            &constraints,
+            self.segments,
+            &self.indexer.get_document_lengths(),
+            &self.indexer.deleted_documents,
+            k,
+            offset,
+        )
+    }
+
+    // The words of a ranked query are scored in every field they are looked up in, the fields of a
+    // word are kept together so that BM25F can combine them, and a pattern is looked up as the
+    // terms it expands to. A run of words which has synonyms becomes a group in every field, which
+    // scores a document with the best of the run and each of its synonyms, so several synonyms in
+    // a document do not add up to more than the words they stand for. The synonyms are scored with
+    // the weight they were expanded with, and a synonym made of more than one word is a phrase
+    // whose terms share the weight, which an index without positions can not match.
+    fn get_ranked_query_terms(
+        &self,
+        tokens: Vec<Token>,
+        expansions: Vec<SynonymExpansion>,
+    ) -> Result<Vec<QueryTerm>, SearchEngineError> {
+        let mut query_terms = Vec::with_capacity(tokens.len() * 2);
+        let mut expansions = expansions.into_iter().peekable();
+        let mut i = 0;
+        while i < tokens.len() {
+            let word = &tokens[i].word;
+            let Some(expansion) = expansions.next_if(|expansion| expansion.first_token == i) else {
+                let words = if is_wildcard_pattern(word) {
+                    self.expand_wildcard_pattern(word)
+                } else {
+                    vec![word.clone()]
+                };
+                for word in words {
+                    let mut field_terms: Vec<String> = get_query_fields(&word)
+                        .iter()
+                        .map(|field| field.get_field_term(strip_field(&word)))
+                        .collect();
+                    query_terms.push(if field_terms.len() == 1 {
+                        QueryTerm::Term(field_terms.pop().unwrap(), 1.0)
+                    } else {
+                        QueryTerm::Fields(field_terms, 1.0)
+                    });
+                }
+                i += 1;
+                continue;
+            };
+            let run = &tokens[i..i + expansion.no_of_tokens];
+            if !self.indexer.get_include_positions()
+                && let Some(synonym) = expansion.synonyms.iter().find(|synonym| synonym.len() > 1)
+            {
+                return Err(SearchEngineError::InvalidQuery(format!(
+                    "the synonym \"{}\" of \"{}\" is a phrase, which the index can not match \
+                     since it was built without positions",
+                    synonym
+                        .iter()
+                        .map(|(term, _)| term.as_str())
+                        .collect::<Vec<_>>()
+                        .join(" "),
+                    run.iter()
+                        .map(|token| token.word.as_str())
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                )));
+            }
+            for field in get_query_fields(word) {
+                let mut members = Vec::with_capacity(expansion.synonyms.len() + 1);
+                let mut words: Vec<QueryTerm> = run
+                    .iter()
+                    .map(|token| {
+                        QueryTerm::Term(field.get_field_term(strip_field(&token.word)), 1.0)
+                    })
+                    .collect();
+                members.push(if words.len() == 1 {
+                    words.pop().unwrap()
+                } else {
+                    QueryTerm::Group(words, ScoreCombination::Sum)
+                });
+                for synonym in &expansion.synonyms {
+                    let weight = expansion.weight / synonym.len() as f32;
+                    members.push(match synonym.as_slice() {
+                        [(term, _)] => QueryTerm::Term(field.get_field_term(term), weight),
+                        _ => QueryTerm::Phrase(
+                            synonym
+                                .iter()
+                                .map(|(term, offset)| (field.get_field_term(term), *offset))
+                                .collect(),
+                            weight,
+                        ),
+                    });
+                }
+                query_terms.push(QueryTerm::Group(members, ScoreCombination::Max));
+            }
+            i += expansion.no_of_tokens;
+        }
+        Ok(query_terms)
+    }
+
+    // A wildcard pattern is replaced by the terms it expands to at the position of the pattern,
+    // the ranked retrieval algorithms take the union of their postings like for any other terms
+    fn expand_wildcard_tokens(&self, tokens: Vec<Token>) -> Vec<Token> {
+        let mut expanded_tokens = Vec::with_capacity(tokens.len());
+        for token in tokens {
+            if is_wildcard_pattern(&token.word) {
+                expanded_tokens.extend(self.expand_wildcard_pattern(&token.word).into_iter().map(
+                    |word| Token {
+                        position: token.position,
+                        word,
+                    },
+                ));
+            } else {
+                expanded_tokens.push(token);
+            }
+        }
+        expanded_tokens
+    }
+
+    // A pattern in a boolean query becomes an OR of the terms it expands to, a pattern which does
+    // not match any term matches no documents
+    fn expand_wildcard_node(&self, query_node: QueryNode) -> Result<QueryNode, SearchEngineError> {
+        Ok(match query_node {
+            QueryNode::Term(term) if is_wildcard_pattern(&term) => QueryNode::Or(
+                self.expand_wildcard_pattern(&term)
+                    .into_iter()
+                    .map(QueryNode::Term)
+                    .collect(),
+            ),
+            QueryNode::Phrase(phrase) => {
+                self.check_positions()?;
+                check_positional_wildcards(&phrase.terms)?;
+                QueryNode::Phrase(phrase)
+            }
+            QueryNode::And(children) => QueryNode::And(
+                children
+                    .into_iter()
+                    .map(|child| self.expand_wildcard_node(child))
+                    .collect::<Result<_, _>>()?,
+            ),
+            QueryNode::Or(children) => QueryNode::Or(
+                children
+                    .into_iter()
+                    .map(|child| self.expand_wildcard_node(child))
+                    .collect::<Result<_, _>>()?,
+            ),
+            QueryNode::Not(child) => QueryNode::Not(Box::new(self.expand_wildcard_node(*child)?)),
+            query_node => query_node,
+        })
+    }
+
+    // The matching terms of every segment are collected, a term keeps its title: prefix only if
+    // the pattern has it
+    fn expand_wildcard_pattern(&self, pattern: &str) -> Vec<String> {
+        let Some(wildcard_pattern) = WildcardPattern::new(pattern) else {
+            return vec![pattern.to_string()];
+        };
+        let field = Field::from_term(pattern);
+        let mut document_frequencies: FxHashMap<String, u32> = FxHashMap::default();
+        for segment in self.segments {
+            let metadata = &segment.in_memory_index_metadata;
+            for (term, term_id) in metadata.lexicon.find_wildcard(&wildcard_pattern) {
+                if Field::from_term(&term) == field {
+                    *document_frequencies.entry(term).or_default() +=
+                        metadata.get_term_frequency(term_id);
+                }
+            }
+        }
+        let mut expansions: Vec<(String, u32)> = document_frequencies.into_iter().collect();
+        expansions.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        expansions
+            .into_iter()
+            .take(MAX_WILDCARD_EXPANSIONS)
+            .map(|(term, _)| term)
+            .collect()
+    }
+
+    // Suggests a correction of the query when a word of it is not in the index, or is rare while a
+    // similar word is far more common. The candidates are looked up in the BK-trees of the
+    // segments, the closest one wins and a tie goes to the one present in the most documents.
+    // Returns None if there is nothing to correct.
+    pub fn suggest_query(&self, query: &str) -> Option<String> {
+        let mut corrected = false;
+        let words: Vec<String> = query
+            .split_whitespace()
+            .map(|word| match self.suggest_word(word) {
+                Some(correction) => {
+                    corrected = true;
+                    correction
+                }
+                None => word.to_string(),
+            })
+            .collect();
+        corrected.then(|| words.join(" "))
+    }
+
+    fn suggest_word(&self, word: &str) -> Option<String> {
+        let query_term = self.parser.analyze_query_word(word)?;
+        if is_wildcard_pattern(&query_term) {
+            return None;
+        }
+        let document_frequency = self.get_document_frequency(&query_term);
+        if document_frequency >= RARE_TERM_DOCUMENT_FREQUENCY {
+            return None;
+        }
+        // only the words of the body are kept in the BK-trees, the title terms are looked up
+        // through them
+        let field = Field::from_term(&query_term);
+        let bare_term = query_term.strip_prefix(TITLE_PREFIX).unwrap_or(&query_term);
+        let edit_distance = get_max_edit_distance(bare_term);
+        if edit_distance == 0 {
+            return None;
+        }
+
+        let min_document_frequency =
+            (document_frequency * CORRECTION_DOCUMENT_FREQUENCY_RATIO).max(1);
+        let mut best_candidate: Option<(u32, u32, String)> = None;
+        for segment in self.segments {
+            let candidates = segment.in_memory_index_metadata.bk_tree.find_with_distance(
+                bare_term,
+                edit_distance,
+                &segment.in_memory_index_metadata.lexicon,
+            );
+            for (candidate, distance) in candidates {
+                if distance == 0 {
+                    continue;
+                }
+                let candidate_frequency =
+                    self.get_document_frequency(&field.get_field_term(&candidate));
+                if candidate_frequency < min_document_frequency {
+                    continue;
+                }
+                let is_better = match &best_candidate {
+                    None => true,
+                    Some((best_distance, best_frequency, best_term)) => {
+                        (distance, std::cmp::Reverse(candidate_frequency), &candidate)
+                            < (
+                                *best_distance,
+                                std::cmp::Reverse(*best_frequency),
+                                best_term,
+                            )
+                    }
+                };
+                if is_better {
+                    best_candidate = Some((distance, candidate_frequency, candidate));
+                }
+            }
+        }
+
+        let (_, _, correction) = best_candidate?;
+        Some(replace_word(word, &correction))
+    }
+
+    // the no of documents containing the term across all the segments
+    fn get_document_frequency(&self, term: &str) -> u32 {
+        self.segments
+            .iter()
+            .map(|segment| {
+                let metadata = &segment.in_memory_index_metadata;
+                match metadata.get_term_id(term) {
+                    0 => 0,
+                    term_id => metadata.get_term_frequency(term_id),
+                }
+            })
+            .sum()
+    }
+}
+
+// The positions of the terms a pattern expands to can not be matched against each other, so a
+// pattern can not be part of a phrase or a NEAR query
+fn check_positional_wildcards<'a, I: IntoIterator<Item = &'a Token>>(
+    tokens: I,
+) -> Result<(), SearchEngineError> {
+    match tokens
+        .into_iter()
+        .find(|token| is_wildcard_pattern(&token.word))
+    {
+        Some(token) => Err(SearchEngineError::InvalidQuery(format!(
+            "the wildcard pattern {} can not be part of a phrase or a NEAR query",
+            token.word
+        ))),
+        None => Ok(()),
+    }
+}
+
+// Short words have too many neighbours for a correction to be meaningful
+fn get_max_edit_distance(term: &str) -> u32 {
+    match term.chars().count() {
+        0..=2 => 0,
+        3..=5 => 1,
+        _ => 2,
+    }
+}
+
+// Swaps the word of the query for its correction, keeping the title: prefix, quotes and any other
+// characters around it
+fn replace_word(word: &str, correction: &str) -> String {
+    let (prefix, rest) = match word.strip_prefix(TITLE_PREFIX) {
+        Some(rest) => (TITLE_PREFIX, rest),
+        None => ("", word),
+    };
+    let start = rest.len()
+        - rest
+            .trim_start_matches(|c: char| !c.is_alphanumeric())
+            .len();
+    let end = rest.trim_end_matches(|c: char| !c.is_alphanumeric()).len();
+    format!("{}{}{}{}", prefix, &rest[..start], correction, &rest[end..])
+}
+
+// A body word is looked up in the title of the documents as well, so that a document whose title
+// matches the query gets the score of both. A word with the title: prefix is only looked up in the
+// titles.
+fn get_query_fields(word: &str) -> &'static [Field] {
+    match Field::from_term(word) {
+        Field::Body => &[Field::Title, Field::Body],
+        Field::Title => &[Field::Title],
+    }
+}
@@ -0,0 +1,157 @@
+use crate::{segment::segment::Segment, utils::bitmap::Bitmap};
+
+// The base segment is rewritten on its own once this fraction of its documents are deleted but
+// still have postings in it
+pub const DEFAULT_BASE_SEGMENT_DELETED_RATIO: f32 = 0.2;
+
+// Segments are grouped into tiers by their number of documents, every tier holds segments which
+// are merge_factor times larger than the ones in the tier below it.
+// Once merge_factor adjacent segments are in the same tier they are merged into a single segment
+// of the next tier, so the no of segments stays logarithmic in the no of added documents while
+// every document is only merged a logarithmic no of times.
+// Only adjacent segments are merged so that every segment keeps a contiguous range of doc ids.
+// The base segment is never part of a tier since it is usually far larger than the rest, it is
+// only rewritten to purge the postings of its deleted documents.
+pub struct TieredMergePolicy {
+    merge_factor: usize,
+    base_segment_deleted_ratio: f32,
+}
+
+impl TieredMergePolicy {
+    pub fn new(merge_factor: usize) -> Self {
+        assert!(merge_factor >= 2, "the merge factor has to be at least 2");
+        Self {
+            merge_factor,
+            base_segment_deleted_ratio: DEFAULT_BASE_SEGMENT_DELETED_RATIO,
+        }
+    }
+
+    pub fn set_base_segment_deleted_ratio(&mut self, base_segment_deleted_ratio: f32) {
+        self.base_segment_deleted_ratio = base_segment_deleted_ratio;
+    }
+
+    pub fn get_base_segment_deleted_ratio(&self) -> f32 {
+        self.base_segment_deleted_ratio
+    }
+
+    fn is_base_segment_stale(&self, base_segment: &Segment, deleted_documents: &Bitmap) -> bool {
+        if base_segment.no_of_docs == 0 {
+            return false;
+        }
+        let no_of_deleted_docs = deleted_documents
+            .count_range(base_segment.get_doc_id_range())
+            .saturating_sub(base_segment.no_of_purged_docs);
+        no_of_deleted_docs as f32 / base_segment.no_of_docs as f32 > self.base_segment_deleted_ratio
+    }
+
+    fn get_tier(&self, no_of_docs: u32) -> u32 {
+        let mut tier = 0;
+        let mut no_of_docs = no_of_docs as usize;
+        while no_of_docs >= self.merge_factor {
+            no_of_docs /= self.merge_factor;
+            tier += 1;
+        }
+        tier
+    }
+
+    // The segments have to be in the order of their doc ids, starting with the base segment.
+    // Returns the ids of the segments which should be merged, the base segment is merged on its
+    // own before the tiers and the smallest tier is merged first since it is the cheapest.
+    pub fn find_merge(&self, segments: &[Segment], deleted_documents: &Bitmap) -> Option<Vec<u32>> {
+        let (base_segment, segments) = segments.split_first()?;
+        if self.is_base_segment_stale(base_segment, deleted_documents) {
+            return Some(vec![base_segment.segment_id]);
+        }
+        let tiers: Vec<u32> = segments
+            .iter()
+            .map(|segment| self.get_tier(segment.no_of_docs))
+            .collect();
+
+        let mut merge: Option<(u32, usize)> = None;
+        for (start, window) in tiers.windows(self.merge_factor).enumerate() {
+            if window.iter().all(|&tier| tier == window[0])
+                && merge.is_none_or(|(tier, _)| window[0] < tier)
+            {
+                merge = Some((window[0], start));
+            }
+        }
+
+        merge.map(|(_, start)| {
+            segments[start..start + self.merge_factor]
+                .iter()
+                .map(|segment| segment.segment_id)
+                .collect()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::in_memory_index_metadata::in_memory_index_metadata::InMemoryIndexMetadata;
+
+    use super::*;
+
+    // the first size is the one of the base segment
+    fn create_segments(sizes: &[u32]) -> Vec<Segment> {
+        let mut first_doc_id = 1;
+        let mut segments = Vec::new();
+        for (i, &no_of_docs) in sizes.iter().enumerate() {
+            segments.push(Segment::new(
+                i as u32,
+                first_doc_id,
+                no_of_docs,
+                InMemoryIndexMetadata::new(),
+            ));
+            first_doc_id += no_of_docs;
+        }
+        segments
+    }
+
+    #[test]
+    fn test_find_merge() {
+        let merge_policy = TieredMergePolicy::new(3);
+        let deleted_documents = Bitmap::new();
+        let find_merge =
+            |sizes: &[u32]| merge_policy.find_merge(&create_segments(sizes), &deleted_documents);
+        assert_eq!(find_merge(&[]), None);
+        assert_eq!(find_merge(&[100, 1, 2]), None);
+        assert_eq!(find_merge(&[100, 1, 2, 1]), Some(vec![1, 2, 3]));
+        // the segments of a tier have to be adjacent
+        assert_eq!(find_merge(&[100, 1, 2, 5, 1]), None);
+        // the smallest tier is merged first
+        assert_eq!(
+            find_merge(&[100, 30, 40, 50, 4, 5, 1, 2, 2]),
+            Some(vec![6, 7, 8])
+        );
+        // the base segment is not part of a tier
+        assert_eq!(find_merge(&[1, 2, 1]), None);
+    }
+
+    #[test]
+    fn test_find_base_segment_merge() {
+        let merge_policy = TieredMergePolicy::new(3);
+        let mut segments = create_segments(&[10, 1, 2, 1]);
+        let mut deleted_documents = Bitmap::new();
+        // the deleted documents of the other segments do not count
+        for doc_id in [1, 2, 11, 12, 13, 14] {
+            deleted_documents.insert(doc_id);
+        }
+        assert_eq!(
+            merge_policy.find_merge(&segments, &deleted_documents),
+            Some(vec![1, 2, 3])
+        );
+
+        deleted_documents.insert(3);
+        assert_eq!(
+            merge_policy.find_merge(&segments, &deleted_documents),
+            Some(vec![0])
+        );
+
+        // the documents which were purged by the last merge of the base segment are not counted
+        segments[0].no_of_purged_docs = 3;
+        assert_eq!(
+            merge_policy.find_merge(&segments, &deleted_documents),
+            Some(vec![1, 2, 3])
+        );
+    }
+}
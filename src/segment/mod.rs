@@ -0,0 +1,4 @@
+pub mod merge_policy;
+pub mod segment;
+pub mod segment_merger;
+pub mod segment_verifier;
@@ -0,0 +1,282 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Read, Write},
+    ops::RangeInclusive,
+    path::Path,
+};
+
+use crate::{
+    in_memory_index_metadata::in_memory_index_metadata::InMemoryIndexMetadata,
+    utils::{
+        field::{DocumentLengths, Field},
+        paths::{
+            get_save_bk_tree_path, get_save_segment_ids_path, get_save_segment_metadata_path,
+            get_save_term_metadata_path, get_save_terms_path, get_segment_directory_path,
+            get_segments_directory_path,
+        },
+    },
+};
+
+// A segment is a self contained part of the index, it has its own inverted index file and term
+// metadata and it holds the postings of a contiguous range of doc ids.
+// The doc ids are global so the document metadata is shared by all the segments.
+// Segment 0 is the base segment built from the whole dataset, the documents which are added later
+// are written to new segments which are merged together in the background.
+pub struct Segment {
+    pub segment_id: u32,
+    pub first_doc_id: u32,
+    pub no_of_docs: u32,
+    // the no of documents in the whole collection when the segment was written, the max scores
+    // of the terms were computed with it
+    pub collection_no_of_docs: u32,
+    // the no of deleted documents whose postings were already purged from the segment by a merge,
+    // the rest of its deleted documents still have postings in it
+    pub no_of_purged_docs: u32,
+    // the average lengths of the fields of the collection when the segment was written, which the
+    // max scores were computed with. They are the averages of the first collection_no_of_docs
+    // documents so they are not saved, they are 0 until they are set.
+    pub average_body_length: f32,
+    pub average_title_length: f32,
+    pub in_memory_index_metadata: InMemoryIndexMetadata,
+}
+
+impl Segment {
+    pub fn new(
+        segment_id: u32,
+        first_doc_id: u32,
+        no_of_docs: u32,
+        in_memory_index_metadata: InMemoryIndexMetadata,
+    ) -> Self {
+        Self {
+            segment_id,
+            first_doc_id,
+            no_of_docs,
+            collection_no_of_docs: first_doc_id + no_of_docs - 1,
+            no_of_purged_docs: 0,
+            average_body_length: 0.0,
+            average_title_length: 0.0,
+            in_memory_index_metadata,
+        }
+    }
+
+    pub fn set_average_lengths(&mut self, document_lengths: &DocumentLengths) {
+        let document_lengths = document_lengths.get_prefix(self.collection_no_of_docs);
+        self.average_body_length = document_lengths.average_body_length;
+        self.average_title_length = document_lengths.average_title_length;
+    }
+
+    pub fn get_average_length(&self, field: Field) -> f32 {
+        match field {
+            Field::Body => self.average_body_length,
+            Field::Title => self.average_title_length,
+        }
+    }
+
+    pub fn get_doc_id_range(&self) -> RangeInclusive<u32> {
+        self.first_doc_id..=self.first_doc_id + self.no_of_docs - 1
+    }
+
+    // The term metadata and the BK-tree are written before the segment metadata, so a segment
+    // which has a segment metadata file is complete.
+    pub fn save(&self, index_directory_path: &Path) -> io::Result<()> {
+        let segment_directory_path =
+            get_segment_directory_path(index_directory_path, self.segment_id);
+        fs::create_dir_all(&segment_directory_path)?;
+
+        self.in_memory_index_metadata
+            .save_terms_file(&get_save_terms_path(&segment_directory_path))?;
+        let file = File::create(get_save_bk_tree_path(&segment_directory_path))?;
+        self.in_memory_index_metadata
+            .save_bk_tree(BufWriter::new(file))?;
+        self.save_metadata(index_directory_path)
+    }
+
+    // The base segment has no segment metadata until it is rewritten by a merge, the rest of the
+    // index then has to know how many of its documents were purged
+    pub fn save_metadata(&self, index_directory_path: &Path) -> io::Result<()> {
+        let segment_directory_path =
+            get_segment_directory_path(index_directory_path, self.segment_id);
+        let file = File::create(get_save_segment_metadata_path(&segment_directory_path))?;
+        let mut writer = BufWriter::new(file);
+        writer.write_all(&self.segment_id.to_le_bytes())?;
+        writer.write_all(&self.first_doc_id.to_le_bytes())?;
+        writer.write_all(&self.no_of_docs.to_le_bytes())?;
+        writer.write_all(&self.collection_no_of_docs.to_le_bytes())?;
+        writer.write_all(&self.no_of_purged_docs.to_le_bytes())?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    pub fn load(index_directory_path: &Path, segment_id: u32) -> io::Result<Segment> {
+        let mut segment = Segment::load_metadata(index_directory_path, segment_id)?;
+        let segment_directory_path = get_segment_directory_path(index_directory_path, segment_id);
+        segment.in_memory_index_metadata = load_terms(&segment_directory_path)?;
+        let bk_tree_path = get_save_bk_tree_path(&segment_directory_path);
+        if bk_tree_path.exists() {
+            segment
+                .in_memory_index_metadata
+                .load_bk_tree(&fs::read(bk_tree_path)?)?;
+        }
+        Ok(segment)
+    }
+
+    // Loads the doc ids of the segment without its term metadata, which is left empty
+    pub fn load_metadata(index_directory_path: &Path, segment_id: u32) -> io::Result<Segment> {
+        let segment_directory_path = get_segment_directory_path(index_directory_path, segment_id);
+
+        let file = File::open(get_save_segment_metadata_path(&segment_directory_path))?;
+        let mut reader = BufReader::new(file);
+        let mut buf = [0u8; 4];
+        reader.read_exact(&mut buf)?;
+        if u32::from_le_bytes(buf) != segment_id {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("segment metadata does not belong to segment {}", segment_id),
+            ));
+        }
+        reader.read_exact(&mut buf)?;
+        let first_doc_id = u32::from_le_bytes(buf);
+        reader.read_exact(&mut buf)?;
+        let no_of_docs = u32::from_le_bytes(buf);
+        reader.read_exact(&mut buf)?;
+        let collection_no_of_docs = u32::from_le_bytes(buf);
+        // a segment which was saved before the purged documents were counted has none of them
+        let no_of_purged_docs = match reader.read_exact(&mut buf) {
+            Ok(()) => u32::from_le_bytes(buf),
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => 0,
+            Err(e) => return Err(e),
+        };
+
+        let mut segment = Segment::new(
+            segment_id,
+            first_doc_id,
+            no_of_docs,
+            InMemoryIndexMetadata::new(),
+        );
+        segment.collection_no_of_docs = collection_no_of_docs;
+        segment.no_of_purged_docs = no_of_purged_docs;
+        Ok(segment)
+    }
+}
+
+// The base segment only has segment metadata once it has been rewritten by a merge, until then
+// its max scores were computed with the documents it holds and none of them were purged.
+// Its no of documents is given since it is only known from the segments which follow it.
+pub fn load_base_segment_metadata(
+    index_directory_path: &Path,
+    no_of_docs: u32,
+) -> io::Result<Segment> {
+    let mut segment = if get_save_segment_metadata_path(index_directory_path).exists() {
+        Segment::load_metadata(index_directory_path, 0)?
+    } else {
+        Segment::new(0, 1, no_of_docs, InMemoryIndexMetadata::new())
+    };
+    segment.no_of_docs = no_of_docs;
+    Ok(segment)
+}
+
+// A segment which was saved before terms.sidx has its term metadata in term_metadata.sidx, which
+// is read in full
+pub fn load_terms(segment_directory_path: &Path) -> io::Result<InMemoryIndexMetadata> {
+    let terms_path = get_save_terms_path(segment_directory_path);
+    if terms_path.exists() {
+        return InMemoryIndexMetadata::open_terms_file(&terms_path);
+    }
+    let file = File::open(get_save_term_metadata_path(segment_directory_path))?;
+    let mut in_memory_index_metadata = InMemoryIndexMetadata::new();
+    in_memory_index_metadata.load_term_metadata(BufReader::new(file))?;
+    Ok(in_memory_index_metadata)
+}
+
+// The ids of the live segments are kept in a single file along with the next segment id, so
+// that replacing the merged segments with the result of their merge is a single rename.
+// Segment ids are never reused.
+pub fn save_segment_ids(
+    index_directory_path: &Path,
+    segment_ids: &[u32],
+    next_segment_id: u32,
+) -> io::Result<()> {
+    fs::create_dir_all(get_segments_directory_path(index_directory_path))?;
+    let segment_ids_path = get_save_segment_ids_path(index_directory_path);
+    let temp_path = segment_ids_path.with_extension("sidx.tmp");
+
+    let mut writer = BufWriter::new(File::create(&temp_path)?);
+    writer.write_all(&next_segment_id.to_le_bytes())?;
+    writer.write_all(&(segment_ids.len() as u32).to_le_bytes())?;
+    for segment_id in segment_ids {
+        writer.write_all(&segment_id.to_le_bytes())?;
+    }
+    writer.flush()?;
+    drop(writer);
+
+    fs::rename(&temp_path, &segment_ids_path)
+}
+
+// An index without a segments file only has the base segment
+pub fn load_segment_ids(index_directory_path: &Path) -> io::Result<(Vec<u32>, u32)> {
+    let segment_ids_path = get_save_segment_ids_path(index_directory_path);
+    if !segment_ids_path.exists() {
+        return Ok((Vec::new(), 1));
+    }
+    let mut reader = BufReader::new(File::open(&segment_ids_path)?);
+    let mut buf = [0u8; 4];
+    reader.read_exact(&mut buf)?;
+    let next_segment_id = u32::from_le_bytes(buf);
+    reader.read_exact(&mut buf)?;
+    let no_of_segments = u32::from_le_bytes(buf) as usize;
+
+    let mut segment_ids = Vec::with_capacity(no_of_segments);
+    for _ in 0..no_of_segments {
+        reader.read_exact(&mut buf)?;
+        segment_ids.push(u32::from_le_bytes(buf));
+    }
+    Ok((segment_ids, next_segment_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_save_and_load_segment() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut in_memory_index_metadata = InMemoryIndexMetadata::new();
+        in_memory_index_metadata.set_term_id("misery".to_string(), 1);
+        in_memory_index_metadata.set_term_frequency(2);
+        in_memory_index_metadata.set_max_term_score(1.5);
+        in_memory_index_metadata.set_chunk_block_max_metadata(Vec::new());
+        in_memory_index_metadata.set_block_ids(vec![0]);
+        in_memory_index_metadata.no_of_terms = 1;
+        in_memory_index_metadata.no_of_blocks = 1;
+
+        let mut segment = Segment::new(3, 11, 5, in_memory_index_metadata);
+        segment.collection_no_of_docs = 20;
+        segment.no_of_purged_docs = 2;
+        segment.save(temp_dir.path()).unwrap();
+        assert_eq!(segment.get_doc_id_range(), 11..=15);
+
+        let loaded_segment = Segment::load(temp_dir.path(), 3).unwrap();
+        assert_eq!(loaded_segment.first_doc_id, 11);
+        assert_eq!(loaded_segment.no_of_docs, 5);
+        assert_eq!(loaded_segment.collection_no_of_docs, 20);
+        assert_eq!(loaded_segment.no_of_purged_docs, 2);
+        let term_metadata = loaded_segment
+            .in_memory_index_metadata
+            .get_term_metadata("misery")
+            .unwrap();
+        assert_eq!(term_metadata.term_frequency, 2);
+
+        assert!(Segment::load(temp_dir.path(), 4).is_err());
+    }
+
+    #[test]
+    fn test_save_and_load_segment_ids() {
+        let temp_dir = TempDir::new().unwrap();
+        assert_eq!(load_segment_ids(temp_dir.path()).unwrap(), (Vec::new(), 1));
+
+        save_segment_ids(temp_dir.path(), &[2, 5], 7).unwrap();
+        assert_eq!(load_segment_ids(temp_dir.path()).unwrap(), (vec![2, 5], 7));
+    }
+}
@@ -0,0 +1,152 @@
+use std::{
+    fs::{self, File},
+    io,
+    path::PathBuf,
+    thread::{self, JoinHandle},
+};
+
+use crate::{
+    compressor::compressor::CompressionAlgorithm,
+    indexer::spimi::{index_iterator::IndexIterator, spimi::Spimi},
+    scoring::{ScoringModel, bm_25::BM25Params},
+    segment::segment::{Segment, load_terms},
+    utils::{
+        bitmap::Bitmap,
+        field::DocumentLengths,
+        paths::{get_inverted_index_path, get_segment_directory_path},
+    },
+};
+
+// Merges a few segments into a new segment. The merger owns everything it needs so that the merge
+// can run on a background thread while the queries are still answered by the segments which are
+// being merged, the segments are read back from the disk since they are immutable once written.
+pub struct SegmentMerger {
+    index_directory_path: PathBuf,
+    segment_ids: Vec<u32>,
+    merged_segment_id: u32,
+    document_lengths: Box<[u32]>,
+    average_document_length: f32,
+    title_lengths: Box<[u32]>,
+    average_title_length: f32,
+    deleted_documents: Bitmap,
+    include_positions: bool,
+    compression_algorithm: CompressionAlgorithm,
+    scoring_model: ScoringModel,
+    bm25_params: BM25Params,
+    // the base segment has no segment metadata of its own
+    base_segment_no_of_docs: u32,
+}
+
+impl SegmentMerger {
+    #[allow(
+        clippy::too_many_arguments,
+        reason = "the merger runs on a thread of its own and takes everything it needs up front"
+    )]
+    pub fn new(
+        index_directory_path: PathBuf,
+        segment_ids: Vec<u32>,
+        merged_segment_id: u32,
+        document_lengths: Box<[u32]>,
+        average_document_length: f32,
+        title_lengths: Box<[u32]>,
+        average_title_length: f32,
+        deleted_documents: Bitmap,
+        include_positions: bool,
+        compression_algorithm: CompressionAlgorithm,
+        scoring_model: ScoringModel,
+        bm25_params: BM25Params,
+    ) -> Self {
+        Self {
+            index_directory_path,
+            segment_ids,
+            merged_segment_id,
+            document_lengths,
+            average_document_length,
+            title_lengths,
+            average_title_length,
+            deleted_documents,
+            include_positions,
+            compression_algorithm,
+            scoring_model,
+            bm25_params,
+            base_segment_no_of_docs: 0,
+        }
+    }
+
+    pub fn set_base_segment_no_of_docs(&mut self, base_segment_no_of_docs: u32) {
+        self.base_segment_no_of_docs = base_segment_no_of_docs;
+    }
+
+    pub fn spawn(self) -> JoinHandle<io::Result<Segment>> {
+        thread::spawn(move || self.merge())
+    }
+
+    // The segments have to be adjacent and in the order of their doc ids, the merged segment
+    // covers all of their doc ids. The postings of deleted documents are purged during the merge.
+    // The base segment is only ever merged on its own, its merged segment is written to a segment
+    // directory of its own like any other and it is up to the caller to move it back in its place.
+    pub fn merge(self) -> io::Result<Segment> {
+        let mut segments = Vec::with_capacity(self.segment_ids.len());
+        for &segment_id in &self.segment_ids {
+            let segment = if segment_id == 0 {
+                Segment::new(
+                    0,
+                    1,
+                    self.base_segment_no_of_docs,
+                    load_terms(&self.index_directory_path)?,
+                )
+            } else {
+                Segment::load(&self.index_directory_path, segment_id)?
+            };
+            // the whole term metadata is read by the merge, so it is checked as well as the blocks
+            segment.in_memory_index_metadata.check_checksums()?;
+            segments.push(segment);
+        }
+
+        let mut index_iterators = Vec::with_capacity(segments.len());
+        for segment in &segments {
+            let segment_directory_path =
+                get_segment_directory_path(&self.index_directory_path, segment.segment_id);
+            index_iterators.push(IndexIterator::new(
+                &get_inverted_index_path(&segment_directory_path),
+                &segment.in_memory_index_metadata,
+                self.compression_algorithm.clone(),
+            )?);
+        }
+
+        let merged_segment_directory_path =
+            get_segment_directory_path(&self.index_directory_path, self.merged_segment_id);
+        fs::create_dir_all(&merged_segment_directory_path)?;
+        let in_memory_index_metadata = Spimi::merge_index_files(
+            index_iterators,
+            File::create(get_inverted_index_path(&merged_segment_directory_path))?,
+            self.include_positions,
+            &DocumentLengths {
+                body_lengths: &self.document_lengths,
+                average_body_length: self.average_document_length,
+                title_lengths: &self.title_lengths,
+                average_title_length: self.average_title_length,
+            },
+            &self.deleted_documents,
+            self.compression_algorithm.clone(),
+            self.scoring_model,
+            self.bm25_params,
+            128,
+        )?;
+
+        let mut merged_segment = Segment::new(
+            self.merged_segment_id,
+            segments[0].first_doc_id,
+            segments.iter().map(|segment| segment.no_of_docs).sum(),
+            in_memory_index_metadata,
+        );
+        merged_segment.collection_no_of_docs = self.document_lengths.len() as u32;
+        merged_segment.average_body_length = self.average_document_length;
+        merged_segment.average_title_length = self.average_title_length;
+        merged_segment.no_of_purged_docs = self
+            .deleted_documents
+            .count_range(merged_segment.get_doc_id_range());
+        merged_segment.save(&self.index_directory_path)?;
+        Ok(merged_segment)
+    }
+}
@@ -0,0 +1,473 @@
+use std::{
+    fs::{self, File},
+    io,
+    ops::RangeInclusive,
+    panic::{self, AssertUnwindSafe},
+    path::Path,
+};
+
+use memmap2::Mmap;
+
+use crate::{
+    compressor::compressor::CompressionAlgorithm,
+    in_memory_index_metadata::in_memory_index_metadata::InMemoryIndexMetadata,
+    indexer::spimi::{index_iterator::IndexIterator, spimi::Spimi},
+    scoring::scorer::{Scorer, TermStatistics},
+    segment::segment::load_terms,
+    utils::{
+        bitmap::Bitmap,
+        block::BlockView,
+        field::{DocumentLengths, Field},
+        index_format::InvertedIndexFooter,
+        paths::get_inverted_index_path,
+    },
+};
+
+// What verifying a segment found, the problems are empty if the segment is fine
+#[derive(Debug, Clone, Default)]
+pub struct SegmentReport {
+    pub segment_id: u32,
+    pub first_doc_id: u32,
+    pub no_of_docs: u32,
+    pub collection_no_of_docs: u32,
+    pub no_of_terms: u32,
+    pub no_of_blocks: u32,
+    pub no_of_postings: u64,
+    // false if the segment can not be rebuilt from what is left of it, because its term metadata
+    // can not be read or it was written with another compression algorithm. Such a segment can
+    // only be rebuilt from the dataset along with the rest of the index.
+    pub repairable: bool,
+    // the blocks which can not be decoded, including the ones which a term points to but which
+    // are not in the inverted index. A repair leaves them out along with the postings in them.
+    pub damaged_block_ids: Vec<u32>,
+    pub problems: Vec<String>,
+}
+
+impl SegmentReport {
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct IndexReport {
+    pub segments: Vec<SegmentReport>,
+    // the problems which do not belong to a single segment
+    pub problems: Vec<String>,
+}
+
+impl IndexReport {
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty() && self.segments.iter().all(SegmentReport::is_ok)
+    }
+
+    // whether a repair can rebuild every damaged segment
+    pub fn is_repairable(&self) -> bool {
+        self.problems.is_empty()
+            && self
+                .segments
+                .iter()
+                .all(|segment_report| segment_report.is_ok() || segment_report.repairable)
+    }
+}
+
+// Checks every part of a segment which can be checked: the checksums of the term metadata and
+// of the blocks, the layout of every block, and the postings of every term against its metadata.
+// The frequencies and the max scores of every term are recomputed from its postings the same
+// way the merge computes them, document_lengths has to hold the statistics of the collection at
+// the time the segment was written.
+pub fn verify_segment(
+    segment_directory_path: &Path,
+    report: &mut SegmentReport,
+    document_lengths: &DocumentLengths,
+    compression_algorithm: CompressionAlgorithm,
+) {
+    let in_memory_index_metadata = match load_terms(segment_directory_path)
+        .and_then(|metadata| metadata.check_checksums().map(|_| metadata))
+    {
+        Ok(in_memory_index_metadata) => in_memory_index_metadata,
+        Err(e) => {
+            report
+                .problems
+                .push(format!("the term metadata can not be read: {}", e));
+            return;
+        }
+    };
+    report.no_of_blocks = in_memory_index_metadata.no_of_blocks;
+
+    let inverted_index_path = get_inverted_index_path(segment_directory_path);
+    let footer = match InvertedIndexFooter::read_from_file(&inverted_index_path) {
+        Ok(footer) => footer,
+        Err(e) => {
+            report.problems.push(e.to_string());
+            None
+        }
+    };
+    if let Some(footer) = &footer {
+        if footer.get_no_of_blocks() != report.no_of_blocks {
+            report.problems.push(format!(
+                "the inverted index has {} blocks but the term metadata has {}",
+                footer.get_no_of_blocks(),
+                report.no_of_blocks
+            ));
+        }
+        if footer.compression_algorithm != compression_algorithm {
+            report.problems.push(format!(
+                "the inverted index was compressed with {} but {} is configured",
+                footer.compression_algorithm, compression_algorithm
+            ));
+            return;
+        }
+    }
+    let inverted_index = match map_inverted_index(&inverted_index_path) {
+        Ok(inverted_index) => inverted_index,
+        Err(e) => {
+            report
+                .problems
+                .push(format!("the inverted index can not be read: {}", e));
+            return;
+        }
+    };
+    report.repairable = true;
+
+    for block_id in 0..report.no_of_blocks {
+        if let Err(e) = verify_block(&inverted_index, block_id, footer.as_ref()) {
+            report.problems.push(e.to_string());
+            report.damaged_block_ids.push(block_id);
+        }
+    }
+
+    let scorer = in_memory_index_metadata
+        .scoring_model
+        .get_scorer(in_memory_index_metadata.bm25_params);
+    let term_verifier = TermVerifier {
+        in_memory_index_metadata: &in_memory_index_metadata,
+        inverted_index: &inverted_index,
+        compression_algorithm,
+        scorer: scorer.as_ref(),
+        document_lengths,
+        doc_id_range: report.first_doc_id..=report.first_doc_id + report.no_of_docs - 1,
+    };
+    // a block which is damaged is reported once rather than for every term in it
+    let mut no_of_terms_in_damaged_blocks = 0;
+    for term in in_memory_index_metadata.iter_sorted_terms() {
+        report.no_of_terms += 1;
+        let mut problems = Vec::new();
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            term_verifier.verify_term(&term, &report.damaged_block_ids, &mut problems)
+        }));
+        match result {
+            Ok(TermResult::Verified(no_of_postings)) => report.no_of_postings += no_of_postings,
+            Ok(TermResult::InDamagedBlock) => no_of_terms_in_damaged_blocks += 1,
+            Ok(TermResult::Damaged(block_id)) => {
+                if !report.damaged_block_ids.contains(&block_id) {
+                    report.damaged_block_ids.push(block_id);
+                }
+            }
+            Err(_) => problems.push("its postings can not be decompressed".to_string()),
+        }
+        report.problems.extend(
+            problems
+                .into_iter()
+                .map(|problem| format!("term {}: {}", term, problem)),
+        );
+    }
+    if no_of_terms_in_damaged_blocks > 0 {
+        report.problems.push(format!(
+            "{} terms have postings in the damaged blocks",
+            no_of_terms_in_damaged_blocks
+        ));
+    }
+    report.damaged_block_ids.sort_unstable();
+}
+
+// Writes a new inverted index and term metadata for the segment to rebuilt_segment_directory_path
+// from the postings which can still be read, the postings in the damaged blocks are left out.
+// The frequencies and the max scores are computed again by the merge.
+#[allow(
+    clippy::too_many_arguments,
+    reason = "a segment is rebuilt with the settings of the index it belongs to"
+)]
+pub fn rebuild_segment(
+    segment_directory_path: &Path,
+    in_memory_index_metadata: &InMemoryIndexMetadata,
+    damaged_block_ids: &[u32],
+    rebuilt_segment_directory_path: &Path,
+    document_lengths: &DocumentLengths,
+    deleted_documents: &Bitmap,
+    include_positions: bool,
+    compression_algorithm: CompressionAlgorithm,
+) -> io::Result<InMemoryIndexMetadata> {
+    let inverted_index_path = get_inverted_index_path(segment_directory_path);
+    // the footer knows whether the segment was written with positions, a legacy segment is
+    // assumed to be written the way the index is configured
+    let include_positions = match InvertedIndexFooter::read_from_file(&inverted_index_path) {
+        Ok(Some(footer)) => footer.include_positions,
+        _ => include_positions,
+    };
+    let index_iterator = IndexIterator::with_skipped_blocks(
+        &inverted_index_path,
+        in_memory_index_metadata,
+        compression_algorithm.clone(),
+        damaged_block_ids.to_vec(),
+    )?;
+
+    fs::create_dir_all(rebuilt_segment_directory_path)?;
+    Spimi::merge_index_files(
+        vec![index_iterator],
+        File::create(get_inverted_index_path(rebuilt_segment_directory_path))?,
+        include_positions,
+        document_lengths,
+        deleted_documents,
+        compression_algorithm,
+        in_memory_index_metadata.scoring_model,
+        in_memory_index_metadata.bm25_params,
+        128,
+    )
+}
+
+fn map_inverted_index(inverted_index_path: &Path) -> io::Result<Mmap> {
+    let file = File::open(inverted_index_path)?;
+    // SAFETY: the files of a segment are not modified while it is verified, a repair writes new
+    // files and renames them into place
+    unsafe { Mmap::map(&file) }
+}
+
+fn verify_block(
+    inverted_index: &[u8],
+    block_id: u32,
+    footer: Option<&InvertedIndexFooter>,
+) -> io::Result<()> {
+    let block_view = match footer {
+        Some(footer) => BlockView::new_checked(inverted_index, block_id, footer)?,
+        None => BlockView::new(inverted_index, block_id)?,
+    };
+    for term_index in 1..block_view.get_no_of_terms() {
+        if block_view.get_term(term_index - 1) >= block_view.get_term(term_index) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("the terms of block {} are not in order", block_id),
+            ));
+        }
+    }
+    Ok(())
+}
+
+enum TermResult {
+    // the postings of the term were checked, with their count
+    Verified(u64),
+    // the term has postings in a block which is already known to be damaged
+    InDamagedBlock,
+    // the chunks of the term in this block can not be decoded
+    Damaged(u32),
+}
+
+struct TermVerifier<'a> {
+    in_memory_index_metadata: &'a InMemoryIndexMetadata,
+    inverted_index: &'a [u8],
+    compression_algorithm: CompressionAlgorithm,
+    scorer: &'a dyn Scorer,
+    document_lengths: &'a DocumentLengths<'a>,
+    doc_id_range: RangeInclusive<u32>,
+}
+
+impl TermVerifier<'_> {
+    fn verify_term(
+        &self,
+        term: &str,
+        damaged_block_ids: &[u32],
+        problems: &mut Vec<String>,
+    ) -> TermResult {
+        let term_metadata = self
+            .in_memory_index_metadata
+            .get_term_metadata(term)
+            .unwrap();
+        let no_of_blocks = self.in_memory_index_metadata.no_of_blocks;
+        if let Some(&block_id) = term_metadata
+            .block_ids
+            .iter()
+            .find(|&&block_id| block_id >= no_of_blocks)
+        {
+            problems.push(format!("block {} is past the last block", block_id));
+            return TermResult::Damaged(block_id);
+        }
+        if term_metadata
+            .block_ids
+            .iter()
+            .any(|block_id| damaged_block_ids.contains(block_id))
+        {
+            return TermResult::InDamagedBlock;
+        }
+
+        // the doc ids and frequencies of every chunk of the term
+        let mut chunks: Vec<Vec<(u32, u32)>> = Vec::new();
+        for (i, &block_id) in term_metadata.block_ids.iter().enumerate() {
+            let block_view = match BlockView::new(self.inverted_index, block_id) {
+                Ok(block_view) => block_view,
+                Err(e) => {
+                    problems.push(e.to_string());
+                    return TermResult::Damaged(block_id);
+                }
+            };
+            let term_index = block_view.check_if_term_exists(term_metadata.term_id);
+            if term_index == -1 {
+                problems.push(format!("it is missing from block {}", block_id));
+                continue;
+            }
+            let block_chunks = match block_view.try_decode_chunks_for_term(
+                term_metadata.term_id,
+                term_index as usize,
+                self.compression_algorithm.clone(),
+            ) {
+                Ok(block_chunks) => block_chunks,
+                Err(e) => {
+                    problems.push(format!("block {}: {}", block_id, e));
+                    return TermResult::Damaged(block_id);
+                }
+            };
+            if let Some(block_chunk_counts) = term_metadata.block_chunk_counts
+                && block_chunk_counts[i] as usize != block_chunks.len()
+            {
+                problems.push(format!(
+                    "it has {} chunks in block {} but its metadata says {}",
+                    block_chunks.len(),
+                    block_id,
+                    block_chunk_counts[i]
+                ));
+            }
+            for mut chunk in block_chunks {
+                chunk.decode_doc_ids();
+                chunk.decode_doc_frequencies();
+                if chunk.doc_ids.len() != chunk.no_of_postings as usize
+                    || chunk.doc_frequencies.len() != chunk.no_of_postings as usize
+                {
+                    problems.push(format!(
+                        "a chunk in block {} does not have the {} postings it claims",
+                        block_id, chunk.no_of_postings
+                    ));
+                    return TermResult::Damaged(block_id);
+                }
+                if chunk.doc_ids.last() != Some(&chunk.max_doc_id) {
+                    problems.push(format!(
+                        "a chunk in block {} has a max doc id of {} but its last doc id is {:?}",
+                        block_id,
+                        chunk.max_doc_id,
+                        chunk.doc_ids.last()
+                    ));
+                }
+                chunks.push(
+                    chunk
+                        .doc_ids
+                        .into_iter()
+                        .zip(chunk.doc_frequencies)
+                        .collect(),
+                );
+            }
+        }
+
+        let postings: Vec<(u32, u32)> = chunks.iter().flatten().copied().collect();
+        if postings.is_empty() {
+            problems.push("it has no postings".to_string());
+            return TermResult::Verified(0);
+        }
+        let mut previous_doc_id = 0;
+        for &(doc_id, frequency) in &postings {
+            if doc_id <= previous_doc_id {
+                problems.push(format!(
+                    "doc id {} comes after doc id {}",
+                    doc_id, previous_doc_id
+                ));
+                break;
+            }
+            if !self.doc_id_range.contains(&doc_id) {
+                problems.push(format!("doc id {} is outside of the segment", doc_id));
+                break;
+            }
+            if frequency == 0 {
+                problems.push(format!("doc id {} has a frequency of 0", doc_id));
+                break;
+            }
+            previous_doc_id = doc_id;
+        }
+
+        let term_frequency = postings.len() as u32;
+        let collection_frequency: u64 = postings
+            .iter()
+            .map(|&(_, frequency)| frequency as u64)
+            .sum();
+        if term_metadata.term_frequency != term_frequency {
+            problems.push(format!(
+                "it has {} postings but its metadata says {}",
+                term_frequency, term_metadata.term_frequency
+            ));
+        }
+        if term_metadata.collection_frequency != collection_frequency {
+            problems.push(format!(
+                "it occurs {} times but its metadata says {}",
+                collection_frequency, term_metadata.collection_frequency
+            ));
+        }
+
+        // the max scores are computed again with the statistics the segment was written with
+        let field = Field::from_term(term);
+        let term_statistics = TermStatistics {
+            no_of_docs: self.document_lengths.get_no_of_docs(),
+            document_frequency: term_frequency,
+            collection_frequency,
+            average_document_length: self.document_lengths.get_average_length(field),
+        };
+        let chunk_max_scores: Vec<(u32, f32)> = chunks
+            .iter()
+            .filter(|chunk| !chunk.is_empty())
+            .map(|chunk| {
+                let max_score = chunk
+                    .iter()
+                    .map(|&(doc_id, frequency)| {
+                        let doc_length = self.document_lengths.get_length(field, doc_id);
+                        self.scorer.score(frequency, doc_length, &term_statistics)
+                    })
+                    .fold(f32::MIN, f32::max);
+                (chunk.last().unwrap().0, max_score)
+            })
+            .collect();
+        let max_score = chunk_max_scores
+            .iter()
+            .map(|&(_, max_score)| max_score)
+            .fold(f32::MIN, f32::max);
+        if !scores_match(term_metadata.max_score, max_score) {
+            problems.push(format!(
+                "its max score is {} but its postings score up to {}",
+                term_metadata.max_score, max_score
+            ));
+        }
+        if let Some(chunk_block_max_metadata) = term_metadata.chunk_block_max_metadata {
+            if chunk_block_max_metadata.len() != chunk_max_scores.len() {
+                problems.push(format!(
+                    "it has {} chunks but block max scores for {}",
+                    chunk_max_scores.len(),
+                    chunk_block_max_metadata.len()
+                ));
+            } else if let Some((metadata, _)) = chunk_block_max_metadata
+                .iter()
+                .zip(&chunk_max_scores)
+                .find(|(metadata, chunk_max_score)| {
+                    let (last_doc_id, max_score) = **chunk_max_score;
+                    metadata.chunk_last_doc_id != last_doc_id
+                        || !scores_match(metadata.chunk_max_term_score, max_score)
+                })
+            {
+                problems.push(format!(
+                    "the block max score of the chunk ending at doc id {} does not match its postings",
+                    metadata.chunk_last_doc_id
+                ));
+            }
+        }
+        TermResult::Verified(term_frequency as u64)
+    }
+}
+
+// The scores are computed the same way as when the segment was written, the tolerance only
+// covers a legacy index whose averages were saved with less precision
+fn scores_match(saved_score: f32, score: f32) -> bool {
+    (saved_score - score).abs() <= 1e-4 * score.abs().max(1.0)
+}
@@ -0,0 +1,376 @@
+use std::{io, net::SocketAddr, time::Instant};
+
+use serde_json::{Value, json};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::search_engine::{error::SearchEngineError, search_engine::SearchEngine};
+
+// Serves the search engine over HTTP with JSON responses, the requests are handled one at a time
+// on the thread which calls serve.
+//
+// GET /search?q=stephen+king&k=10&offset=0  ranked results of the query along with the time taken
+// GET /metadata                             the metadata of the index
+// GET /terms/<term>                         the document frequency of the term
+// POST /shutdown                            stops the server once the segment merges are done
+//
+// A search request with a k or an offset above the limits of the server is rejected, since every
+// segment keeps k + offset documents while the query is answered.
+pub struct SearchServer {
+    server: Server,
+    default_k: usize,
+    max_k: usize,
+    max_offset: usize,
+}
+
+pub const DEFAULT_MAX_K: usize = 1000;
+pub const DEFAULT_MAX_OFFSET: usize = 10000;
+
+impl SearchServer {
+    // Port 0 binds to any free port, get_address returns the one which was picked
+    pub fn bind(address: &str) -> Result<Self, SearchEngineError> {
+        let server =
+            Server::http(address).map_err(|e| SearchEngineError::Io(io::Error::other(e)))?;
+        Ok(Self {
+            server,
+            default_k: 10,
+            max_k: DEFAULT_MAX_K,
+            max_offset: DEFAULT_MAX_OFFSET,
+        })
+    }
+
+    pub fn get_address(&self) -> Option<SocketAddr> {
+        self.server.server_addr().to_ip()
+    }
+
+    // The no of results returned when a search request does not have k, the max k is raised to it
+    // so that a request without k is never rejected
+    pub fn set_default_k(&mut self, default_k: usize) {
+        self.default_k = default_k;
+        self.max_k = self.max_k.max(default_k);
+    }
+
+    pub fn set_max_k(&mut self, max_k: usize) {
+        self.max_k = max_k;
+    }
+
+    pub fn set_max_offset(&mut self, max_offset: usize) {
+        self.max_offset = max_offset;
+    }
+
+    // Handles requests until stop is called from another thread or a shutdown request comes in.
+    // A segment merge which is still running would leave a partial segment behind, so it is
+    // waited for before returning.
+    pub fn serve(&self, search_engine: &mut SearchEngine) -> Result<(), SearchEngineError> {
+        for request in self.server.incoming_requests() {
+            if *request.method() == Method::Post && request.url() == "/shutdown" {
+                respond(request, 200, json!({"status": "shutting down"}));
+                break;
+            }
+            self.handle_request(search_engine, request);
+        }
+        search_engine.wait_for_segment_merges()
+    }
+
+    pub fn stop(&self) {
+        self.server.unblock();
+    }
+
+    fn handle_request(&self, search_engine: &mut SearchEngine, request: Request) {
+        let (status_code, body) = if *request.method() != Method::Get {
+            (405, json!({"error": "only GET requests are supported"}))
+        } else {
+            let (path, query_string) = request.url().split_once('?').unwrap_or((request.url(), ""));
+            self.route(search_engine, path, query_string)
+        };
+        respond(request, status_code, body);
+    }
+
+    fn route(
+        &self,
+        search_engine: &mut SearchEngine,
+        path: &str,
+        query_string: &str,
+    ) -> (u16, Value) {
+        let parameters = parse_query_string(query_string);
+        let get_parameter = |name: &str| {
+            parameters
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.as_str())
+        };
+
+        match path {
+            "/search" => {
+                let Some(query) = get_parameter("q") else {
+                    return (400, json!({"error": "the q parameter is missing"}));
+                };
+                let k = match get_parameter("k").map(str::parse::<usize>) {
+                    None => self.default_k,
+                    Some(Ok(k)) => k,
+                    Some(Err(_)) => return (400, json!({"error": "k has to be a number"})),
+                };
+                let offset = match get_parameter("offset").map(str::parse::<usize>) {
+                    None => 0,
+                    Some(Ok(offset)) => offset,
+                    Some(Err(_)) => return (400, json!({"error": "offset has to be a number"})),
+                };
+                if k > self.max_k {
+                    return (
+                        400,
+                        json!({"error": format!("k can not be more than {}", self.max_k)}),
+                    );
+                }
+                if offset > self.max_offset {
+                    return (
+                        400,
+                        json!({"error": format!("offset can not be more than {}", self.max_offset)}),
+                    );
+                }
+
+                let start_time = Instant::now();
+                match search_engine.query(query, k, offset) {
+                    Ok(results) => (
+                        200,
+                        json!({
+                            "query": query,
+                            "k": k,
+                            "offset": offset,
+                            "took_ms": start_time.elapsed().as_secs_f64() * 1000.0,
+                            "results": results,
+                            "did_you_mean": search_engine.suggest_query(query),
+                        }),
+                    ),
+                    Err(e) => error_response(e),
+                }
+            }
+            "/metadata" => match search_engine.get_index_metadata() {
+                Ok(metadata) => (200, json!(metadata)),
+                Err(e) => error_response(e),
+            },
+            _ => match path.strip_prefix("/terms/") {
+                Some(term) if !term.is_empty() => {
+                    let term = percent_decode(term);
+                    match search_engine.get_document_frequency(&term) {
+                        Some(document_frequency) => (
+                            200,
+                            json!({"term": term, "document_frequency": document_frequency}),
+                        ),
+                        None => (
+                            404,
+                            json!({"error": format!("{} is not in the index", term)}),
+                        ),
+                    }
+                }
+                _ => (404, json!({"error": format!("{} was not found", path)})),
+            },
+        }
+    }
+}
+
+fn respond(request: Request, status_code: u16, body: Value) {
+    let content_type = Header::from_bytes("Content-Type", "application/json").unwrap();
+    let response = Response::from_string(body.to_string())
+        .with_status_code(status_code)
+        .with_header(content_type);
+    // the client has gone away, there is no one left to tell about it
+    let _ = request.respond(response);
+}
+
+fn error_response(e: SearchEngineError) -> (u16, Value) {
+    let status_code = match e {
+        SearchEngineError::InvalidQuery(_) => 400,
+        _ => 500,
+    };
+    (status_code, json!({"error": e.to_string()}))
+}
+
+fn parse_query_string(query_string: &str) -> Vec<(String, String)> {
+    query_string
+        .split('&')
+        .filter(|parameter| !parameter.is_empty())
+        .map(|parameter| {
+            let (key, value) = parameter.split_once('=').unwrap_or((parameter, ""));
+            (percent_decode(key), percent_decode(value))
+        })
+        .collect()
+}
+
+// + is a space in a query string, invalid escapes are kept as they are
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' if i + 2 < bytes.len() => {
+                match std::str::from_utf8(&bytes[i + 1..i + 3])
+                    .ok()
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                {
+                    Some(byte) => {
+                        decoded.push(byte);
+                        i += 2;
+                    }
+                    None => decoded.push(b'%'),
+                }
+            }
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        net::TcpStream,
+        thread,
+    };
+
+    use tempfile::TempDir;
+
+    use crate::{
+        search_engine::builder::SearchEngineBuilder, utils::test_utils::write_test_dataset,
+    };
+
+    use super::*;
+
+    fn get(address: SocketAddr, path: &str) -> (u16, Value) {
+        send(address, "GET", path)
+    }
+
+    fn send(address: SocketAddr, method: &str, path: &str) -> (u16, Value) {
+        let mut stream = TcpStream::connect(address).unwrap();
+        write!(
+            stream,
+            "{} {} HTTP/1.1\r\nHost: localhost\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            method, path
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        let status_code = head.split_whitespace().nth(1).unwrap().parse().unwrap();
+        (status_code, serde_json::from_str(body).unwrap())
+    }
+
+    #[test]
+    fn test_percent_decode() {
+        assert_eq!(percent_decode("stephen+king"), "stephen king");
+        assert_eq!(percent_decode("%22misery%20movie%22"), "\"misery movie\"");
+        assert_eq!(percent_decode("100%"), "100%");
+        assert_eq!(percent_decode("%zz"), "%zz");
+    }
+
+    #[test]
+    fn test_serve() {
+        let temp_dir = TempDir::new().unwrap();
+        let dataset_dir = temp_dir.path().join("dataset");
+        write_test_dataset(
+            &dataset_dir,
+            &[
+                ("Misery", "misery is a novel by stephen king"),
+                ("River", "a quiet river flows past the old mill"),
+                ("Garden", "the garden was full of roses in spring"),
+            ],
+        );
+
+        let mut search_engine = SearchEngineBuilder::new(temp_dir.path().join("index"))
+            .dataset_directory_path(&dataset_dir)
+            .index()
+            .unwrap();
+
+        let server = SearchServer::bind("127.0.0.1:0").unwrap();
+        let address = server.get_address().unwrap();
+        // the server is stopped before checking the responses so that a failing check can not
+        // leave it waiting for requests
+        let responses = thread::scope(|scope| {
+            let client = scope.spawn(|| {
+                let responses: Vec<(u16, Value)> = [
+                    "/search?q=stephen+king&k=5",
+                    "/metadata",
+                    "/terms/River",
+                    "/terms/volcano",
+                    "/search",
+                    "/search?q=king&k=ten",
+                    "/unknown",
+                    "/search?q=king&k=1001",
+                    "/search?q=king&k=18446744073709551615",
+                    "/search?q=king&offset=10001",
+                    "/search?q=king&k=1000&offset=10000",
+                ]
+                .iter()
+                .map(|path| get(address, path))
+                .collect();
+                server.stop();
+                responses
+            });
+            server.serve(&mut search_engine).unwrap();
+            client.join().unwrap()
+        });
+
+        let (status_code, body) = &responses[0];
+        assert_eq!(*status_code, 200);
+        assert_eq!(body["k"], 5);
+        assert!(body["took_ms"].as_f64().is_some());
+        let results = body["results"].as_array().unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0]["doc_id"], 1);
+        assert_eq!(results[0]["document"]["doc_name"], "Misery");
+        assert!(results[0]["score"].as_f64().unwrap() > 0.0);
+        assert!(body["did_you_mean"].is_null());
+
+        let (status_code, body) = &responses[1];
+        assert_eq!(*status_code, 200);
+        assert_eq!(body["no_of_docs"], 3);
+
+        let (status_code, body) = &responses[2];
+        assert_eq!(*status_code, 200);
+        assert_eq!(body["document_frequency"], 1);
+
+        let status_codes: Vec<u16> = responses[3..].iter().map(|(code, _)| *code).collect();
+        assert_eq!(status_codes, vec![404, 400, 400, 404, 400, 400, 400, 200]);
+        assert_eq!(responses[7].1["error"], "k can not be more than 1000");
+        assert_eq!(responses[9].1["error"], "offset can not be more than 10000");
+    }
+
+    #[test]
+    fn test_shutdown() {
+        let temp_dir = TempDir::new().unwrap();
+        let dataset_dir = temp_dir.path().join("dataset");
+        write_test_dataset(
+            &dataset_dir,
+            &[("Misery", "misery is a novel by stephen king")],
+        );
+        let mut search_engine = SearchEngineBuilder::new(temp_dir.path().join("index"))
+            .dataset_directory_path(&dataset_dir)
+            .index()
+            .unwrap();
+
+        let server = SearchServer::bind("127.0.0.1:0").unwrap();
+        let address = server.get_address().unwrap();
+        // serve returns once the shutdown request has been answered, stop is never called
+        let responses = thread::scope(|scope| {
+            let client = scope.spawn(|| {
+                [
+                    get(address, "/shutdown"),
+                    send(address, "POST", "/search"),
+                    send(address, "POST", "/shutdown"),
+                ]
+            });
+            let result = server.serve(&mut search_engine);
+            (client.join().unwrap(), result)
+        });
+
+        let (responses, result) = responses;
+        assert!(result.is_ok());
+        assert_eq!(responses[0].0, 404);
+        assert_eq!(responses[1].0, 405);
+        assert_eq!(responses[2].0, 200);
+        assert_eq!(responses[2].1["status"], "shutting down");
+    }
+}
@@ -0,0 +1,148 @@
+use std::{
+    io::{self, Read, Write},
+    ops::RangeInclusive,
+};
+
+// A bitmap over the doc ids, it is used to keep track of the deleted documents.
+// Since doc ids start from 1, bit i of the bitmap represents the document with doc id i.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Bitmap {
+    words: Vec<u64>,
+    no_of_set_bits: u32,
+}
+
+impl Bitmap {
+    pub fn new() -> Self {
+        Self {
+            words: Vec::new(),
+            no_of_set_bits: 0,
+        }
+    }
+
+    // returns false if the doc id was already present in the bitmap
+    pub fn insert(&mut self, doc_id: u32) -> bool {
+        let word_index = (doc_id / 64) as usize;
+        if word_index >= self.words.len() {
+            self.words.resize(word_index + 1, 0);
+        }
+        let mask = 1u64 << (doc_id % 64);
+        if self.words[word_index] & mask != 0 {
+            return false;
+        }
+        self.words[word_index] |= mask;
+        self.no_of_set_bits += 1;
+        true
+    }
+
+    pub fn contains(&self, doc_id: u32) -> bool {
+        match self.words.get((doc_id / 64) as usize) {
+            Some(word) => word & (1u64 << (doc_id % 64)) != 0,
+            None => false,
+        }
+    }
+
+    pub fn len(&self) -> u32 {
+        self.no_of_set_bits
+    }
+
+    // the no of doc ids in the range which are in the bitmap
+    pub fn count_range(&self, doc_ids: RangeInclusive<u32>) -> u32 {
+        let (start, end) = (*doc_ids.start() as u64, *doc_ids.end() as u64);
+        let mut count = 0;
+        for (word_index, word) in self.words.iter().enumerate() {
+            let word_start = word_index as u64 * 64;
+            if word_start > end {
+                break;
+            }
+            if word_start + 63 < start || *word == 0 {
+                continue;
+            }
+            let mut mask = u64::MAX;
+            if start > word_start {
+                mask &= u64::MAX << (start - word_start);
+            }
+            if end < word_start + 63 {
+                mask &= u64::MAX >> (63 - (end - word_start));
+            }
+            count += (word & mask).count_ones();
+        }
+        count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.no_of_set_bits == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.words.clear();
+        self.no_of_set_bits = 0;
+    }
+
+    pub fn save<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_all(&self.no_of_set_bits.to_le_bytes())?;
+        writer.write_all(&(self.words.len() as u32).to_le_bytes())?;
+        for word in &self.words {
+            writer.write_all(&word.to_le_bytes())?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    pub fn load<R: Read>(&mut self, mut reader: R) -> io::Result<()> {
+        let mut buf = [0u8; 4];
+        reader.read_exact(&mut buf)?;
+        self.no_of_set_bits = u32::from_le_bytes(buf);
+        reader.read_exact(&mut buf)?;
+        let no_of_words = u32::from_le_bytes(buf) as usize;
+
+        let mut word_buf = [0u8; 8];
+        self.words = Vec::with_capacity(no_of_words);
+        for _ in 0..no_of_words {
+            reader.read_exact(&mut word_buf)?;
+            self.words.push(u64::from_le_bytes(word_buf));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_insert_and_contains() {
+        let mut bitmap = Bitmap::new();
+        assert!(bitmap.is_empty());
+        assert!(bitmap.insert(1));
+        assert!(bitmap.insert(64));
+        assert!(bitmap.insert(1000));
+        assert!(!bitmap.insert(64));
+
+        assert_eq!(bitmap.len(), 3);
+        assert!(bitmap.contains(1));
+        assert!(bitmap.contains(64));
+        assert!(bitmap.contains(1000));
+        assert!(!bitmap.contains(2));
+        assert!(!bitmap.contains(100_000));
+
+        assert_eq!(bitmap.count_range(1..=1000), 3);
+        assert_eq!(bitmap.count_range(2..=64), 1);
+        assert_eq!(bitmap.count_range(65..=999), 0);
+        assert_eq!(bitmap.count_range(64..=100_000), 2);
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let mut bitmap = Bitmap::new();
+        bitmap.insert(3);
+        bitmap.insert(129);
+
+        let mut bytes = Vec::new();
+        bitmap.save(&mut bytes).unwrap();
+
+        let mut loaded_bitmap = Bitmap::new();
+        loaded_bitmap.load(bytes.as_slice()).unwrap();
+        assert_eq!(loaded_bitmap, bitmap);
+        assert!(loaded_bitmap.contains(129));
+    }
+}
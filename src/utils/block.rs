@@ -1,10 +1,15 @@
 use std::{
+    cmp::Ordering,
     fs::File,
     io::{self, BufReader, Read, Seek},
 };
 
-use crate::{compressor::compressor::CompressionAlgorithm, utils::chunk::Chunk};
+use crate::{
+    compressor::compressor::CompressionAlgorithm,
+    utils::{chunk::Chunk, index_format::InvertedIndexFooter},
+};
 pub const MINIMUM_BLOCK_SIZE: u32 = 6;
+pub const DEFAULT_MAX_BLOCK_SIZE: u8 = 64;
 /*
  The unit of storage in our inverted index is a block.
  Each term can span across multiple blocks and so we need to keep track of the block ids for
@@ -28,7 +33,7 @@ pub struct Block {
 impl Block {
     pub fn new(block_id: u32, max_block_size: Option<u8>) -> Self {
         Self {
-            max_block_size: max_block_size.unwrap_or_else(|| 64),
+            max_block_size: max_block_size.unwrap_or(DEFAULT_MAX_BLOCK_SIZE),
             current_block_size: 4,
             no_of_terms: 0,
             block_id,
@@ -83,7 +88,7 @@ impl Block {
 
     // since max_block_size is in kb, multiply by 1000
     pub fn space_left(&self) -> u32 {
-        self.max_block_size as u32 * 1000 as u32 - self.current_block_size
+        self.max_block_size as u32 * 1000 - self.current_block_size
     }
 
     pub fn add_chunk_bytes(&mut self, chunk_bytes: Vec<u8>) {
@@ -99,8 +104,7 @@ impl Block {
         term_id: u32,
         term_index: usize,
         compression_algorithm: CompressionAlgorithm,
-    ) -> Vec<Chunk> {
-        let mut chunk_vec: Vec<Chunk> = Vec::new();
+    ) -> Vec<Chunk<'_>> {
         let term_offset_start = self.term_offsets[term_index] as usize;
         let term_off_end = if term_index == self.terms.len() - 1 {
             self.chunk_bytes.len()
@@ -108,26 +112,11 @@ impl Block {
             self.term_offsets[term_index + 1] as usize
         };
 
-        let chunk_bytes = &self.chunk_bytes[term_offset_start..term_off_end];
-        let mut chunk_offset = 0;
-        let mut current_chunk = Chunk::new(term_id, compression_algorithm);
-        while chunk_offset + 4 < chunk_bytes.len() {
-            let chunk_size = u32::from_le_bytes(
-                chunk_bytes[chunk_offset..chunk_offset + 4]
-                    .try_into()
-                    .unwrap(),
-            );
-            // if we are trying to get the chunks for the last term present in the block we stop when we reach
-            // a chunk of size 0
-            if chunk_size == 0 {
-                break;
-            }
-            chunk_offset += 4;
-            current_chunk.decode(&chunk_bytes[chunk_offset..chunk_offset + chunk_size as usize]);
-            chunk_vec.push(current_chunk.clone());
-            chunk_offset += chunk_size as usize;
-        }
-        chunk_vec
+        decode_chunks(
+            &self.chunk_bytes[term_offset_start..term_off_end],
+            term_id,
+            compression_algorithm,
+        )
     }
 
     // We store the no of terms, the terms, the term offsets and then the chunk_bytes
@@ -157,8 +146,29 @@ impl Block {
             (self.block_id * block_size as u32).into(),
         ))?;
         let mut block_bytes: Vec<u8> = vec![0; block_size];
-        reader.read(&mut block_bytes).unwrap();
-        self.no_of_terms = u32::from_le_bytes(block_bytes[0..4].try_into().unwrap());
+        reader.read_exact(&mut block_bytes)?;
+        self.decode_bytes(&block_bytes)
+    }
+
+    // Same as decode, but the bytes of the block are checked against their checksum in the footer
+    // of the inverted index before they are decoded
+    pub fn decode_checked(
+        &mut self,
+        reader: &mut BufReader<&mut File>,
+        footer: &InvertedIndexFooter,
+    ) -> io::Result<()> {
+        let block_size = self.max_block_size as usize * 1000;
+        reader.seek(std::io::SeekFrom::Start(
+            self.block_id as u64 * block_size as u64,
+        ))?;
+        let mut block_bytes: Vec<u8> = vec![0; block_size];
+        reader.read_exact(&mut block_bytes)?;
+        footer.check_block(self.block_id, &block_bytes)?;
+        self.decode_bytes(&block_bytes)
+    }
+
+    fn decode_bytes(&mut self, block_bytes: &[u8]) -> io::Result<()> {
+        self.no_of_terms = get_no_of_terms(block_bytes, self.block_id)? as u32;
         let mut offset = 4;
         self.terms.clear();
         for _ in 0..self.no_of_terms {
@@ -179,6 +189,197 @@ impl Block {
     }
 }
 
+// The no of terms of a block, a damaged block can claim more terms than fit in it
+fn get_no_of_terms(block_bytes: &[u8], block_id: u32) -> io::Result<usize> {
+    let no_of_terms = block_bytes
+        .get(0..4)
+        .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()) as usize)
+        .filter(|&no_of_terms| 4 + no_of_terms * 6 <= block_bytes.len());
+    no_of_terms.ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("block {} has more terms than fit in it", block_id),
+        )
+    })
+}
+
+// A block which borrows its bytes from a memory mapped inverted index instead of reading them into
+// a buffer, the term ids and term offsets are read from the bytes when they are needed.
+#[derive(Debug, Clone, Copy)]
+pub struct BlockView<'a> {
+    terms: &'a [u8],
+    term_offsets: &'a [u8],
+    chunk_bytes: &'a [u8],
+}
+
+impl<'a> BlockView<'a> {
+    // The blocks of an inverted index all have the default size, a block which is cut short or
+    // claims more terms than fit in it is an error
+    pub fn new(inverted_index: &'a [u8], block_id: u32) -> io::Result<Self> {
+        let block_size = DEFAULT_MAX_BLOCK_SIZE as usize * 1000;
+        let block_start = block_id as usize * block_size;
+        let block_bytes = inverted_index
+            .get(block_start..block_start + block_size)
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("block {} is past the end of the inverted index", block_id),
+                )
+            })?;
+        let no_of_terms = get_no_of_terms(block_bytes, block_id)?;
+        let terms_end = 4 + no_of_terms * 4;
+        let term_offsets_end = terms_end + no_of_terms * 2;
+        Ok(Self {
+            terms: &block_bytes[4..terms_end],
+            term_offsets: &block_bytes[terms_end..term_offsets_end],
+            chunk_bytes: &block_bytes[term_offsets_end..],
+        })
+    }
+
+    // Same as new, but the bytes of the block are checked against their checksum in the footer
+    // of the inverted index as well
+    pub fn new_checked(
+        inverted_index: &'a [u8],
+        block_id: u32,
+        footer: &InvertedIndexFooter,
+    ) -> io::Result<Self> {
+        let block = Self::new(inverted_index, block_id)?;
+        let block_size = DEFAULT_MAX_BLOCK_SIZE as usize * 1000;
+        let block_start = block_id as usize * block_size;
+        footer.check_block(
+            block_id,
+            &inverted_index[block_start..block_start + block_size],
+        )?;
+        Ok(block)
+    }
+
+    pub fn get_no_of_terms(&self) -> usize {
+        self.terms.len() / 4
+    }
+
+    pub fn get_term(&self, index: usize) -> u32 {
+        u32::from_le_bytes(self.terms[index * 4..index * 4 + 4].try_into().unwrap())
+    }
+
+    fn get_term_offset(&self, index: usize) -> usize {
+        u16::from_le_bytes(
+            self.term_offsets[index * 2..index * 2 + 2]
+                .try_into()
+                .unwrap(),
+        ) as usize
+    }
+
+    // the terms of a block are sorted
+    pub fn check_if_term_exists(&self, term_id: u32) -> i64 {
+        let (mut low, mut high) = (0, self.get_no_of_terms());
+        while low < high {
+            let mid = (low + high) / 2;
+            match self.get_term(mid).cmp(&term_id) {
+                Ordering::Less => low = mid + 1,
+                Ordering::Greater => high = mid,
+                Ordering::Equal => return mid as i64,
+            }
+        }
+        -1
+    }
+
+    pub fn decode_chunks_for_term(
+        &self,
+        term_id: u32,
+        term_index: usize,
+        compression_algorithm: CompressionAlgorithm,
+    ) -> Vec<Chunk<'a>> {
+        let term_offset_start = self.get_term_offset(term_index);
+        let term_offset_end = if term_index == self.get_no_of_terms() - 1 {
+            self.chunk_bytes.len()
+        } else {
+            self.get_term_offset(term_index + 1)
+        };
+        decode_chunks(
+            &self.chunk_bytes[term_offset_start..term_offset_end],
+            term_id,
+            compression_algorithm,
+        )
+    }
+
+    // Same as decode_chunks_for_term, but chunks which run past the chunks of the term are an
+    // error instead of a panic
+    pub fn try_decode_chunks_for_term(
+        &self,
+        term_id: u32,
+        term_index: usize,
+        compression_algorithm: CompressionAlgorithm,
+    ) -> io::Result<Vec<Chunk<'a>>> {
+        let corrupt = |message: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("the chunks of term {} {}", term_id, message),
+            )
+        };
+        let term_offset_start = self.get_term_offset(term_index);
+        let term_offset_end = if term_index == self.get_no_of_terms() - 1 {
+            self.chunk_bytes.len()
+        } else {
+            self.get_term_offset(term_index + 1)
+        };
+        let chunk_bytes = self
+            .chunk_bytes
+            .get(term_offset_start..term_offset_end)
+            .ok_or_else(|| corrupt("have offsets outside of the block"))?;
+
+        let mut chunk_vec: Vec<Chunk> = Vec::new();
+        let mut chunk_offset = 0;
+        while chunk_offset + 4 < chunk_bytes.len() {
+            let chunk_size = u32::from_le_bytes(
+                chunk_bytes[chunk_offset..chunk_offset + 4]
+                    .try_into()
+                    .unwrap(),
+            ) as usize;
+            if chunk_size == 0 {
+                break;
+            }
+            chunk_offset += 4;
+            let bytes = chunk_bytes
+                .get(chunk_offset..chunk_offset + chunk_size)
+                .ok_or_else(|| corrupt("run past the end of the block"))?;
+            let mut chunk = Chunk::new(term_id, compression_algorithm.clone());
+            chunk
+                .try_decode(bytes)
+                .map_err(|e| corrupt(&e.to_string()))?;
+            chunk_vec.push(chunk);
+            chunk_offset += chunk_size;
+        }
+        Ok(chunk_vec)
+    }
+}
+
+// The chunks of a term are stored one after the other, each prefixed with its size. If the term
+// is the last one in its block we keep decoding chunks until we get a chunk which has a size of 0.
+fn decode_chunks(
+    chunk_bytes: &[u8],
+    term_id: u32,
+    compression_algorithm: CompressionAlgorithm,
+) -> Vec<Chunk<'_>> {
+    let mut chunk_vec: Vec<Chunk> = Vec::new();
+    let mut chunk_offset = 0;
+    while chunk_offset + 4 < chunk_bytes.len() {
+        let chunk_size = u32::from_le_bytes(
+            chunk_bytes[chunk_offset..chunk_offset + 4]
+                .try_into()
+                .unwrap(),
+        );
+        if chunk_size == 0 {
+            break;
+        }
+        chunk_offset += 4;
+        let mut chunk = Chunk::new(term_id, compression_algorithm.clone());
+        chunk.decode(&chunk_bytes[chunk_offset..chunk_offset + chunk_size as usize]);
+        chunk_vec.push(chunk);
+        chunk_offset += chunk_size as usize;
+    }
+    chunk_vec
+}
+
 #[cfg(test)]
 mod tests {
     use crate::{indexer::spimi::spimi_merge_writer::SpimiMergeWriter, utils::posting::Posting};
@@ -191,6 +392,39 @@ mod tests {
         Posting { doc_id, positions }
     }
 
+    #[test]
+    fn test_decode_checked() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let file = temp_file.reopen().unwrap();
+        let mut writer =
+            SpimiMergeWriter::new(file, None, Some(64), true, CompressionAlgorithm::VarByte);
+        let block_ids = writer
+            .add_term(7, vec![create_test_posting(3, vec![1, 4])])
+            .unwrap();
+        writer.finish().unwrap();
+
+        let footer = InvertedIndexFooter::read_from_file(temp_file.path())
+            .unwrap()
+            .unwrap();
+        assert_eq!(footer.get_no_of_blocks(), 1);
+        assert_eq!(footer.compression_algorithm, CompressionAlgorithm::VarByte);
+        let mut file = temp_file.reopen().unwrap();
+        let mut block = Block::new(block_ids[0], Some(64));
+        block
+            .decode_checked(&mut BufReader::new(&mut file), &footer)
+            .unwrap();
+        assert_eq!(block.terms, vec![7]);
+
+        let mut bytes = std::fs::read(temp_file.path()).unwrap();
+        bytes[5] ^= 1;
+        std::fs::write(temp_file.path(), &bytes).unwrap();
+        let mut file = temp_file.reopen().unwrap();
+        let e = block
+            .decode_checked(&mut BufReader::new(&mut file), &footer)
+            .unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+    }
+
     #[test]
     fn test_add_single_term_verify_block_content() {
         let temp_file = NamedTempFile::new().unwrap();
@@ -221,6 +455,96 @@ mod tests {
         assert_eq!(chunks[0].doc_ids.len(), 0);
     }
 
+    #[test]
+    fn test_block_view() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let file = temp_file.reopen().unwrap();
+        let mut writer =
+            SpimiMergeWriter::new(file, None, Some(64), true, CompressionAlgorithm::VarByte);
+        writer
+            .add_term(1, vec![create_test_posting(10, vec![1])])
+            .unwrap();
+        let block_ids = writer
+            .add_term(
+                2,
+                vec![
+                    create_test_posting(20, vec![2]),
+                    create_test_posting(30, vec![4, 9]),
+                ],
+            )
+            .unwrap();
+        writer
+            .add_term(5, vec![create_test_posting(40, vec![3])])
+            .unwrap();
+        writer.finish().unwrap();
+
+        let mut file = temp_file.reopen().unwrap();
+        let mut block = Block::new(block_ids[0], Some(64));
+        block.decode(&mut BufReader::new(&mut file)).unwrap();
+        let inverted_index = std::fs::read(temp_file.path()).unwrap();
+        let block_view = BlockView::new(&inverted_index, block_ids[0]).unwrap();
+
+        assert_eq!(block_view.get_no_of_terms(), 3);
+        for term_id in [1, 2, 3, 5, 6] {
+            assert_eq!(
+                block_view.check_if_term_exists(term_id),
+                block.check_if_term_exists(term_id)
+            );
+        }
+        for (term_index, term_id) in [1, 2, 5].into_iter().enumerate() {
+            let chunks = block_view.decode_chunks_for_term(
+                term_id,
+                term_index,
+                CompressionAlgorithm::VarByte,
+            );
+            let expected_chunks =
+                block.decode_chunks_for_term(term_id, term_index, CompressionAlgorithm::VarByte);
+            assert_eq!(chunks.len(), expected_chunks.len());
+            for (chunk, expected_chunk) in chunks.iter().zip(&expected_chunks) {
+                assert_eq!(chunk.max_doc_id, expected_chunk.max_doc_id);
+                assert_eq!(chunk.no_of_postings, expected_chunk.no_of_postings);
+                assert_eq!(chunk.compressed_doc_ids, expected_chunk.compressed_doc_ids);
+            }
+        }
+    }
+
+    #[test]
+    fn test_damaged_block_view() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let file = temp_file.reopen().unwrap();
+        let mut writer =
+            SpimiMergeWriter::new(file, None, Some(64), true, CompressionAlgorithm::VarByte);
+        writer
+            .add_term(1, vec![create_test_posting(10, vec![1, 3])])
+            .unwrap();
+        writer.finish().unwrap();
+        let footer = InvertedIndexFooter::read_from_file(temp_file.path())
+            .unwrap()
+            .unwrap();
+        let mut inverted_index = std::fs::read(temp_file.path()).unwrap();
+        assert!(BlockView::new_checked(&inverted_index, 0, &footer).is_ok());
+        let block_view = BlockView::new(&inverted_index, 0).unwrap();
+        let chunks = block_view
+            .try_decode_chunks_for_term(1, 0, CompressionAlgorithm::VarByte)
+            .unwrap();
+        assert_eq!(chunks.len(), 1);
+        assert!(BlockView::new(&inverted_index, 1).is_err());
+
+        // the size of the chunk points past the end of the block, after the no of terms, the term
+        // and its offset
+        inverted_index[4 + 4 + 2..4 + 4 + 2 + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        let block_view = BlockView::new(&inverted_index, 0).unwrap();
+        let e = BlockView::new_checked(&inverted_index, 0, &footer).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+        assert!(
+            block_view
+                .try_decode_chunks_for_term(1, 0, CompressionAlgorithm::VarByte)
+                .is_err()
+        );
+        inverted_index[0..4].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(BlockView::new(&inverted_index, 0).is_err());
+    }
+
     #[test]
     fn test_add_multiple_terms_verify_block() {
         let temp_file = NamedTempFile::new().unwrap();
@@ -484,7 +808,7 @@ mod tests {
                 chunk.decode_doc_ids();
                 chunk.decode_doc_frequencies();
                 for doc_id in &chunk.doc_ids {
-                    postings1_read.push(doc_id.clone());
+                    postings1_read.push(*doc_id);
                 }
             }
         }
@@ -506,7 +830,7 @@ mod tests {
                 chunk.decode_doc_ids();
                 chunk.decode_doc_frequencies();
                 for doc_id in &chunk.doc_ids {
-                    postings2_read.push(doc_id.clone());
+                    postings2_read.push(*doc_id);
                 }
             }
         }
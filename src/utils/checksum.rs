@@ -0,0 +1,114 @@
+use std::io::{self, Read, Write};
+
+use crc32fast::Hasher;
+
+// Passes the bytes on to the writer and keeps the checksum of everything written so far, the
+// checksum is the CRC-32 which zip and png use. A file can be checksummed while it is being
+// written without keeping its bytes around.
+pub struct ChecksumWriter<W: Write> {
+    writer: W,
+    hasher: Hasher,
+}
+
+impl<W: Write> ChecksumWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            hasher: Hasher::new(),
+        }
+    }
+
+    pub fn get_checksum(&self) -> u32 {
+        self.hasher.clone().finalize()
+    }
+
+    // writes the checksum of everything written so far after it
+    pub fn finish(mut self) -> io::Result<W> {
+        let checksum = self.get_checksum();
+        self.writer.write_all(&checksum.to_le_bytes())?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+impl<W: Write> Write for ChecksumWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.writer.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+// The reading side of ChecksumWriter
+pub struct ChecksumReader<R: Read> {
+    reader: R,
+    hasher: Hasher,
+}
+
+impl<R: Read> ChecksumReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            hasher: Hasher::new(),
+        }
+    }
+
+    // reads the checksum which follows the bytes read so far and compares it with theirs
+    pub fn finish(mut self, file_name: &str) -> io::Result<R> {
+        let checksum = self.hasher.clone().finalize();
+        let mut buf = [0u8; 4];
+        self.reader.read_exact(&mut buf)?;
+        if u32::from_le_bytes(buf) != checksum {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("the checksum of {} does not match its contents", file_name),
+            ));
+        }
+        Ok(self.reader)
+    }
+}
+
+impl<R: Read> Read for ChecksumReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.reader.read(buf)?;
+        self.hasher.update(&buf[..read]);
+        Ok(read)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(ChecksumWriter::new(io::sink()).get_checksum(), 0);
+        let mut writer = ChecksumWriter::new(io::sink());
+        writer.write_all(b"12345").unwrap();
+        writer.write_all(b"6789").unwrap();
+        assert_eq!(writer.get_checksum(), 0xCBF43926);
+    }
+
+    #[test]
+    fn test_checksum_reader_and_writer() {
+        let mut writer = ChecksumWriter::new(Vec::new());
+        writer.write_all(b"misery is a novel").unwrap();
+        let mut bytes = writer.finish().unwrap();
+
+        let mut reader = ChecksumReader::new(&bytes[..]);
+        let mut contents = Vec::new();
+        reader.by_ref().take(17).read_to_end(&mut contents).unwrap();
+        assert_eq!(contents, b"misery is a novel");
+        assert!(reader.finish("test").is_ok());
+
+        bytes[3] ^= 1;
+        let mut reader = ChecksumReader::new(&bytes[..]);
+        reader.by_ref().take(17).read_to_end(&mut contents).unwrap();
+        let e = reader.finish("test").err().unwrap();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -1,3 +1,5 @@
+use std::io;
+
 use crate::compressor::compressor::{CompressionAlgorithm, Compressor};
 
 // The Chunk is a unit of storage for a posting list
@@ -7,15 +9,17 @@ use crate::compressor::compressor::{CompressionAlgorithm, Compressor};
 // the no of postings in this chunk(it may have less than 128 postings), the max document id
 // stored in the chunk and then the compressed doc_ids, the compressed frequenices
 // and then the compressed positions if we are choosing to store positions.
+// A decoded chunk borrows its compressed sections from the bytes it was decoded from, which are
+// usually the memory mapped inverted index, so only the sections which are decompressed are read.
 #[derive(Debug, Clone, PartialEq)]
-pub struct Chunk {
+pub struct Chunk<'a> {
     pub size_of_chunk: u32,
     pub max_doc_id: u32,
     pub no_of_postings: u8,
-    pub compressed_doc_ids: Vec<u8>,
-    pub compressed_doc_frequencies: Vec<u8>,
-    pub compressed_doc_positions: Vec<u8>,
-    pub indexed_compressed_positions: Vec<Vec<u8>>,
+    pub compressed_doc_ids: &'a [u8],
+    pub compressed_doc_frequencies: &'a [u8],
+    pub compressed_doc_positions: &'a [u8],
+    pub indexed_compressed_positions: Vec<&'a [u8]>,
     pub compressor: Compressor,
     pub p_for_delta_compressor: Compressor,
     pub doc_ids: Vec<u32>,
@@ -24,7 +28,7 @@ pub struct Chunk {
     pub term: u32,
 }
 
-impl Chunk {
+impl<'a> Chunk<'a> {
     pub fn new(term: u32, compression_algorithm: CompressionAlgorithm) -> Self {
         Self {
             // the default size of the chunk is 9
@@ -32,9 +36,9 @@ impl Chunk {
             size_of_chunk: 9,
             max_doc_id: 0,
             no_of_postings: 0,
-            compressed_doc_ids: Vec::new(),
-            compressed_doc_positions: Vec::new(),
-            compressed_doc_frequencies: Vec::new(),
+            compressed_doc_ids: &[],
+            compressed_doc_positions: &[],
+            compressed_doc_frequencies: &[],
             indexed_compressed_positions: Vec::new(),
             compressor: Compressor::new(compression_algorithm),
             p_for_delta_compressor: Compressor::new(CompressionAlgorithm::Simple16),
@@ -58,10 +62,10 @@ impl Chunk {
     //     if self.compressed_doc_ids.len() > 0 {
     //         let mut doc_ids = self
     //             .compressor
-    //             .decompress_list_with_dgaps(&self.compressed_doc_ids);
+    //             .decompress_list_with_dgaps(self.compressed_doc_ids);
     //         doc_ids.truncate(self.no_of_postings as usize);
     //         self.doc_ids = doc_ids;
-    //         self.compressed_doc_ids.clear();
+    //         self.compressed_doc_ids = &[];
     //     }
     //     self.doc_ids.as_slice()
     // }
@@ -70,10 +74,10 @@ impl Chunk {
     //     if self.compressed_doc_frequencies.len() > 0 {
     //         let mut doc_freq = self
     //             .compressor
-    //             .decompress_list(&self.compressed_doc_frequencies);
+    //             .decompress_list(self.compressed_doc_frequencies);
     //         doc_freq.truncate(self.no_of_postings as usize);
     //         self.doc_frequencies = doc_freq;
-    //         self.compressed_doc_frequencies.clear();
+    //         self.compressed_doc_frequencies = &[];
     //     }
     //     &self.doc_frequencies
     // }
@@ -83,10 +87,10 @@ impl Chunk {
     }
 
     pub fn get_posting_list(&self, index: usize) -> Vec<u32> {
-        if self.indexed_compressed_positions.len() > 0 {
+        if !self.indexed_compressed_positions.is_empty() {
             let mut positions = self
                 .compressor
-                .decompress_list_with_dgaps(&self.indexed_compressed_positions[index]);
+                .decompress_list_with_dgaps(self.indexed_compressed_positions[index]);
             positions.truncate(self.doc_frequencies[index] as usize);
             return positions;
         }
@@ -94,41 +98,41 @@ impl Chunk {
     }
 
     pub fn decode_doc_ids(&mut self) {
-        if self.compressed_doc_ids.len() > 0 {
+        if !self.compressed_doc_ids.is_empty() {
             if self.no_of_postings == 128 {
                 self.doc_ids = self
                     .p_for_delta_compressor
-                    .decompress_list_with_dgaps(&self.compressed_doc_ids);
+                    .decompress_list_with_dgaps(self.compressed_doc_ids);
             } else {
                 self.doc_ids = self
                     .compressor
-                    .decompress_list_with_dgaps(&self.compressed_doc_ids);
+                    .decompress_list_with_dgaps(self.compressed_doc_ids);
             }
             self.doc_ids.truncate(self.no_of_postings as usize);
-            self.compressed_doc_ids.clear();
+            self.compressed_doc_ids = &[];
         }
     }
 
     pub fn decode_doc_frequencies(&mut self) {
-        if self.compressed_doc_frequencies.len() > 0 {
+        if !self.compressed_doc_frequencies.is_empty() {
             if self.no_of_postings == 128 {
                 self.doc_frequencies = self
                     .p_for_delta_compressor
-                    .decompress_list(&self.compressed_doc_frequencies);
+                    .decompress_list(self.compressed_doc_frequencies);
             } else {
                 self.doc_frequencies = self
                     .compressor
-                    .decompress_list(&self.compressed_doc_frequencies);
+                    .decompress_list(self.compressed_doc_frequencies);
             }
             self.doc_frequencies.truncate(self.no_of_postings as usize);
-            self.compressed_doc_frequencies.clear();
+            self.compressed_doc_frequencies = &[];
         }
     }
 
     // we divide the compressed_doc_positions into individual compressed segments
     // so that retrieval of these segments is easier
     pub fn index_positions(&mut self) {
-        if self.compressed_doc_positions.len() == 0 {
+        if self.compressed_doc_positions.is_empty() {
             return;
         }
         let mut offset = 0;
@@ -141,11 +145,11 @@ impl Chunk {
             ) as usize;
             offset += 2;
             self.indexed_compressed_positions
-                .push(self.compressed_doc_positions[offset..offset + positions_length].to_vec());
+                .push(&self.compressed_doc_positions[offset..offset + positions_length]);
             offset += positions_length;
         }
         self.indexed_compressed_positions.shrink_to_fit();
-        self.compressed_doc_positions.clear();
+        self.compressed_doc_positions = &[];
     }
 
     pub fn add_doc_id(&mut self, doc_id: u32) {
@@ -198,7 +202,7 @@ impl Chunk {
         chunk_bytes
     }
 
-    pub fn decode(&mut self, chunk_bytes: &[u8]) {
+    pub fn decode(&mut self, chunk_bytes: &'a [u8]) {
         self.size_of_chunk = (4 + chunk_bytes.len()) as u32;
         let mut offset = 0;
         self.no_of_postings =
@@ -212,18 +216,52 @@ impl Chunk {
         let doc_id_bytes_length =
             u16::from_le_bytes(chunk_bytes[offset..offset + 2].try_into().unwrap()) as usize;
         offset += 2;
-        self.compressed_doc_ids = chunk_bytes[offset..offset + doc_id_bytes_length].to_vec();
+        self.compressed_doc_ids = &chunk_bytes[offset..offset + doc_id_bytes_length];
         offset += doc_id_bytes_length;
         let doc_freq_bytes_length =
             u16::from_le_bytes(chunk_bytes[offset..offset + 2].try_into().unwrap()) as usize;
         offset += 2;
-        self.compressed_doc_frequencies =
-            chunk_bytes[offset..offset + doc_freq_bytes_length].to_vec();
+        self.compressed_doc_frequencies = &chunk_bytes[offset..offset + doc_freq_bytes_length];
         offset += doc_freq_bytes_length;
-        self.compressed_doc_positions = chunk_bytes[offset..].to_vec();
+        self.compressed_doc_positions = &chunk_bytes[offset..];
         self.index_positions();
     }
 
+    // Same as decode, but the lengths in the chunk are checked against its size first so that a
+    // damaged chunk is an error instead of a panic
+    pub fn try_decode(&mut self, chunk_bytes: &'a [u8]) -> io::Result<()> {
+        let corrupt =
+            |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+        let read_length = |offset: usize| {
+            chunk_bytes
+                .get(offset..offset + 2)
+                .map(|bytes| u16::from_le_bytes(bytes.try_into().unwrap()) as usize)
+                .ok_or_else(|| corrupt("a chunk is cut short"))
+        };
+        let no_of_postings = *chunk_bytes
+            .first()
+            .ok_or_else(|| corrupt("a chunk is empty"))?;
+        if no_of_postings > 0 {
+            let mut offset = 5;
+            offset += 2 + read_length(offset)?;
+            offset += 2 + read_length(offset)?;
+            if offset > chunk_bytes.len() {
+                return Err(corrupt("a chunk is cut short"));
+            }
+            // the positions of every posting are prefixed with their length
+            if offset < chunk_bytes.len() {
+                for _ in 0..no_of_postings {
+                    offset += 2 + read_length(offset)?;
+                }
+                if offset > chunk_bytes.len() {
+                    return Err(corrupt("the positions of a chunk are cut short"));
+                }
+            }
+        }
+        self.decode(chunk_bytes);
+        Ok(())
+    }
+
     pub fn set_max_doc_id(&mut self, doc_id: u32) {
         self.max_doc_id = self.max_doc_id.max(doc_id);
     }
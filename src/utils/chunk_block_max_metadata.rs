@@ -1,4 +1,6 @@
+// repr(C) so that the flat array of the chunk metadata of a segment can be mapped from its file
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(C)]
 pub struct ChunkBlockMaxMetadata {
     pub chunk_last_doc_id: u32,
     pub chunk_max_term_score: f32,
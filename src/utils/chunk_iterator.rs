@@ -1,20 +1,150 @@
-use crate::utils::chunk::Chunk;
+use std::fmt::Debug;
+
+use crate::{
+    compressor::compressor::CompressionAlgorithm,
+    utils::{block::BlockView, chunk::Chunk, chunk_block_max_metadata::ChunkBlockMaxMetadata},
+};
+
+// Checks a block against its checksum before its chunks are decoded. An iterator can not return
+// an error while it moves, so the checker keeps the error of a damaged block for the query to
+// report.
+pub trait BlockChecker: Debug {
+    fn check_block(&self, block_id: u32) -> bool;
+}
+
+// Decodes the chunks of a term from the memory mapped inverted index one block at a time, when the
+// iterator first lands on one of the chunks of the block. The chunks of the blocks which are
+// skipped over are never decoded, nor checked.
+#[derive(Debug)]
+pub struct ChunkLoader<'a> {
+    blocks: Vec<BlockView<'a>>,
+    block_ids: Vec<u32>,
+    block_checker: &'a dyn BlockChecker,
+    term_id: u32,
+    // the index of the first chunk of every block, followed by the no of chunks
+    block_chunk_offsets: Vec<usize>,
+    loaded_blocks: Vec<bool>,
+    compression_algorithm: CompressionAlgorithm,
+}
+
+impl<'a> ChunkLoader<'a> {
+    pub fn new(
+        blocks: Vec<BlockView<'a>>,
+        block_ids: Vec<u32>,
+        block_checker: &'a dyn BlockChecker,
+        term_id: u32,
+        block_chunk_counts: &[u32],
+        compression_algorithm: CompressionAlgorithm,
+    ) -> Self {
+        let mut block_chunk_offsets = Vec::with_capacity(blocks.len() + 1);
+        block_chunk_offsets.push(0);
+        for &chunk_count in block_chunk_counts {
+            block_chunk_offsets.push(block_chunk_offsets.last().unwrap() + chunk_count as usize);
+        }
+        Self {
+            loaded_blocks: vec![false; blocks.len()],
+            blocks,
+            block_ids,
+            block_checker,
+            term_id,
+            block_chunk_offsets,
+            compression_algorithm,
+        }
+    }
+
+    // Decodes every chunk of the block holding the chunk into its place in chunks
+    fn load(&mut self, chunk_index: usize, chunks: &mut [Chunk<'a>]) {
+        let no_of_blocks = self.blocks.len();
+        // a block without any chunks of the term has the same offset as the block after it
+        let block_index = self.block_chunk_offsets[..no_of_blocks]
+            .partition_point(|&offset| offset <= chunk_index)
+            - 1;
+        if self.loaded_blocks[block_index] {
+            return;
+        }
+        self.loaded_blocks[block_index] = true;
+
+        // the chunks of a damaged block get a single posting without any occurrences at their
+        // last doc id, so the iterator can still move past them until the query fails
+        let first_chunk_index = self.block_chunk_offsets[block_index];
+        if !self.block_checker.check_block(self.block_ids[block_index]) {
+            for chunk in &mut chunks[first_chunk_index..self.block_chunk_offsets[block_index + 1]] {
+                chunk.doc_ids = vec![chunk.max_doc_id];
+                chunk.doc_frequencies = vec![0];
+                chunk.no_of_postings = 1;
+            }
+            return;
+        }
+        let block = &self.blocks[block_index];
+        let term_index = block.check_if_term_exists(self.term_id);
+        if term_index == -1 {
+            return;
+        }
+        for (i, chunk) in block
+            .decode_chunks_for_term(
+                self.term_id,
+                term_index as usize,
+                self.compression_algorithm.clone(),
+            )
+            .into_iter()
+            .enumerate()
+        {
+            chunks[first_chunk_index + i] = chunk;
+        }
+    }
+}
+
 #[derive(Debug)]
-pub struct ChunkIterator {
-    pub chunks: Vec<Chunk>,
+pub struct ChunkIterator<'a> {
+    pub chunks: Vec<Chunk<'a>>,
     pub current_chunk_index: usize,
     pub current_doc_id_index: usize,
+    no_of_postings: u32,
+    chunk_loader: Option<ChunkLoader<'a>>,
 }
 
-impl ChunkIterator {
-    pub fn new(chunks: Vec<Chunk>) -> Self {
+impl<'a> ChunkIterator<'a> {
+    pub fn new(chunks: Vec<Chunk<'a>>) -> Self {
         Self {
+            no_of_postings: chunks.iter().map(|c| c.no_of_postings as u32).sum(),
             chunks,
             current_chunk_index: 0,
             current_doc_id_index: 0,
+            chunk_loader: None,
         }
     }
+
+    // The chunks start out empty with only their max doc id taken from the block max metadata,
+    // which is all advance needs to skip over them
+    pub fn new_lazy(
+        chunk_loader: ChunkLoader<'a>,
+        chunk_block_max_metadata: &[ChunkBlockMaxMetadata],
+        no_of_postings: u32,
+    ) -> Self {
+        let chunks = chunk_block_max_metadata
+            .iter()
+            .map(|chunk_metadata| {
+                let mut chunk = Chunk::new(
+                    chunk_loader.term_id,
+                    chunk_loader.compression_algorithm.clone(),
+                );
+                chunk.max_doc_id = chunk_metadata.chunk_last_doc_id;
+                chunk
+            })
+            .collect();
+        Self {
+            chunks,
+            current_chunk_index: 0,
+            current_doc_id_index: 0,
+            no_of_postings,
+            chunk_loader: Some(chunk_loader),
+        }
+    }
+
     pub fn init(&mut self) {
+        if let Some(chunk_loader) = &mut self.chunk_loader {
+            chunk_loader.load(self.current_chunk_index, &mut self.chunks);
+        }
         self.chunks[self.current_chunk_index].decode_doc_ids();
         self.chunks[self.current_chunk_index].decode_doc_frequencies();
         self.current_doc_id_index = 0;
@@ -24,9 +154,10 @@ impl ChunkIterator {
         self.current_doc_id_index = 0;
     }
     pub fn get_no_of_postings(&self) -> u32 {
-        self.chunks.iter().map(|c| c.no_of_postings as u32).sum()
+        self.no_of_postings
     }
 
+    #[cfg(test)]
     pub fn contains_doc_id(&self, doc_id: u32) -> bool {
         self.chunks[self.current_chunk_index]
             .doc_ids
@@ -52,14 +183,14 @@ impl ChunkIterator {
             < self.chunks[self.current_chunk_index].get_no_of_postings() as usize
         {
             self.current_doc_id_index += 1;
-            return true;
+            true
         } else {
             if self.current_chunk_index + 1 < self.chunks.len() {
                 self.current_chunk_index += 1;
                 self.init();
-                return true;
+                true
             } else {
-                return false;
+                false
             }
         }
     }
@@ -68,13 +199,9 @@ impl ChunkIterator {
         if self.current_doc_id_index + 1
             < self.chunks[self.current_chunk_index].get_no_of_postings() as usize
         {
-            return true;
+            true
         } else {
-            if self.current_chunk_index + 1 < self.chunks.len() {
-                return true;
-            } else {
-                return false;
-            }
+            self.current_chunk_index + 1 < self.chunks.len()
         }
     }
 
@@ -93,14 +220,13 @@ impl ChunkIterator {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::compressor::compressor::CompressionAlgorithm;
 
     fn create_test_chunk(
         term: u32,
         doc_ids: Vec<u32>,
         frequencies: Vec<u32>,
         positions: Vec<Vec<u32>>,
-    ) -> Chunk {
+    ) -> Chunk<'static> {
         let mut chunk = Chunk::new(term, CompressionAlgorithm::VarByte);
 
         for (i, &doc_id) in doc_ids.iter().enumerate() {
@@ -548,4 +674,111 @@ mod tests {
         assert_eq!(iterator.get_doc_frequency(), 15);
         assert_eq!(iterator.get_posting_list(), vec![6, 7, 8, 9]);
     }
+
+    #[test]
+    fn test_lazy_chunk_loading() {
+        let temp_file = tempfile::NamedTempFile::new().unwrap();
+        let mut writer = crate::indexer::spimi::spimi_merge_writer::SpimiMergeWriter::new(
+            temp_file.reopen().unwrap(),
+            None,
+            None,
+            true,
+            CompressionAlgorithm::VarByte,
+        );
+        let doc_ids: Vec<u32> = (0..10_000).map(|i| i * 3 + 1).collect();
+        let postings = doc_ids
+            .iter()
+            .map(|&doc_id| crate::utils::posting::Posting {
+                doc_id,
+                positions: (0..20).map(|position| position * 7).collect(),
+            })
+            .collect();
+        let block_ids = writer.add_term(1, postings).unwrap();
+        let block_chunk_counts = writer.get_block_chunk_counts().to_vec();
+        writer.finish().unwrap();
+        assert!(block_ids.len() > 2);
+
+        let chunk_block_max_metadata: Vec<ChunkBlockMaxMetadata> = doc_ids
+            .chunks(128)
+            .map(|chunk_doc_ids| ChunkBlockMaxMetadata::new(*chunk_doc_ids.last().unwrap(), 1.0))
+            .collect();
+        assert_eq!(
+            block_chunk_counts.iter().sum::<u32>() as usize,
+            chunk_block_max_metadata.len()
+        );
+        let inverted_index = std::fs::read(temp_file.path()).unwrap();
+        let create_iterator = |block_checker: &'static TestBlockChecker| {
+            let blocks = block_ids
+                .iter()
+                .map(|&block_id| BlockView::new(&inverted_index, block_id).unwrap())
+                .collect();
+            let chunk_loader = ChunkLoader::new(
+                blocks,
+                block_ids.clone(),
+                block_checker,
+                1,
+                &block_chunk_counts,
+                CompressionAlgorithm::VarByte,
+            );
+            let mut iterator =
+                ChunkIterator::new_lazy(chunk_loader, &chunk_block_max_metadata, 10_000);
+            iterator.init();
+            iterator
+        };
+
+        // skipping to the end of the list only checks and decodes the first and the last block
+        let block_checker = Box::leak(Box::default());
+        let mut iterator = create_iterator(block_checker);
+        iterator.advance(doc_ids[9_990]);
+        assert_eq!(iterator.get_doc_id(), doc_ids[9_990]);
+        assert_eq!(iterator.get_doc_frequency(), 20);
+        let chunk_loader = iterator.chunk_loader.as_ref().unwrap();
+        assert_eq!(
+            chunk_loader
+                .loaded_blocks
+                .iter()
+                .filter(|&&loaded| loaded)
+                .count(),
+            2
+        );
+        assert_eq!(
+            *block_checker.checked_block_ids.borrow(),
+            vec![block_ids[0], *block_ids.last().unwrap()]
+        );
+
+        let mut iterator = create_iterator(Box::leak(Box::default()));
+        let mut iterated_doc_ids = vec![iterator.get_doc_id()];
+        while iterator.next() {
+            iterated_doc_ids.push(iterator.get_doc_id());
+        }
+        assert_eq!(iterated_doc_ids, doc_ids);
+        assert_eq!(iterator.get_no_of_postings(), 10_000);
+
+        // a damaged block is reported to the checker and the iterator still gets to the end
+        let block_checker = Box::leak(Box::new(TestBlockChecker {
+            damaged_block_id: Some(block_ids[1]),
+            ..Default::default()
+        }));
+        let mut iterator = create_iterator(block_checker);
+        while iterator.next() {}
+        assert_eq!(iterator.get_doc_id(), *doc_ids.last().unwrap());
+        assert_eq!(
+            block_checker.checked_block_ids.borrow().len(),
+            block_ids.len()
+        );
+    }
+
+    // remembers the blocks it was asked to check
+    #[derive(Debug, Default)]
+    struct TestBlockChecker {
+        checked_block_ids: std::cell::RefCell<Vec<u32>>,
+        damaged_block_id: Option<u32>,
+    }
+
+    impl BlockChecker for TestBlockChecker {
+        fn check_block(&self, block_id: u32) -> bool {
+            self.checked_block_ids.borrow_mut().push(block_id);
+            self.damaged_block_id != Some(block_id)
+        }
+    }
 }
@@ -13,17 +13,17 @@ pub struct Dictionary {
 
 impl Dictionary {
     pub fn new() -> Dictionary {
-        return Dictionary {
+        Dictionary {
             no_of_terms: 0,
             current_size: 0,
             dictionary: BTreeMap::new(),
-        };
+        }
     }
 
     // max size of dictionary in bytes
     // we are keeping it as 200 MB
     pub fn max_size(&self) -> u32 {
-        return 200_000_000;
+        200_000_000
     }
     pub fn size(&self) -> u32 {
         self.current_size
@@ -35,9 +35,10 @@ impl Dictionary {
     }
 
     pub fn does_term_already_exist(&mut self, term: &str) -> bool {
-        return self.dictionary.contains_key(term);
+        self.dictionary.contains_key(term)
     }
 
+    #[cfg(test)]
     pub fn add_term_posting(&mut self, term: &str, posting: Vec<Posting>) {
         for posting in &posting {
             let posting_length = posting.positions.len() as u32;
@@ -48,6 +49,7 @@ impl Dictionary {
         self.current_size += term.len() as u32;
     }
 
+    #[cfg(test)]
     pub fn get_postings(&self, term: &str) -> Option<&Vec<Posting>> {
         self.dictionary.get(term)
     }
@@ -112,7 +114,7 @@ mod dictionary_tests {
 
     #[test]
     fn test_get_postings_nonexistent_term() {
-        let mut dict = Dictionary::new();
+        let dict = Dictionary::new();
         let postings = dict.get_postings("nonexistent");
         assert!(postings.is_none());
     }
@@ -0,0 +1,122 @@
+// The fields of an article which are searchable. The terms of every field are kept in the same
+// inverted index, the terms of the title are prefixed with title: so that they do not mix with the
+// terms of the body. The tokens never contain a colon so a prefixed term can not clash with a word.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    Body,
+    Title,
+}
+
+pub const TITLE_PREFIX: &str = "title:";
+
+// A match in the title says more about a document than a match in its body, the scores of the
+// title terms are multiplied by the title weight at query time
+pub const DEFAULT_TITLE_WEIGHT: f32 = 2.0;
+
+impl Field {
+    // the term which a word of the field is indexed as
+    pub fn get_field_term(&self, word: &str) -> String {
+        match self {
+            Field::Body => word.to_string(),
+            Field::Title => format!("{}{}", TITLE_PREFIX, word),
+        }
+    }
+
+    pub fn from_term(term: &str) -> Field {
+        if term.starts_with(TITLE_PREFIX) {
+            Field::Title
+        } else {
+            Field::Body
+        }
+    }
+}
+
+// The no of tokens in every field of the documents along with the average over the collection,
+// the lengths are indexed by doc id - 1. An index which was built before the titles were indexed
+// has no title lengths, it does not have any title terms either.
+#[derive(Debug, Clone, Copy)]
+pub struct DocumentLengths<'a> {
+    pub body_lengths: &'a [u32],
+    pub average_body_length: f32,
+    pub title_lengths: &'a [u32],
+    pub average_title_length: f32,
+}
+
+impl<'a> DocumentLengths<'a> {
+    pub fn get_no_of_docs(&self) -> u32 {
+        self.body_lengths.len() as u32
+    }
+
+    // The lengths of the first no_of_docs documents and their averages, which are the statistics
+    // the collection had when it had no_of_docs documents. They are averaged the same way as the
+    // indexer does it so that the scores come out the same.
+    pub fn get_prefix(&self, no_of_docs: u32) -> DocumentLengths<'a> {
+        let body_lengths = &self.body_lengths[..(no_of_docs as usize).min(self.body_lengths.len())];
+        let title_lengths =
+            &self.title_lengths[..(no_of_docs as usize).min(self.title_lengths.len())];
+        let average = |lengths: &[u32]| {
+            let total: u64 = lengths.iter().map(|&length| length as u64).sum();
+            ((total as f64) / (body_lengths.len() as f64)) as f32
+        };
+        DocumentLengths {
+            body_lengths,
+            average_body_length: average(body_lengths),
+            title_lengths,
+            average_title_length: average(title_lengths),
+        }
+    }
+
+    pub fn get_length(&self, field: Field, doc_id: u32) -> u32 {
+        let lengths = match field {
+            Field::Body => self.body_lengths,
+            Field::Title => self.title_lengths,
+        };
+        lengths.get(doc_id as usize - 1).copied().unwrap_or(0)
+    }
+
+    pub fn get_average_length(&self, field: Field) -> f32 {
+        match field {
+            Field::Body => self.average_body_length,
+            Field::Title => self.average_title_length,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_field_terms() {
+        assert_eq!(Field::Body.get_field_term("misery"), "misery");
+        assert_eq!(Field::Title.get_field_term("misery"), "title:misery");
+        assert_eq!(Field::from_term("title:misery"), Field::Title);
+        assert_eq!(Field::from_term("misery"), Field::Body);
+    }
+
+    #[test]
+    fn test_document_lengths() {
+        let document_lengths = DocumentLengths {
+            body_lengths: &[100, 40],
+            average_body_length: 70.0,
+            title_lengths: &[1, 3],
+            average_title_length: 2.0,
+        };
+        assert_eq!(document_lengths.get_no_of_docs(), 2);
+        assert_eq!(document_lengths.get_length(Field::Body, 2), 40);
+        assert_eq!(document_lengths.get_length(Field::Title, 2), 3);
+        assert_eq!(document_lengths.get_average_length(Field::Title), 2.0);
+
+        let without_titles = DocumentLengths {
+            title_lengths: &[],
+            ..document_lengths
+        };
+        assert_eq!(without_titles.get_length(Field::Title, 1), 0);
+
+        let prefix = document_lengths.get_prefix(1);
+        assert_eq!(prefix.get_no_of_docs(), 1);
+        assert_eq!(prefix.get_average_length(Field::Body), 100.0);
+        assert_eq!(prefix.get_average_length(Field::Title), 1.0);
+        assert_eq!(without_titles.get_prefix(1).average_title_length, 0.0);
+    }
+}
@@ -4,25 +4,31 @@ use crate::utils::chunk_block_max_metadata::ChunkBlockMaxMetadata;
 pub struct InMemoryTermMetadata<'a> {
     pub term_id: u32,
     pub term_frequency: u32,
+    pub collection_frequency: u64,
     pub max_score: f32,
     pub block_ids: &'a [u32],
-    pub chunk_block_max_metadata: Option<&'a Box<[ChunkBlockMaxMetadata]>>,
+    pub chunk_block_max_metadata: Option<&'a [ChunkBlockMaxMetadata]>,
+    pub block_chunk_counts: Option<&'a [u32]>,
 }
 
 impl<'a> InMemoryTermMetadata<'a> {
     pub fn new(
         term_id: u32,
         term_frequency: u32,
+        collection_frequency: u64,
         max_score: f32,
         block_ids: &'a [u32],
-        chunk_block_max_metadata: Option<&'a Box<[ChunkBlockMaxMetadata]>>,
+        chunk_block_max_metadata: Option<&'a [ChunkBlockMaxMetadata]>,
+        block_chunk_counts: Option<&'a [u32]>,
     ) -> Self {
         Self {
             term_id,
             term_frequency,
+            collection_frequency,
             max_score,
             block_ids,
             chunk_block_max_metadata,
+            block_chunk_counts,
         }
     }
 }
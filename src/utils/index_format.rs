@@ -0,0 +1,244 @@
+use std::{
+    fs::File,
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+use crate::{compressor::compressor::CompressionAlgorithm, utils::block::DEFAULT_MAX_BLOCK_SIZE};
+
+// The version of the format of the index files which is written, an index saved by a newer
+// version can not be read. The files which were saved before the format had a version have no
+// header at all, they are still read as version 0.
+pub const FORMAT_VERSION: u32 = 1;
+
+pub const TERMS_MAGIC: [u8; 4] = *b"SETM";
+pub const DOCUMENT_METADATA_MAGIC: [u8; 4] = *b"SEDM";
+pub const BUILD_MANIFEST_MAGIC: [u8; 4] = *b"SEBM";
+pub const RUN_DOCUMENTS_MAGIC: [u8; 4] = *b"SERD";
+pub const ANALYZER_MAGIC: [u8; 4] = *b"SEAN";
+pub const BK_TREE_MAGIC: [u8; 4] = *b"SEBK";
+pub const INVERTED_INDEX_MAGIC: [u8; 8] = *b"SEINVIDX";
+
+// no of blocks, block size, chunk size, compression algorithm and flags, checksum, version, magic
+pub const INVERTED_INDEX_FOOTER_LEN: usize = 4 * 6 + 8;
+
+// An unknown version is reported as Unsupported rather than InvalidData, the file is fine but
+// this build can not read it
+pub fn check_format_version(format_version: u32, file_name: &str) -> io::Result<()> {
+    if format_version == 0 || format_version > FORMAT_VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!(
+                "{} was saved with format version {} but this build reads versions up to {}",
+                file_name, format_version, FORMAT_VERSION
+            ),
+        ));
+    }
+    Ok(())
+}
+
+pub fn write_file_header<W: Write + ?Sized>(writer: &mut W, magic: &[u8; 4]) -> io::Result<()> {
+    writer.write_all(magic)?;
+    writer.write_all(&FORMAT_VERSION.to_le_bytes())
+}
+
+// returns the format version of the file
+pub fn read_file_header<R: Read + ?Sized>(
+    reader: &mut R,
+    magic: &[u8; 4],
+    file_name: &str,
+) -> io::Result<u32> {
+    let mut buf = [0u8; 4];
+    reader.read_exact(&mut buf)?;
+    if &buf != magic {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{} does not start with the expected header", file_name),
+        ));
+    }
+    reader.read_exact(&mut buf)?;
+    let format_version = u32::from_le_bytes(buf);
+    check_format_version(format_version, file_name)?;
+    Ok(format_version)
+}
+
+// The blocks of the inverted index are addressed by their id times the block size, so the header
+// of the file is written after the last block. The checksums of the blocks come first followed
+// by a fixed size footer which ends with the magic, so that it can be found from the end of the
+// file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvertedIndexFooter {
+    pub format_version: u32,
+    pub block_size: u32, // the size of every block in bytes
+    pub chunk_size: u32, // the max no of postings in a chunk
+    pub compression_algorithm: CompressionAlgorithm,
+    pub include_positions: bool,
+    // the CRC-32 of every block, the no of blocks is the no of checksums
+    pub block_checksums: Vec<u32>,
+}
+
+impl InvertedIndexFooter {
+    pub fn get_no_of_blocks(&self) -> u32 {
+        self.block_checksums.len() as u32
+    }
+
+    pub fn write<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let mut bytes =
+            Vec::with_capacity(self.block_checksums.len() * 4 + INVERTED_INDEX_FOOTER_LEN);
+        for checksum in &self.block_checksums {
+            bytes.extend_from_slice(&checksum.to_le_bytes());
+        }
+        bytes.extend_from_slice(&self.get_no_of_blocks().to_le_bytes());
+        bytes.extend_from_slice(&self.block_size.to_le_bytes());
+        bytes.extend_from_slice(&self.chunk_size.to_le_bytes());
+        bytes.extend_from_slice(&[
+            self.compression_algorithm.get_id(),
+            self.include_positions as u8,
+            0,
+            0,
+        ]);
+        // the checksum covers the block checksums and the fields before it
+        let checksum = crc32fast::hash(&bytes);
+        bytes.extend_from_slice(&checksum.to_le_bytes());
+        bytes.extend_from_slice(&self.format_version.to_le_bytes());
+        bytes.extend_from_slice(&INVERTED_INDEX_MAGIC);
+        writer.write_all(&bytes)
+    }
+
+    // Returns None for an inverted index which was saved before it had a footer, such an index
+    // only has whole blocks of the default size
+    pub fn read_from_file(inverted_index_path: &Path) -> io::Result<Option<Self>> {
+        let mut file = File::open(inverted_index_path)?;
+        let file_len = file.metadata()?.len();
+        let file_name = inverted_index_path.display().to_string();
+        let corrupt = |message: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} {}", file_name, message),
+            )
+        };
+
+        let mut footer = [0u8; INVERTED_INDEX_FOOTER_LEN];
+        if file_len >= INVERTED_INDEX_FOOTER_LEN as u64 {
+            file.seek(SeekFrom::End(-(INVERTED_INDEX_FOOTER_LEN as i64)))?;
+            file.read_exact(&mut footer)?;
+        }
+        if footer[24..] != INVERTED_INDEX_MAGIC {
+            if file_len % (DEFAULT_MAX_BLOCK_SIZE as u64 * 1000) == 0 {
+                return Ok(None);
+            }
+            return Err(corrupt("is truncated, it does not end with a footer"));
+        }
+        let read_u32 =
+            |offset: usize| u32::from_le_bytes(footer[offset..offset + 4].try_into().unwrap());
+        let no_of_blocks = read_u32(0) as u64;
+        let block_size = read_u32(4);
+        let format_version = read_u32(20);
+        check_format_version(format_version, &file_name)?;
+        if no_of_blocks * (block_size as u64 + 4) + INVERTED_INDEX_FOOTER_LEN as u64 != file_len {
+            return Err(corrupt(
+                "is truncated, its length does not match the no of blocks",
+            ));
+        }
+
+        let mut bytes = vec![0u8; no_of_blocks as usize * 4];
+        file.seek(SeekFrom::Start(no_of_blocks * block_size as u64))?;
+        file.read_exact(&mut bytes)?;
+        bytes.extend_from_slice(&footer[..16]);
+        if crc32fast::hash(&bytes) != read_u32(16) {
+            return Err(corrupt("has a footer which does not match its checksum"));
+        }
+        let compression_algorithm = CompressionAlgorithm::from_id(footer[12])
+            .ok_or_else(|| corrupt("has an unknown compression algorithm"))?;
+        let block_checksums = bytes[..no_of_blocks as usize * 4]
+            .chunks_exact(4)
+            .map(|checksum| u32::from_le_bytes(checksum.try_into().unwrap()))
+            .collect();
+        Ok(Some(Self {
+            format_version,
+            block_size,
+            chunk_size: read_u32(8),
+            compression_algorithm,
+            include_positions: footer[13] == 1,
+            block_checksums,
+        }))
+    }
+
+    pub fn check_block(&self, block_id: u32, block_bytes: &[u8]) -> io::Result<()> {
+        match self.block_checksums.get(block_id as usize) {
+            Some(&checksum) if checksum == crc32fast::hash(block_bytes) => Ok(()),
+            Some(_) => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "block {} of the inverted index does not match its checksum",
+                    block_id
+                ),
+            )),
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("block {} is past the end of the inverted index", block_id),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::NamedTempFile;
+
+    use super::*;
+
+    #[test]
+    fn test_read_inverted_index_footer() {
+        let block_size = DEFAULT_MAX_BLOCK_SIZE as usize * 1000;
+        let blocks = [vec![1u8; block_size], vec![2u8; block_size]];
+        let footer = InvertedIndexFooter {
+            format_version: FORMAT_VERSION,
+            block_size: block_size as u32,
+            chunk_size: 128,
+            compression_algorithm: CompressionAlgorithm::VarByte,
+            include_positions: true,
+            block_checksums: blocks.iter().map(|block| crc32fast::hash(block)).collect(),
+        };
+        let mut temp_file = NamedTempFile::new().unwrap();
+        for block in &blocks {
+            temp_file.write_all(block).unwrap();
+        }
+        footer.write(&mut temp_file).unwrap();
+
+        let read_footer = InvertedIndexFooter::read_from_file(temp_file.path())
+            .unwrap()
+            .unwrap();
+        assert_eq!(read_footer, footer);
+        assert!(read_footer.check_block(1, &blocks[1]).is_ok());
+        assert!(read_footer.check_block(0, &blocks[1]).is_err());
+        assert!(read_footer.check_block(2, &blocks[1]).is_err());
+
+        // a file with only whole blocks was saved before the footer
+        let legacy_file = NamedTempFile::new().unwrap();
+        std::fs::write(legacy_file.path(), &blocks[0]).unwrap();
+        assert_eq!(
+            InvertedIndexFooter::read_from_file(legacy_file.path()).unwrap(),
+            None
+        );
+
+        // a truncated file has neither
+        let bytes = std::fs::read(temp_file.path()).unwrap();
+        std::fs::write(legacy_file.path(), &bytes[..bytes.len() - 1]).unwrap();
+        let e = InvertedIndexFooter::read_from_file(legacy_file.path()).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+        std::fs::write(legacy_file.path(), &bytes[block_size..]).unwrap();
+        let e = InvertedIndexFooter::read_from_file(legacy_file.path()).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+
+        let mut newer_footer = footer.clone();
+        newer_footer.format_version = FORMAT_VERSION + 1;
+        let mut newer_file = NamedTempFile::new().unwrap();
+        for block in &blocks {
+            newer_file.write_all(block).unwrap();
+        }
+        newer_footer.write(&mut newer_file).unwrap();
+        let e = InvertedIndexFooter::read_from_file(newer_file.path()).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::Unsupported);
+    }
+}
@@ -1,10 +1,17 @@
+pub mod bitmap;
 pub mod block;
+pub mod checksum;
 pub mod chunk;
 pub mod chunk_block_max_metadata;
 pub mod chunk_iterator;
 pub mod dictionary;
+pub mod field;
 pub mod in_memory_term_metadata;
+pub mod index_format;
 pub mod paths;
 pub mod posting;
 pub mod term;
+#[cfg(test)]
+pub mod test_utils;
 pub mod types;
+pub mod wildcard;
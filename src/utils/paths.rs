@@ -1,12 +1,77 @@
 use std::path::{Path, PathBuf};
 
+// the term metadata and the lexicon of a segment, they are mapped when the segment is loaded
+pub fn get_save_terms_path<P: AsRef<Path>>(path: P) -> PathBuf {
+    path.as_ref().join("terms.sidx")
+}
+
+// the term metadata of an index which was saved before terms.sidx, it can still be loaded
 pub fn get_save_term_metadata_path<P: AsRef<Path>>(path: P) -> PathBuf {
     path.as_ref().join("term_metadata.sidx")
 }
 
+// the BK-tree over the terms of a segment which spelling corrections are looked up in
+pub fn get_save_bk_tree_path<P: AsRef<Path>>(path: P) -> PathBuf {
+    path.as_ref().join("bk_tree.sidx")
+}
+
 pub fn get_save_doc_metadata_path<P: AsRef<Path>>(path: P) -> PathBuf {
     path.as_ref().join("document_metadata.sidx")
 }
+pub fn get_save_deleted_documents_path<P: AsRef<Path>>(path: P) -> PathBuf {
+    path.as_ref().join("deleted_documents.sidx")
+}
+// the analyzer which the index was built with, the queries are analyzed with it as well
+pub fn get_save_analyzer_path<P: AsRef<Path>>(path: P) -> PathBuf {
+    path.as_ref().join("analyzer.sidx")
+}
 pub fn get_inverted_index_path<P: AsRef<Path>>(path: P) -> PathBuf {
     path.as_ref().join("inverted_index.idx")
 }
+
+// The build manifest records the input files which have been indexed into temporary index files
+// so far, a build which was stopped resumes from it
+pub fn get_build_manifest_path<P: AsRef<Path>>(path: P) -> PathBuf {
+    path.as_ref().join("build_manifest.sidx")
+}
+
+// the temporary index file of a run of the build, it is written under a different name first
+pub fn get_run_path<P: AsRef<Path>>(path: P, run_id: u32) -> PathBuf {
+    path.as_ref().join(format!("{}.tmpidx", run_id))
+}
+
+pub fn get_unfinished_run_path<P: AsRef<Path>>(path: P, run_id: u32) -> PathBuf {
+    path.as_ref().join(format!("{}.tmpidx.part", run_id))
+}
+
+// the lengths, names and urls of the documents of a run
+pub fn get_run_documents_path<P: AsRef<Path>>(path: P, run_id: u32) -> PathBuf {
+    path.as_ref().join(format!("{}.tmpdocs", run_id))
+}
+
+// the temporary index files of newly added documents are written to this directory
+pub fn get_delta_directory_path<P: AsRef<Path>>(path: P) -> PathBuf {
+    path.as_ref().join("delta")
+}
+
+// The base segment which is built from the whole dataset lives in the index directory itself,
+// every other segment has a directory of its own.
+pub fn get_segment_directory_path<P: AsRef<Path>>(path: P, segment_id: u32) -> PathBuf {
+    if segment_id == 0 {
+        path.as_ref().to_path_buf()
+    } else {
+        get_segments_directory_path(path).join(segment_id.to_string())
+    }
+}
+
+pub fn get_segments_directory_path<P: AsRef<Path>>(path: P) -> PathBuf {
+    path.as_ref().join("segments")
+}
+
+pub fn get_save_segment_metadata_path<P: AsRef<Path>>(path: P) -> PathBuf {
+    path.as_ref().join("segment_metadata.sidx")
+}
+
+pub fn get_save_segment_ids_path<P: AsRef<Path>>(path: P) -> PathBuf {
+    get_segments_directory_path(path).join("segments.sidx")
+}
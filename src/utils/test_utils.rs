@@ -0,0 +1,23 @@
+use std::{fs::File, io::Write, path::Path};
+
+// Writes the documents as a zstd compressed batch of json lines, the same format
+// which is produced by the python preprocessing script
+pub fn write_test_dataset(dataset_dir: &Path, documents: &[(&str, &str)]) {
+    write_test_batch(dataset_dir, "batch_0", documents);
+}
+
+pub fn write_test_batch(dataset_dir: &Path, batch_name: &str, documents: &[(&str, &str)]) {
+    let batch_dir = dataset_dir.join(batch_name);
+    std::fs::create_dir_all(&batch_dir).unwrap();
+    let file = File::create(batch_dir.join(format!("{}.zst", batch_name))).unwrap();
+    let mut encoder = zstd::Encoder::new(file, 3).unwrap();
+    for (title, text) in documents {
+        let line = serde_json::json!({
+            "title": title,
+            "url": format!("https://en.wikipedia.org/wiki/{}", title),
+            "text": text,
+        });
+        writeln!(encoder, "{}", line).unwrap();
+    }
+    encoder.finish().unwrap();
+}
@@ -1,18 +1,35 @@
-#[derive(Clone, Debug)]
+use serde::Serialize;
+
+#[derive(Clone, Debug, Serialize)]
 pub struct DocumentMetadata {
     pub doc_name: String,
     pub doc_url: String,
     pub doc_length: u32,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
+pub struct SearchResult {
+    pub doc_id: u32,
+    pub document: DocumentMetadata,
+    pub score: f32,
+}
+
+#[derive(Clone, Debug, Serialize)]
 pub struct SearchEngineMetadata {
     pub no_of_docs: u32,
+    pub no_of_deleted_docs: u32,
     pub no_of_terms: u32,
     pub no_of_blocks: u32,
+    pub no_of_segments: u32,
     pub size_of_index: f64,
     pub dataset_directory_path: String,
     pub index_directory_path: String,
     pub compression_algorithm: String,
     pub query_algorithm: String,
+    pub scoring_model: String,
+    pub bm25_k1: f32,
+    pub bm25_b: f32,
+    pub title_weight: f32,
+    pub tokenizer: String,
+    pub stemmer: String,
 }
@@ -0,0 +1,84 @@
+// A query word with a * (any no of characters) or a ? (exactly one character) in it is a pattern
+// which is expanded into the terms of the index matching it. The pattern has to start with a few
+// literal characters so that only a small range of the sorted terms has to be scanned.
+pub const MIN_WILDCARD_PREFIX_LENGTH: usize = 2;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WildcardPattern {
+    pattern: Vec<char>,
+    prefix: String,
+}
+
+pub fn is_wildcard_pattern(term: &str) -> bool {
+    term.contains(['*', '?'])
+}
+
+impl WildcardPattern {
+    // returns None if the term does not have any wildcards
+    pub fn new(pattern: &str) -> Option<Self> {
+        let prefix_length = pattern.find(['*', '?'])?;
+        Some(Self {
+            pattern: pattern.chars().collect(),
+            prefix: pattern[..prefix_length].to_string(),
+        })
+    }
+
+    // the characters before the first wildcard, every matching term starts with them
+    pub fn get_prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    // A * which does not lead to a match is retried one character further along the term, only
+    // the last * has to be retried since the earlier ones can not do any better
+    pub fn matches(&self, term: &str) -> bool {
+        let term: Vec<char> = term.chars().collect();
+        let pattern = &self.pattern;
+        let (mut pattern_index, mut term_index) = (0, 0);
+        let mut last_star: Option<(usize, usize)> = None;
+        while term_index < term.len() {
+            if pattern_index < pattern.len()
+                && (pattern[pattern_index] == '?' || pattern[pattern_index] == term[term_index])
+            {
+                pattern_index += 1;
+                term_index += 1;
+            } else if pattern_index < pattern.len() && pattern[pattern_index] == '*' {
+                last_star = Some((pattern_index, term_index));
+                pattern_index += 1;
+            } else if let Some((star_index, star_term_index)) = last_star {
+                last_star = Some((star_index, star_term_index + 1));
+                pattern_index = star_index + 1;
+                term_index = star_term_index + 1;
+            } else {
+                return false;
+            }
+        }
+        pattern[pattern_index..].iter().all(|&c| c == '*')
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wildcard_pattern() {
+        assert!(WildcardPattern::new("astronomy").is_none());
+
+        let pattern = WildcardPattern::new("astro*").unwrap();
+        assert_eq!(pattern.get_prefix(), "astro");
+        assert!(pattern.matches("astro"));
+        assert!(pattern.matches("astronomy"));
+        assert!(!pattern.matches("astr"));
+
+        let pattern = WildcardPattern::new("col?r").unwrap();
+        assert_eq!(pattern.get_prefix(), "col");
+        assert!(pattern.matches("color"));
+        assert!(!pattern.matches("colour"));
+        assert!(!pattern.matches("colr"));
+
+        let pattern = WildcardPattern::new("co*o*r").unwrap();
+        assert!(pattern.matches("colour"));
+        assert!(pattern.matches("coor"));
+        assert!(!pattern.matches("colours"));
+    }
+}